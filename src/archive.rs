@@ -0,0 +1,103 @@
+//! Compression of rotated files in the background, for loggers writing the
+//! current file uncompressed.
+
+use crate::{codec::Codec, health::Monitor};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+/// Compresses rotated files on a thread of its own, started with the first
+/// file and stopped once every file given to it is compressed and the
+/// archiver is dropped.
+#[derive(Default)]
+pub(crate) struct Archiver {
+    worker: Mutex<Option<(mpsc::Sender<Job>, JoinHandle<()>)>>,
+}
+
+/// A rotated file to compress in place.
+struct Job {
+    path: PathBuf,
+    codec: Arc<dyn Codec>,
+    monitor: Arc<Monitor>,
+}
+
+impl Job {
+    fn run(self) {
+        match compress(&self.path, self.codec.as_ref()) {
+            // files deleted for being too old need no compressing
+            Err(error) if error.kind() != io::ErrorKind::NotFound => self.monitor.failed(&error),
+            _ => {}
+        }
+    }
+}
+
+impl Archiver {
+    /// Compresses the finished file at `path` with the codec, replacing it
+    /// once done. Failures are recorded in the monitor, leaving the file
+    /// uncompressed.
+    pub(crate) fn compress(&self, path: PathBuf, codec: Arc<dyn Codec>, monitor: Arc<Monitor>) {
+        let job = Job {
+            path,
+            codec,
+            monitor,
+        };
+        let mut worker = self
+            .worker
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        if worker.is_none() {
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let spawned = thread::Builder::new()
+                .name("jsonl_gzip_logger compress".to_string())
+                .spawn(move || receiver.into_iter().for_each(Job::run));
+            match spawned {
+                Ok(thread) => *worker = Some((sender, thread)),
+                Err(error) => return job.monitor.failed(&error),
+            }
+        }
+        let (sender, _) = worker.as_ref().expect("started above");
+        if let Err(mpsc::SendError(job)) = sender.send(job) {
+            // the thread panicked, so compress here instead
+            job.run();
+        }
+    }
+}
+
+impl Drop for Archiver {
+    fn drop(&mut self) {
+        let worker = self
+            .worker
+            .get_mut()
+            .unwrap_or_else(|error| error.into_inner());
+        if let Some((sender, thread)) = worker.take() {
+            drop(sender);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Compresses the file at `path` into a temporary file next to it, then
+/// renames that over it.
+fn compress(path: &Path, codec: &dyn Codec) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_os_string();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let result = (|| {
+        let sink = File::create(&temporary)?;
+        let sync = sink.try_clone()?;
+        let mut compressor = codec.compress(Box::new(BufWriter::new(sink)))?;
+        io::copy(&mut File::open(path)?, &mut compressor)?;
+        compressor.finish()?.flush()?;
+        // the uncompressed file must not be replaced by a partial one
+        sync.sync_data()?;
+        fs::rename(&temporary, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
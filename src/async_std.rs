@@ -0,0 +1,38 @@
+//! Non-blocking logging for applications running on async-std.
+
+use crate::{facade, worker, InitError, LoggerBuilder};
+use log::LevelFilter;
+
+pub use crate::worker::{Durability, Flushed, WorkerGuard};
+pub use crate::LoggerHandle;
+
+/// Creates and installs a global logger that logs to a new .jsonl.gz file at
+/// the given path without blocking the logging task.
+///
+/// Compression and file IO happen on async-std's blocking thread pool, so
+/// async tasks only pay for formatting the entry. The returned
+/// [`WorkerGuard`] finishes the file when dropped and must outlive all
+/// logging.
+pub fn init<P: AsRef<std::path::Path>>(
+    path: P,
+    level: LevelFilter,
+) -> Result<WorkerGuard, InitError> {
+    LoggerBuilder::new().level(level).init_async_std(path)
+}
+
+impl LoggerBuilder {
+    /// Like [`async_std::init`](init), but with this builder's configuration.
+    pub fn init_async_std<P: AsRef<std::path::Path>>(
+        self,
+        path: P,
+    ) -> Result<WorkerGuard, InitError> {
+        facade::ensure_uninstalled()?;
+        let dest = self.create(path)?;
+        let (filter, install, queue) = (self.filter.clone(), self.install_options(), self.queue);
+        let (logger, guard, worker) = worker::channel(dest, filter, self.format()?, queue);
+        // start the writer first so that an installed logger always has one
+        ::async_std::task::spawn_blocking(worker);
+        let generation = facade::install(Box::new(logger), install)?;
+        Ok(guard.installed(generation))
+    }
+}
@@ -0,0 +1,698 @@
+//! Configuration of the logger before installing it.
+
+use crate::{
+    clock::{Clock, DefaultClock},
+    codec::Codec,
+    dest::{Dest, DestOptions, SyncPolicy},
+    facade::{self, DefaultGuard, InitGuard, InstallOptions, Tee},
+    filter::Filter,
+    fork::{ForkPolicy, Owned},
+    format::{ErrorHook, Format},
+    handle::{LoggerHandle, Target},
+    hub::Hub,
+    InitError, JsonlGzipLogger, LogEntry,
+};
+use log::LevelFilter;
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, SystemTime},
+};
+
+/// Builder for configuring and installing the global logger.
+///
+/// ```no_run
+/// # use jsonl_gzip_logger::LoggerBuilder;
+/// let _guard = LoggerBuilder::new()
+///     .level(log::LevelFilter::Debug)
+///     .init("app.jsonl.gz")
+///     .unwrap();
+/// ```
+pub struct LoggerBuilder {
+    pub(crate) filter: Filter,
+    pub(crate) clock: Box<dyn Clock>,
+    pub(crate) dest: DestOptions,
+    pub(crate) fork: ForkPolicy,
+    pub(crate) session_id: bool,
+    /// Random ID of the run, generated when first needed.
+    run_id: OnceLock<String>,
+    /// Whether files start with a header.
+    pub(crate) header: bool,
+    pub(crate) pid: bool,
+    pub(crate) timestamps: bool,
+    pub(crate) source_location: bool,
+    pub(crate) recent: usize,
+    #[cfg(feature = "http")]
+    pub(crate) http: Option<crate::http::HttpServer>,
+    #[cfg(feature = "encryption")]
+    pub(crate) body_encryption: Option<crate::encryption::EncryptionKey>,
+    #[cfg(feature = "pii")]
+    pub(crate) pii: Option<crate::pii::PiiHasher>,
+    pub(crate) redactor: crate::redact::Redactor,
+    pub(crate) max_body: Option<usize>,
+    pub(crate) sampler: Option<crate::limit::Sampler>,
+    pub(crate) collapse_repeats: bool,
+    pub(crate) rate_limit: Option<u32>,
+    pub(crate) drop_reports: Option<Duration>,
+    /// Capacity of the queue in front of a non-blocking writer, if bounded.
+    pub(crate) queue: Option<(usize, crate::Overflow)>,
+    pub(crate) tee: Option<Tee>,
+    pub(crate) flush_interval: Option<Duration>,
+    pub(crate) on_error: ErrorHook,
+}
+
+impl LoggerBuilder {
+    /// Creates a builder with the default configuration: logging at
+    /// [`LevelFilter::Info`] with offsets from a
+    /// [`MonotonicClock`](crate::clock::MonotonicClock).
+    ///
+    /// On `wasm32-unknown-unknown` offsets stay at zero unless a
+    /// [`clock`](Self::clock) is configured.
+    pub fn new() -> Self {
+        Self {
+            filter: Filter::new(LevelFilter::Info),
+            clock: Box::new(DefaultClock::new()),
+            dest: DestOptions::default(),
+            fork: ForkPolicy::default(),
+            session_id: false,
+            run_id: OnceLock::new(),
+            header: false,
+            pid: false,
+            timestamps: false,
+            source_location: true,
+            recent: 0,
+            #[cfg(feature = "http")]
+            http: None,
+            #[cfg(feature = "encryption")]
+            body_encryption: None,
+            #[cfg(feature = "pii")]
+            pii: None,
+            redactor: crate::redact::Redactor::default(),
+            max_body: None,
+            sampler: None,
+            collapse_repeats: false,
+            rate_limit: None,
+            drop_reports: None,
+            queue: None,
+            tee: None,
+            flush_interval: None,
+            on_error: crate::format::default_error_hook(),
+        }
+    }
+
+    /// Sets the maximum level that is logged.
+    pub fn level(self, level: LevelFilter) -> Self {
+        self.filter.set_default(level);
+        self
+    }
+
+    /// Sets the clock used to compute entry offsets.
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Includes a random UUID, generated when the logger is installed, in
+    /// every entry so that entries from one process lifetime can be grouped
+    /// after files are merged.
+    ///
+    /// The same ID is always recorded in [`header`](Self::header)s and
+    /// [`append`](Self::append)ed session markers, so runs can be told apart
+    /// by those alone.
+    pub fn session_id(mut self, enabled: bool) -> Self {
+        self.session_id = enabled;
+        self
+    }
+
+    /// Records the ID of the writing process in every entry, so that entries
+    /// from processes sharing a logger configured before `fork()`, such as
+    /// pre-fork workers, can be told apart.
+    ///
+    /// The ID is read when each entry is logged, so children record their
+    /// own.
+    pub fn pid(mut self, enabled: bool) -> Self {
+        self.pid = enabled;
+        self
+    }
+
+    /// Records the wall-clock time in every entry, in addition to the
+    /// offset, so that entries can be matched with other systems' logs.
+    ///
+    /// Wall-clock time is not available on `wasm32-unknown-unknown`, where
+    /// this must stay disabled.
+    pub fn timestamps(mut self, enabled: bool) -> Self {
+        self.timestamps = enabled;
+        self
+    }
+
+    /// Records the module path, file, and line each entry was logged from.
+    ///
+    /// Enabled by default. Disabling it makes files smaller, and is
+    /// worthwhile when targets already identify where entries come from.
+    pub fn source_location(mut self, enabled: bool) -> Self {
+        self.source_location = enabled;
+        self
+    }
+
+    /// Keeps the last `count` entries in memory, so that health endpoints
+    /// and crash reporters can include them without reading the file back.
+    ///
+    /// The entries are available from the non-blocking loggers'
+    /// `LoggerHandle::recent`.
+    pub fn recent_entries(mut self, count: usize) -> Self {
+        self.recent = count;
+        self
+    }
+
+    /// Sets the gzip compression level, from 0 (none) to 9 (smallest files).
+    ///
+    /// Defaults to 1, the fastest level that compresses.
+    pub fn compression_level(mut self, level: u32) -> Self {
+        self.dest.compression = Some(level.min(9));
+        self
+    }
+
+    /// Compresses with LZ4 frames instead of gzip, for applications where
+    /// even the fastest gzip level costs too much CPU on the logging path.
+    ///
+    /// Files are larger, and [`read`](crate::read) recognizes and reads
+    /// them like gzip files.
+    /// [`compression_level`](Self::compression_level) has no effect.
+    #[cfg(feature = "lz4")]
+    pub fn lz4(mut self, enabled: bool) -> Self {
+        self.dest.codec = enabled.then(|| Arc::new(crate::codec::Lz4) as _);
+        self
+    }
+
+    /// Compresses with the given codec instead of gzip.
+    ///
+    /// Files must be read with [`read_with_codec`](crate::read_with_codec)
+    /// and the same codec. [`compression_level`](Self::compression_level)
+    /// has no effect.
+    pub fn codec<C: Codec + 'static>(mut self, codec: C) -> Self {
+        self.dest.codec = Some(Arc::new(codec));
+        self
+    }
+
+    /// Buffers up to `bytes` of compressed output before writing it, in
+    /// addition to the compressor's own buffering. Defaults to 0, meaning no
+    /// extra buffer.
+    ///
+    /// Flushing the logger also flushes the buffer.
+    pub fn buffer_size(mut self, bytes: usize) -> Self {
+        self.dest.buffer_size = bytes;
+        self
+    }
+
+    /// Sets whether an existing file at the log path is truncated, which is
+    /// the default, or makes initialization fail instead.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.dest.keep_existing = !overwrite;
+        self
+    }
+
+    /// Appends to an existing file at the log path instead of truncating it,
+    /// starting with a [session marker](crate::LogEntry::is_session_marker)
+    /// so that readers can tell where this run's entries begin.
+    ///
+    /// Each run adds another gzip member to the file, which the readers in
+    /// this crate and `zcat` read as one stream. Takes precedence over
+    /// [`overwrite`](Self::overwrite). Encrypted files cannot be appended
+    /// to, and hash chains start over in each run.
+    pub fn append(mut self, append: bool) -> Self {
+        self.dest.append = append;
+        self
+    }
+
+    /// Also forwards every record to another logger, such as an existing
+    /// `env_logger`, so that archiving can be added without replacing it.
+    ///
+    /// `level` is the most verbose level the other logger needs; it filters
+    /// records for itself as usual.
+    pub fn tee<L: log::Log + 'static>(mut self, logger: L, level: LevelFilter) -> Self {
+        self.tee = Some(Tee {
+            logger: Arc::new(logger),
+            level,
+        });
+        self
+    }
+
+    /// Flushes after every `count` entries, so that at most that many are
+    /// lost if the process dies, at the cost of compressing less well.
+    pub fn flush_every_entries(mut self, count: u64) -> Self {
+        self.dest.flush.entries = Some(count.max(1));
+        self
+    }
+
+    /// Flushes once `bytes` bytes of uncompressed entries have been written
+    /// since the last flush.
+    pub fn flush_every_bytes(mut self, bytes: u64) -> Self {
+        self.dest.flush.bytes = Some(bytes.max(1));
+        self
+    }
+
+    /// Ends the gzip member after every `count` entries and starts a new
+    /// one, producing a file of concatenated members that tools can skip
+    /// through and decode in parallel, at the cost of compressing less well.
+    ///
+    /// The file still reads as one stream, and with
+    /// [`codec`](Self::codec) or
+    #[cfg_attr(feature = "lz4", doc = "[`lz4`](Self::lz4)")]
+    #[cfg_attr(not(feature = "lz4"), doc = "`lz4`")]
+    /// this has no effect.
+    pub fn gzip_member_entries(mut self, count: u64) -> Self {
+        self.dest.member.entries = Some(count.max(1));
+        self
+    }
+
+    /// Ends the gzip member once `bytes` bytes of uncompressed entries have
+    /// been written to it, as [`gzip_member_entries`](Self::gzip_member_entries)
+    /// does by count.
+    pub fn gzip_member_bytes(mut self, bytes: u64) -> Self {
+        self.dest.member.bytes = Some(bytes.max(1));
+        self
+    }
+
+    /// Bounds the queue between the logging threads and the writer of a
+    /// non-blocking logger to `capacity` entries, handling entries logged
+    /// while it is full as `overflow` says.
+    ///
+    /// The queue is unbounded by default, so a destination that cannot keep
+    /// up during a burst of logging makes it grow without limit. Entries
+    /// dropped on overflow are counted towards
+    /// [`report_drops`](Self::report_drops). A capacity of 0 is treated as
+    /// 1, and loggers without a background writer ignore this.
+    ///
+    /// With [`Overflow::Block`](crate::Overflow::Block), an
+    /// [error hook](Self::on_error) that logs can deadlock the writer, since
+    /// it runs on the writer's thread.
+    pub fn queue_capacity(mut self, capacity: usize, overflow: crate::Overflow) -> Self {
+        self.queue = Some((capacity, overflow));
+        self
+    }
+
+    /// Flushes the logger every `interval` on a background thread, so that
+    /// entries do not sit in the compressor indefinitely on quiet services
+    /// and tailing tools see them.
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// Sets when files are synced to the storage device, so that entries
+    /// survive a kernel panic or power loss and not only a crash of the
+    /// process. Files are never synced unless asked to by default.
+    ///
+    /// [`SyncPolicy::Every`] makes the logger flush at least as often as it
+    /// syncs, like [`flush_interval`](Self::flush_interval).
+    pub fn sync_policy(mut self, policy: SyncPolicy) -> Self {
+        self.dest.sync = policy;
+        self
+    }
+
+    /// Calls `hook` with errors that logging cannot return to its caller,
+    /// such as a full disk, instead of printing them to stderr.
+    ///
+    /// Entries that fail to be serialized or written are dropped either
+    /// way; logging never panics because of them.
+    pub fn on_error<F: Fn(&io::Error) + Send + Sync + 'static>(mut self, hook: F) -> Self {
+        self.on_error = Arc::new(hook);
+        self
+    }
+
+    /// Sets what a child process created with `fork()` does with the logger
+    /// it inherits. Defaults to [`ForkPolicy::Disable`].
+    pub fn fork_policy(mut self, policy: ForkPolicy) -> Self {
+        self.fork = policy;
+        self
+    }
+
+    /// Encrypts the log file with the given key after compressing it.
+    ///
+    /// Encrypted files can be read with
+    /// [`read_encrypted`](crate::read_encrypted).
+    #[cfg(feature = "encryption")]
+    pub fn encryption(mut self, key: crate::encryption::EncryptionKey) -> Self {
+        self.dest.encryption = Some(crate::encryption::Encryption::Key(key));
+        self
+    }
+
+    /// Encrypts the log file to the given recipient after compressing it,
+    /// so that only the holder of the matching
+    /// [`IdentityKey`](crate::encryption::IdentityKey) can read it, and not
+    /// the process writing it.
+    ///
+    /// Encrypted files can be read with
+    /// [`read_with_identity`](crate::read_with_identity). This replaces any
+    /// key set with [`encryption`](Self::encryption).
+    #[cfg(feature = "encryption")]
+    pub fn encryption_to(mut self, recipient: crate::encryption::RecipientKey) -> Self {
+        self.dest.encryption = Some(crate::encryption::Encryption::Recipient(recipient));
+        self
+    }
+
+    /// Encrypts only the body of every entry with the given key, leaving the
+    /// offset, level and target readable for filtering and metrics.
+    ///
+    /// Bodies can be decrypted with
+    /// [`LogEntryIter::decrypt_bodies`](crate::LogEntryIter::decrypt_bodies).
+    #[cfg(feature = "encryption")]
+    pub fn body_encryption(mut self, key: crate::encryption::EncryptionKey) -> Self {
+        self.body_encryption = Some(key);
+        self
+    }
+
+    /// Rewrites the body and string key-value fields of every entry with the
+    /// given rule before it is written, such as to remove bearer tokens.
+    ///
+    /// Rules apply in the order they are added, and before
+    #[cfg_attr(feature = "pii", doc = "[`pii_hashing`](Self::pii_hashing)")]
+    #[cfg_attr(not(feature = "pii"), doc = "`pii_hashing`")]
+    /// and body encryption. Field keys and values other than strings are
+    /// left as they are.
+    pub fn redact<F: Fn(&str) -> String + Send + Sync + 'static>(mut self, rule: F) -> Self {
+        self.redactor.push(Arc::new(rule));
+        self
+    }
+
+    /// Cuts entry bodies longer than the given number of bytes down to that
+    /// length, marking the entries as
+    /// [`truncated`](crate::LogEntry::truncated).
+    ///
+    /// Bodies are cut at a character boundary after
+    /// [`redact`](Self::redact) rules and
+    #[cfg_attr(feature = "pii", doc = "[`pii_hashing`](Self::pii_hashing)")]
+    #[cfg_attr(not(feature = "pii"), doc = "`pii_hashing`")]
+    /// are applied, and before body encryption.
+    pub fn max_body_bytes(mut self, bytes: usize) -> Self {
+        self.max_body = Some(bytes);
+        self
+    }
+
+    /// Writes only one in every `one_in` entries logged at the level,
+    /// starting with the first, so that verbose instrumentation can stay
+    /// enabled without filling the disk.
+    ///
+    /// Levels are sampled independently, and levels this is not called for
+    /// keep every entry:
+    ///
+    /// ```no_run
+    /// # use jsonl_gzip_logger::LoggerBuilder;
+    /// use log::Level;
+    ///
+    /// let _guard = LoggerBuilder::new()
+    ///     .level(log::LevelFilter::Trace)
+    ///     .sample(Level::Trace, 100)
+    ///     .sample(Level::Debug, 10)
+    ///     .init("app.jsonl.gz")
+    ///     .unwrap();
+    /// ```
+    ///
+    /// Sampling applies to entries that pass the [level](Self::level)
+    /// filters, before the [rate limit](Self::rate_limit). A `one_in` of 0
+    /// or 1 keeps every entry.
+    pub fn sample(mut self, level: log::Level, one_in: u32) -> Self {
+        self.sampler
+            .get_or_insert_with(Default::default)
+            .set(level, one_in);
+        self
+    }
+
+    /// Writes only the first of consecutive entries with the same level,
+    /// target and body, followed by an entry counting the rest, as syslog
+    /// does for retry loops logging the same message over and over.
+    ///
+    /// The count is written once a different entry is logged or the logger
+    /// is flushed, as an entry of this crate's own at the level of the
+    /// repeated entries with the body `message repeated N times`, and the
+    /// count and target in `repeated` and `target` fields. Key-values are
+    /// not compared.
+    pub fn collapse_repeats(mut self, enabled: bool) -> Self {
+        self.collapse_repeats = enabled;
+        self
+    }
+
+    /// Writes at most the given number of entries per second for each
+    /// target, dropping the rest, so that one chatty module cannot flood the
+    /// log.
+    ///
+    /// Seconds are counted on the logger's [clock](Self::clock). Once a
+    /// second with dropped entries is over, the next entry logged is
+    /// preceded by a WARN entry of this crate's own for each target,
+    /// reporting how many were dropped in `suppressed` and the target in
+    /// `target` fields.
+    pub fn rate_limit(mut self, entries_per_second: u32) -> Self {
+        self.rate_limit = Some(entries_per_second);
+        self
+    }
+
+    /// Writes a WARN entry of this crate's own when entries were dropped,
+    /// at most once per interval of the logger's [clock](Self::clock), so
+    /// that readers know the log is incomplete.
+    ///
+    /// Entries are counted when a queue in front of the destination is
+    /// full, writing or sending them fails, or the
+    /// [rate limit](Self::rate_limit) is exceeded. Reports have the body
+    /// `dropped N entries since last report`, the total in a `dropped`
+    /// field, and the counts for each cause in `queue`, `io` and
+    /// `rate_limit` fields. A report is written with the first entry
+    /// logged once one is due, and when the logger is flushed.
+    pub fn report_drops(mut self, interval: Duration) -> Self {
+        self.drop_reports = Some(interval);
+        self
+    }
+
+    /// Replaces every match of the regex in entry bodies and string fields
+    /// with the replacement, which can refer to capture groups as in
+    /// [`Regex::replace_all`](regex::Regex::replace_all).
+    ///
+    /// This is a [`redact`](Self::redact) rule.
+    #[cfg(feature = "regex")]
+    pub fn redact_regex(self, regex: regex::Regex, replacement: impl Into<String>) -> Self {
+        let replacement = replacement.into();
+        self.redact(move |text| regex.replace_all(text, replacement.as_str()).into_owned())
+    }
+
+    /// Replaces emails, phone numbers and credit card numbers in entry bodies
+    /// and the text of their fields with hashes salted with the given salt,
+    /// see [`pii`](crate::pii).
+    #[cfg(feature = "pii")]
+    pub fn pii_hashing(mut self, salt: impl Into<Vec<u8>>) -> Self {
+        self.pii = Some(crate::pii::PiiHasher::new(salt));
+        self
+    }
+
+    /// Appends an HMAC signature to every entry, keyed with the given key.
+    ///
+    /// Signatures can be checked with [`signing::verify`](crate::signing::verify).
+    #[cfg(feature = "signing")]
+    pub fn signing(mut self, key: crate::signing::SigningKey) -> Self {
+        self.dest.signing = Some(key);
+        self
+    }
+
+    /// Links every entry to the previous one with a hash, so that removed or
+    /// reordered entries are detected by [`chain::verify`](crate::chain::verify).
+    #[cfg(feature = "hash-chain")]
+    pub fn hash_chain(mut self, enabled: bool) -> Self {
+        self.dest.hash_chain = enabled;
+        self
+    }
+
+    /// Links every entry to the previous one like [`hash_chain`](Self::hash_chain),
+    /// but with an HMAC keyed with the given key, so that the chain cannot be
+    /// recomputed after editing the file without the key.
+    ///
+    /// Keyed chains are verified with
+    /// [`chain::verify_keyed`](crate::chain::verify_keyed).
+    #[cfg(all(feature = "hash-chain", feature = "signing"))]
+    pub fn keyed_hash_chain(mut self, key: crate::signing::SigningKey) -> Self {
+        self.dest.hash_chain = true;
+        self.dest.chain_key = Some(key);
+        self
+    }
+
+    /// Creates and installs a global logger that logs to a new .jsonl.gz
+    /// file at the given path.
+    ///
+    /// The returned guard finishes the file when dropped.
+    pub fn init<P: AsRef<Path>>(self, path: P) -> Result<InitGuard, InitError> {
+        facade::ensure_uninstalled()?;
+        let install = self.install_options();
+        Self::install(self.build(path)?, install)
+    }
+
+    /// Creates a logger that logs to a new .jsonl.gz file at the given path,
+    /// and routes entries logged on the current thread to it until the
+    /// returned guard is dropped, which finishes the file.
+    ///
+    /// Unlike [`init`](Self::init) this leaves the global logger alone, so
+    /// tests running concurrently in one process can each log to their own
+    /// file; see [`set_default`](crate::set_default). A
+    /// [`tee`](Self::tee) and [`flush_interval`](Self::flush_interval) are
+    /// ignored.
+    pub fn init_scoped<P: AsRef<Path>>(self, path: P) -> Result<DefaultGuard, InitError> {
+        facade::set_default(self.build(path)?)
+    }
+
+    /// Creates and installs a global logger that writes the compressed log
+    /// to the given writer instead of a file.
+    ///
+    /// This works on targets without a filesystem, such as browsers, where
+    /// the writer can forward the bytes to IndexedDB or `postMessage`. The
+    /// gzip stream is finished when the returned guard is dropped.
+    pub fn init_writer<W: Write + Send + 'static>(self, writer: W) -> Result<InitGuard, InitError> {
+        facade::ensure_uninstalled()?;
+        let install = self.install_options();
+        Self::install(self.build_writer(writer)?, install)
+    }
+
+    /// Creates a logger that logs to a new .jsonl.gz file at the given path,
+    /// without installing it.
+    ///
+    /// A [`tee`](Self::tee) and [`flush_interval`](Self::flush_interval) are
+    /// ignored, and changing levels after creation only works for installed
+    /// loggers.
+    pub fn build<P: AsRef<Path>>(self, path: P) -> io::Result<JsonlGzipLogger> {
+        let options = self.dest_options();
+        let dest = self.create_with(&options, &path)?;
+        let reopen =
+            (self.fork == ForkPolicy::Reopen).then(|| (path.as_ref().to_path_buf(), options));
+        self.logger(Owned::new(dest, reopen))
+    }
+
+    /// Creates a logger that writes the compressed log to the given writer,
+    /// without installing it, as [`build`](Self::build) does for files.
+    pub fn build_writer<W: Write + Send + 'static>(self, writer: W) -> io::Result<JsonlGzipLogger> {
+        let dest = self.dest_options().wrap(writer)?;
+        self.logger(Owned::new(dest, None))
+    }
+
+    /// Creates a logger writing to `dest`.
+    fn logger(self, dest: Owned) -> io::Result<JsonlGzipLogger> {
+        let filter = self.filter.clone();
+        Ok(JsonlGzipLogger {
+            filter,
+            format: self.format()?,
+            dest: Arc::new(Mutex::new(dest)),
+        })
+    }
+
+    /// Installs `logger` as the global logger.
+    fn install(logger: JsonlGzipLogger, install: InstallOptions) -> Result<InitGuard, InitError> {
+        let target = Target::Dest(Arc::downgrade(&logger.dest), logger.format.on_error.clone());
+        let hub = logger.format.hub.clone();
+        let sampler = logger.format.sampler.clone();
+        let monitor = logger.monitor();
+        let generation = facade::install(Box::new(logger), install)?;
+        Ok(InitGuard::new(LoggerHandle::new(
+            generation, target, hub, sampler, monitor,
+        )))
+    }
+}
+
+impl LoggerBuilder {
+    /// Returns how the configured logger is installed.
+    pub(crate) fn install_options(&self) -> InstallOptions {
+        InstallOptions {
+            level: self.filter.max(),
+            filter: Some(self.filter.clone()),
+            tee: self.tee.clone(),
+            flush_interval: match self.dest.sync {
+                SyncPolicy::Every(interval) if !interval.is_zero() => Some(
+                    self.flush_interval
+                        .map_or(interval, |flush| flush.min(interval)),
+                ),
+                _ => self.flush_interval,
+            },
+        }
+    }
+
+    /// Returns the options for creating destinations, with the start time
+    /// for headers filled in.
+    pub(crate) fn dest_options(&self) -> DestOptions {
+        let mut options = self.dest.clone();
+        options.header = self.header.then(|| {
+            let now = SystemTime::now();
+            let start = now.checked_sub(self.clock.offset()).unwrap_or(now);
+            (start, self.run_id().to_string())
+        });
+        options
+    }
+
+    /// Returns the ID of the run this builder's logger logs, generating it
+    /// on first use.
+    fn run_id(&self) -> &str {
+        self.run_id.get_or_init(crate::id::uuid_v4)
+    }
+
+    /// Creates the log file at the given path and the configured destination
+    /// writing to it.
+    pub(crate) fn create<P: AsRef<Path>>(&self, path: P) -> io::Result<Dest> {
+        self.create_with(&self.dest_options(), path)
+    }
+
+    /// Creates the log file at the given path and a destination with the
+    /// given options writing to it.
+    fn create_with<P: AsRef<Path>>(&self, options: &DestOptions, path: P) -> io::Result<Dest> {
+        let mut dest = options.create(path)?;
+        if options.append {
+            let marker = LogEntry::session_marker(self.run_id().to_string());
+            dest.write_entry(&crate::core::serialize_entry(&marker)?)?;
+        }
+        Ok(dest)
+    }
+
+    /// Creates the configured serialization of entries.
+    pub(crate) fn format(self) -> io::Result<Format> {
+        let session = self.session_id.then(|| self.run_id().to_string());
+        #[allow(unused_mut)]
+        let mut recent = self.recent;
+        #[cfg(feature = "http")]
+        if let Some(http) = &self.http {
+            recent = recent.max(http.recent_capacity());
+        }
+        #[allow(unused_mut)]
+        let mut hub = (self.recent > 0).then(|| Arc::new(Hub::new(recent)));
+        #[cfg(feature = "http")]
+        let http = match &self.http {
+            Some(config) => {
+                let hub = hub.get_or_insert_with(|| Arc::new(Hub::new(recent)));
+                Some(crate::http::Server::start(config, hub.clone())?)
+            }
+            None => None,
+        };
+        Ok(Format {
+            clock: self.clock,
+            session,
+            pid: self.pid,
+            timestamps: self.timestamps,
+            source_location: self.source_location,
+            #[cfg(feature = "pii")]
+            pii: self.pii,
+            redactor: self.redactor,
+            max_body: self.max_body,
+            sampler: Arc::new(self.sampler.unwrap_or_default()),
+            dedup: self.collapse_repeats.then(Default::default),
+            rate_limit: self.rate_limit.map(crate::limit::RateLimit::new),
+            drops: Arc::new(crate::limit::Drops::new(self.drop_reports)),
+            #[cfg(feature = "encryption")]
+            body_cipher: self
+                .body_encryption
+                .as_ref()
+                .map(crate::encryption::BodyCipher::new)
+                .transpose()?,
+            on_error: self.on_error,
+            hub,
+            #[cfg(feature = "http")]
+            _http: http,
+        })
+    }
+}
+
+impl Default for LoggerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
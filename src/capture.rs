@@ -0,0 +1,111 @@
+//! Recording the output of child processes as log entries.
+
+use log::Level;
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+};
+
+/// Configuration for logging every line a child process writes to stdout
+/// and stderr.
+///
+/// Entries go to whichever logger is installed, like any other entry.
+///
+/// ```no_run
+/// # use jsonl_gzip_logger::capture::Capture;
+/// # use std::process::Command;
+/// let status = Capture::new("make")
+///     .run(Command::new("make").arg("all"))
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Capture {
+    target: String,
+    stdout_level: Level,
+    stderr_level: Level,
+}
+
+impl Capture {
+    /// Creates a configuration logging to the given target, with stdout
+    /// lines at [`Level::Info`] and stderr lines at [`Level::Warn`].
+    pub fn new(target: impl Into<String>) -> Self {
+        Self {
+            target: target.into(),
+            stdout_level: Level::Info,
+            stderr_level: Level::Warn,
+        }
+    }
+
+    /// Sets the level of lines written to stdout.
+    pub fn stdout_level(mut self, level: Level) -> Self {
+        self.stdout_level = level;
+        self
+    }
+
+    /// Sets the level of lines written to stderr.
+    pub fn stderr_level(mut self, level: Level) -> Self {
+        self.stderr_level = level;
+        self
+    }
+
+    /// Spawns the command with its stdout and stderr captured.
+    pub fn spawn(&self, command: &mut Command) -> io::Result<CapturedChild> {
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let readers = vec![
+            self.forward(child.stdout.take().unwrap(), self.stdout_level),
+            self.forward(child.stderr.take().unwrap(), self.stderr_level),
+        ];
+        Ok(CapturedChild { child, readers })
+    }
+
+    /// Runs the command to completion with its stdout and stderr captured.
+    pub fn run(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        self.spawn(command)?.wait()
+    }
+
+    /// Logs every line read from `source` on a new thread.
+    fn forward<R: Read + Send + 'static>(&self, source: R, level: Level) -> JoinHandle<()> {
+        let target = self.target.clone();
+        thread::spawn(move || {
+            let mut source = BufReader::new(source);
+            let mut line = Vec::new();
+            while matches!(source.read_until(b'\n', &mut line), Ok(len) if len > 0) {
+                let text = String::from_utf8_lossy(&line);
+                let text = text.trim_end_matches(['\n', '\r']);
+                log::log!(target: &target, level, "{}", text);
+                line.clear();
+            }
+        })
+    }
+}
+
+/// Child process whose output is being logged.
+pub struct CapturedChild {
+    child: Child,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl CapturedChild {
+    /// Returns the OS-assigned process identifier of the child.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Forces the child to exit.
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()
+    }
+
+    /// Waits for the child to exit and for all of its output to be logged.
+    pub fn wait(mut self) -> io::Result<ExitStatus> {
+        let status = self.child.wait()?;
+        for reader in self.readers.drain(..) {
+            let _ = reader.join();
+        }
+        Ok(status)
+    }
+}
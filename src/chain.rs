@@ -0,0 +1,158 @@
+//! Hash-chained entries for detecting deleted or reordered records.
+//!
+//! Chained entries carry an extra field, `"prev"`, holding the hex-encoded
+//! SHA-256 of the previous line as written. The first entry of a file links
+//! to a hash of all zeros. Entries removed from the end of a file cannot be
+//! detected this way; pair chaining with [`signing`](crate::signing) or an
+//! externally recorded last hash for that.
+//!
+//! Anyone can recompute a plain hash chain after editing a file. Chains
+//! keyed with [`LoggerBuilder::keyed_hash_chain`](crate::LoggerBuilder::keyed_hash_chain)
+//! link entries with an HMAC-SHA256 instead, which only holders of the key
+//! can recompute and verify with [`verify_keyed`].
+
+#[cfg(feature = "signing")]
+use crate::signing::SigningKey;
+#[cfg(feature = "signing")]
+use hmac::Mac;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{io::BufRead, path::Path};
+
+/// Link of the first entry in a file.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Links each written entry to the one before it.
+pub(crate) struct Chain {
+    prev: String,
+    /// Key of the HMAC linking entries instead of a plain hash.
+    #[cfg(feature = "signing")]
+    key: Option<SigningKey>,
+}
+
+impl Chain {
+    pub(crate) fn new() -> Self {
+        Self {
+            prev: GENESIS.to_string(),
+            #[cfg(feature = "signing")]
+            key: None,
+        }
+    }
+
+    /// Creates a chain linking entries with an HMAC keyed with the key.
+    #[cfg(feature = "signing")]
+    pub(crate) fn keyed(key: &SigningKey) -> Self {
+        Self {
+            key: Some(key.clone()),
+            ..Self::new()
+        }
+    }
+
+    /// Returns the entry, which must be a JSON object, with its link added.
+    pub(crate) fn link(&self, entry: &[u8]) -> Vec<u8> {
+        let mut linked = Vec::with_capacity(entry.len() + self.prev.len() + 11);
+        linked.extend_from_slice(&entry[..entry.len() - 1]);
+        linked.extend_from_slice(b",\"prev\":\"");
+        linked.extend_from_slice(self.prev.as_bytes());
+        linked.extend_from_slice(b"\"}");
+        linked
+    }
+
+    /// Records the final form of the line that was just written.
+    pub(crate) fn record(&mut self, line: &[u8]) {
+        #[cfg(feature = "signing")]
+        if let Some(key) = &self.key {
+            let mut mac = key.mac();
+            mac.update(line);
+            self.prev = crate::dest::hex(&mac.finalize().into_bytes());
+            return;
+        }
+        self.prev = crate::dest::hex(&Sha256::digest(line));
+    }
+}
+
+/// The part of an entry needed for verifying the chain.
+#[derive(Deserialize)]
+struct Link {
+    prev: Option<String>,
+}
+
+/// Result of verifying the hash chain of a log file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Verification {
+    /// Number of entries correctly linked to the previous entry.
+    pub valid: usize,
+    /// Line numbers (starting at 1) of entries without a link.
+    pub unchained: Vec<usize>,
+    /// Line numbers (starting at 1) of entries that do not link to the
+    /// line before them, meaning entries were removed, reordered or modified.
+    pub broken: Vec<usize>,
+}
+
+impl Verification {
+    /// Returns true if every entry is correctly linked.
+    pub fn is_valid(&self) -> bool {
+        self.unchained.is_empty() && self.broken.is_empty()
+    }
+}
+
+/// Verifies the hash chain of a chained .jsonl.gz log file.
+///
+/// A truncated last line is ignored, like when reading.
+pub fn verify<P: AsRef<Path>>(path: P) -> std::io::Result<Verification> {
+    verify_with(path.as_ref(), Chain::new())
+}
+
+/// Verifies the chain of a .jsonl.gz log file chained with
+/// [`LoggerBuilder::keyed_hash_chain`](crate::LoggerBuilder::keyed_hash_chain)
+/// and the given key.
+///
+/// A truncated last line is ignored, like when reading.
+#[cfg(feature = "signing")]
+pub fn verify_keyed<P: AsRef<Path>>(path: P, key: &SigningKey) -> std::io::Result<Verification> {
+    verify_with(path.as_ref(), Chain::keyed(key))
+}
+
+/// Verifies that every entry links to the previous one with the given
+/// chain.
+fn verify_with(path: &Path, mut chain: Chain) -> std::io::Result<Verification> {
+    let mut source = crate::read(path)?.source;
+    let mut verification = Verification::default();
+    let mut line = Vec::new();
+    let mut number = 0;
+    loop {
+        line.clear();
+        source.read_until(b'\n', &mut line)?;
+        if line.pop() != Some(b'\n') {
+            return Ok(verification);
+        }
+        number += 1;
+        match serde_json::from_slice::<Link>(&line) {
+            Ok(Link { prev: Some(prev) }) if prev == chain.prev => verification.valid += 1,
+            Ok(Link { prev: Some(_) }) => verification.broken.push(number),
+            _ => verification.unchained.push(number),
+        }
+        chain.record(&line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each link is the hash of the line recorded before it.
+    #[test]
+    fn links_previous_line() {
+        let mut chain = Chain::new();
+        let first = chain.link(b"{\"body\":\"a\"}");
+        assert_eq!(
+            format!("{{\"body\":\"a\",\"prev\":\"{}\"}}", GENESIS).as_bytes(),
+            &first[..]
+        );
+        chain.record(&first);
+        let second = chain.link(b"{\"body\":\"b\"}");
+        let expected = crate::dest::hex(&Sha256::digest(&first));
+        let link: Link = serde_json::from_slice(&second).unwrap();
+        assert_eq!(Some(expected), link.prev);
+    }
+}
@@ -0,0 +1,167 @@
+//! Positions in log files that reading can be resumed from.
+//!
+//! Iterators over gzip files opened with [`read`](crate::read) can export a
+//! [`Checkpoint`] after any entry, which can be stored and later passed to
+//! [`resume`] to continue with the next entry without decompressing the
+//! file from the start:
+//!
+//! ```no_run
+//! use jsonl_gzip_logger::checkpoint::{resume, Checkpoint};
+//!
+//! let mut entries = jsonl_gzip_logger::read("app.jsonl.gz").unwrap();
+//! entries.next();
+//! let saved = serde_json::to_string(&entries.checkpoint().unwrap()).unwrap();
+//!
+//! let checkpoint: Checkpoint = serde_json::from_str(&saved).unwrap();
+//! let rest = resume("app.jsonl.gz", &checkpoint).unwrap();
+//! ```
+
+use crate::{codec, LogEntryIter};
+use flate2::bufread::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// Position after an entry of a gzip log file, returned by
+/// [`LogEntryIter::checkpoint`].
+///
+/// Checkpoints only apply to the file they were taken from, and stay valid
+/// while it is appended to. They serialize with `serde`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Position of the gzip member containing the next entry.
+    pub(crate) position: u64,
+    /// Uncompressed bytes of the member before the next entry.
+    pub(crate) skip: u64,
+}
+
+/// Opens the gzip log file at `path` to be read from the checkpoint on.
+///
+/// The checkpoint must have been taken from the same file. Resuming starts
+/// decompressing at the gzip member the checkpoint is in, so files split
+/// into members with
+/// [`LoggerBuilder::gzip_member_entries`](crate::LoggerBuilder::gzip_member_entries)
+/// resume faster.
+pub fn resume<P: AsRef<Path>>(path: P, checkpoint: &Checkpoint) -> io::Result<LogEntryIter> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(checkpoint.position))?;
+    let mut entries = LogEntryIter::detect_at(file, checkpoint.position)?;
+    entries.skip_bytes(checkpoint.skip)?;
+    Ok(entries)
+}
+
+/// Where the members of a gzip stream start, as uncompressed and compressed
+/// positions, shared between the stream's reader and its iterator.
+#[derive(Clone, Default)]
+pub(crate) struct Boundaries(Arc<Mutex<VecDeque<(u64, u64)>>>);
+
+impl Boundaries {
+    /// Records a member starting at the given positions.
+    fn push(&self, decoded: u64, position: u64) {
+        self.0.lock().unwrap().push_back((decoded, position));
+    }
+
+    /// Returns the checkpoint at the given uncompressed position, which
+    /// must not be before the last one asked for.
+    pub(crate) fn checkpoint(&self, consumed: u64) -> Option<Checkpoint> {
+        let mut boundaries = self.0.lock().unwrap();
+        // earlier members are not needed for this or later checkpoints
+        while boundaries
+            .get(1)
+            .is_some_and(|&(decoded, _)| decoded <= consumed)
+        {
+            boundaries.pop_front();
+        }
+        let &(decoded, position) = boundaries.front()?;
+        Some(Checkpoint {
+            position,
+            skip: consumed - decoded,
+        })
+    }
+}
+
+/// Decoder of a gzip stream's members, one after another, recording where
+/// each starts.
+pub(crate) struct Members {
+    /// Decoder of the current member, only missing while starting the next
+    /// one.
+    decoder: Option<GzDecoder<BufReader<Counting>>>,
+    /// Uncompressed bytes read so far.
+    decoded: u64,
+    boundaries: Boundaries,
+}
+
+impl Members {
+    /// Starts decoding a stream whose first member is at `position` in its
+    /// file.
+    pub(crate) fn new(source: Box<dyn Read + Send>, position: u64) -> (Self, Boundaries) {
+        let boundaries = Boundaries::default();
+        boundaries.push(0, position);
+        let source = BufReader::new(Counting { source, position });
+        let members = Self {
+            decoder: Some(GzDecoder::new(source)),
+            decoded: 0,
+            boundaries: boundaries.clone(),
+        };
+        (members, boundaries)
+    }
+}
+
+impl Read for Members {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let decoder = self
+                .decoder
+                .as_mut()
+                .expect("a member is always being read");
+            let read = decoder.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                self.decoded += read as u64;
+                return Ok(read);
+            }
+            // the member ended, and the stream either ends too or continues
+            // with the next one
+            let source = decoder.get_mut();
+            if source.fill_buf()?.is_empty() {
+                return Ok(0);
+            }
+            let position = source.get_ref().position - source.buffer().len() as u64;
+            let decoder = self.decoder.take().expect("a member is always being read");
+            self.decoder = Some(GzDecoder::new(decoder.into_inner()));
+            self.boundaries.push(self.decoded, position);
+        }
+    }
+}
+
+/// Reader keeping track of its position in the file it reads.
+struct Counting {
+    source: Box<dyn Read + Send>,
+    position: u64,
+}
+
+impl Read for Counting {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.source.read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+/// Returns a reader of the source's uncompressed entries, and the
+/// boundaries of its members if it is a gzip stream.
+pub(crate) fn decode(
+    source: Box<dyn Read + Send>,
+    position: u64,
+) -> io::Result<(Box<dyn Read + Send>, Option<Boundaries>)> {
+    let (format, source) = codec::sniff(source)?;
+    if format == codec::Format::Gzip {
+        let (members, boundaries) = Members::new(source, position);
+        return Ok((Box::new(members), Some(boundaries)));
+    }
+    Ok((codec::decoder(format, source)?, None))
+}
@@ -0,0 +1,167 @@
+//! Time sources for log entry offsets.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Source of the offsets recorded in log entries.
+pub trait Clock: Send + Sync {
+    /// Returns the time elapsed since logging started.
+    fn offset(&self) -> Duration;
+}
+
+/// The clock used unless another one is configured.
+///
+/// `wasm32-unknown-unknown` has no time source in `std`, so offsets there stay
+/// at zero unless a [`Clock`] backed by the host, such as one reading
+/// `performance.now()`, is configured.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) type DefaultClock = MonotonicClock;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) type DefaultClock = ManualClock;
+
+/// Clock backed by [`Instant`], measuring from its creation.
+///
+/// This is the clock used unless another one is configured, except on
+/// `wasm32-unknown-unknown` where [`Instant`] is unavailable.
+#[derive(Clone, Copy, Debug)]
+pub struct MonotonicClock {
+    start: Instant,
+}
+
+impl MonotonicClock {
+    /// Creates a clock that starts at zero now.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn offset(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock reading a time cached by a background thread.
+///
+/// Reading the clock is a single atomic load, which is cheaper than
+/// [`Instant::now`] for extreme logging rates, at the cost of offsets only
+/// being as precise as the update interval. The thread stops once every
+/// clone of the clock has been dropped.
+#[derive(Clone, Debug)]
+pub struct CoarseClock {
+    nanos: Arc<AtomicU64>,
+}
+
+/// Finest resolution of a [`CoarseClock`], so that its thread does not spin.
+const MIN_RESOLUTION: Duration = Duration::from_millis(1);
+
+impl CoarseClock {
+    /// Creates a clock that starts at zero now and is updated every
+    /// `resolution`.
+    ///
+    /// Resolutions below one millisecond, including zero, are raised to one
+    /// millisecond.
+    pub fn new(resolution: Duration) -> Self {
+        let resolution = resolution.max(MIN_RESOLUTION);
+        let nanos = Arc::new(AtomicU64::new(0));
+        let cached = Arc::downgrade(&nanos);
+        let start = Instant::now();
+        thread::spawn(move || update(cached, start, resolution));
+        Self { nanos }
+    }
+}
+
+/// Stores the time since `start` in `cached` every `resolution` while the
+/// clock exists.
+fn update(cached: Weak<AtomicU64>, start: Instant, resolution: Duration) {
+    while let Some(nanos) = cached.upgrade() {
+        nanos.store(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        drop(nanos);
+        thread::sleep(resolution);
+    }
+}
+
+impl Clock for CoarseClock {
+    fn offset(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// Clock that only moves when told to.
+///
+/// Useful for deterministic offsets in tests and simulations, and for replay
+/// tools that set the clock to each historical offset before logging it.
+/// Clones share the same time.
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    offset: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the offset reported by the clock.
+    pub fn set(&self, offset: Duration) {
+        *self.offset.lock().unwrap() = offset;
+    }
+
+    /// Moves the clock forward by the given amount.
+    pub fn advance(&self, by: Duration) {
+        *self.offset.lock().unwrap() += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn offset(&self) -> Duration {
+        *self.offset.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A manual clock only reports the times it was given, shared by clones.
+    #[test]
+    fn manual_clock() {
+        let clock = ManualClock::new();
+        assert_eq!(Duration::ZERO, clock.offset());
+        clock.set(Duration::from_secs(5));
+        clock.clone().advance(Duration::from_millis(250));
+        assert_eq!(Duration::from_millis(5250), clock.offset());
+    }
+
+    /// A coarse clock advances as its thread updates it.
+    #[test]
+    fn coarse_clock() {
+        let clock = CoarseClock::new(Duration::from_millis(1));
+        let first = clock.offset();
+        thread::sleep(Duration::from_millis(50));
+        assert!(clock.offset() > first);
+    }
+
+    /// A monotonic clock never goes backwards.
+    #[test]
+    fn monotonic_clock() {
+        let clock = MonotonicClock::new();
+        let first = clock.offset();
+        assert!(clock.offset() >= first);
+    }
+}
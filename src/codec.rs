@@ -0,0 +1,323 @@
+//! Compression formats that log files can be written in.
+//!
+//! Files are gzip by default. A [`Codec`] given to
+//! [`LoggerBuilder::codec`](crate::LoggerBuilder::codec) compresses them in
+//! another format, and the same codec reads them back with
+//! [`read_with_codec`](crate::read_with_codec):
+//!
+//! ```
+//! use jsonl_gzip_logger::{
+//!     codec::{Codec, Compressor},
+//!     core::Encoder,
+//!     LogEntryIter,
+//! };
+//! use std::io::{self, Cursor, Read, Write};
+//!
+//! /// Stores entries uncompressed.
+//! struct Plain;
+//!
+//! struct PlainWriter(Box<dyn Write + Send>);
+//!
+//! impl Write for PlainWriter {
+//!     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+//!         self.0.write(buf)
+//!     }
+//!
+//!     fn flush(&mut self) -> io::Result<()> {
+//!         self.0.flush()
+//!     }
+//! }
+//!
+//! impl Compressor<Box<dyn Write + Send>> for PlainWriter {
+//!     fn get_ref(&self) -> &Box<dyn Write + Send> {
+//!         &self.0
+//!     }
+//!
+//!     fn finish(self: Box<Self>) -> io::Result<Box<dyn Write + Send>> {
+//!         Ok(self.0)
+//!     }
+//! }
+//!
+//! impl Codec for Plain {
+//!     fn compress(
+//!         &self,
+//!         sink: Box<dyn Write + Send>,
+//!     ) -> io::Result<Box<dyn Compressor<Box<dyn Write + Send>>>> {
+//!         Ok(Box::new(PlainWriter(sink)))
+//!     }
+//!
+//!     fn decompress(&self, source: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+//!         Ok(source)
+//!     }
+//! }
+//!
+//! let mut encoder = Encoder::with_codec(Box::new(Vec::new()), &Plain).unwrap();
+//! encoder
+//!     .write_line(br#"{"offset":{"secs":0,"nanos":0},"level":"INFO","target":"a","body":"hi"}"#)
+//!     .unwrap();
+//! encoder.finish().unwrap();
+//!
+//! let line = br#"{"offset":{"secs":0,"nanos":0},"level":"INFO","target":"a","body":"hi"}
+//! "#;
+//! let entries: Vec<_> = LogEntryIter::with_codec(Cursor::new(line), &Plain)
+//!     .unwrap()
+//!     .collect();
+//! assert_eq!("hi", entries[0].body);
+//! ```
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use std::io::{self, Cursor, Read, Write};
+
+/// First bytes of a gzip member.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// First bytes of a zstd frame.
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+/// First bytes of an LZ4 frame.
+const LZ4_MAGIC: &[u8] = &[0x04, 0x22, 0x4d, 0x18];
+
+/// A compression format for log files.
+///
+/// Codecs are shared between threads, so any state they keep must be
+/// synchronized.
+pub trait Codec: Send + Sync {
+    /// Wraps the sink in a writer compressing everything written to it.
+    fn compress(
+        &self,
+        sink: Box<dyn Write + Send>,
+    ) -> io::Result<Box<dyn Compressor<Box<dyn Write + Send>>>>;
+
+    /// Wraps the source in a reader decompressing everything read from it.
+    ///
+    /// Streams written to the same file one after another, such as by
+    /// [`LoggerBuilder::append`](crate::LoggerBuilder::append), should be
+    /// read as one.
+    fn decompress(&self, source: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>>;
+}
+
+/// Writer returned by [`Codec::compress`], compressing into a sink of type
+/// `W`.
+///
+/// Flushing must write everything written so far through to the sink in a
+/// form that can be decompressed, so that flushed entries can be read while
+/// the file is still being written.
+pub trait Compressor<W>: Write + Send {
+    /// Returns a reference to the sink.
+    fn get_ref(&self) -> &W;
+
+    /// Ends the compressed stream and returns the sink.
+    ///
+    /// Loggers drop their compressor instead of finishing it when they are
+    /// dropped, so compressors whose streams need ending should also end
+    /// them when dropped.
+    fn finish(self: Box<Self>) -> io::Result<W>;
+}
+
+/// The default codec, writing gzip members at the given level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gzip {
+    /// Compression level, from 0 (none) to 9 (smallest files).
+    pub level: u32,
+}
+
+impl Default for Gzip {
+    /// The fastest level that compresses.
+    fn default() -> Self {
+        Self { level: 1 }
+    }
+}
+
+impl Codec for Gzip {
+    fn compress(
+        &self,
+        sink: Box<dyn Write + Send>,
+    ) -> io::Result<Box<dyn Compressor<Box<dyn Write + Send>>>> {
+        Ok(Box::new(GzEncoder::new(
+            sink,
+            Compression::new(self.level.min(9)),
+        )))
+    }
+
+    fn decompress(&self, source: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(MultiGzDecoder::new(source)))
+    }
+}
+
+impl<W: Write + Send> Compressor<W> for GzEncoder<W> {
+    fn get_ref(&self) -> &W {
+        GzEncoder::get_ref(self)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<W> {
+        GzEncoder::finish(*self)
+    }
+}
+
+/// LZ4 frames, which cost far less CPU than gzip for larger files.
+#[cfg(feature = "lz4")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Lz4;
+
+#[cfg(feature = "lz4")]
+impl Codec for Lz4 {
+    fn compress(
+        &self,
+        sink: Box<dyn Write + Send>,
+    ) -> io::Result<Box<dyn Compressor<Box<dyn Write + Send>>>> {
+        Ok(Box::new(Lz4Writer::new(sink)))
+    }
+
+    fn decompress(&self, source: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(lz4_flex::frame::FrameDecoder::new(source)))
+    }
+}
+
+/// LZ4 frame encoder that, like [`GzEncoder`], finishes the frame when
+/// dropped, so that destinations are complete once their logger is gone.
+#[cfg(feature = "lz4")]
+pub(crate) struct Lz4Writer<W: Write>(Option<lz4_flex::frame::FrameEncoder<W>>);
+
+#[cfg(feature = "lz4")]
+impl<W: Write> Lz4Writer<W> {
+    /// Starts a frame written to the sink.
+    pub(crate) fn new(sink: W) -> Self {
+        Self(Some(lz4_flex::frame::FrameEncoder::new(sink)))
+    }
+
+    fn encoder(&mut self) -> &mut lz4_flex::frame::FrameEncoder<W> {
+        self.0.as_mut().expect("encoder used after finishing")
+    }
+
+    /// Returns a reference to the sink.
+    pub(crate) fn get_ref(&self) -> &W {
+        self.0
+            .as_ref()
+            .expect("encoder used after finishing")
+            .get_ref()
+    }
+
+    /// Ends the frame and returns the sink.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        let encoder = self.0.take().expect("encoder used after finishing");
+        Ok(encoder.finish()?)
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl<W: Write> Write for Lz4Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder().flush()
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl<W: Write + Send> Compressor<W> for Lz4Writer<W> {
+    fn get_ref(&self) -> &W {
+        Lz4Writer::get_ref(self)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<W> {
+        Lz4Writer::finish(*self)
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl<W: Write> Drop for Lz4Writer<W> {
+    fn drop(&mut self) {
+        if let Some(encoder) = &mut self.0 {
+            // errors cannot be reported from a destructor, as with gzip
+            let _ = encoder.try_finish();
+        }
+    }
+}
+
+/// Format of a log file, as told by its first bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Gzip,
+    Zstd,
+    Lz4,
+    /// Anything else, taken to be uncompressed text.
+    Plain,
+}
+
+/// Identifies the format of the source from its first bytes, and returns it
+/// with the source still starting at them.
+pub(crate) fn sniff(
+    mut source: Box<dyn Read + Send>,
+) -> io::Result<(Format, Box<dyn Read + Send>)> {
+    let mut magic = Vec::with_capacity(4);
+    source.by_ref().take(4).read_to_end(&mut magic)?;
+    let format = if magic.starts_with(GZIP_MAGIC) {
+        Format::Gzip
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Format::Zstd
+    } else if magic.starts_with(LZ4_MAGIC) {
+        Format::Lz4
+    } else {
+        Format::Plain
+    };
+    Ok((format, Box::new(Cursor::new(magic).chain(source))))
+}
+
+/// Wraps the source in a decoder for the given format.
+///
+/// Fails if the format needs a feature that is not enabled.
+pub(crate) fn decoder(
+    format: Format,
+    source: Box<dyn Read + Send>,
+) -> io::Result<Box<dyn Read + Send>> {
+    match format {
+        Format::Gzip => Gzip::default().decompress(source),
+        #[cfg(feature = "zstd")]
+        Format::Zstd => Ok(Box::new(zstd::stream::read::Decoder::new(source)?)),
+        #[cfg(not(feature = "zstd"))]
+        Format::Zstd => Err(unsupported("zstd")),
+        #[cfg(feature = "lz4")]
+        Format::Lz4 => Lz4.decompress(source),
+        #[cfg(not(feature = "lz4"))]
+        Format::Lz4 => Err(unsupported("lz4")),
+        Format::Plain => Ok(source),
+    }
+}
+
+/// Error for a format that needs the given feature.
+#[cfg(not(all(feature = "zstd", feature = "lz4")))]
+fn unsupported(feature: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("reading this file needs the `{}` feature", feature),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gzip streams and plain text are told apart by their first bytes.
+    #[test]
+    fn detects_formats() {
+        let line = b"{\"body\":\"hi\"}\n".to_vec();
+        let mut compressed = GzEncoder::new(Vec::new(), Compression::fast());
+        compressed.write_all(&line).unwrap();
+        let compressed = compressed.finish().unwrap();
+
+        for bytes in [compressed, line.clone(), Vec::new()] {
+            let expected = if bytes.is_empty() {
+                Vec::new()
+            } else {
+                line.clone()
+            };
+            let mut read = Vec::new();
+            let (format, source) = sniff(Box::new(Cursor::new(bytes))).unwrap();
+            decoder(format, source)
+                .unwrap()
+                .read_to_end(&mut read)
+                .unwrap();
+            assert_eq!(expected, read);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! Collecting entries from many processes into one log file.
+//!
+//! A [`Collector`] listens on a Unix domain socket and writes every entry it
+//! receives to a single .jsonl.gz file. Processes send their entries by
+//! installing a client logger with [`LoggerBuilder::init_client`], which
+//! writes each entry to the socket as one JSON line.
+//!
+//! Offsets are replaced with the collector's clock when entries arrive, so all
+//! processes share one timeline. Partial lines from clients that exit mid-write
+//! are discarded, keeping the file well-formed, as are lines longer than 1 MiB.
+//! At most 256 clients are served at once; further clients are disconnected.
+
+use crate::{
+    dest::Dest,
+    facade,
+    filter::Filter,
+    format::{ErrorHook, Format},
+    limit::DropCause,
+    InitError, LogEntry, LoggerBuilder,
+};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// How often idle connections check whether the collector is stopping.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Longest line read from a client, including the newline.
+const MAX_LINE_LEN: usize = 1024 * 1024;
+/// Most clients served at once.
+const MAX_CONNECTIONS: usize = 256;
+
+/// State shared between the collector and its connection threads.
+struct Shared {
+    clock: Box<dyn crate::clock::Clock>,
+    dest: Mutex<Option<Dest>>,
+    stopping: AtomicBool,
+    on_error: ErrorHook,
+}
+
+impl Shared {
+    fn dest(&self) -> MutexGuard<'_, Option<Dest>> {
+        self.dest.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// Server writing the entries of all connected clients to one log file.
+///
+/// Dropping the collector stops it and finishes the file; use
+/// [`Collector::finish`] to also observe errors.
+pub struct Collector {
+    socket: PathBuf,
+    shared: Arc<Shared>,
+    acceptor: Option<JoinHandle<Vec<JoinHandle<()>>>>,
+}
+
+impl LoggerBuilder {
+    /// Starts a collector listening on the Unix socket at `socket` that writes
+    /// the entries of all clients to a new .jsonl.gz file at `path`.
+    ///
+    /// The level of the builder is ignored, as clients filter their own
+    /// entries. Entries that cannot be written, and clients turned away, are
+    /// passed to the [error hook](Self::on_error). The socket file must not
+    /// already exist.
+    pub fn collector<P: AsRef<Path>, Q: AsRef<Path>>(
+        self,
+        socket: P,
+        path: Q,
+    ) -> io::Result<Collector> {
+        let listener = UnixListener::bind(&socket)?;
+        let dest = self.create(path)?;
+        let shared = Arc::new(Shared {
+            clock: self.clock,
+            dest: Mutex::new(Some(dest)),
+            stopping: AtomicBool::new(false),
+            on_error: self.on_error,
+        });
+        let acceptor = {
+            let shared = shared.clone();
+            thread::spawn(move || accept(listener, shared))
+        };
+        Ok(Collector {
+            socket: socket.as_ref().to_path_buf(),
+            shared,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    /// Creates and installs a global logger that sends its entries to the
+    /// [`Collector`] listening on the Unix socket at `socket`.
+    ///
+    /// Each entry is written to the socket immediately, so nothing is lost
+    /// when a short-lived process exits without calling [`deinit`](crate::deinit).
+    /// Dropping the returned guard uninstalls the logger.
+    pub fn init_client<P: AsRef<Path>>(self, socket: P) -> Result<ClientGuard, InitError> {
+        facade::ensure_uninstalled()?;
+        let stream = UnixStream::connect(socket)?;
+        let (filter, install) = (self.filter.clone(), self.install_options());
+        let logger = ClientLogger {
+            filter,
+            format: self.format()?,
+            stream: Mutex::new(stream),
+            pid: crate::fork::current_pid(),
+        };
+        let generation = facade::install(Box::new(logger), install)?;
+        Ok(ClientGuard { generation })
+    }
+}
+
+/// Guard that uninstalls the client logger when dropped.
+#[must_use = "dropping the guard immediately stops logging"]
+pub struct ClientGuard {
+    /// Generation of the logger in the facade.
+    generation: u64,
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        facade::uninstall(Some(self.generation));
+    }
+}
+
+impl Collector {
+    /// Flushes every entry received so far to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        match &mut *self.shared.dest() {
+            Some(dest) => dest.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops accepting clients, waits for connected clients to disconnect or
+    /// go idle, and finishes the file.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        let acceptor = match self.acceptor.take() {
+            Some(acceptor) => acceptor,
+            None => return Ok(()),
+        };
+        self.shared.stopping.store(true, Ordering::SeqCst);
+        // wake the acceptor up so it notices it is stopping
+        let _ = UnixStream::connect(&self.socket);
+        for connection in acceptor.join().unwrap_or_default() {
+            let _ = connection.join();
+        }
+        let _ = std::fs::remove_file(&self.socket);
+        let dest = self.shared.dest().take();
+        match dest {
+            Some(dest) => dest.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Accepts clients until stopping, returning their connection threads.
+fn accept(listener: UnixListener, shared: Arc<Shared>) -> Vec<JoinHandle<()>> {
+    let mut connections = Vec::new();
+    let mut draining = false;
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                connections.retain(|connection: &JoinHandle<()>| !connection.is_finished());
+                if connections.len() >= MAX_CONNECTIONS {
+                    let message = "too many clients connected to the collector";
+                    (shared.on_error)(&io::Error::other(message));
+                    continue;
+                }
+                let shared = shared.clone();
+                connections.push(thread::spawn(move || receive(stream, &shared)));
+            }
+            // everything that connected before stopping has been accepted
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+            Err(_) => {}
+        }
+        // clients queued before the collector was stopped are still served
+        if !draining && shared.stopping.load(Ordering::SeqCst) {
+            if listener.set_nonblocking(true).is_err() {
+                break;
+            }
+            draining = true;
+        }
+    }
+    connections
+}
+
+/// Writes the entries sent by one client until it disconnects, or until the
+/// collector is stopping and the client is idle.
+fn receive(stream: UnixStream, shared: &Shared) {
+    if stream.set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+        return;
+    }
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    // whether the current line is the end of one that was too long
+    let mut skipping = false;
+    loop {
+        let limit = (MAX_LINE_LEN - line.len()) as u64;
+        match (&mut reader).take(limit).read_until(b'\n', &mut line) {
+            Ok(_) if line.last() == Some(&b'\n') => {
+                if !std::mem::take(&mut skipping) {
+                    write(&line, shared);
+                }
+                line.clear();
+            }
+            Ok(_) if line.len() == MAX_LINE_LEN => {
+                skipping = true;
+                line.clear();
+            }
+            // a partial line at the end is from a client that exited mid-write
+            Ok(_) => return,
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                // bytes read before the timeout stay in `line`
+                if shared.stopping.load(Ordering::SeqCst) {
+                    return;
+                }
+            }
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => return,
+        }
+    }
+}
+
+/// Writes a line received from a client to the file, with the collector's
+/// offset. Lines that are not entries are discarded.
+fn write(line: &[u8], shared: &Shared) {
+    let Ok(mut entry) = serde_json::from_slice::<LogEntry>(line) else {
+        return;
+    };
+    entry.offset = shared.clock.offset();
+    let result = serde_json::to_vec(&entry)
+        .map_err(io::Error::from)
+        .and_then(|entry| match &mut *shared.dest() {
+            Some(dest) => dest.write_entry(&entry),
+            None => Ok(()),
+        });
+    if let Err(error) = result {
+        (shared.on_error)(&error);
+    }
+}
+
+/// Logger that sends its entries to a [`Collector`].
+struct ClientLogger {
+    filter: Filter,
+    format: Format,
+    stream: Mutex<UnixStream>,
+    /// The process owning the connection, see [`ForkPolicy`](crate::ForkPolicy).
+    pid: u32,
+}
+
+impl log::Log for ClientLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // forked children share the parent's connection
+        self.filter.enabled(metadata) && crate::fork::current_pid() == self.pid
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            self.format.entries(record, |mut line| {
+                line.push(b'\n');
+                if let Ok(mut stream) = self.stream.lock() {
+                    // the collector going away must not break the client
+                    if stream.write_all(&line).is_err() {
+                        self.format.drops.count(DropCause::Io);
+                    }
+                }
+            });
+        }
+    }
+
+    fn flush(&self) {
+        if crate::fork::current_pid() != self.pid {
+            return;
+        }
+        self.format.pending(|mut line| {
+            line.push(b'\n');
+            if let Ok(mut stream) = self.stream.lock() {
+                if stream.write_all(&line).is_err() {
+                    self.format.drops.count(DropCause::Io);
+                }
+            }
+        });
+    }
+}
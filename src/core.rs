@@ -0,0 +1,406 @@
+//! Log writer without filesystem assumptions.
+//!
+//! [`Writer`] turns log records into a compressed JSON lines stream written
+//! to any [`Write`] sink, with offsets from any [`Clock`]. It does not
+//! install a global logger, so it can be used on platforms without files
+//! and driven directly in tests. The loggers installed by this crate are
+//! built on the same [`Encoder`].
+//!
+//! ```
+//! use jsonl_gzip_logger::{clock::ManualClock, core::Writer, LogEntryIter};
+//! use log::Level;
+//! use std::io::Cursor;
+//!
+//! let mut writer = Writer::with_clock(Vec::new(), ManualClock::new());
+//! writer
+//!     .log(
+//!         &log::Record::builder()
+//!             .level(Level::Info)
+//!             .target("app")
+//!             .args(format_args!("hello"))
+//!             .build(),
+//!     )
+//!     .unwrap();
+//! let bytes = writer.finish().unwrap();
+//! let entries: Vec<_> = LogEntryIter::new(Cursor::new(bytes)).collect();
+//! assert_eq!("hello", entries[0].body);
+//! ```
+
+#[cfg(feature = "lz4")]
+use crate::codec::Lz4Writer;
+use crate::{
+    clock::{Clock, DefaultClock},
+    codec::{Codec, Compressor},
+    LogEntry, LogEntryArgs,
+};
+use flate2::{write::GzEncoder, Compression};
+use std::io::{self, Write};
+
+/// Compresses serialized entries into a sink, one JSON object per line.
+pub struct Encoder<W: Write> {
+    encoder: Inner<W>,
+}
+
+/// Compressed stream an [`Encoder`] writes.
+enum Inner<W: Write> {
+    Gzip(Members<W>),
+    #[cfg(feature = "lz4")]
+    Lz4(Lz4Writer<W>),
+    Custom(Box<dyn Compressor<W>>),
+    /// Entries written as they are, to be compressed later.
+    Plain(W),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Creates an encoder compressing into the given sink with the fastest
+    /// compression level.
+    pub fn new(sink: W) -> Self {
+        Self {
+            encoder: Inner::Gzip(Members::new(sink, Compression::fast())),
+        }
+    }
+
+    /// Creates an encoder compressing into the given sink at the given
+    /// level, from 0 (none) to 9 (best).
+    pub fn with_level(sink: W, level: u32) -> Self {
+        Self {
+            encoder: Inner::Gzip(Members::new(sink, Compression::new(level.min(9)))),
+        }
+    }
+
+    /// Creates an encoder compressing into the given sink as an LZ4 frame
+    /// instead of gzip, which costs far less CPU for a larger file.
+    ///
+    /// Read the result with [`LogEntryIter::lz4`](crate::LogEntryIter::lz4).
+    #[cfg(feature = "lz4")]
+    pub fn lz4(sink: W) -> Self {
+        Self {
+            encoder: Inner::Lz4(Lz4Writer::new(sink)),
+        }
+    }
+
+    /// Creates an encoder writing entries to the given sink uncompressed.
+    pub(crate) fn plain(sink: W) -> Self {
+        Self {
+            encoder: Inner::Plain(sink),
+        }
+    }
+
+    /// Returns the compressed stream as a writer.
+    fn writer(&mut self) -> &mut dyn Write {
+        match &mut self.encoder {
+            Inner::Gzip(encoder) => encoder,
+            #[cfg(feature = "lz4")]
+            Inner::Lz4(encoder) => encoder,
+            Inner::Custom(encoder) => encoder,
+            Inner::Plain(sink) => sink,
+        }
+    }
+
+    /// Writes one serialized entry, which must be a JSON object without a
+    /// trailing newline.
+    ///
+    /// Fails without writing anything if the line contains a newline, which
+    /// would split the entry across lines.
+    pub fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if line.contains(&b'\n') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "entry contains a newline",
+            ));
+        }
+        let writer = self.writer();
+        writer.write_all(line)?;
+        writer.write_all(b"\n")
+    }
+
+    /// Flushes everything written so far through to the sink.
+    ///
+    /// Gzip streams end the current deflate block with a sync flush
+    /// (`Z_SYNC_FLUSH`) and LZ4 frames end the current block, so a reader of
+    /// the unfinished stream can decode every entry written before the
+    /// flush.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer().flush()
+    }
+
+    /// Ends the current gzip member and starts a new one, so that the
+    /// stream can be decoded from the next entry on without the ones before,
+    /// which allows skipping through large files and decoding their parts in
+    /// parallel.
+    ///
+    /// The ended member is flushed through to the sink. Readers of this
+    /// crate read the members as one stream. Does nothing for other formats.
+    pub fn end_member(&mut self) -> io::Result<()> {
+        match &mut self.encoder {
+            Inner::Gzip(encoder) => encoder.end(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns a reference to the sink.
+    pub fn get_ref(&self) -> &W {
+        match &self.encoder {
+            Inner::Gzip(encoder) => encoder.get_ref(),
+            #[cfg(feature = "lz4")]
+            Inner::Lz4(encoder) => encoder.get_ref(),
+            Inner::Custom(encoder) => encoder.get_ref(),
+            Inner::Plain(sink) => sink,
+        }
+    }
+
+    /// Finishes the compressed stream and returns the sink.
+    pub fn finish(self) -> io::Result<W> {
+        match self.encoder {
+            Inner::Gzip(encoder) => encoder.finish(),
+            #[cfg(feature = "lz4")]
+            Inner::Lz4(encoder) => encoder.finish(),
+            Inner::Custom(encoder) => encoder.finish(),
+            Inner::Plain(sink) => Ok(sink),
+        }
+    }
+}
+
+impl Encoder<Box<dyn Write + Send>> {
+    /// Creates an encoder compressing into the given sink with a custom
+    /// codec instead of gzip.
+    pub fn with_codec(sink: Box<dyn Write + Send>, codec: &dyn Codec) -> io::Result<Self> {
+        Ok(Self {
+            encoder: Inner::Custom(codec.compress(sink)?),
+        })
+    }
+}
+
+/// Gzip encoder that can end its member and continue in a new one.
+struct Members<W: Write> {
+    /// Encoder of the current member, only missing while starting the next
+    /// one.
+    encoder: Option<GzEncoder<W>>,
+    level: Compression,
+}
+
+impl<W: Write> Members<W> {
+    fn new(sink: W, level: Compression) -> Self {
+        Self {
+            encoder: Some(GzEncoder::new(sink, level)),
+            level,
+        }
+    }
+
+    fn encoder(&mut self) -> &mut GzEncoder<W> {
+        self.encoder
+            .as_mut()
+            .expect("a member is always being written")
+    }
+
+    fn get_ref(&self) -> &W {
+        let encoder = self.encoder.as_ref();
+        encoder.expect("a member is always being written").get_ref()
+    }
+
+    /// Ends the current member and starts the next one.
+    fn end(&mut self) -> io::Result<()> {
+        self.encoder().try_finish()?;
+        self.encoder().get_mut().flush()?;
+        // finishing again writes nothing, so it cannot fail and lose the
+        // sink
+        let sink = self.finish_member()?;
+        self.encoder = Some(GzEncoder::new(sink, self.level));
+        Ok(())
+    }
+
+    fn finish_member(&mut self) -> io::Result<W> {
+        let encoder = self.encoder.take();
+        encoder.expect("a member is always being written").finish()
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.finish_member()
+    }
+}
+
+impl<W: Write> Write for Members<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder().flush()
+    }
+}
+
+/// Writes log records to a sink as a compressed JSON lines stream.
+pub struct Writer<W: Write, C: Clock = DefaultClock> {
+    encoder: Encoder<W>,
+    clock: C,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a writer with offsets measured from now.
+    pub fn new(sink: W) -> Self {
+        Self::with_clock(sink, DefaultClock::new())
+    }
+}
+
+impl<W: Write, C: Clock> Writer<W, C> {
+    /// Creates a writer with offsets from the given clock.
+    pub fn with_clock(sink: W, clock: C) -> Self {
+        Self {
+            encoder: Encoder::new(sink),
+            clock,
+        }
+    }
+
+    /// Writes an entry for the record, regardless of its level.
+    pub fn log(&mut self, record: &log::Record) -> io::Result<()> {
+        let fields = crate::kv::fields(record);
+        let entry = serialize(&LogEntryArgs {
+            offset: self.clock.offset(),
+            level: record.level(),
+            target: record.target(),
+            body: *record.args(),
+            session: None,
+            pid: None,
+            time: None,
+            module_path: record.module_path(),
+            file: record.file(),
+            line: record.line(),
+            fields: fields.as_ref(),
+            truncated: false,
+        })?;
+        self.encoder.write_line(&entry)
+    }
+
+    /// Writes an existing entry unchanged, ignoring the clock.
+    pub fn write_entry(&mut self, entry: &LogEntry) -> io::Result<()> {
+        self.encoder.write_line(&serialize_entry(entry)?)
+    }
+
+    /// Flushes everything written so far through to the sink.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+
+    /// Ends the current gzip member and starts a new one, as
+    /// [`Encoder::end_member`] does.
+    pub fn end_member(&mut self) -> io::Result<()> {
+        self.encoder.end_member()
+    }
+
+    /// Returns a reference to the sink.
+    pub fn get_ref(&self) -> &W {
+        self.encoder.get_ref()
+    }
+
+    /// Finishes the compressed stream and returns the sink.
+    pub fn finish(self) -> io::Result<W> {
+        self.encoder.finish()
+    }
+}
+
+/// Serializes an existing entry as a JSON object without a trailing newline.
+pub(crate) fn serialize_entry(entry: &LogEntry) -> io::Result<Vec<u8>> {
+    #[cfg(feature = "minimal-json")]
+    return serialize(&LogEntryArgs {
+        offset: entry.offset,
+        level: entry.level,
+        target: &entry.target,
+        body: format_args!("{}", entry.body),
+        session: entry.session.as_deref(),
+        pid: entry.pid,
+        time: entry.time,
+        module_path: entry.module_path.as_deref(),
+        file: entry.file.as_deref(),
+        line: entry.line,
+        fields: Some(&entry.fields).filter(|fields| !fields.is_empty()),
+        truncated: entry.truncated,
+    });
+    #[cfg(not(feature = "minimal-json"))]
+    return Ok(serde_json::to_vec(entry)?);
+}
+
+/// Serializes an entry as a JSON object without a trailing newline.
+///
+/// Fails if formatting the body fails.
+pub(crate) fn serialize(entry: &LogEntryArgs) -> io::Result<Vec<u8>> {
+    #[cfg(feature = "minimal-json")]
+    return crate::json::entry(entry);
+    #[cfg(not(feature = "minimal-json"))]
+    return Ok(serde_json::to_vec(entry)?);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::ManualClock, LogEntryIter};
+    use log::Level;
+    use std::{io::Cursor, time::Duration};
+
+    fn read(bytes: Vec<u8>) -> Vec<LogEntry> {
+        LogEntryIter::new(Cursor::new(bytes)).collect()
+    }
+
+    #[test]
+    fn writes_records_with_clock_offsets() {
+        let clock = ManualClock::new();
+        let mut writer = Writer::with_clock(Vec::new(), clock.clone());
+        let mut log = |body| {
+            writer
+                .log(
+                    &log::Record::builder()
+                        .level(Level::Warn)
+                        .target("app")
+                        .args(format_args!("{}", body))
+                        .build(),
+                )
+                .unwrap()
+        };
+        log("first");
+        clock.set(Duration::from_secs(3));
+        log("second");
+        let entries = read(writer.finish().unwrap());
+        assert_eq!(2, entries.len());
+        assert_eq!(Duration::ZERO, entries[0].offset);
+        assert_eq!(Level::Warn, entries[0].level);
+        assert_eq!("first", entries[0].body);
+        assert_eq!(Duration::from_secs(3), entries[1].offset);
+        assert_eq!("second", entries[1].body);
+    }
+
+    #[test]
+    fn rejects_newlines() {
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_line(b"{}").unwrap();
+        let error = encoder.write_line(b"{\n}").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, error.kind());
+        let data = encoder.finish().unwrap();
+        let mut lines = String::new();
+        io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&data[..]), &mut lines).unwrap();
+        assert_eq!("{}\n", lines);
+    }
+
+    #[test]
+    fn copies_entries() {
+        let entry = LogEntry {
+            offset: Duration::from_millis(1500),
+            level: Level::Error,
+            target: "db".to_string(),
+            body: "lost connection".to_string(),
+            session: Some("run".to_string()),
+            pid: None,
+            time: None,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: Default::default(),
+            truncated: false,
+        };
+        let mut writer = Writer::new(Vec::new());
+        writer.write_entry(&entry).unwrap();
+        let entries = read(writer.finish().unwrap());
+        assert_eq!(1, entries.len());
+        assert_eq!(entry.offset, entries[0].offset);
+        assert_eq!(entry.body, entries[0].body);
+        assert_eq!(entry.session, entries[0].session);
+    }
+}
@@ -0,0 +1,542 @@
+//! Compressed destination that serialized log entries are written to.
+
+use crate::{
+    archive::Archiver,
+    codec::Codec,
+    core::Encoder,
+    health::Monitor,
+    index::Indexer,
+    rotation::{Counting, Rotator},
+    template::Template,
+};
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicU64, Arc},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// When log files are synced to the storage device, making sure entries
+/// written to them survive a crash of the whole system.
+///
+/// Flushing only hands entries to the OS, which writes them out in its own
+/// time, so without syncing a kernel panic or power loss can take entries
+/// that were already flushed with it. Syncing costs a round trip to the
+/// device, which on slow storage can take milliseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Only sync when asked to with
+    /// [`Durability::Synced`](crate::non_blocking::Durability::Synced).
+    #[default]
+    Never,
+    /// Sync whenever the logger is flushed, including by
+    /// [`flush_interval`](crate::LoggerBuilder::flush_interval), and when a
+    /// file is finished.
+    OnFlush,
+    /// Sync files once they are finished by rotation or by uninstalling the
+    /// logger, so that archived files are complete on disk.
+    OnRotation,
+    /// Sync at most once per interval when the logger is flushed, which
+    /// happens at least as often in the background, and when a file is
+    /// finished.
+    Every(Duration),
+}
+
+/// Options for creating a [`Dest`], kept so that equivalent destinations can
+/// be created later.
+#[derive(Clone, Default)]
+pub(crate) struct DestOptions {
+    /// Gzip compression level from 0 to 9, or `None` for the fastest.
+    pub(crate) compression: Option<u32>,
+    /// Codec compressing entries instead of gzip.
+    pub(crate) codec: Option<Arc<dyn Codec>>,
+    /// Capacity of the buffer in front of the writer, or 0 for none.
+    pub(crate) buffer_size: usize,
+    /// Whether creating a file fails if one already exists.
+    pub(crate) keep_existing: bool,
+    /// Whether an existing file is appended to instead, which takes
+    /// precedence over `keep_existing`.
+    pub(crate) append: bool,
+    /// Compressed size at which files are rotated.
+    pub(crate) max_size: Option<u64>,
+    /// Period at whose multiples files are rotated.
+    pub(crate) period: Option<Duration>,
+    /// Age after which rotated files are deleted.
+    pub(crate) max_age: Option<Duration>,
+    /// Bytes that the current and rotated files may take up together.
+    pub(crate) max_total_size: Option<u64>,
+    /// Whether files get an index of their gzip members.
+    pub(crate) index: bool,
+    /// Start time and run ID recorded in a header at the start of every
+    /// file, if one is written.
+    pub(crate) header: Option<(SystemTime, String)>,
+    /// When entries are flushed without being asked to.
+    pub(crate) flush: Threshold,
+    /// When gzip members are ended and new ones started.
+    pub(crate) member: Threshold,
+    #[cfg(feature = "encryption")]
+    pub(crate) encryption: Option<crate::encryption::Encryption>,
+    #[cfg(feature = "signing")]
+    pub(crate) signing: Option<crate::signing::SigningKey>,
+    #[cfg(feature = "hash-chain")]
+    pub(crate) hash_chain: bool,
+    /// Key of the HMAC linking entries if the hash chain is keyed.
+    #[cfg(all(feature = "hash-chain", feature = "signing"))]
+    pub(crate) chain_key: Option<crate::signing::SigningKey>,
+    /// When files are synced.
+    pub(crate) sync: SyncPolicy,
+    /// Template naming rotated files instead of their index.
+    pub(crate) rotated_name: Option<String>,
+    /// Archiver compressing rotated files, if files are written
+    /// uncompressed and compressed once rotated.
+    pub(crate) archiver: Option<Arc<Archiver>>,
+    /// Whether a link to the current file is kept next to it.
+    pub(crate) latest_link: bool,
+    /// Health of the destinations created, shared by all of them.
+    pub(crate) monitor: Arc<Monitor>,
+}
+
+impl DestOptions {
+    /// Creates the log file at the given path and a destination writing to
+    /// it.
+    pub(crate) fn create<P: AsRef<Path>>(&self, path: P) -> io::Result<Dest> {
+        let path = path.as_ref();
+        #[cfg(feature = "encryption")]
+        if self.append && self.encryption.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "encrypted files cannot be appended to",
+            ));
+        }
+        #[cfg(feature = "encryption")]
+        if self.index && self.encryption.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "encrypted files cannot be indexed",
+            ));
+        }
+        if self.index && self.append {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "indexed files cannot be appended to",
+            ));
+        }
+        if self.index && self.archiver.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "indexed files cannot be compressed on rotation",
+            ));
+        }
+        #[cfg(feature = "encryption")]
+        if self.encryption.is_some() && self.archiver.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "encrypted files cannot be compressed on rotation",
+            ));
+        }
+        let template = self
+            .rotated_name
+            .as_deref()
+            .map(Template::parse)
+            .transpose()?;
+        let file = create_file(path, self)?;
+        let sync = file.try_clone()?;
+        // files can always be rotated on demand
+        let written = Arc::new(AtomicU64::new(file.metadata()?.len()));
+        let counting = Counting {
+            inner: file,
+            written: Arc::clone(&written),
+        };
+        let index = match self.index {
+            true => Some(Indexer::create(path, Arc::clone(&written))?),
+            false => None,
+        };
+        self.monitor
+            .file(Some((path.to_path_buf(), Arc::clone(&written))));
+        let mut dest = self.wrap_indexed(counting, index)?;
+        dest.rotator = Some(Rotator::new(path, self, written, template));
+        dest.file = Some(sync);
+        Ok(dest)
+    }
+
+    /// Returns whether files created with these options are rotated by size
+    /// or time.
+    pub(crate) fn rotates(&self) -> bool {
+        self.max_size.is_some() || self.period.is_some()
+    }
+
+    /// Creates a destination writing to the given writer.
+    pub(crate) fn wrap<W: Write + Send + 'static>(&self, writer: W) -> io::Result<Dest> {
+        self.wrap_indexed(writer, None)
+    }
+
+    /// Creates a destination writing to the given writer and recording its
+    /// members in the given index.
+    fn wrap_indexed<W: Write + Send + 'static>(
+        &self,
+        writer: W,
+        index: Option<Indexer>,
+    ) -> io::Result<Dest> {
+        let writer: Box<dyn Write + Send> = match self.buffer_size {
+            0 => Box::new(writer),
+            capacity => Box::new(BufWriter::with_capacity(capacity, writer)),
+        };
+        #[cfg(feature = "encryption")]
+        let writer: Box<dyn Write + Send> = match &self.encryption {
+            Some(encryption) => Box::new(crate::encryption::EncryptingWriter::with_encryption(
+                writer, encryption,
+            )?),
+            None => Box::new(writer),
+        };
+        #[cfg(not(feature = "encryption"))]
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+        let mut dest = Dest::new(self.encoder(writer)?);
+        dest.monitor = Arc::clone(&self.monitor);
+        dest.sync = self.sync;
+        dest.flush_at = self.flush;
+        dest.member_at = self.member;
+        if index.is_some() && self.member.entries.is_none() && self.member.bytes.is_none() {
+            dest.member_at.bytes = Some(crate::index::DEFAULT_MEMBER_BYTES);
+        }
+        dest.index = index;
+        #[cfg(feature = "signing")]
+        if let Some(key) = &self.signing {
+            dest.sign(key);
+        }
+        #[cfg(feature = "hash-chain")]
+        if self.hash_chain {
+            #[cfg(feature = "signing")]
+            let chain = match &self.chain_key {
+                Some(key) => crate::chain::Chain::keyed(key),
+                None => crate::chain::Chain::new(),
+            };
+            #[cfg(not(feature = "signing"))]
+            let chain = crate::chain::Chain::new();
+            dest.chain(chain);
+        }
+        if let Some((start, run_id)) = &self.header {
+            let header = crate::Header::current(*start, run_id.clone()).to_entry();
+            dest.write_entry(&crate::core::serialize_entry(&header)?)?;
+        }
+        Ok(dest)
+    }
+
+    /// Compresses the rotated file at `path` in the background, if files
+    /// are compressed once rotated.
+    fn archive(&self, path: PathBuf) {
+        if let Some(archiver) = &self.archiver {
+            let codec = self.codec.clone().unwrap_or_else(|| {
+                let level = self
+                    .compression
+                    .unwrap_or(crate::codec::Gzip::default().level);
+                Arc::new(crate::codec::Gzip { level })
+            });
+            archiver.compress(path, codec, Arc::clone(&self.monitor));
+        }
+    }
+
+    /// Creates the encoder compressing entries into the given writer, or
+    /// writing them uncompressed if they are compressed once rotated.
+    fn encoder(&self, writer: Box<dyn Write + Send>) -> io::Result<Encoder<Box<dyn Write + Send>>> {
+        Ok(match (&self.codec, self.compression) {
+            _ if self.archiver.is_some() => Encoder::plain(writer),
+            (Some(codec), _) => Encoder::with_codec(writer, codec.as_ref())?,
+            (None, Some(level)) => Encoder::with_level(writer, level),
+            (None, None) => Encoder::new(writer),
+        })
+    }
+}
+
+/// Creates a new log file, truncating any existing one unless
+/// `keep_existing` is set, in which case creation fails instead, or `append`
+/// is set, in which case it is appended to.
+///
+/// On Windows the file is shared for reading and deletion but not writing, so
+/// readers and tail tools can open it while it is written, as on Unix, and it
+/// can be renamed. Long paths need no special handling since the standard
+/// library adds the `\\?\` prefix itself.
+pub(crate) fn create_file(path: &Path, dest: &DestOptions) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    if dest.append {
+        options.append(true).create(true);
+    } else if dest.keep_existing {
+        options.write(true).create_new(true);
+    } else {
+        options.write(true).create(true).truncate(true);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_SHARE_READ: u32 = 0x1;
+        const FILE_SHARE_DELETE: u32 = 0x4;
+        options.share_mode(FILE_SHARE_READ | FILE_SHARE_DELETE);
+    }
+    options.open(path)
+}
+
+/// Returns the path next to `path` with `tag` inserted before the
+/// extensions, e.g. `app.4242.jsonl.gz` for `app.jsonl.gz`.
+pub(crate) fn tagged_path(path: &Path, tag: impl fmt::Display) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = match name.split_once('.') {
+        Some((stem, extensions)) => format!("{}.{}.{}", stem, tag, extensions),
+        None => format!("{}.{}", name, tag),
+    };
+    path.with_file_name(name)
+}
+
+/// Amounts of entries written since something was last done, such as a
+/// flush, that trigger doing it again.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Threshold {
+    /// Number of entries.
+    pub(crate) entries: Option<u64>,
+    /// Number of uncompressed bytes, including newlines.
+    pub(crate) bytes: Option<u64>,
+}
+
+impl Threshold {
+    /// Returns whether the given numbers of entries and bytes reach the
+    /// threshold.
+    fn reached(&self, (entries, bytes): (u64, u64)) -> bool {
+        self.entries.is_some_and(|max| entries >= max) || self.bytes.is_some_and(|max| bytes >= max)
+    }
+}
+
+/// Compressed destination of serialized log entries.
+///
+/// Applies the per-line processing that depends on the order entries are
+/// written in, so it must only be used by one writer at a time.
+pub(crate) struct Dest {
+    encoder: Encoder<Box<dyn Write + Send>>,
+    #[cfg(feature = "hash-chain")]
+    chain: Option<crate::chain::Chain>,
+    #[cfg(feature = "signing")]
+    signer: Option<crate::signing::Signer>,
+    /// The file being written, for syncing, if writing to one.
+    file: Option<File>,
+    sync: SyncPolicy,
+    /// When the file was last synced, if it has been.
+    synced_at: Option<Instant>,
+    rotator: Option<Rotator>,
+    index: Option<Indexer>,
+    flush_at: Threshold,
+    member_at: Threshold,
+    /// Entries and uncompressed bytes written since the last flush.
+    unflushed: (u64, u64),
+    /// Entries and uncompressed bytes written to the current gzip member.
+    unended: (u64, u64),
+    monitor: Arc<Monitor>,
+}
+
+impl Dest {
+    /// Creates a destination writing entries to the given encoder.
+    pub(crate) fn new(encoder: Encoder<Box<dyn Write + Send>>) -> Self {
+        Self {
+            encoder,
+            #[cfg(feature = "hash-chain")]
+            chain: None,
+            #[cfg(feature = "signing")]
+            signer: None,
+            file: None,
+            sync: SyncPolicy::Never,
+            synced_at: None,
+            rotator: None,
+            index: None,
+            flush_at: Threshold::default(),
+            member_at: Threshold::default(),
+            unflushed: (0, 0),
+            unended: (0, 0),
+            monitor: Arc::default(),
+        }
+    }
+
+    /// Signs every entry written from now on with the given key.
+    #[cfg(feature = "signing")]
+    pub(crate) fn sign(&mut self, key: &crate::signing::SigningKey) {
+        self.signer = Some(crate::signing::Signer::new(key));
+    }
+
+    /// Links every entry written from now on to the previous one with the
+    /// given chain.
+    #[cfg(feature = "hash-chain")]
+    pub(crate) fn chain(&mut self, chain: crate::chain::Chain) {
+        self.chain = Some(chain);
+    }
+
+    /// Returns the monitor recording the health of this destination.
+    pub(crate) fn monitor(&self) -> &Arc<Monitor> {
+        &self.monitor
+    }
+
+    /// Writes one serialized entry, which must be a JSON object without a
+    /// trailing newline.
+    pub(crate) fn write_entry(&mut self, entry: &[u8]) -> io::Result<()> {
+        let result = self.try_write_entry(entry);
+        self.monitor.written(&result);
+        result
+    }
+
+    fn try_write_entry(&mut self, entry: &[u8]) -> io::Result<()> {
+        if self.rotator.as_ref().is_some_and(Rotator::due) && self.rotate().is_err() {
+            // the monitor has the error, and the entry still goes to the
+            // current file rather than being dropped
+            if let Some(rotator) = &mut self.rotator {
+                rotator.postpone();
+            }
+        }
+        #[allow(unused_mut)]
+        let mut line = std::borrow::Cow::Borrowed(entry);
+        #[cfg(feature = "hash-chain")]
+        if let Some(chain) = &self.chain {
+            line = chain.link(&line).into();
+        }
+        // the signature also covers the link, so it is added last
+        #[cfg(feature = "signing")]
+        if let Some(signer) = &mut self.signer {
+            line = signer.sign(&line).into();
+        }
+        #[cfg(feature = "hash-chain")]
+        if let Some(chain) = &mut self.chain {
+            chain.record(&line);
+        }
+        if let Some(index) = &mut self.index {
+            index.entry(entry)?;
+        }
+        self.encoder.write_line(&line)?;
+        let bytes = line.len() as u64 + 1;
+        self.unflushed = (self.unflushed.0 + 1, self.unflushed.1 + bytes);
+        self.unended = (self.unended.0 + 1, self.unended.1 + bytes);
+        if self.member_at.reached(self.unended) {
+            self.unended = (0, 0);
+            self.encoder.end_member()?;
+            if let Some(index) = &mut self.index {
+                index.member_ended();
+            }
+        }
+        if self.flush_at.reached(self.unflushed) {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the current file and continues in a new one at its path.
+    ///
+    /// The current file is renamed out of the way unless something else,
+    /// such as logrotate, already moved it. Fails if not writing to a file.
+    pub(crate) fn rotate(&mut self) -> io::Result<()> {
+        let result = self.try_rotate();
+        if let Err(error) = &result {
+            self.monitor.failed(error);
+        }
+        result
+    }
+
+    fn try_rotate(&mut self) -> io::Result<()> {
+        let Some(rotator) = &self.rotator else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only loggers writing to a file can be rotated",
+            ));
+        };
+        let moved = self
+            .file
+            .as_ref()
+            .is_some_and(|file| moved(file, rotator.path()));
+        let rotated = match moved {
+            true => None,
+            false => Some(rotator.rotate()?),
+        };
+        let (path, options) = rotator.reopen();
+        let options = options.clone();
+        let fresh = options.create(path)?;
+        std::mem::replace(self, fresh).finish()?;
+        // other tools take care of files they moved
+        if let Some(rotated) = rotated {
+            options.archive(rotated);
+        }
+        Ok(())
+    }
+
+    /// Flushes everything written so far and syncs the file, if writing to
+    /// one, to the storage device.
+    pub(crate) fn sync(&mut self) -> io::Result<()> {
+        self.flush_encoder()?;
+        self.sync_file()
+    }
+
+    /// Flushes everything written so far through to the underlying writer,
+    /// syncing the file too if the sync policy says so.
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.flush_encoder()?;
+        let due = match self.sync {
+            SyncPolicy::Never | SyncPolicy::OnRotation => false,
+            SyncPolicy::OnFlush => true,
+            SyncPolicy::Every(interval) => self
+                .synced_at
+                .is_none_or(|synced_at| synced_at.elapsed() >= interval),
+        };
+        match due {
+            true => self.sync_file(),
+            false => Ok(()),
+        }
+    }
+
+    fn flush_encoder(&mut self) -> io::Result<()> {
+        self.unflushed = (0, 0);
+        self.encoder
+            .flush()
+            .inspect_err(|error| self.monitor.failed(error))
+    }
+
+    /// Syncs the file, if writing to one, to the storage device.
+    fn sync_file(&mut self) -> io::Result<()> {
+        if let Some(file) = &self.file {
+            file.sync_data()
+                .inspect_err(|error| self.monitor.failed(error))?;
+            self.synced_at = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Finishes the compressed stream and flushes the underlying writer,
+    /// syncing the file unless the sync policy is to never do so.
+    pub(crate) fn finish(self) -> io::Result<()> {
+        let monitor = self.monitor;
+        let file = self.file.filter(|_| self.sync != SyncPolicy::Never);
+        self.encoder
+            .finish()
+            .and_then(|mut writer| writer.flush())
+            .and_then(|()| file.map_or(Ok(()), |file| file.sync_data()))
+            .inspect_err(|error| monitor.failed(error))
+    }
+}
+
+/// Returns whether `path` no longer refers to `file`, because the file was
+/// renamed or deleted and possibly replaced since it was opened.
+///
+/// Only missing files are detected outside of Unix.
+fn moved(file: &File, path: &Path) -> bool {
+    let Ok(current) = std::fs::metadata(path) else {
+        return true;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        file.metadata()
+            .is_ok_and(|opened| (opened.dev(), opened.ino()) != (current.dev(), current.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (file, current);
+        false
+    }
+}
+
+/// Encodes bytes as lowercase hex.
+#[cfg(any(feature = "signing", feature = "hash-chain", feature = "pii"))]
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
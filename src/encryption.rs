@@ -0,0 +1,750 @@
+//! Encryption of log files at rest.
+//!
+//! Encrypted files are the compressed stream split into AES-256-GCM frames.
+//! The file starts with a header of the magic bytes `JGLE`, a format
+//! version, and a random 7 byte nonce prefix. Each frame is a big-endian
+//! `u32` ciphertext length followed by the ciphertext, sealed with a nonce
+//! made of the prefix, a big-endian `u32` frame counter, and a byte that is
+//! 1 only for the final frame. A missing final frame means the file was
+//! truncated.
+//!
+//! Files can also be encrypted to a [`RecipientKey`], the public half of an
+//! [`IdentityKey`], so that the machine writing them cannot read them back.
+//! Their format version is 2, and their header also holds a random
+//! ephemeral X25519 public key between the version and the nonce prefix.
+//! The file's AES-256-GCM key is derived from the X25519 shared secret with
+//! HKDF-SHA256, salted with the ephemeral and recipient public keys.
+//!
+//! Alternatively only entry bodies can be encrypted, see [`BODY_PREFIX`].
+
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Magic bytes at the start of an encrypted file.
+pub(crate) const MAGIC: &[u8; 4] = b"JGLE";
+/// Version of the encrypted file format.
+const VERSION: u8 = 1;
+/// Version of the format of files encrypted to a recipient.
+const RECIPIENT_VERSION: u8 = 2;
+/// Context of the HKDF deriving the keys of files encrypted to a recipient.
+const RECIPIENT_INFO: &[u8] = b"jsonl_gzip_logger recipient v2";
+/// Length of the random nonce prefix in the header.
+const PREFIX_LEN: usize = 7;
+/// Maximum amount of plaintext sealed in one frame.
+const FRAME_LEN: usize = 64 * 1024;
+/// Length of the authentication tag appended to each frame.
+const TAG_LEN: usize = 16;
+
+/// 256 bit key for encrypting and decrypting log files.
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// Creates a key from raw bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Generates a new random key.
+    pub fn generate() -> io::Result<Self> {
+        let mut bytes = [0; 32];
+        getrandom::getrandom(&mut bytes).map_err(io::Error::from)?;
+        Ok(Self(bytes))
+    }
+
+    /// Returns the raw bytes of the key.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Parses a key from 64 hex characters.
+    pub fn from_hex(hex: &str) -> io::Result<Self> {
+        parse_hex(hex).map(Self)
+    }
+
+    /// Returns the key as 64 lowercase hex characters, the format of key
+    /// files.
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.0)
+    }
+
+    /// Reads a key file, containing either the key in hex (surrounding
+    /// whitespace is ignored) or its 32 raw bytes.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        read_key_file(path.as_ref()).map(Self)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.0.into())
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// Secret X25519 key for decrypting files encrypted to its
+/// [`RecipientKey`].
+#[derive(Clone)]
+pub struct IdentityKey([u8; 32]);
+
+impl IdentityKey {
+    /// Creates a key from raw bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Generates a new random key.
+    pub fn generate() -> io::Result<Self> {
+        let mut bytes = [0; 32];
+        getrandom::getrandom(&mut bytes).map_err(io::Error::from)?;
+        Ok(Self(bytes))
+    }
+
+    /// Returns the raw bytes of the key.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Parses a key from 64 hex characters.
+    pub fn from_hex(hex: &str) -> io::Result<Self> {
+        parse_hex(hex).map(Self)
+    }
+
+    /// Returns the key as 64 lowercase hex characters, the format of key
+    /// files.
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.0)
+    }
+
+    /// Reads a key file, in the same formats as
+    /// [`EncryptionKey::from_file`].
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        read_key_file(path.as_ref()).map(Self)
+    }
+
+    /// Returns the public key that files are encrypted to for this key to
+    /// decrypt them.
+    pub fn recipient(&self) -> RecipientKey {
+        RecipientKey(PublicKey::from(&StaticSecret::from(self.0)).to_bytes())
+    }
+
+    /// Returns the key of a file whose header holds the given ephemeral
+    /// public key.
+    fn file_key(&self, ephemeral: [u8; 32]) -> io::Result<EncryptionKey> {
+        let shared = StaticSecret::from(self.0).diffie_hellman(&PublicKey::from(ephemeral));
+        if !shared.was_contributory() {
+            return Err(tampered());
+        }
+        Ok(derive_key(shared.as_bytes(), &ephemeral, &self.recipient()))
+    }
+}
+
+impl fmt::Debug for IdentityKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("IdentityKey(..)")
+    }
+}
+
+/// Public X25519 key that files can be encrypted to, so that only the
+/// holder of the matching [`IdentityKey`] can decrypt them.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RecipientKey([u8; 32]);
+
+impl RecipientKey {
+    /// Creates a key from raw bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the raw bytes of the key.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Parses a key from 64 hex characters.
+    pub fn from_hex(hex: &str) -> io::Result<Self> {
+        parse_hex(hex).map(Self)
+    }
+
+    /// Returns the key as 64 lowercase hex characters, the format of key
+    /// files.
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.0)
+    }
+
+    /// Reads a key file, in the same formats as
+    /// [`EncryptionKey::from_file`].
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        read_key_file(path.as_ref()).map(Self)
+    }
+
+    /// Returns a random ephemeral public key and the key of a file with it
+    /// in its header.
+    fn file_key(&self) -> io::Result<([u8; 32], EncryptionKey)> {
+        let mut secret = [0; 32];
+        getrandom::getrandom(&mut secret).map_err(io::Error::from)?;
+        let secret = StaticSecret::from(secret);
+        let ephemeral = PublicKey::from(&secret).to_bytes();
+        let shared = secret.diffie_hellman(&PublicKey::from(self.0));
+        if !shared.was_contributory() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid recipient key",
+            ));
+        }
+        Ok((ephemeral, derive_key(shared.as_bytes(), &ephemeral, self)))
+    }
+}
+
+impl fmt::Debug for RecipientKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RecipientKey({})", self.to_hex())
+    }
+}
+
+/// Derives the key of a file encrypted to the recipient from the shared
+/// secret.
+fn derive_key(shared: &[u8; 32], ephemeral: &[u8; 32], recipient: &RecipientKey) -> EncryptionKey {
+    let mut salt = [0; 64];
+    salt[..32].copy_from_slice(ephemeral);
+    salt[32..].copy_from_slice(&recipient.0);
+    let mut key = [0; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(RECIPIENT_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    EncryptionKey(key)
+}
+
+/// Parses 32 bytes from 64 hex characters.
+fn parse_hex(hex: &str) -> io::Result<[u8; 32]> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "key must be 64 hex characters");
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut bytes = [0; 32];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).unwrap();
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+/// Encodes 32 bytes as 64 lowercase hex characters.
+fn to_hex(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Reads a key file, containing either the key in hex (surrounding
+/// whitespace is ignored) or its 32 raw bytes.
+fn read_key_file(path: &Path) -> io::Result<[u8; 32]> {
+    let contents = std::fs::read(path)?;
+    if let Ok(bytes) = <[u8; 32]>::try_from(&contents[..]) {
+        return Ok(bytes);
+    }
+    let text = std::str::from_utf8(&contents)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    parse_hex(text.trim())
+}
+
+/// How a log file is encrypted.
+#[derive(Clone, Debug)]
+pub(crate) enum Encryption {
+    /// With a key shared by writers and readers.
+    Key(EncryptionKey),
+    /// To a recipient, whose identity key readers need.
+    Recipient(RecipientKey),
+}
+
+/// Builds the nonce of a frame.
+fn nonce(prefix: &[u8; PREFIX_LEN], counter: u32, last: bool) -> Nonce<aes_gcm::aead::consts::U12> {
+    let mut nonce = [0; 12];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = u8::from(last);
+    nonce.into()
+}
+
+/// Error for ciphertext that fails to authenticate.
+fn tampered() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "encrypted log frame failed to authenticate",
+    )
+}
+
+/// Writer that encrypts everything written to it.
+///
+/// Flushing seals the buffered plaintext into a frame so that everything
+/// flushed so far can be decrypted. The final frame is written on drop.
+pub(crate) struct EncryptingWriter<W: Write> {
+    inner: Option<W>,
+    cipher: Aes256Gcm,
+    /// Ephemeral public key of a file encrypted to a recipient.
+    ephemeral: Option<[u8; 32]>,
+    prefix: [u8; PREFIX_LEN],
+    counter: u32,
+    header_written: bool,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    /// Creates a writer that encrypts into `inner` with the given key.
+    pub(crate) fn new(inner: W, key: &EncryptionKey) -> io::Result<Self> {
+        let mut prefix = [0; PREFIX_LEN];
+        getrandom::getrandom(&mut prefix).map_err(io::Error::from)?;
+        Ok(Self {
+            inner: Some(inner),
+            cipher: key.cipher(),
+            ephemeral: None,
+            prefix,
+            counter: 0,
+            header_written: false,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Creates a writer that encrypts into `inner` as configured.
+    pub(crate) fn with_encryption(inner: W, encryption: &Encryption) -> io::Result<Self> {
+        match encryption {
+            Encryption::Key(key) => Self::new(inner, key),
+            Encryption::Recipient(recipient) => {
+                let (ephemeral, key) = recipient.file_key()?;
+                let mut writer = Self::new(inner, &key)?;
+                writer.ephemeral = Some(ephemeral);
+                Ok(writer)
+            }
+        }
+    }
+
+    /// Encrypts the buffered plaintext into a frame.
+    fn seal(&mut self, last: bool) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        if !self.header_written {
+            inner.write_all(MAGIC)?;
+            match &self.ephemeral {
+                Some(ephemeral) => {
+                    inner.write_all(&[RECIPIENT_VERSION])?;
+                    inner.write_all(ephemeral)?;
+                }
+                None => inner.write_all(&[VERSION])?,
+            }
+            inner.write_all(&self.prefix)?;
+            self.header_written = true;
+        }
+        let nonce = nonce(&self.prefix, self.counter, last);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, &self.buffer[..])
+            .map_err(|_| io::Error::other("failed to encrypt log frame"))?;
+        inner.write_all(&(ciphertext.len() as u32).to_be_bytes())?;
+        inner.write_all(&ciphertext)?;
+        self.buffer.clear();
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::other("too many encrypted log frames"))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(FRAME_LEN - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == FRAME_LEN {
+            self.seal(false)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() || !self.header_written {
+            self.seal(false)?;
+        }
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for EncryptingWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.seal(true);
+            let _ = self.inner.as_mut().unwrap().flush();
+        }
+    }
+}
+
+/// Reader that decrypts a stream written by an [`EncryptingWriter`].
+pub(crate) struct DecryptingReader<R: Read> {
+    inner: R,
+    /// Cipher of the file, only missing before the header of a file
+    /// encrypted to a recipient is read.
+    cipher: Option<Aes256Gcm>,
+    /// Key of the recipient the file is expected to be encrypted to.
+    identity: Option<IdentityKey>,
+    prefix: Option<[u8; PREFIX_LEN]>,
+    counter: u32,
+    finished: bool,
+    plaintext: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    /// Creates a reader that decrypts `inner` with the given key.
+    pub(crate) fn new(inner: R, key: &EncryptionKey) -> Self {
+        Self {
+            inner,
+            cipher: Some(key.cipher()),
+            identity: None,
+            prefix: None,
+            counter: 0,
+            finished: false,
+            plaintext: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Creates a reader that decrypts `inner`, which must have been
+    /// encrypted to the identity's recipient key.
+    pub(crate) fn with_identity(inner: R, identity: &IdentityKey) -> Self {
+        Self {
+            inner,
+            cipher: None,
+            identity: Some(identity.clone()),
+            prefix: None,
+            counter: 0,
+            finished: false,
+            plaintext: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Reads the file header, returning the nonce prefix.
+    fn read_header(&mut self) -> io::Result<[u8; PREFIX_LEN]> {
+        let mut header = [0; 5];
+        self.inner.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an encrypted log file",
+            ));
+        }
+        match (header[4], &self.identity) {
+            (VERSION, None) => {}
+            (RECIPIENT_VERSION, Some(identity)) => {
+                let mut ephemeral = [0; 32];
+                self.inner.read_exact(&mut ephemeral)?;
+                self.cipher = Some(identity.file_key(ephemeral)?.cipher());
+            }
+            (VERSION | RECIPIENT_VERSION, _) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "encrypted log file needs a different kind of key",
+                ))
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unsupported encrypted log version",
+                ))
+            }
+        }
+        let mut prefix = [0; PREFIX_LEN];
+        self.inner.read_exact(&mut prefix)?;
+        Ok(prefix)
+    }
+
+    /// Reads and decrypts the next frame into the plaintext buffer.
+    fn next_frame(&mut self) -> io::Result<()> {
+        let prefix = match self.prefix {
+            Some(prefix) => prefix,
+            None => {
+                let prefix = self.read_header()?;
+                self.prefix = Some(prefix);
+                prefix
+            }
+        };
+        let mut len = [0; 4];
+        self.inner.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if !(TAG_LEN..=FRAME_LEN + TAG_LEN).contains(&len) {
+            return Err(tampered());
+        }
+        let mut ciphertext = vec![0; len];
+        self.inner.read_exact(&mut ciphertext)?;
+        let cipher = self
+            .cipher
+            .as_ref()
+            .expect("reading the header sets the cipher");
+        // the final frame is the only one sealed with the last flag set
+        self.plaintext = match cipher.decrypt(&nonce(&prefix, self.counter, false), &ciphertext[..])
+        {
+            Ok(plaintext) => plaintext,
+            Err(_) => {
+                let plaintext = cipher
+                    .decrypt(&nonce(&prefix, self.counter, true), &ciphertext[..])
+                    .map_err(|_| tampered())?;
+                self.finished = true;
+                plaintext
+            }
+        };
+        self.position = 0;
+        self.counter = self.counter.wrapping_add(1);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.next_frame()?;
+        }
+        let len = buf.len().min(self.plaintext.len() - self.position);
+        buf[..len].copy_from_slice(&self.plaintext[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// Prefix of encrypted entry bodies.
+///
+/// The rest of the body is the base64 encoding of a 12 byte nonce followed by
+/// the AES-256-GCM ciphertext of the original body.
+pub const BODY_PREFIX: &str = "enc1:";
+
+/// Encrypts entry bodies, each with its own nonce.
+pub(crate) struct BodyCipher {
+    cipher: Aes256Gcm,
+    prefix: [u8; 4],
+    counter: AtomicU64,
+}
+
+impl BodyCipher {
+    /// Creates a cipher for encrypting bodies with the given key.
+    pub(crate) fn new(key: &EncryptionKey) -> io::Result<Self> {
+        let mut prefix = [0; 4];
+        getrandom::getrandom(&mut prefix).map_err(io::Error::from)?;
+        Ok(Self {
+            cipher: key.cipher(),
+            prefix,
+            counter: AtomicU64::new(0),
+        })
+    }
+
+    /// Returns the encrypted form of a body.
+    pub(crate) fn seal(&self, body: &str) -> String {
+        let mut nonce = [0; 12];
+        nonce[..4].copy_from_slice(&self.prefix);
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+        nonce[4..].copy_from_slice(&counter.to_be_bytes());
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce.into(), body.as_bytes())
+            .expect("AES-GCM encryption of a log body cannot fail");
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        format!("{}{}", BODY_PREFIX, STANDARD.encode(sealed))
+    }
+}
+
+/// Decrypts a body encrypted with [`LoggerBuilder::body_encryption`].
+///
+/// Bodies that are not encrypted are returned unchanged.
+///
+/// [`LoggerBuilder::body_encryption`]: crate::LoggerBuilder::body_encryption
+pub fn decrypt_body(body: &str, key: &EncryptionKey) -> io::Result<String> {
+    let encoded = match body.strip_prefix(BODY_PREFIX) {
+        Some(encoded) => encoded,
+        None => return Ok(body.to_string()),
+    };
+    let sealed = STANDARD
+        .decode(encoded)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    if sealed.len() < 12 + TAG_LEN {
+        return Err(tampered());
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    let nonce: [u8; 12] = nonce.try_into().unwrap();
+    let plaintext = key
+        .cipher()
+        .decrypt(&nonce.into(), ciphertext)
+        .map_err(|_| tampered())?;
+    String::from_utf8(plaintext).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encrypts the given chunks, flushing after each one.
+    fn encrypt(key: &EncryptionKey, chunks: &[&[u8]]) -> Vec<u8> {
+        let mut ciphertext = Vec::new();
+        let mut writer = EncryptingWriter::new(&mut ciphertext, key).unwrap();
+        for chunk in chunks {
+            writer.write_all(chunk).unwrap();
+            writer.flush().unwrap();
+        }
+        drop(writer);
+        ciphertext
+    }
+
+    /// Decrypts all of the given ciphertext.
+    fn decrypt(key: &EncryptionKey, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+        let mut plaintext = Vec::new();
+        DecryptingReader::new(ciphertext, key).read_to_end(&mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    /// Encrypted data decrypts to the original, across frame boundaries.
+    #[test]
+    fn round_trip() {
+        let key = EncryptionKey::generate().unwrap();
+        let large = vec![7; FRAME_LEN * 2 + 10];
+        let ciphertext = encrypt(&key, &[b"hello ", &large, b"world"]);
+        assert_eq!(MAGIC, &ciphertext[..4]);
+
+        let plaintext = decrypt(&key, &ciphertext).unwrap();
+        assert_eq!(b"hello ", &plaintext[..6]);
+        assert_eq!(&large[..], &plaintext[6..6 + large.len()]);
+        assert_eq!(b"world", &plaintext[6 + large.len()..]);
+    }
+
+    /// The wrong key fails to decrypt.
+    #[test]
+    fn wrong_key() {
+        let ciphertext = encrypt(&EncryptionKey::from_bytes([1; 32]), &[b"secret"]);
+        let error = decrypt(&EncryptionKey::from_bytes([2; 32]), &ciphertext).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    }
+
+    /// Modified ciphertext fails to decrypt.
+    #[test]
+    fn tampering_detected() {
+        let key = EncryptionKey::from_bytes([3; 32]);
+        let mut ciphertext = encrypt(&key, &[b"secret"]);
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 1;
+        assert!(decrypt(&key, &ciphertext).is_err());
+    }
+
+    /// Encrypted bodies decrypt with the key only, each with a fresh nonce.
+    #[test]
+    fn body_round_trip() {
+        let key = EncryptionKey::from_bytes([5; 32]);
+        let cipher = BodyCipher::new(&key).unwrap();
+        let first = cipher.seal("card=4111");
+        let second = cipher.seal("card=4111");
+        assert!(first.starts_with(BODY_PREFIX));
+        assert!(!first.contains("4111"));
+        assert_ne!(first, second);
+        assert_eq!("card=4111", decrypt_body(&first, &key).unwrap());
+        assert_eq!("card=4111", decrypt_body(&second, &key).unwrap());
+        assert_eq!("plain", decrypt_body("plain", &key).unwrap());
+        let wrong = EncryptionKey::from_bytes([6; 32]);
+        assert!(decrypt_body(&first, &wrong).is_err());
+    }
+
+    /// Keys survive a round trip through hex, and key files hold either form.
+    #[test]
+    fn key_files() {
+        let key = EncryptionKey::generate().unwrap();
+        assert_eq!(
+            key.as_bytes(),
+            EncryptionKey::from_hex(&key.to_hex()).unwrap().as_bytes()
+        );
+        assert!(EncryptionKey::from_hex("abc").is_err());
+        assert!(EncryptionKey::from_hex(&"zz".repeat(32)).is_err());
+
+        let dir = std::env::temp_dir();
+        let hex = dir.join("jsonl_gzip_logger_key_files.hex");
+        std::fs::write(&hex, format!("{}\n", key.to_hex())).unwrap();
+        assert_eq!(
+            key.as_bytes(),
+            EncryptionKey::from_file(&hex).unwrap().as_bytes()
+        );
+        let raw = dir.join("jsonl_gzip_logger_key_files.bin");
+        std::fs::write(&raw, key.as_bytes()).unwrap();
+        assert_eq!(
+            key.as_bytes(),
+            EncryptionKey::from_file(&raw).unwrap().as_bytes()
+        );
+    }
+
+    /// Files encrypted to a recipient decrypt with its identity only, each
+    /// with a fresh key.
+    #[test]
+    fn recipient_round_trip() {
+        let identity = IdentityKey::from_bytes([7; 32]);
+        let encryption = Encryption::Recipient(identity.recipient());
+        let mut ciphertexts = Vec::new();
+        for _ in 0..2 {
+            let mut ciphertext = Vec::new();
+            let mut writer =
+                EncryptingWriter::with_encryption(&mut ciphertext, &encryption).unwrap();
+            writer.write_all(b"secret").unwrap();
+            drop(writer);
+            assert_eq!(RECIPIENT_VERSION, ciphertext[4]);
+            ciphertexts.push(ciphertext);
+        }
+        assert_ne!(ciphertexts[0], ciphertexts[1]);
+
+        let decrypt = |identity: &IdentityKey, ciphertext: &[u8]| {
+            let mut plaintext = Vec::new();
+            DecryptingReader::with_identity(ciphertext, identity)
+                .read_to_end(&mut plaintext)
+                .map(|_| plaintext)
+        };
+        assert_eq!(b"secret", &decrypt(&identity, &ciphertexts[0]).unwrap()[..]);
+        let other = IdentityKey::from_bytes([8; 32]);
+        assert!(decrypt(&other, &ciphertexts[0]).is_err());
+
+        // files encrypted with a shared key need that key
+        let ciphertext = encrypt(&EncryptionKey::from_bytes([7; 32]), &[b"secret"]);
+        assert!(decrypt(&identity, &ciphertext).is_err());
+        assert!(self::decrypt(&EncryptionKey::from_bytes([7; 32]), &ciphertexts[0]).is_err());
+    }
+
+    /// Recipient keys cannot be weak points.
+    #[test]
+    fn weak_recipient_rejected() {
+        let encryption = Encryption::Recipient(RecipientKey::from_bytes([0; 32]));
+        assert!(EncryptingWriter::with_encryption(Vec::new(), &encryption).is_err());
+        let identity = IdentityKey::generate().unwrap();
+        assert_eq!(
+            identity.recipient(),
+            RecipientKey::from_hex(&identity.recipient().to_hex()).unwrap()
+        );
+    }
+
+    /// Dropping frames from the end is detected as truncation.
+    #[test]
+    fn truncation_detected() {
+        let key = EncryptionKey::from_bytes([4; 32]);
+        let mut writer = EncryptingWriter::new(Vec::new(), &key).unwrap();
+        writer.write_all(b"first").unwrap();
+        writer.flush().unwrap();
+        // taking the inner writer skips writing the final frame on drop
+        let truncated = writer.inner.take().unwrap();
+
+        let mut reader = DecryptingReader::new(&truncated[..], &key);
+        let mut plaintext = Vec::new();
+        let error = reader.read_to_end(&mut plaintext).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+        assert_eq!(b"first", &plaintext[..]);
+    }
+}
@@ -0,0 +1,346 @@
+//! Global logger whose destination can be swapped after installation.
+//!
+//! `log` only allows installing a global logger once per process, so this
+//! crate installs a single facade and swaps the logger behind it instead.
+//! Threads can also temporarily route their entries to a scoped logger.
+
+use crate::{filter::Filter, InitError, LoggerHandle};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::{
+    cell::RefCell,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+/// The logger installed with `log`, forwarding to the current logger.
+struct Facade {
+    state: RwLock<State>,
+}
+
+/// Mutable state of the facade.
+struct State {
+    /// The current global logger.
+    logger: Option<Box<dyn Log>>,
+    /// The level of the current global logger.
+    level: LevelFilter,
+    /// The filter of the current global logger, if it can be changed.
+    filter: Option<Filter>,
+    /// The level of the current global logger's tee.
+    tee_level: LevelFilter,
+    /// Whether the facade has been installed with `log`.
+    installed: bool,
+    /// Number of global loggers installed so far, identifying the current
+    /// one.
+    generation: u64,
+}
+
+static FACADE: Facade = Facade {
+    state: RwLock::new(State {
+        logger: None,
+        level: LevelFilter::Off,
+        filter: None,
+        tee_level: LevelFilter::Off,
+        installed: false,
+        generation: 0,
+    }),
+};
+
+/// Number of live [`DefaultGuard`]s across all threads.
+static SCOPES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Stack of scoped loggers for the current thread, innermost last.
+    static SCOPED: RefCell<Vec<Arc<dyn Log>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns the innermost scoped logger of the current thread, if any.
+fn scoped() -> Option<Arc<dyn Log>> {
+    SCOPED.with(|scoped| scoped.borrow().last().cloned())
+}
+
+impl Log for Facade {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        if let Some(logger) = scoped() {
+            return logger.enabled(metadata);
+        }
+        let state = self.state.read().unwrap();
+        state
+            .logger
+            .as_ref()
+            .is_some_and(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(logger) = scoped() {
+            logger.log(record);
+        } else if let Some(logger) = &self.state.read().unwrap().logger {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(logger) = scoped() {
+            logger.flush();
+        } else if let Some(logger) = &self.state.read().unwrap().logger {
+            logger.flush();
+        }
+    }
+}
+
+impl State {
+    /// Installs the facade with `log` if that has not happened yet.
+    fn ensure_installed(&mut self) -> Result<(), InitError> {
+        if !self.installed {
+            log::set_logger(&FACADE)?;
+            self.installed = true;
+        }
+        Ok(())
+    }
+
+    /// Updates `log`'s max level so that no logger misses entries.
+    ///
+    /// While scoped loggers exist every level must reach the facade, and each
+    /// logger filters for itself.
+    fn update_max_level(&self) {
+        if SCOPES.load(Ordering::SeqCst) > 0 {
+            log::set_max_level(LevelFilter::Trace);
+        } else {
+            log::set_max_level(self.level);
+        }
+    }
+}
+
+/// Another logger receiving every record alongside this crate's logger, set
+/// with [`LoggerBuilder::tee`](crate::LoggerBuilder::tee).
+#[derive(Clone)]
+pub(crate) struct Tee {
+    pub(crate) logger: Arc<dyn Log>,
+    pub(crate) level: LevelFilter,
+}
+
+/// Logger forwarding every record to both of its loggers.
+struct Both {
+    ours: Box<dyn Log>,
+    other: Arc<dyn Log>,
+}
+
+impl Log for Both {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.ours.enabled(metadata) || self.other.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        // each logger filters for itself
+        self.ours.log(record);
+        self.other.log(record);
+    }
+
+    fn flush(&self) {
+        self.ours.flush();
+        self.other.flush();
+    }
+}
+
+/// How a logger is installed, besides the logger itself.
+pub(crate) struct InstallOptions {
+    /// The most verbose level the logger accepts.
+    pub(crate) level: LevelFilter,
+    /// The logger's filter, if it can be changed after installation.
+    pub(crate) filter: Option<Filter>,
+    pub(crate) tee: Option<Tee>,
+    /// How often the logger is flushed in the background.
+    pub(crate) flush_interval: Option<Duration>,
+}
+
+impl InstallOptions {
+    /// Creates options for a logger accepting up to `level`, without a tee
+    /// or background flushing.
+    pub(crate) fn new(level: LevelFilter) -> Self {
+        Self {
+            level,
+            filter: None,
+            tee: None,
+            flush_interval: None,
+        }
+    }
+}
+
+/// Fails if a logger from this crate is already installed.
+///
+/// Called before creating a logger's file or connection, so that a failed
+/// second installation does not truncate the file the installed logger is
+/// writing to.
+pub(crate) fn ensure_uninstalled() -> Result<(), InitError> {
+    match FACADE.state.read().unwrap().logger {
+        Some(_) => Err(InitError::AlreadyInitialized),
+        None => Ok(()),
+    }
+}
+
+/// Makes `logger` the global logger as configured by `options`, and returns
+/// its generation for an [`InitGuard`].
+///
+/// Fails if a logger from this crate is already installed, or if another
+/// crate installed its own global logger.
+pub(crate) fn install(logger: Box<dyn Log>, options: InstallOptions) -> Result<u64, InitError> {
+    let mut state = FACADE.state.write().unwrap();
+    if state.logger.is_some() {
+        return Err(InitError::AlreadyInitialized);
+    }
+    state.ensure_installed()?;
+    let (logger, tee_level) = match options.tee {
+        Some(tee) => {
+            let both = Both {
+                ours: logger,
+                other: tee.logger,
+            };
+            (Box::new(both) as Box<dyn Log>, tee.level)
+        }
+        None => (logger, LevelFilter::Off),
+    };
+    state.logger = Some(logger);
+    state.level = options.level.max(tee_level);
+    state.filter = options.filter;
+    state.tee_level = tee_level;
+    state.generation += 1;
+    state.update_max_level();
+    if let Some(interval) = options.flush_interval {
+        flush_periodically(state.generation, interval);
+    }
+    Ok(state.generation)
+}
+
+/// Changes the installed logger's filter with `change`, if it has one and is
+/// of the given generation, or whichever it is if none is given, and updates
+/// `log`'s max level to match.
+pub(crate) fn reconfigure(generation: Option<u64>, change: impl FnOnce(&Filter)) {
+    let mut state = FACADE.state.write().unwrap();
+    if generation.is_some_and(|generation| generation != state.generation) {
+        return;
+    }
+    if let Some(filter) = state.filter.clone() {
+        change(&filter);
+        state.level = filter.max().max(state.tee_level);
+        state.update_max_level();
+    }
+}
+
+/// Flushes the logger of the given generation every `interval` on a
+/// background thread, until it is uninstalled.
+fn flush_periodically(generation: u64, interval: Duration) {
+    let flusher = move || loop {
+        thread::sleep(interval);
+        let state = FACADE.state.read().unwrap();
+        match &state.logger {
+            Some(logger) if state.generation == generation => logger.flush(),
+            _ => break,
+        }
+    };
+    // without threads, e.g. on wasm32-unknown-unknown, entries are only
+    // flushed on demand
+    let _ = thread::Builder::new()
+        .name("jsonl_gzip_logger flush".to_string())
+        .spawn(flusher);
+}
+
+/// Uninstalls the global logger installed by this crate, flushing and
+/// finishing its destination.
+///
+/// Afterwards entries are discarded until a logger is installed again, so
+/// sequential tests in one process can each log to a fresh file. Does
+/// nothing if no logger is installed.
+pub fn deinit() {
+    uninstall(None);
+}
+
+/// Uninstalls the global logger if it is of the given generation, or
+/// whichever it is if none is given.
+pub(crate) fn uninstall(generation: Option<u64>) {
+    let logger = {
+        let mut state = FACADE.state.write().unwrap();
+        if generation.is_some_and(|generation| generation != state.generation) {
+            return;
+        }
+        state.level = LevelFilter::Off;
+        state.filter = None;
+        state.update_max_level();
+        state.logger.take()
+    };
+    if let Some(logger) = logger {
+        logger.flush();
+    }
+}
+
+/// Guard that uninstalls the global logger when dropped, flushing and
+/// finishing its destination like [`deinit`].
+///
+/// Keep it alive for as long as logging should continue, typically by
+/// binding it in `main`, so that entries logged right before the process
+/// exits are not lost. Does nothing if the logger it was returned for has
+/// already been uninstalled.
+#[must_use = "dropping the guard immediately stops logging"]
+pub struct InitGuard {
+    handle: LoggerHandle,
+}
+
+impl InitGuard {
+    /// Creates a guard for the logger controlled by `handle`.
+    pub(crate) fn new(handle: LoggerHandle) -> Self {
+        Self { handle }
+    }
+
+    /// Returns a handle for controlling the logger.
+    pub fn handle(&self) -> LoggerHandle {
+        self.handle.clone()
+    }
+}
+
+impl Drop for InitGuard {
+    fn drop(&mut self) {
+        uninstall(Some(self.handle.generation()));
+    }
+}
+
+/// Routes entries logged on the current thread to `logger` until the
+/// returned guard is dropped.
+///
+/// Scopes nest, and while one is active the global logger does not see the
+/// thread's entries. Entries logged on other threads, including threads
+/// spawned from this one, are unaffected. This lets concurrently running
+/// tests each capture their own entries, for example with a
+/// [`TestLogger`](crate::test::TestLogger).
+pub fn set_default<L: Log + 'static>(logger: L) -> Result<DefaultGuard, InitError> {
+    let mut state = FACADE.state.write().unwrap();
+    state.ensure_installed()?;
+    SCOPED.with(|scoped| scoped.borrow_mut().push(Arc::new(logger)));
+    SCOPES.fetch_add(1, Ordering::SeqCst);
+    state.update_max_level();
+    Ok(DefaultGuard {
+        _not_send: PhantomData,
+    })
+}
+
+/// Guard that ends a scope started by [`set_default`] when dropped.
+#[must_use = "dropping the guard immediately ends the scope"]
+pub struct DefaultGuard {
+    // the scope belongs to the thread that created it
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for DefaultGuard {
+    fn drop(&mut self) {
+        let logger = SCOPED.with(|scoped| scoped.borrow_mut().pop());
+        let state = FACADE.state.read().unwrap();
+        SCOPES.fetch_sub(1, Ordering::SeqCst);
+        state.update_max_level();
+        drop(state);
+        if let Some(logger) = logger {
+            logger.flush();
+        }
+    }
+}
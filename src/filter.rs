@@ -0,0 +1,251 @@
+//! Levels per target prefix, including `RUST_LOG`-style directives, which
+//! can be changed while logging.
+
+use crate::{facade, LoggerBuilder};
+use log::{LevelFilter, Metadata};
+use std::{
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+use thiserror::Error;
+
+/// Error for a filter directive that could not be parsed.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid filter directive `{directive}`")]
+pub struct FilterError {
+    /// The offending directive.
+    pub directive: String,
+}
+
+impl LoggerBuilder {
+    /// Sets the maximum level logged for targets starting with `prefix`,
+    /// overriding [`level`](Self::level) for them.
+    ///
+    /// When several prefixes match a target the longest one wins, so
+    /// `.target_level("hyper", LevelFilter::Warn)` together with
+    /// `.target_level("hyper::client", LevelFilter::Debug)` logs debug
+    /// entries from `hyper::client::pool` but only warnings from
+    /// `hyper::server`. Prefixes are matched as plain strings, so `hyper`
+    /// also matches `hyper_util`.
+    pub fn target_level<S: Into<String>>(self, prefix: S, level: LevelFilter) -> Self {
+        self.filter.set(prefix.into(), level);
+        self
+    }
+
+    /// Applies comma-separated `RUST_LOG`-style directives such as
+    /// `info,my_crate::db=debug,hyper=off`.
+    ///
+    /// A directive is a level, which sets [`level`](Self::level), a target
+    /// prefix with a level, which works like
+    /// [`target_level`](Self::target_level), or a bare target prefix, which
+    /// logs everything from it. Regular expression filters after a `/` are
+    /// not supported.
+    pub fn parse_filters(self, directives: &str) -> Result<Self, FilterError> {
+        self.filter.apply(directives)?;
+        Ok(self)
+    }
+
+    /// Applies the directives in the `RUST_LOG` environment variable, as
+    /// [`parse_filters`](Self::parse_filters) does, if it is set.
+    pub fn filters_from_env(self) -> Result<Self, FilterError> {
+        match std::env::var("RUST_LOG") {
+            Ok(directives) => self.parse_filters(&directives),
+            Err(_) => Ok(self),
+        }
+    }
+}
+
+/// Changes the level of the installed logger for targets without a more
+/// specific one, as [`LoggerBuilder::level`] does before installing it.
+///
+/// Does nothing if no logger built with [`LoggerBuilder`] is installed.
+pub fn set_level(level: LevelFilter) {
+    facade::reconfigure(None, |filter| filter.set_default(level));
+}
+
+/// Changes the level of the installed logger for targets starting with
+/// `prefix`, as [`LoggerBuilder::target_level`] does before installing it.
+///
+/// Does nothing if no logger built with [`LoggerBuilder`] is installed.
+pub fn set_target_level<S: Into<String>>(prefix: S, level: LevelFilter) {
+    let prefix = prefix.into();
+    facade::reconfigure(None, |filter| filter.set(prefix, level));
+}
+
+/// Applies `RUST_LOG`-style directives to the installed logger, as
+/// [`LoggerBuilder::parse_filters`] does before installing it.
+///
+/// Nothing changes if any directive is invalid. Does nothing if no logger
+/// built with [`LoggerBuilder`] is installed.
+pub fn set_filters(directives: &str) -> Result<(), FilterError> {
+    let directives = parse(directives)?;
+    facade::reconfigure(None, |filter| filter.apply_parsed(directives));
+    Ok(())
+}
+
+/// Decides which records are logged, by target.
+///
+/// Clones share their levels, so that the installed logger sees changes made
+/// through the facade.
+#[derive(Clone, Debug)]
+pub(crate) struct Filter(Arc<RwLock<Rules>>);
+
+/// Levels of a [`Filter`].
+#[derive(Debug)]
+struct Rules {
+    /// Level for targets without a more specific one.
+    default: LevelFilter,
+    /// Levels per target prefix, longest prefix first.
+    targets: Vec<(String, LevelFilter)>,
+}
+
+/// A parsed directive: a target prefix, or `None` for the default level,
+/// and its level.
+pub(crate) type Directive = (Option<String>, LevelFilter);
+
+impl Filter {
+    /// Creates a filter logging every target up to `level`.
+    pub(crate) fn new(level: LevelFilter) -> Self {
+        Self(Arc::new(RwLock::new(Rules {
+            default: level,
+            targets: Vec::new(),
+        })))
+    }
+
+    /// Sets the level for targets without a more specific one.
+    pub(crate) fn set_default(&self, level: LevelFilter) {
+        self.0.write().unwrap().default = level;
+    }
+
+    /// Sets the level for targets starting with `prefix`, replacing any
+    /// level set for the same prefix.
+    pub(crate) fn set(&self, prefix: String, level: LevelFilter) {
+        let mut rules = self.0.write().unwrap();
+        rules.targets.retain(|(existing, _)| *existing != prefix);
+        let index = rules
+            .targets
+            .partition_point(|(existing, _)| existing.len() >= prefix.len());
+        rules.targets.insert(index, (prefix, level));
+    }
+
+    /// Applies `RUST_LOG`-style directives, or nothing if any is invalid.
+    pub(crate) fn apply(&self, directives: &str) -> Result<(), FilterError> {
+        self.apply_parsed(parse(directives)?);
+        Ok(())
+    }
+
+    /// Applies directives in order.
+    pub(crate) fn apply_parsed(&self, directives: Vec<Directive>) {
+        for (target, level) in directives {
+            match target {
+                Some(target) => self.set(target, level),
+                None => self.set_default(level),
+            }
+        }
+    }
+
+    /// Returns the maximum level logged for `target`.
+    pub(crate) fn level(&self, target: &str) -> LevelFilter {
+        let rules = self.0.read().unwrap();
+        rules
+            .targets
+            .iter()
+            .find(|(prefix, _)| target.starts_with(prefix.as_str()))
+            .map_or(rules.default, |(_, level)| *level)
+    }
+
+    /// Returns whether records with this metadata are logged.
+    pub(crate) fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level(metadata.target())
+    }
+
+    /// Returns the most verbose level logged for any target.
+    pub(crate) fn max(&self) -> LevelFilter {
+        let rules = self.0.read().unwrap();
+        rules
+            .targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(rules.default, Ord::max)
+    }
+}
+
+/// Parses comma-separated `RUST_LOG`-style directives.
+pub(crate) fn parse(directives: &str) -> Result<Vec<Directive>, FilterError> {
+    let mut parsed = Vec::new();
+    for directive in directives.split(',').map(str::trim) {
+        let invalid = || FilterError {
+            directive: directive.to_string(),
+        };
+        if directive.is_empty() {
+            continue;
+        }
+        if directive.contains('/') {
+            return Err(invalid());
+        }
+        parsed.push(match directive.split_once('=') {
+            Some((target, level)) => {
+                let level = LevelFilter::from_str(level.trim()).map_err(|_| invalid())?;
+                let target = target.trim();
+                if target.is_empty() {
+                    return Err(invalid());
+                }
+                (Some(target.to_string()), level)
+            }
+            None => match LevelFilter::from_str(directive) {
+                Ok(level) => (None, level),
+                Err(_) => (Some(directive.to_string()), LevelFilter::Trace),
+            },
+        });
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_prefix_wins() {
+        let filter = Filter::new(LevelFilter::Info);
+        filter.set("hyper".to_string(), LevelFilter::Warn);
+        filter.set("hyper::client".to_string(), LevelFilter::Debug);
+        filter.set("my_app".to_string(), LevelFilter::Trace);
+        filter.set("my_app".to_string(), LevelFilter::Off);
+
+        assert_eq!(LevelFilter::Debug, filter.level("hyper::client::pool"));
+        assert_eq!(LevelFilter::Warn, filter.level("hyper::server"));
+        assert_eq!(LevelFilter::Off, filter.level("my_app"));
+        assert_eq!(LevelFilter::Info, filter.level("other"));
+        assert_eq!(LevelFilter::Debug, filter.max());
+    }
+
+    #[test]
+    fn parses_directives() {
+        let builder = LoggerBuilder::new()
+            .parse_filters(" warn, my_crate::db=debug ,hyper=OFF,,noisy")
+            .unwrap();
+        let filter = builder.filter;
+        assert_eq!(LevelFilter::Warn, filter.level("other"));
+        assert_eq!(LevelFilter::Debug, filter.level("my_crate::db::pool"));
+        assert_eq!(LevelFilter::Off, filter.level("hyper"));
+        assert_eq!(LevelFilter::Trace, filter.level("noisy"));
+    }
+
+    #[test]
+    fn rejects_invalid_directives() {
+        for directives in ["info,db=loud", "=debug", "info/foo", "a=b=c"] {
+            let error = LoggerBuilder::new().parse_filters(directives).err();
+            assert!(error.is_some(), "{}", directives);
+        }
+        let error = LoggerBuilder::new().parse_filters("info,db=loud").err();
+        assert_eq!("db=loud", error.unwrap().directive);
+    }
+
+    #[test]
+    fn invalid_directives_change_nothing() {
+        let filter = Filter::new(LevelFilter::Info);
+        assert!(filter.apply("debug,db=loud").is_err());
+        assert_eq!(LevelFilter::Info, filter.level("db"));
+    }
+}
@@ -0,0 +1,244 @@
+//! Generation of synthetic log files for benchmarks and recovery tests.
+
+use crate::LogEntryArgs;
+use flate2::{write::GzEncoder, Compression};
+use log::Level;
+use std::{fs, io::Write, ops::Range, path::Path, time::Duration};
+
+/// Description of a synthetic log file.
+#[derive(Clone, Debug)]
+pub struct FixtureSpec {
+    /// Number of entries to generate.
+    pub entries: usize,
+    /// Relative weights of the error, warn, info, debug, and trace levels.
+    pub level_weights: [u32; 5],
+    /// Targets to pick from uniformly.
+    pub targets: Vec<String>,
+    /// Range of body lengths in bytes.
+    pub body_len: Range<usize>,
+    /// Average time between consecutive entries.
+    pub interval: Duration,
+    /// Damage to apply to the generated file.
+    pub corruption: Corruption,
+    /// Seed for the generator; the same spec always produces the same file.
+    pub seed: u64,
+}
+
+impl Default for FixtureSpec {
+    fn default() -> Self {
+        Self {
+            entries: 1000,
+            level_weights: [1, 4, 30, 40, 25],
+            targets: vec![
+                "app".to_string(),
+                "app::db".to_string(),
+                "app::http".to_string(),
+            ],
+            body_len: 16..128,
+            interval: Duration::from_millis(10),
+            corruption: Corruption::None,
+            seed: 0,
+        }
+    }
+}
+
+/// Damage applied to a generated file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corruption {
+    /// A well-formed file.
+    None,
+    /// The last entry is cut in half and has no trailing newline, as if the
+    /// process died mid-write.
+    TruncatedLastLine,
+    /// A line that is not valid JSON follows every `n`th entry.
+    GarbageLines {
+        /// Number of valid entries between garbage lines.
+        every: usize,
+    },
+    /// The compressed file is cut short, losing the gzip trailer and the
+    /// end of the deflate stream.
+    TruncatedStream,
+}
+
+/// Writes a synthetic .jsonl.gz log file described by `spec`.
+pub fn generate_fixture<P: AsRef<Path>>(path: P, spec: &FixtureSpec) -> std::io::Result<()> {
+    let mut rng = Rng::new(spec.seed);
+    let mut dest = GzEncoder::new(Vec::new(), Compression::fast());
+    let mut offset = Duration::ZERO;
+    let mut body = String::new();
+    for index in 0..spec.entries {
+        offset += spec.interval.mul_f64(rng.next_f64() * 2.0);
+        random_body(&mut rng, &spec.body_len, &mut body);
+        let target = match spec.targets.len() {
+            0 => "",
+            len => &spec.targets[rng.below(len as u64) as usize],
+        };
+        let mut line = serde_json::to_vec(&LogEntryArgs {
+            offset,
+            level: random_level(&mut rng, &spec.level_weights),
+            target,
+            body: format_args!("{}", body),
+            session: None,
+            pid: None,
+            time: None,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: None,
+            truncated: false,
+        })?;
+        line.push(b'\n');
+        if spec.corruption == Corruption::TruncatedLastLine && index + 1 == spec.entries {
+            line.truncate(line.len() / 2);
+        }
+        dest.write_all(&line)?;
+        if let Corruption::GarbageLines { every } = spec.corruption {
+            if every != 0 && (index + 1) % every == 0 {
+                dest.write_all(b"{\"offset\":garbage\n")?;
+            }
+        }
+    }
+    let mut bytes = dest.finish()?;
+    if spec.corruption == Corruption::TruncatedStream {
+        bytes.truncate(bytes.len() * 9 / 10);
+    }
+    fs::write(path, bytes)
+}
+
+/// Picks a level according to the given weights.
+fn random_level(rng: &mut Rng, weights: &[u32; 5]) -> Level {
+    const LEVELS: [Level; 5] = [
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+    let total: u64 = weights.iter().map(|&w| u64::from(w)).sum();
+    if total == 0 {
+        return Level::Info;
+    }
+    let mut pick = rng.below(total);
+    for (level, &weight) in LEVELS.iter().zip(weights) {
+        if pick < u64::from(weight) {
+            return *level;
+        }
+        pick -= u64::from(weight);
+    }
+    unreachable!()
+}
+
+/// Fills `body` with random lowercase words of a length within `len`.
+fn random_body(rng: &mut Rng, len: &Range<usize>, body: &mut String) {
+    body.clear();
+    let target = if len.is_empty() {
+        len.start
+    } else {
+        len.start + rng.below((len.end - len.start) as u64) as usize
+    };
+    while body.len() < target {
+        if !body.is_empty() && rng.below(6) == 0 {
+            body.push(' ');
+        } else {
+            body.push((b'a' + rng.below(26) as u8) as char);
+        }
+    }
+}
+
+/// Small deterministic xorshift64* generator.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift must not start at zero
+        Self(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a number in `0..bound`.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// Returns a number in `0.0..1.0`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a unique temporary path for a test.
+    fn path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "jsonl_gzip_logger_fixture_{}_{}.jsonl.gz",
+            name,
+            std::process::id()
+        ))
+    }
+
+    /// Generates a fixture with the given corruption and reads it back.
+    fn generate(name: &str, corruption: Corruption) -> Vec<crate::LogEntry> {
+        let path = path(name);
+        let spec = FixtureSpec {
+            entries: 50,
+            corruption,
+            ..FixtureSpec::default()
+        };
+        generate_fixture(&path, &spec).unwrap();
+        let entries = crate::read(&path).unwrap().collect();
+        fs::remove_file(path).unwrap();
+        entries
+    }
+
+    /// An uncorrupted fixture has every entry, in offset order.
+    #[test]
+    fn well_formed() {
+        let entries = generate("well_formed", Corruption::None);
+        assert_eq!(50, entries.len());
+        assert!(entries.windows(2).all(|w| w[0].offset <= w[1].offset));
+        assert!(entries.iter().all(|e| (16..128).contains(&e.body.len())));
+    }
+
+    /// The same seed produces the same entries.
+    #[test]
+    fn deterministic() {
+        let first = generate("deterministic_1", Corruption::None);
+        let second = generate("deterministic_2", Corruption::None);
+        assert_eq!(first.len(), second.len());
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.offset, b.offset);
+            assert_eq!(a.body, b.body);
+        }
+    }
+
+    /// A truncated last line is not read.
+    #[test]
+    fn truncated_last_line() {
+        assert_eq!(
+            49,
+            generate("truncated_line", Corruption::TruncatedLastLine).len()
+        );
+    }
+
+    /// Reading stops at the first garbage line.
+    #[test]
+    fn garbage_lines() {
+        let entries = generate("garbage", Corruption::GarbageLines { every: 10 });
+        assert_eq!(10, entries.len());
+    }
+
+    /// A truncated stream loses the entries at its end.
+    #[test]
+    fn truncated_stream() {
+        assert!(generate("truncated_stream", Corruption::TruncatedStream).len() < 50);
+    }
+}
@@ -0,0 +1,126 @@
+//! Reading log files while they are written, like `tail -f`, including
+//! across rotations.
+
+use crate::LogEntryIter;
+use flate2::bufread::GzDecoder;
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, BufReader, Read, Seek},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// How often a followed file is checked for new data once everything
+/// written to it has been read.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Opens the gzip log file at `path` to be read while it is written,
+/// returning the entries already in it and then the ones written later.
+///
+/// Entries become readable once the logger flushes them, so a logger being
+/// followed should flush regularly, such as with
+/// [`LoggerBuilder::flush_interval`](crate::LoggerBuilder::flush_interval).
+/// Iteration blocks until the next entry is readable, checking for it every
+/// [`POLL_INTERVAL`], and only ends at an entry that cannot be read.
+///
+/// When the file is rotated, reading continues with the new file at `path`
+/// once the rotated one has been finished, and when it is truncated,
+/// reading starts over at its beginning.
+pub fn follow<P: AsRef<Path>>(path: P) -> io::Result<LogEntryIter> {
+    let path = path.as_ref().to_path_buf();
+    let file = BufReader::new(Growing(File::open(&path)?));
+    Ok(LogEntryIter::decoded(Following {
+        path,
+        decoder: Some(GzDecoder::new(file)),
+    }))
+}
+
+/// File that is waited on to grow instead of ending.
+struct Growing(File);
+
+impl Read for Growing {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.0.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Decoder of a followed file's gzip members, one after another.
+struct Following {
+    path: PathBuf,
+    /// Decoder of the current member, only missing while starting the next
+    /// one.
+    decoder: Option<GzDecoder<BufReader<Growing>>>,
+}
+
+impl Read for Following {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let decoder = self
+                .decoder
+                .as_mut()
+                .expect("a member is always being read");
+            let read = decoder.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            let decoder = self.decoder.take().expect("a member is always being read");
+            let mut file = decoder.into_inner();
+            self.next_member(&mut file)?;
+            self.decoder = Some(GzDecoder::new(file));
+        }
+    }
+}
+
+impl Following {
+    /// Waits, after a member ended, until the next one can be read, either
+    /// from the same file or from the file the writer rotated to.
+    fn next_member(&self, file: &mut BufReader<Growing>) -> io::Result<()> {
+        loop {
+            if !file.buffer().is_empty() {
+                return Ok(());
+            }
+            let current = &mut file.get_mut().0;
+            let position = current.stream_position()?;
+            let metadata = current.metadata()?;
+            if metadata.len() > position {
+                return Ok(());
+            }
+            if metadata.len() < position {
+                current.rewind()?;
+                return Ok(());
+            }
+            // members are only started at the path, so a finished file that
+            // is no longer there has been rotated
+            if let Ok(at_path) = fs::metadata(&self.path) {
+                if at_path.len() < position || !same_file(&metadata, &at_path) {
+                    if let Ok(rotated_to) = File::open(&self.path) {
+                        *file = BufReader::new(Growing(rotated_to));
+                        return Ok(());
+                    }
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Returns whether the metadata is of the same file.
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+/// Returns whether the metadata is of the same file, as far as creation
+/// times tell.
+#[cfg(not(unix))]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    a.created().ok() == b.created().ok()
+}
@@ -0,0 +1,101 @@
+//! Protection against forked processes writing into their parent's file.
+//!
+//! A child created with `fork()` inherits a copy of its parent's logger,
+//! including any compressed data not yet written. If both processes kept
+//! writing, the gzip stream would be corrupted, so loggers remember which
+//! process created them and never write to the inherited destination from
+//! another process.
+
+use crate::dest::{Dest, DestOptions};
+use std::path::{Path, PathBuf};
+
+/// What a forked child process does with a logger inherited from its parent.
+///
+/// Non-blocking loggers always discard the child's entries, since the
+/// parent's writer thread does not exist in the child. So do the TCP, UDP,
+/// collector client and shared-memory ring loggers, whose connection, socket
+/// or ring belongs to the parent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ForkPolicy {
+    /// Entries logged by the child are discarded.
+    #[default]
+    Disable,
+    /// The child logs to a new file next to its parent's, named with the
+    /// child's process ID before the extensions, e.g. `app.4242.jsonl.gz`.
+    Reopen,
+}
+
+/// Destination that is only written by the process that created it.
+pub(crate) struct Owned {
+    pid: u32,
+    dest: Option<Dest>,
+    /// Path and options for reopening in a child, if it should.
+    reopen: Option<(PathBuf, DestOptions)>,
+}
+
+impl Owned {
+    /// Wraps a destination created by the current process.
+    ///
+    /// `reopen` is the path `dest` writes to and its options, if children
+    /// should reopen a file next to it.
+    pub(crate) fn new(dest: Dest, reopen: Option<(PathBuf, DestOptions)>) -> Self {
+        Self {
+            pid: current_pid(),
+            dest: Some(dest),
+            reopen,
+        }
+    }
+
+    /// Returns the destination this process may write to, if any.
+    pub(crate) fn get(&mut self) -> Option<&mut Dest> {
+        let pid = current_pid();
+        if pid != self.pid {
+            self.pid = pid;
+            // dropping the parent's destination would write its buffered data
+            std::mem::forget(self.dest.take());
+            self.dest = self
+                .reopen
+                .as_ref()
+                .and_then(|(path, options)| options.create(child_path(path, pid)).ok());
+        }
+        self.dest.as_mut()
+    }
+}
+
+impl Drop for Owned {
+    fn drop(&mut self) {
+        if current_pid() != self.pid {
+            std::mem::forget(self.dest.take());
+        }
+    }
+}
+
+/// Returns the current process's ID.
+///
+/// WebAssembly has no processes, so there it is always 0.
+pub(crate) fn current_pid() -> u32 {
+    #[cfg(not(target_family = "wasm"))]
+    return std::process::id();
+    #[cfg(target_family = "wasm")]
+    return 0;
+}
+
+/// Returns the path of a child's log file next to its parent's.
+pub(crate) fn child_path(path: &Path, pid: u32) -> PathBuf {
+    crate::dest::tagged_path(path, pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Child files are named after their parent's with the process ID.
+    #[test]
+    fn child_paths() {
+        assert_eq!(
+            Path::new("logs/app.42.jsonl.gz"),
+            child_path(Path::new("logs/app.jsonl.gz"), 42)
+        );
+        assert_eq!(Path::new("app.42"), child_path(Path::new("app"), 42));
+    }
+}
@@ -0,0 +1,271 @@
+//! Serialization of log records into entries.
+
+use crate::{
+    clock::Clock,
+    hub::Hub,
+    limit::{DropCause, Drops, Repeats},
+    LogEntry, LogEntryArgs,
+};
+use log::Level;
+use serde_json::{Map, Value};
+use std::{
+    borrow::Cow,
+    fmt::Arguments,
+    io,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// Turns log records into serialized entries, shared by all loggers writing
+/// to a file.
+pub(crate) struct Format {
+    pub(crate) clock: Box<dyn Clock>,
+    pub(crate) session: Option<String>,
+    /// Whether entries record the ID of the process writing them.
+    pub(crate) pid: bool,
+    /// Whether entries record the wall-clock time.
+    pub(crate) timestamps: bool,
+    /// Whether entries record where in the source they were logged.
+    pub(crate) source_location: bool,
+    #[cfg(feature = "pii")]
+    pub(crate) pii: Option<crate::pii::PiiHasher>,
+    pub(crate) redactor: crate::redact::Redactor,
+    /// Length in bytes that longer bodies are cut down to.
+    pub(crate) max_body: Option<usize>,
+    /// Shared with handles, which can change the rates after installation.
+    pub(crate) sampler: Arc<crate::limit::Sampler>,
+    pub(crate) dedup: Option<crate::limit::Dedup>,
+    pub(crate) rate_limit: Option<crate::limit::RateLimit>,
+    /// Entries dropped on their way to the destination, shared with
+    /// background writers.
+    pub(crate) drops: Arc<Drops>,
+    #[cfg(feature = "encryption")]
+    pub(crate) body_cipher: Option<crate::encryption::BodyCipher>,
+    /// Receives errors that logging cannot return.
+    pub(crate) on_error: ErrorHook,
+    /// Receives every entry, for keeping recent ones and live streams.
+    pub(crate) hub: Option<Arc<Hub>>,
+    /// Server streaming the hub's entries, stopped when dropped.
+    #[cfg(feature = "http")]
+    pub(crate) _http: Option<crate::http::Server>,
+}
+
+/// Callback receiving errors that logging cannot return to its caller.
+pub(crate) type ErrorHook = Arc<dyn Fn(&io::Error) + Send + Sync>;
+
+/// Returns the hook used unless another one is configured, which reports
+/// errors on stderr.
+pub(crate) fn default_error_hook() -> ErrorHook {
+    Arc::new(|error| eprintln!("jsonl_gzip_logger: {}", error))
+}
+
+impl Format {
+    /// Serializes a record, passing each line to write for it to `emit` as
+    /// a JSON object without a trailing newline.
+    ///
+    /// The entry may be preceded by notices about earlier entries, such as
+    /// how many were suppressed by the rate limit, or not be written at
+    /// all. Errors serializing are reported, dropping the entry.
+    pub(crate) fn entries(&self, record: &log::Record, mut emit: impl FnMut(Vec<u8>)) {
+        self.report_drops(false, &mut emit);
+        if !self.sampler.keep(record.level()) {
+            return;
+        }
+        if let Some(dedup) = &self.dedup {
+            let body: Cow<str> = match record.args().as_str() {
+                Some(body) => body.into(),
+                None => record.args().to_string().into(),
+            };
+            let (unique, repeats) = dedup.admit(record.level(), record.target(), &body);
+            if let Some(line) = repeats.and_then(|repeats| self.repeated(repeats)) {
+                emit(line);
+            }
+            if !unique {
+                return;
+            }
+        }
+        if let Some(limit) = &self.rate_limit {
+            let offset = self.clock.offset();
+            let (admitted, suppressed) = limit.admit(record.target(), offset);
+            for (target, suppressed) in suppressed {
+                let mut fields = Map::new();
+                fields.insert("target".to_string(), target.as_str().into());
+                fields.insert("suppressed".to_string(), suppressed.into());
+                let body = format_args!("suppressed {} entries from {}", suppressed, target);
+                if let Some(line) = self.notice(offset, Level::Warn, body, fields) {
+                    emit(line);
+                }
+            }
+            if !admitted {
+                self.drops.count(DropCause::RateLimit);
+                return;
+            }
+        }
+        match self.try_entry(record) {
+            Ok(line) => emit(line),
+            Err(error) => self.report(&error),
+        }
+    }
+
+    /// Passes the lines for notices about earlier entries that are still
+    /// pending to `emit`, so that they are written before the log is
+    /// flushed.
+    pub(crate) fn pending(&self, mut emit: impl FnMut(Vec<u8>)) {
+        if let Some(dedup) = &self.dedup {
+            if let Some(line) = dedup.flush().and_then(|repeats| self.repeated(repeats)) {
+                emit(line);
+            }
+        }
+        self.report_drops(true, &mut emit);
+    }
+
+    /// Passes the line for a report of the entries dropped since the last
+    /// one to `emit`, if one is due.
+    fn report_drops(&self, flushing: bool, emit: &mut impl FnMut(Vec<u8>)) {
+        let offset = || self.clock.offset();
+        let Some([queue, io, rate_limit]) = self.drops.take_report(offset, flushing) else {
+            return;
+        };
+        let dropped = queue + io + rate_limit;
+        let mut fields = Map::new();
+        fields.insert("dropped".to_string(), dropped.into());
+        fields.insert("queue".to_string(), queue.into());
+        fields.insert("io".to_string(), io.into());
+        fields.insert("rate_limit".to_string(), rate_limit.into());
+        let body = format_args!("dropped {} entries since last report", dropped);
+        if let Some(line) = self.notice(self.clock.offset(), Level::Warn, body, fields) {
+            emit(line);
+        }
+    }
+
+    /// Serializes the notice that the last entry of a target was repeated.
+    fn repeated(&self, repeats: Repeats) -> Option<Vec<u8>> {
+        let mut fields = Map::new();
+        fields.insert("target".to_string(), repeats.target.into());
+        fields.insert("repeated".to_string(), repeats.count.into());
+        let body = format_args!("message repeated {} times", repeats.count);
+        self.notice(self.clock.offset(), repeats.level, body, fields)
+    }
+
+    /// Serializes an entry of this crate's own, returning `None` after
+    /// reporting the error if that fails.
+    fn notice(
+        &self,
+        offset: Duration,
+        level: Level,
+        body: Arguments,
+        fields: Map<String, Value>,
+    ) -> Option<Vec<u8>> {
+        let record = log::Record::builder()
+            .level(level)
+            .target(LogEntry::OWN_TARGET)
+            .args(body)
+            .build();
+        self.serialize(offset, &record, body, Some(fields), false)
+            .map_err(|error| self.report(&error))
+            .ok()
+    }
+
+    /// Passes an error that cannot be returned to the error hook.
+    pub(crate) fn report(&self, error: &io::Error) {
+        (self.on_error)(error);
+    }
+
+    /// Serializes a record as a JSON object without a trailing newline.
+    fn try_entry(&self, record: &log::Record) -> io::Result<Vec<u8>> {
+        let offset = self.clock.offset();
+        if self.rewrites_body() {
+            let mut body = self.redactor.redact(record.args().to_string());
+            #[cfg(feature = "pii")]
+            if let Some(pii) = &self.pii {
+                body = pii.scrub(&body).into_owned();
+            }
+            // after scrubbing, so that a cut cannot leave part of a secret
+            let truncated = self.max_body.is_some_and(|max| truncate(&mut body, max));
+            // encryption comes last so that nothing else sees the ciphertext
+            #[cfg(feature = "encryption")]
+            if let Some(cipher) = &self.body_cipher {
+                body = cipher.seal(&body);
+            }
+            return self.serialize(
+                offset,
+                record,
+                format_args!("{}", body),
+                self.fields(record),
+                truncated,
+            );
+        }
+        self.serialize(offset, record, *record.args(), self.fields(record), false)
+    }
+
+    /// Returns the record's key-values as they are written.
+    fn fields(&self, record: &log::Record) -> Option<Map<String, Value>> {
+        let mut fields = crate::kv::fields(record);
+        if let Some(fields) = &mut fields {
+            self.redactor.redact_fields(fields);
+            #[cfg(feature = "pii")]
+            if let Some(pii) = &self.pii {
+                fields.values_mut().for_each(|value| pii.scrub_value(value));
+            }
+        }
+        fields
+    }
+
+    /// Returns whether bodies are changed before being written.
+    fn rewrites_body(&self) -> bool {
+        #[allow(unused_mut)]
+        let mut rewrites = !self.redactor.is_empty() || self.max_body.is_some();
+        #[cfg(feature = "pii")]
+        {
+            rewrites |= self.pii.is_some();
+        }
+        #[cfg(feature = "encryption")]
+        {
+            rewrites |= self.body_cipher.is_some();
+        }
+        rewrites
+    }
+
+    /// Serializes an entry for the record with the given offset and body.
+    fn serialize(
+        &self,
+        offset: Duration,
+        record: &log::Record,
+        body: Arguments,
+        fields: Option<Map<String, Value>>,
+        truncated: bool,
+    ) -> io::Result<Vec<u8>> {
+        let entry = crate::core::serialize(&LogEntryArgs {
+            offset,
+            level: record.level(),
+            target: record.target(),
+            body,
+            session: self.session.as_deref(),
+            pid: self.pid.then(crate::fork::current_pid),
+            time: self.timestamps.then(SystemTime::now),
+            module_path: record.module_path().filter(|_| self.source_location),
+            file: record.file().filter(|_| self.source_location),
+            line: record.line().filter(|_| self.source_location),
+            fields: fields.as_ref(),
+            truncated,
+        })?;
+        if let Some(hub) = &self.hub {
+            hub.publish(&entry);
+        }
+        Ok(entry)
+    }
+}
+
+/// Cuts the body down to at most `max` bytes, at a character boundary,
+/// returning whether it was longer.
+fn truncate(body: &mut String, max: usize) -> bool {
+    if body.len() <= max {
+        return false;
+    }
+    let mut end = max;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    body.truncate(end);
+    true
+}
@@ -0,0 +1,197 @@
+//! Control over an installed logger besides the `log` facade.
+
+use crate::{
+    facade,
+    filter::FilterError,
+    fork::Owned,
+    format::ErrorHook,
+    health::{Health, Monitor},
+    hub::Hub,
+    limit::Sampler,
+    queue::Sender,
+    worker::{self, Durability, Flushed, Message},
+    LogEntry,
+};
+use log::LevelFilter;
+use std::{
+    io,
+    sync::{Arc, Mutex, Weak},
+};
+
+/// Cloneable handle for controlling an installed logger, returned by the
+/// guards of both blocking and non-blocking loggers.
+///
+/// Once the logger has been uninstalled, flushes complete right away, level
+/// changes are ignored, and rotating fails.
+#[derive(Clone)]
+pub struct LoggerHandle {
+    /// Generation of the logger in the facade.
+    generation: u64,
+    target: Target,
+    hub: Option<Arc<Hub>>,
+    sampler: Arc<Sampler>,
+    monitor: Arc<Monitor>,
+}
+
+/// Where a [`LoggerHandle`] sends its requests.
+#[derive(Clone)]
+pub(crate) enum Target {
+    /// The destination of a blocking logger, which the handle must not keep
+    /// alive, and where its errors go.
+    Dest(Weak<Mutex<Owned>>, ErrorHook),
+    /// The writer of a non-blocking logger, owned by the given process.
+    Worker(Sender, u32),
+}
+
+impl LoggerHandle {
+    /// Creates a handle for the logger of the given generation.
+    pub(crate) fn new(
+        generation: u64,
+        target: Target,
+        hub: Option<Arc<Hub>>,
+        sampler: Arc<Sampler>,
+        monitor: Arc<Monitor>,
+    ) -> Self {
+        Self {
+            generation,
+            target,
+            hub,
+            sampler,
+            monitor,
+        }
+    }
+
+    /// Returns the generation of the logger in the facade.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Flushes every entry logged before this call to the destination.
+    pub fn flush(&self) {
+        self.written_up_to_now(Durability::Written).wait();
+    }
+
+    /// Returns a future that resolves once every entry logged before this
+    /// call has been compressed and written to the destination.
+    pub fn flush_async(&self) -> Flushed {
+        self.written_up_to_now(Durability::Written)
+    }
+
+    /// Returns a barrier that completes once everything logged before this
+    /// call has reached the given durability.
+    ///
+    /// The barrier can be awaited or, outside of async code, waited on with
+    /// [`Flushed::wait`], which makes it usable for checkpointing workflows
+    /// that must not proceed until their logs are persisted. Blocking loggers
+    /// write before returning, so their barriers are already complete.
+    pub fn written_up_to_now(&self, durability: Durability) -> Flushed {
+        match &self.target {
+            Target::Dest(dest, on_error) => {
+                let result = with_dest(dest, |dest| match durability {
+                    Durability::Written => dest.flush(),
+                    Durability::Synced => dest.sync(),
+                });
+                if let Some(Err(error)) = result {
+                    on_error(&error);
+                }
+                worker::completed()
+            }
+            // forked children must not wait for a writer thread they do not
+            // have
+            Target::Worker(_, pid) if *pid != crate::fork::current_pid() => worker::completed(),
+            Target::Worker(sender, _) => worker::flush(sender, durability),
+        }
+    }
+
+    /// Finishes the current file and continues in a new one at its path,
+    /// renaming the finished file as rotation by size or time would.
+    ///
+    /// If the file was already renamed or deleted, for example by
+    /// logrotate, it is left where it is and only reopened. This makes
+    /// rotating safe to trigger from anywhere, including a thread woken by a
+    /// signal as `rotate_on_sighup` does with the `sighup` feature on Unix.
+    ///
+    /// Fails if the logger does not write to a file, if renaming or creating
+    /// a file fails, or if the logger has been uninstalled.
+    pub fn rotate(&self) -> io::Result<()> {
+        let uninstalled = || io::Error::other("the logger is not installed");
+        match &self.target {
+            Target::Dest(dest, _) => {
+                with_dest(dest, |dest| dest.rotate()).unwrap_or_else(|| Err(uninstalled()))
+            }
+            Target::Worker(sender, _) => {
+                let (reply, result) = std::sync::mpsc::channel();
+                sender
+                    .send(Message::Rotate(reply))
+                    .map_err(|_| uninstalled())?;
+                result.recv().unwrap_or_else(|_| Err(uninstalled()))
+            }
+        }
+    }
+
+    /// Changes the level for targets without a more specific one, like
+    /// [`set_level`](crate::set_level) but only while this handle's logger
+    /// is installed.
+    pub fn set_level(&self, level: LevelFilter) {
+        facade::reconfigure(Some(self.generation), |filter| filter.set_default(level));
+    }
+
+    /// Changes the level for targets starting with `prefix`, like
+    /// [`set_target_level`](crate::set_target_level) but only while this
+    /// handle's logger is installed.
+    pub fn set_target_level<S: Into<String>>(&self, prefix: S, level: LevelFilter) {
+        let prefix = prefix.into();
+        facade::reconfigure(Some(self.generation), |filter| filter.set(prefix, level));
+    }
+
+    /// Applies `RUST_LOG`-style directives, like
+    /// [`set_filters`](crate::set_filters) but only while this handle's
+    /// logger is installed.
+    pub fn set_filters(&self, directives: &str) -> Result<(), FilterError> {
+        let directives = crate::filter::parse(directives)?;
+        facade::reconfigure(Some(self.generation), |filter| {
+            filter.apply_parsed(directives)
+        });
+        Ok(())
+    }
+
+    /// Writes only one in every `one_in` entries logged at the level from
+    /// now on, like [`LoggerBuilder::sample`](crate::LoggerBuilder::sample).
+    /// A `one_in` of 0 or 1 keeps every entry again.
+    pub fn set_sampling(&self, level: log::Level, one_in: u32) {
+        self.sampler.set(level, one_in);
+    }
+
+    /// Returns when the logger last wrote an entry, the file it writes to
+    /// and its size, and its most recent error, so that supervisors can
+    /// detect a wedged or failing logger.
+    ///
+    /// Non-blocking loggers report on their writer, which may lag behind
+    /// what has been logged. The last state is kept after the logger is
+    /// uninstalled.
+    pub fn health(&self) -> Health {
+        self.monitor.health()
+    }
+
+    /// Returns the most recent entries, oldest first.
+    ///
+    /// Empty unless enabled with
+    /// [`LoggerBuilder::recent_entries`](crate::LoggerBuilder::recent_entries).
+    pub fn recent(&self) -> Vec<LogEntry> {
+        match &self.hub {
+            Some(hub) => hub.recent_entries(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Runs `f` on a blocking logger's destination, if the logger still exists
+/// and this process may write to it.
+fn with_dest<T>(
+    dest: &Weak<Mutex<Owned>>,
+    f: impl FnOnce(&mut crate::dest::Dest) -> T,
+) -> Option<T> {
+    let dest = dest.upgrade()?;
+    let mut owned = dest.lock().ok()?;
+    owned.get().map(f)
+}
@@ -0,0 +1,143 @@
+//! Metadata record written at the start of each file.
+
+use crate::{LogEntry, LoggerBuilder};
+use log::Level;
+use serde_json::{Map, Value};
+use std::time::{Duration, SystemTime};
+
+/// Version of the entry format written by this crate, recorded in headers.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// Body of header entries.
+const BODY: &str = "header";
+
+impl LoggerBuilder {
+    /// Starts every file with a [`Header`] entry describing the process that
+    /// wrote it, so that readers can attribute files to runs and convert
+    /// offsets to absolute times.
+    ///
+    /// Like [`timestamps`](Self::timestamps) this needs the wall-clock time,
+    /// so it must stay disabled on `wasm32-unknown-unknown`.
+    pub fn header(mut self, enabled: bool) -> Self {
+        self.header = enabled;
+        self
+    }
+}
+
+/// Process metadata from the first entry of a file written with
+/// [`LoggerBuilder::header`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    /// ID of the process that wrote the file.
+    pub pid: u32,
+    /// Name of the host the process ran on, if it could be determined.
+    pub hostname: Option<String>,
+    /// File name of the process's executable, if it could be determined.
+    pub executable: Option<String>,
+    /// Time at which entry offsets are zero.
+    pub start: SystemTime,
+    /// Random ID of the run, the same as in entries logged with
+    /// [`LoggerBuilder::session_id`].
+    pub session: String,
+    /// [`FORMAT_VERSION`] of the crate that wrote the file.
+    pub format_version: u64,
+}
+
+impl Header {
+    /// Describes the current process, whose offsets start at `start`, in the
+    /// run with the given ID.
+    pub(crate) fn current(start: SystemTime, session: String) -> Self {
+        Self {
+            pid: crate::fork::current_pid(),
+            hostname: hostname(),
+            executable: std::env::current_exe().ok().and_then(|path| {
+                let name = path.file_name()?;
+                Some(name.to_string_lossy().into_owned())
+            }),
+            start,
+            session,
+            format_version: FORMAT_VERSION,
+        }
+    }
+
+    /// Returns the entry recording this header.
+    pub(crate) fn to_entry(&self) -> LogEntry {
+        let mut fields = Map::new();
+        fields.insert("pid".to_string(), self.pid.into());
+        if let Some(hostname) = &self.hostname {
+            fields.insert("hostname".to_string(), hostname.clone().into());
+        }
+        if let Some(executable) = &self.executable {
+            fields.insert("executable".to_string(), executable.clone().into());
+        }
+        fields.insert("format_version".to_string(), self.format_version.into());
+        LogEntry {
+            offset: Duration::ZERO,
+            level: Level::Info,
+            target: LogEntry::OWN_TARGET.to_string(),
+            body: BODY.to_string(),
+            session: Some(self.session.clone()),
+            pid: None,
+            time: Some(self.start),
+            module_path: None,
+            file: None,
+            line: None,
+            fields,
+            truncated: false,
+        }
+    }
+
+    /// Reads a header back from its entry.
+    pub(crate) fn from_entry(entry: &LogEntry) -> Option<Self> {
+        if entry.target != LogEntry::OWN_TARGET || entry.body != BODY {
+            return None;
+        }
+        let string = |key| {
+            entry
+                .fields
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        Some(Self {
+            pid: entry.fields.get("pid")?.as_u64()?.try_into().ok()?,
+            hostname: string("hostname"),
+            executable: string("executable"),
+            start: entry.time?,
+            session: entry.session.clone()?,
+            format_version: entry.fields.get("format_version")?.as_u64()?,
+        })
+    }
+}
+
+/// Returns the name of this host, if it can be determined without platform
+/// APIs.
+fn hostname() -> Option<String> {
+    let from_file = |path| {
+        let name = std::fs::read_to_string(path).ok()?;
+        Some(name.trim().to_string()).filter(|name| !name.is_empty())
+    };
+    from_file("/proc/sys/kernel/hostname")
+        .or_else(|| from_file("/etc/hostname"))
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Headers survive being written as entries and read back.
+    #[test]
+    fn round_trips() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(7);
+        let header = Header::current(start, "run".to_string());
+        let entry = header.to_entry();
+        assert_eq!(Some(header), Header::from_entry(&entry));
+        assert!(!entry.is_session_marker());
+        assert_eq!(
+            None,
+            Header::from_entry(&LogEntry::session_marker("run".to_string()))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! State of a logger's destination, for supervisors to check on.
+
+use std::{
+    io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+/// Snapshot of how writing is going, returned by
+/// [`LoggerHandle::health`](crate::LoggerHandle::health).
+///
+/// A logger whose `last_write` stops advancing while entries are logged, or
+/// that keeps reporting new errors, is wedged or failing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Health {
+    /// When an entry was last written to the destination without error.
+    ///
+    /// Always `None` on `wasm32-unknown-unknown`, which has no wall clock.
+    pub last_write: Option<SystemTime>,
+    /// Path of the file currently written to, if writing to a file.
+    pub path: Option<PathBuf>,
+    /// Compressed bytes written to that file so far, including those of
+    /// earlier sessions when appending. Data still buffered in the
+    /// compressor is not counted.
+    pub size: Option<u64>,
+    /// Message of the most recent error writing, flushing, or rotating.
+    pub last_error: Option<String>,
+}
+
+/// Tracks the health of a destination, shared between the destinations a
+/// logger creates as it rotates and the handles of the logger.
+#[derive(Default)]
+pub(crate) struct Monitor {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    last_write: Option<SystemTime>,
+    /// The file written to and its count of compressed bytes.
+    file: Option<(PathBuf, Arc<AtomicU64>)>,
+    last_error: Option<String>,
+}
+
+impl Monitor {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Records that writing continues in the file at `path`, with `written`
+    /// counting its compressed bytes, or not in a file if `None`.
+    pub(crate) fn file(&self, file: Option<(PathBuf, Arc<AtomicU64>)>) {
+        self.state().file = file;
+    }
+
+    /// Records the outcome of writing an entry.
+    pub(crate) fn written(&self, result: &io::Result<()>) {
+        match result {
+            Ok(()) => self.state().last_write = now(),
+            Err(error) => self.failed(error),
+        }
+    }
+
+    /// Records an error writing, flushing, or rotating.
+    pub(crate) fn failed(&self, error: &io::Error) {
+        self.state().last_error = Some(error.to_string());
+    }
+
+    /// Returns a snapshot of the recorded state.
+    pub(crate) fn health(&self) -> Health {
+        let state = self.state();
+        Health {
+            last_write: state.last_write,
+            path: state.file.as_ref().map(|(path, _)| path.clone()),
+            size: state
+                .file
+                .as_ref()
+                .map(|(_, written)| written.load(Ordering::Relaxed)),
+            last_error: state.last_error.clone(),
+        }
+    }
+}
+
+/// Returns the current wall-clock time, if there is a wall clock.
+fn now() -> Option<SystemTime> {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    return Some(SystemTime::now());
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    return None;
+}
@@ -0,0 +1,262 @@
+//! Embedded HTTP server for watching entries live.
+//!
+//! Enabled with [`LoggerBuilder::http_server`], the server exposes:
+//!
+//! - `GET /logs/stream`: every new entry as a Server-Sent Event whose data is
+//!   the entry's JSON.
+//! - `GET /logs/recent?n=500`: the last `n` entries (100 by default) as JSON
+//!   lines.
+//! - `GET /logs/ws`: every new entry as a WebSocket text message holding the
+//!   entry's JSON, with the `websocket` feature.
+//!
+//! The streams only send entries at or above the `level` query parameter and
+//! whose target starts with the `target` query parameter, if given, e.g.
+//! `/logs/ws?level=warn&target=app::db`.
+//!
+//! The server has no authentication, so only bind it to trusted interfaces.
+
+use crate::{hub::Hub, LoggerBuilder};
+use log::LevelFilter;
+use serde::Deserialize;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// Number of entries returned by `/logs/recent` without `n`.
+const DEFAULT_RECENT: usize = 100;
+/// How often an idle event stream sends a comment to keep it open.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Configuration of the embedded HTTP server.
+#[derive(Clone, Debug)]
+pub struct HttpServer {
+    address: SocketAddr,
+    recent: usize,
+}
+
+impl HttpServer {
+    /// Creates a server listening on the given address, such as
+    /// `"127.0.0.1:9870"`.
+    pub fn new<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let address = address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to listen on")
+        })?;
+        Ok(Self {
+            address,
+            recent: 1000,
+        })
+    }
+
+    /// Sets how many entries are kept for `/logs/recent`, 1000 by default.
+    pub fn recent(mut self, count: usize) -> Self {
+        self.recent = count;
+        self
+    }
+}
+
+impl LoggerBuilder {
+    /// Serves the logger's entries over HTTP while it is installed.
+    ///
+    /// See [`http`](crate::http) for the endpoints.
+    pub fn http_server(mut self, server: HttpServer) -> Self {
+        self.http = Some(server);
+        self
+    }
+}
+
+impl HttpServer {
+    /// Returns how many recent entries the server needs kept.
+    pub(crate) fn recent_capacity(&self) -> usize {
+        self.recent
+    }
+}
+
+/// Running server, stopped when dropped.
+pub(crate) struct Server {
+    hub: Arc<Hub>,
+    address: SocketAddr,
+}
+
+impl Server {
+    /// Starts serving the entries published to the hub.
+    pub(crate) fn start(config: &HttpServer, hub: Arc<Hub>) -> io::Result<Self> {
+        let listener = TcpListener::bind(config.address)?;
+        let address = listener.local_addr()?;
+        {
+            let hub = hub.clone();
+            thread::spawn(move || accept(listener, hub));
+        }
+        Ok(Self { hub, address })
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.hub.close();
+        // wake the acceptor up so it notices the hub is closed
+        let _ = TcpStream::connect(self.address);
+    }
+}
+
+/// Serves connections until the hub is closed.
+fn accept(listener: TcpListener, hub: Arc<Hub>) {
+    for stream in listener.incoming() {
+        if hub.is_closed() {
+            break;
+        }
+        if let Ok(stream) = stream {
+            let hub = hub.clone();
+            thread::spawn(move || {
+                let _ = serve(stream, &hub);
+            });
+        }
+    }
+}
+
+/// Parsed request line of an HTTP request.
+pub(crate) struct Request {
+    pub(crate) path: String,
+    pub(crate) query: Vec<(String, String)>,
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Reads a request's line and headers.
+    pub(crate) fn read(reader: &mut impl BufRead) -> io::Result<Self> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let target = line.split(' ').nth(1).unwrap_or("/");
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let path = path.to_string();
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+        Ok(Self {
+            path,
+            query,
+            headers,
+        })
+    }
+
+    /// Returns the value of a query parameter.
+    pub(crate) fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the value of a header, by its lowercase name.
+    #[cfg(feature = "websocket")]
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the filter given by the `level` and `target` parameters.
+    fn filter(&self) -> Filter {
+        Filter {
+            level: self
+                .param("level")
+                .and_then(|level| level.parse().ok())
+                .unwrap_or(LevelFilter::Trace),
+            target: self.param("target").unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// Selection of the entries sent to a stream.
+pub(crate) struct Filter {
+    level: LevelFilter,
+    target: String,
+}
+
+/// The fields of an entry needed for filtering.
+#[derive(Deserialize)]
+struct Fields<'a> {
+    level: log::Level,
+    #[serde(borrow)]
+    target: std::borrow::Cow<'a, str>,
+}
+
+impl Filter {
+    /// Returns whether the serialized entry should be sent.
+    pub(crate) fn matches(&self, entry: &[u8]) -> bool {
+        match serde_json::from_slice::<Fields>(entry) {
+            Ok(fields) => fields.level <= self.level && fields.target.starts_with(&self.target),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Answers one request.
+fn serve(stream: TcpStream, hub: &Hub) -> io::Result<()> {
+    let mut request = BufReader::new(stream.try_clone()?);
+    let request = Request::read(&mut request)?;
+    let mut stream = stream;
+    match request.path.as_str() {
+        "/logs/recent" => {
+            let count = request
+                .param("n")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(DEFAULT_RECENT);
+            let mut body = Vec::new();
+            for entry in hub.recent(count) {
+                body.extend_from_slice(&entry);
+                body.push(b'\n');
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )?;
+            stream.write_all(&body)
+        }
+        "/logs/stream" => {
+            let filter = request.filter();
+            let entries = hub.subscribe();
+            stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+            )?;
+            stream.flush()?;
+            loop {
+                match entries.recv_timeout(KEEP_ALIVE) {
+                    Ok(entry) if !filter.matches(&entry) => continue,
+                    Ok(entry) => {
+                        stream.write_all(b"data: ")?;
+                        stream.write_all(&entry)?;
+                        stream.write_all(b"\n\n")?;
+                    }
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                        stream.write_all(b": keep-alive\n\n")?;
+                    }
+                    Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+                }
+                stream.flush()?;
+            }
+        }
+        #[cfg(feature = "websocket")]
+        "/logs/ws" => crate::websocket::serve(stream, &request, request.filter(), hub),
+        _ => stream
+            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"),
+    }
+}
@@ -0,0 +1,134 @@
+//! In-memory distribution of serialized entries as they are logged.
+
+use crate::LogEntry;
+use std::{
+    collections::VecDeque,
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+};
+
+/// How many entries a subscriber may fall behind before entries are dropped
+/// for it.
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+const SUBSCRIBER_BACKLOG: usize = 1024;
+
+/// Keeps the most recent entries and forwards new ones to subscribers.
+pub(crate) struct Hub {
+    state: Mutex<State>,
+    capacity: usize,
+}
+
+struct State {
+    recent: VecDeque<Arc<[u8]>>,
+    subscribers: Vec<SyncSender<Arc<[u8]>>>,
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
+    closed: bool,
+}
+
+impl Hub {
+    /// Creates a hub remembering the last `capacity` entries.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                recent: VecDeque::with_capacity(capacity),
+                subscribers: Vec::new(),
+                closed: false,
+            }),
+            capacity,
+        }
+    }
+
+    /// Records a serialized entry and sends it to every subscriber.
+    ///
+    /// Subscribers that fall too far behind miss entries rather than
+    /// slowing down logging.
+    pub(crate) fn publish(&self, entry: &[u8]) {
+        let entry: Arc<[u8]> = entry.into();
+        let mut state = self.state.lock().unwrap();
+        if self.capacity > 0 {
+            if state.recent.len() == self.capacity {
+                state.recent.pop_front();
+            }
+            state.recent.push_back(entry.clone());
+        }
+        state
+            .subscribers
+            .retain(|subscriber| match subscriber.try_send(entry.clone()) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+
+    /// Returns up to `count` of the most recent entries, oldest first.
+    pub(crate) fn recent(&self, count: usize) -> Vec<Arc<[u8]>> {
+        let state = self.state.lock().unwrap();
+        let skip = state.recent.len().saturating_sub(count);
+        state.recent.iter().skip(skip).cloned().collect()
+    }
+
+    /// Returns every remembered entry that can be parsed, oldest first.
+    pub(crate) fn recent_entries(&self) -> Vec<LogEntry> {
+        self.recent(self.capacity)
+            .iter()
+            .filter_map(|entry| serde_json::from_slice(entry).ok())
+            .collect()
+    }
+
+    /// Returns a receiver of every entry published from now on.
+    ///
+    /// The receiver disconnects once the hub is closed.
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
+    pub(crate) fn subscribe(&self) -> Receiver<Arc<[u8]>> {
+        let (sender, receiver) = mpsc::sync_channel(SUBSCRIBER_BACKLOG);
+        let mut state = self.state.lock().unwrap();
+        if !state.closed {
+            state.subscribers.push(sender);
+        }
+        receiver
+    }
+
+    /// Disconnects all subscribers, as no more entries will be published.
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
+    pub(crate) fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.subscribers.clear();
+    }
+
+    /// Returns whether the hub has been closed.
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
+    pub(crate) fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Only the most recent entries are kept.
+    #[test]
+    fn keeps_recent() {
+        let hub = Hub::new(2);
+        for entry in [b"1", b"2", b"3"] {
+            hub.publish(entry);
+        }
+        let recent: Vec<_> = hub.recent(10).iter().map(|e| e.to_vec()).collect();
+        assert_eq!(vec![b"2".to_vec(), b"3".to_vec()], recent);
+        assert_eq!(b"3", &*hub.recent(1)[0]);
+    }
+
+    /// Subscribers get entries published after subscribing until closed.
+    #[test]
+    fn subscribers() {
+        let hub = Hub::new(0);
+        hub.publish(b"before");
+        let receiver = hub.subscribe();
+        hub.publish(b"after");
+        assert_eq!(b"after", &*receiver.recv().unwrap());
+        hub.close();
+        assert!(receiver.recv().is_err());
+    }
+}
@@ -0,0 +1,53 @@
+//! Generation of unique IDs.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::SystemTime,
+};
+
+/// Returns a random version 4 UUID in its hyphenated form.
+///
+/// The randomness comes from the standard library's randomly seeded hasher
+/// keys, mixed with the time and process ID.
+pub(crate) fn uuid_v4() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let mut bytes = [0u8; 16];
+    for (index, half) in bytes.chunks_mut(8).enumerate() {
+        // every RandomState gets different keys
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        hasher.write_u32(crate::fork::current_pid());
+        hasher.write_usize(index);
+        half.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    bytes[6] = bytes[6] & 0x0f | 0x40;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IDs are well-formed version 4 UUIDs and differ from each other.
+    #[test]
+    fn uuids() {
+        let id = uuid_v4();
+        assert_eq!(36, id.len());
+        assert_eq!(Some('4'), id.chars().nth(14));
+        assert!("89ab".contains(id.chars().nth(19).unwrap()));
+        assert_ne!(id, uuid_v4());
+    }
+}
@@ -0,0 +1,171 @@
+//! Conversion of existing logs into .jsonl.gz files.
+//!
+//! [`import_jsonl`] converts JSON lines in this crate's schema, for example
+//! decompressed or concatenated archives, and [`import_with`] converts any
+//! line-based format given a [`LineParser`], such as [`EnvLoggerParser`] for
+//! `env_logger`'s default text output.
+
+use crate::{LogEntry, LogWriter};
+use flate2::read::GzDecoder;
+use log::Level;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+/// Turns lines of a foreign log into entries.
+pub trait LineParser {
+    /// Parses one line, without its line ending, or returns `None` to skip
+    /// it.
+    fn parse(&mut self, line: &str) -> Option<LogEntry>;
+}
+
+impl<F: FnMut(&str) -> Option<LogEntry>> LineParser for F {
+    fn parse(&mut self, line: &str) -> Option<LogEntry> {
+        self(line)
+    }
+}
+
+/// Counts of the lines seen by an import.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Import {
+    /// Lines converted into entries.
+    pub imported: usize,
+    /// Lines the parser skipped, including blank ones.
+    pub skipped: usize,
+}
+
+/// Converts a JSON lines file whose lines are [`LogEntry`]s into a new
+/// .jsonl.gz file at `dst`.
+///
+/// `src` may be plain or gzip compressed. Lines that are not entries are
+/// skipped.
+pub fn import_jsonl<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<Import> {
+    import_with(src, dst, |line: &str| serde_json::from_str(line).ok())
+}
+
+/// Converts each line of `src` with `parser` into a new .jsonl.gz file at
+/// `dst`.
+///
+/// `src` may be plain or gzip compressed, and lines that are not valid UTF-8
+/// are skipped.
+pub fn import_with<P: AsRef<Path>, Q: AsRef<Path>, L: LineParser>(
+    src: P,
+    dst: Q,
+    mut parser: L,
+) -> io::Result<Import> {
+    let mut reader = BufReader::new(File::open(src)?);
+    let mut reader: Box<dyn BufRead> = if reader.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        Box::new(BufReader::new(GzDecoder::new(reader)))
+    } else {
+        Box::new(reader)
+    };
+    let mut writer = LogWriter::create(dst)?;
+    let mut import = Import::default();
+    let mut buffer = Vec::new();
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            break;
+        }
+        let line = std::str::from_utf8(&buffer)
+            .ok()
+            .map(|line| line.trim_end_matches(['\n', '\r']));
+        match line.and_then(|line| parser.parse(line)) {
+            Some(entry) => {
+                writer.write_entry(&entry)?;
+                import.imported += 1;
+            }
+            None => import.skipped += 1,
+        }
+    }
+    writer.finish()?;
+    Ok(import)
+}
+
+/// Parser for `env_logger`'s default format, e.g.
+/// `[2024-05-01T12:34:56Z INFO  app::db] connected`.
+///
+/// Timestamps are kept as entry times, and offsets are measured from the
+/// first timestamp seen and are zero for lines without one. Lines not
+/// starting with a bracketed header, such as continuations of multi-line
+/// messages, are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct EnvLoggerParser {
+    start: Option<SystemTime>,
+}
+
+impl EnvLoggerParser {
+    /// Creates a parser.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LineParser for EnvLoggerParser {
+    fn parse(&mut self, line: &str) -> Option<LogEntry> {
+        let (header, body) = line.strip_prefix('[')?.split_once(']')?;
+        let mut parts = header.split_whitespace().peekable();
+        let time = parts.peek().and_then(|part| crate::rfc3339::parse(part));
+        if time.is_some() {
+            parts.next();
+        }
+        let level = Level::from_str(parts.next()?).ok()?;
+        let target = parts.next().unwrap_or_default().to_string();
+        let offset = match time {
+            Some(time) => time
+                .duration_since(*self.start.get_or_insert(time))
+                .unwrap_or_default(),
+            None => Duration::ZERO,
+        };
+        Some(LogEntry {
+            offset,
+            level,
+            target,
+            body: body.strip_prefix(' ').unwrap_or(body).to_string(),
+            session: None,
+            pid: None,
+            time,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: Default::default(),
+            truncated: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_logger_lines() {
+        let mut parser = EnvLoggerParser::new();
+        let first = parser
+            .parse("[2024-05-01T12:34:56Z INFO  app::db] connected to db")
+            .unwrap();
+        assert_eq!(Duration::ZERO, first.offset);
+        assert_eq!(Level::Info, first.level);
+        assert_eq!("app::db", first.target);
+        assert_eq!("connected to db", first.body);
+        assert_eq!(
+            Some(std::time::UNIX_EPOCH + Duration::from_secs(1_714_566_896)),
+            first.time
+        );
+
+        let second = parser
+            .parse("[2024-05-01T12:35:01.500Z ERROR app] lost [connection]")
+            .unwrap();
+        assert_eq!(Duration::from_millis(5500), second.offset);
+        assert_eq!("lost [connection]", second.body);
+
+        let bare = parser.parse("[WARN] no timestamp").unwrap();
+        assert_eq!(Level::Warn, bare.level);
+        assert_eq!("", bare.target);
+        assert!(parser.parse("  continuation").is_none());
+    }
+}
@@ -0,0 +1,275 @@
+//! Sidecar index of gzip members for jumping into large files.
+//!
+//! With [`LoggerBuilder::index`] every file gets an index next to it, named
+//! after it with `.idx` appended, such as `app.jsonl.gz.idx`. Each line of
+//! the index is a JSON [`IndexEntry`] recording where a gzip member starts,
+//! and since a member can be decoded on its own, readers can start there
+//! instead of decompressing everything before it.
+//!
+//! [`read_from_entry`], [`read_range`], [`tail`], and [`read_rev`] use the
+//! index when a file has one, and read the whole file otherwise.
+
+use crate::{LogEntry, LogEntryIter, LoggerBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Uncompressed size of the gzip members of indexed files, unless set with
+/// [`LoggerBuilder::gzip_member_bytes`] or
+/// [`LoggerBuilder::gzip_member_entries`].
+pub const DEFAULT_MEMBER_BYTES: u64 = 1024 * 1024;
+
+impl LoggerBuilder {
+    /// Writes an index of the file's gzip members next to every file, so
+    /// that [`read_from_entry`] and other readers can jump into huge files
+    /// without decompressing them from the start.
+    ///
+    /// Members end every [`DEFAULT_MEMBER_BYTES`] unless configured
+    /// otherwise. Only applies to gzip files written to a path, and
+    /// encrypted files and files that are appended to cannot be indexed.
+    pub fn index(mut self, enabled: bool) -> Self {
+        self.dest.index = enabled;
+        self
+    }
+}
+
+/// Where a gzip member of an indexed file starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Number of entries in the file before the member, including headers
+    /// and session markers.
+    pub entry: u64,
+    /// Offset of the member's first entry.
+    pub offset: Duration,
+    /// Position of the member in the compressed file.
+    pub position: u64,
+}
+
+/// Returns the path of the index of the file at `path`.
+pub fn index_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_owned();
+    name.push(".idx");
+    PathBuf::from(name)
+}
+
+/// Reads the index of the file at `path`, in file order.
+///
+/// The index is written as members start, so it may miss members written
+/// before a crash, and entries pointing past the end of the file are
+/// dropped.
+pub fn read_index<P: AsRef<Path>>(path: P) -> io::Result<Vec<IndexEntry>> {
+    let len = std::fs::metadata(&path)?.len();
+    let index = BufReader::new(File::open(index_path(path))?);
+    let mut entries = Vec::new();
+    for line in index.lines() {
+        // a line cut short by a crash ends the index
+        let Ok(entry) = serde_json::from_str::<IndexEntry>(&line?) else {
+            break;
+        };
+        if entry.position >= len {
+            break;
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Opens the file at `path` to be read from the entry with the given number
+/// on, counting from 0 as [`IndexEntry::entry`] does.
+///
+/// Starts decompressing at the last member before the entry if the file is
+/// indexed, and from the start otherwise.
+pub fn read_from_entry<P: AsRef<Path>>(path: P, entry: u64) -> io::Result<LogEntryIter> {
+    let path = path.as_ref();
+    let start = read_index(path)
+        .unwrap_or_default()
+        .into_iter()
+        .take_while(|member| member.entry <= entry)
+        .last();
+    let mut entries = open_at(path, start.as_ref())?;
+    let skip = entry - start.map_or(0, |member| member.entry);
+    entries.by_ref().take(skip as usize).for_each(drop);
+    Ok(entries)
+}
+
+/// Returns the entries of the file at `path` whose offsets are at least
+/// `from` and less than `to`.
+///
+/// If the file is indexed, only the members that can hold such entries are
+/// decompressed, assuming offsets increase through the file as they do
+/// unless a [`ManualClock`](crate::clock::ManualClock) is moved back.
+/// Otherwise the whole file is read.
+pub fn read_range<P: AsRef<Path>>(
+    path: P,
+    from: Duration,
+    to: Duration,
+) -> io::Result<impl Iterator<Item = LogEntry>> {
+    let path = path.as_ref();
+    let index = read_index(path).unwrap_or_default();
+    let start = index
+        .iter()
+        .take_while(|member| member.offset <= from)
+        .last();
+    let count = index
+        .iter()
+        .find(|member| member.offset >= to)
+        .map_or(u64::MAX, |end| {
+            end.entry - start.map_or(0, |member| member.entry)
+        });
+    let entries = open_at(path, start)?.take(usize::try_from(count).unwrap_or(usize::MAX));
+    Ok(entries.filter(move |entry| (from..to).contains(&entry.offset)))
+}
+
+/// Returns the last `n` entries of the file at `path`, oldest first.
+///
+/// If the file is indexed only its last members are decompressed, and
+/// otherwise the whole file is read while keeping only `n` entries.
+pub fn tail<P: AsRef<Path>>(path: P, n: usize) -> io::Result<Vec<LogEntry>> {
+    let path = path.as_ref();
+    let index = read_index(path).unwrap_or_default();
+    // read twice as many members from the end each time until they hold
+    // enough entries
+    let mut members = 1;
+    loop {
+        let start = index.len().checked_sub(members).map(|start| &index[start]);
+        let mut last = VecDeque::with_capacity(n);
+        for entry in open_at(path, start)? {
+            if last.len() == n {
+                last.pop_front();
+            }
+            if n > 0 {
+                last.push_back(entry);
+            }
+        }
+        if last.len() == n || start.is_none() {
+            return Ok(last.into());
+        }
+        members = members.saturating_mul(2);
+    }
+}
+
+/// Opens the file at `path` to be read newest entry first.
+///
+/// If the file is indexed its members are decompressed one at a time from
+/// the last, and otherwise the whole file is read once the first entry is
+/// asked for.
+pub fn read_rev<P: AsRef<Path>>(path: P) -> io::Result<ReverseEntries> {
+    let path = path.as_ref();
+    let index = read_index(path).unwrap_or_default();
+    let members = match index.is_empty() {
+        true => vec![None],
+        false => index.into_iter().map(Some).collect(),
+    };
+    // fail now rather than when iterating if the file cannot be read
+    File::open(path)?;
+    Ok(ReverseEntries {
+        path: path.to_path_buf(),
+        members,
+        end: None,
+        entries: Vec::new(),
+    })
+}
+
+/// Iterator over the entries of a file, newest first, returned by
+/// [`read_rev`].
+///
+/// Like [`LogEntryIter`], iteration stops at the first entry that cannot be
+/// read.
+pub struct ReverseEntries {
+    path: PathBuf,
+    /// Members not read yet, or `None` for the whole file.
+    members: Vec<Option<IndexEntry>>,
+    /// Number of the first entry of the last member read.
+    end: Option<u64>,
+    /// Entries of the last member read that have not been returned yet.
+    entries: Vec<LogEntry>,
+}
+
+impl Iterator for ReverseEntries {
+    type Item = LogEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.entries.is_empty() {
+            let member = self.members.pop()?;
+            let count = match (member, self.end) {
+                (Some(member), Some(end)) => end - member.entry,
+                _ => u64::MAX,
+            };
+            self.end = member.map(|member| member.entry);
+            let entries = open_at(&self.path, member.as_ref()).ok()?;
+            self.entries = entries
+                .take(usize::try_from(count).unwrap_or(usize::MAX))
+                .collect();
+        }
+        self.entries.pop()
+    }
+}
+
+/// Opens the file at `path` to be read from the given member on, or from
+/// the start.
+pub(crate) fn open_at(path: &Path, member: Option<&IndexEntry>) -> io::Result<LogEntryIter> {
+    let mut file = File::open(path)?;
+    if let Some(member) = member {
+        file.seek(SeekFrom::Start(member.position))?;
+    }
+    LogEntryIter::detect(file)
+}
+
+/// Records the members of a file in its index as they start.
+pub(crate) struct Indexer {
+    index: File,
+    /// Bytes written to the file, which are all on disk whenever a member
+    /// starts.
+    written: Arc<AtomicU64>,
+    /// Entries written to the file.
+    entries: u64,
+    /// Whether the next entry starts a member.
+    member_start: bool,
+}
+
+impl Indexer {
+    /// Creates the index of the file at `path`, truncating any existing one.
+    pub(crate) fn create(path: &Path, written: Arc<AtomicU64>) -> io::Result<Self> {
+        Ok(Self {
+            index: File::create(index_path(path))?,
+            written,
+            entries: 0,
+            member_start: true,
+        })
+    }
+
+    /// Records that a member has ended, so the next entry starts another.
+    pub(crate) fn member_ended(&mut self) {
+        self.member_start = true;
+    }
+
+    /// Records an entry about to be written, given as serialized by the
+    /// logger.
+    pub(crate) fn entry(&mut self, entry: &[u8]) -> io::Result<()> {
+        if std::mem::take(&mut self.member_start) {
+            #[derive(Deserialize)]
+            struct Offset {
+                offset: Duration,
+            }
+            let offset: Offset = serde_json::from_slice(entry)?;
+            let mut line = serde_json::to_vec(&IndexEntry {
+                entry: self.entries,
+                offset: offset.offset,
+                position: self.written.load(Ordering::Relaxed),
+            })?;
+            line.push(b'\n');
+            self.index.write_all(&line)?;
+        }
+        self.entries += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! Checking and repairing damaged log files.
+//!
+//! A process that crashes or is killed while logging leaves its file without
+//! the end of its gzip stream, and often with its last entry cut short.
+//! Readers stop at the damage, [`verify`] reports it, and [`repair`] removes
+//! it so that the file is a complete gzip stream again.
+
+use crate::{
+    codec::Gzip,
+    index::{index_path, read_index},
+    LogEntry,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Result of checking the integrity of a log file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Verification {
+    /// Number of entries that could be read.
+    pub entries: usize,
+    /// Line numbers (starting at 1) of complete lines that are not entries.
+    pub invalid: Vec<usize>,
+    /// Whether the compressed stream ends properly, which for gzip means
+    /// with an intact trailer. Files still being written do not.
+    pub trailer_intact: bool,
+    /// Whether the last line ends with a newline.
+    pub last_line_complete: bool,
+    /// Whether entry offsets never decrease, other than at session markers.
+    pub offsets_monotonic: bool,
+}
+
+impl Verification {
+    /// Returns true if the whole file can be read and is in order.
+    pub fn is_valid(&self) -> bool {
+        self.invalid.is_empty()
+            && self.trailer_intact
+            && self.last_line_complete
+            && self.offsets_monotonic
+    }
+}
+
+/// Checks the integrity of the log file at `path`, in any format [`read`]
+/// recognizes.
+///
+/// Checking stops at damaged compressed data, since nothing after it can be
+/// decoded.
+///
+/// [`read`]: crate::read
+pub fn verify<P: AsRef<Path>>(path: P) -> io::Result<Verification> {
+    let mut source = crate::read(path)?.source;
+    let mut verification = Verification {
+        trailer_intact: true,
+        last_line_complete: true,
+        offsets_monotonic: true,
+        ..Verification::default()
+    };
+    let mut offset = None;
+    let mut line = Vec::new();
+    let mut number = 0;
+    loop {
+        line.clear();
+        if source.read_until(b'\n', &mut line).is_err() {
+            verification.trailer_intact = false;
+        }
+        if line.last() != Some(&b'\n') {
+            verification.last_line_complete = line.is_empty();
+            return Ok(verification);
+        }
+        number += 1;
+        let Ok(entry) = serde_json::from_slice::<LogEntry>(&line) else {
+            verification.invalid.push(number);
+            continue;
+        };
+        verification.entries += 1;
+        // offsets start over at session markers
+        if !entry.is_session_marker() && offset.is_some_and(|offset| entry.offset < offset) {
+            verification.offsets_monotonic = false;
+        }
+        offset = Some(entry.offset);
+    }
+}
+
+/// Truncates the gzip log file at `path` after its last entry that can be
+/// read, leaving a properly terminated gzip stream, and returns whether
+/// anything was removed.
+///
+/// Everything after the last readable entry is removed, including entries
+/// that follow damaged compressed data, since they cannot be found without
+/// decoding what comes before them. Gzip members before the one holding the
+/// last entry are kept as they are, so an [index](crate::index) stays valid
+/// and is cut down to match.
+///
+/// Fails without changing the file if it is not gzip compressed. The file
+/// must not be written to while it is repaired.
+pub fn repair<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let path = path.as_ref();
+    if fs::metadata(path)?.len() == 0 {
+        return Ok(false);
+    }
+    let mut results = crate::read(path)?.results();
+    let Some(mut kept) = results.checkpoint() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "only gzip files can be repaired",
+        ));
+    };
+    let mut damaged = false;
+    while let Some(result) = results.next() {
+        damaged = result.is_err();
+        if !damaged {
+            kept = results.checkpoint().expect("gzip files have checkpoints");
+        }
+    }
+    if !damaged {
+        return Ok(false);
+    }
+
+    // the member holding the last entry is decoded up to its end and
+    // compressed again as the last member
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    file.seek(SeekFrom::Start(kept.position))?;
+    let mut last = Vec::new();
+    GzDecoder::new(&mut file)
+        .take(kept.skip)
+        .read_to_end(&mut last)?;
+    file.set_len(kept.position)?;
+    file.seek(SeekFrom::Start(kept.position))?;
+    if !last.is_empty() {
+        let level = Compression::new(Gzip::default().level);
+        let mut encoder = GzEncoder::new(&mut file, level);
+        encoder.write_all(&last)?;
+        encoder.finish()?;
+    }
+    file.sync_all()?;
+
+    // reading the index drops the members that were removed
+    if let Ok(index) = read_index(path) {
+        let mut lines = Vec::new();
+        for member in &index {
+            serde_json::to_writer(&mut lines, member)?;
+            lines.push(b'\n');
+        }
+        let mut index = File::create(index_path(path))?;
+        index.write_all(&lines)?;
+        index.sync_all()?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,163 @@
+//! Hand-written JSON emitter for entries, used instead of `serde_json` on
+//! the write path with the `minimal-json` feature.
+//!
+//! The output is byte for byte what `serde_json` produces for a
+//! [`LogEntry`](crate::LogEntry).
+
+use crate::LogEntryArgs;
+use std::{
+    fmt::{self, Arguments, Write},
+    io,
+};
+
+/// Serializes an entry as a JSON object without a trailing newline.
+///
+/// Fails if formatting the body fails.
+pub(crate) fn entry(entry: &LogEntryArgs) -> io::Result<Vec<u8>> {
+    let mut out = String::with_capacity(96 + entry.target.len());
+    // writing to a string only fails if a formatted value does
+    let _ = write!(
+        out,
+        r#"{{"offset":{{"secs":{},"nanos":{}}},"level":"{}","target":"#,
+        entry.offset.as_secs(),
+        entry.offset.subsec_nanos(),
+        entry.level
+    );
+    let _ = string(&mut out, format_args!("{}", entry.target));
+    out.push_str(r#","body":"#);
+    string(&mut out, entry.body).map_err(|_| io::Error::other("formatting the body failed"))?;
+    if let Some(session) = entry.session {
+        out.push_str(r#","session":"#);
+        let _ = string(&mut out, format_args!("{}", session));
+    }
+    if let Some(pid) = entry.pid {
+        let _ = write!(out, r#","pid":{}"#, pid);
+    }
+    if let Some(time) = entry.time {
+        // timestamps never need escaping
+        let _ = write!(out, r#","time":"{}""#, crate::rfc3339::format(time));
+    }
+    if let Some(module_path) = entry.module_path {
+        out.push_str(r#","module_path":"#);
+        let _ = string(&mut out, format_args!("{}", module_path));
+    }
+    if let Some(file) = entry.file {
+        out.push_str(r#","file":"#);
+        let _ = string(&mut out, format_args!("{}", file));
+    }
+    if let Some(line) = entry.line {
+        let _ = write!(out, r#","line":{}"#, line);
+    }
+    if let Some(fields) = entry.fields {
+        out.push_str(r#","fields":"#);
+        // key-values may hold arbitrary serde values
+        out.push_str(&serde_json::to_string(fields)?);
+    }
+    if entry.truncated {
+        out.push_str(r#","truncated":true"#);
+    }
+    out.push('}');
+    Ok(out.into_bytes())
+}
+
+/// Writes the formatted arguments as a quoted and escaped JSON string.
+fn string(out: &mut String, value: Arguments) -> fmt::Result {
+    out.push('"');
+    Escaper(out).write_fmt(value)?;
+    out.push('"');
+    Ok(())
+}
+
+/// Escapes everything written through it, as `serde_json` does.
+struct Escaper<'a>(&'a mut String);
+
+impl Write for Escaper<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut start = 0;
+        for (index, byte) in s.bytes().enumerate() {
+            let escape = match byte {
+                b'"' => "\\\"",
+                b'\\' => "\\\\",
+                b'\n' => "\\n",
+                b'\r' => "\\r",
+                b'\t' => "\\t",
+                0x08 => "\\b",
+                0x0c => "\\f",
+                0x00..=0x1f => "",
+                _ => continue,
+            };
+            self.0.push_str(&s[start..index]);
+            if escape.is_empty() {
+                write!(self.0, "\\u{:04x}", byte)?;
+            } else {
+                self.0.push_str(escape);
+            }
+            start = index + 1;
+        }
+        self.0.push_str(&s[start..]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogEntry;
+    use log::Level;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn matches_serde_json() {
+        let bodies = [
+            "",
+            "plain",
+            "quote \" backslash \\ slash /",
+            "lines\nand\r\ttabs",
+            "controls \u{0} \u{8} \u{c} \u{1f} \u{7f}",
+            "unicode é 日本 🦀",
+        ];
+        for body in bodies {
+            for session in [None, Some("3f2a\"x")] {
+                let time = session.map(|_| UNIX_EPOCH + Duration::new(1_714_566_896, 7));
+                let entry = LogEntry {
+                    offset: Duration::new(12, 345_678_901),
+                    level: Level::Warn,
+                    target: format!("app::{}", body),
+                    body: body.to_string(),
+                    session: session.map(str::to_string),
+                    pid: session.map(|_| 4242),
+                    time,
+                    module_path: session.map(|_| "app::db".to_string()),
+                    file: session.map(|_| "src\\db.rs".to_string()),
+                    line: session.map(|_| 42),
+                    fields: match session {
+                        Some(_) => {
+                            serde_json::from_str(r#"{"user":"a\"b","n":[1,2.5,null]}"#).unwrap()
+                        }
+                        None => Default::default(),
+                    },
+                    truncated: session.is_some(),
+                };
+                let minimal = super::entry(&LogEntryArgs {
+                    offset: entry.offset,
+                    level: entry.level,
+                    target: &entry.target,
+                    body: format_args!("{}", entry.body),
+                    session,
+                    pid: entry.pid,
+                    time,
+                    module_path: entry.module_path.as_deref(),
+                    file: entry.file.as_deref(),
+                    line: entry.line,
+                    fields: Some(&entry.fields).filter(|fields| !fields.is_empty()),
+                    truncated: entry.truncated,
+                })
+                .unwrap();
+                assert_eq!(
+                    String::from_utf8(serde_json::to_vec(&entry).unwrap()).unwrap(),
+                    String::from_utf8(minimal).unwrap()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Structured key-values attached to records with the `log` crate's `kv`
+//! feature.
+
+use serde_json::{Map, Value};
+
+/// Collects the record's key-values into a map, or returns `None` if it has
+/// none.
+///
+/// Values are serialized with `serde`, so ones captured with `:serde` keep
+/// their structure. Later values replace earlier ones with the same key.
+#[cfg(feature = "kv")]
+pub(crate) fn fields(record: &log::Record) -> Option<Map<String, Value>> {
+    use log::kv::{Error, Key, VisitSource};
+
+    struct Collect(Map<String, Value>);
+
+    impl<'kvs> VisitSource<'kvs> for Collect {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), Error> {
+            let value =
+                serde_json::to_value(&value).unwrap_or_else(|_| Value::String(value.to_string()));
+            self.0.insert(key.to_string(), value);
+            Ok(())
+        }
+    }
+
+    let source = record.key_values();
+    if source.count() == 0 {
+        return None;
+    }
+    let mut collect = Collect(Map::new());
+    // visiting only fails if the visitor does
+    let _ = source.visit(&mut collect);
+    Some(collect.0)
+}
+
+/// Fields collected from another logging library, as the key-values of a
+/// `log` record.
+#[cfg(any(feature = "slog", feature = "tracing"))]
+pub(crate) struct Fields<'a>(pub(crate) &'a Map<String, Value>);
+
+#[cfg(any(feature = "slog", feature = "tracing"))]
+impl log::kv::Source for Fields<'_> {
+    fn visit<'kvs>(
+        &'kvs self,
+        visitor: &mut dyn log::kv::VisitSource<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        for (key, value) in self.0 {
+            visitor.visit_pair(key.as_str().into(), log::kv::Value::from_serde(value))?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the record's key-values into a map, or returns `None` if it has
+/// none.
+///
+/// Without the `kv` feature records never have key-values.
+#[cfg(not(feature = "kv"))]
+pub(crate) fn fields(_record: &log::Record) -> Option<Map<String, Value>> {
+    None
+}
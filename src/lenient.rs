@@ -0,0 +1,107 @@
+//! Reading damaged log files as far as possible.
+//!
+//! [`LogEntryIter::lenient`] skips lines that are not valid entries instead
+//! of stopping at them, and keeps a [`CorruptionReport`] of what it skipped:
+//!
+//! ```no_run
+//! let mut entries = jsonl_gzip_logger::read("app.jsonl.gz").unwrap().lenient();
+//! for entry in entries.by_ref() {
+//!     println!("{}", entry.body);
+//! }
+//! for line in &entries.report().lines {
+//!     eprintln!("line {} is corrupt: {}", line.line, line.error);
+//! }
+//! ```
+
+use crate::{LogEntry, LogEntryIter, ReadError};
+use std::ops::Range;
+
+impl LogEntryIter {
+    /// Turns this into an iterator that skips lines that cannot be read,
+    /// recording them in a [`CorruptionReport`].
+    pub fn lenient(self) -> Lenient {
+        Lenient {
+            entries: self,
+            line: 0,
+            done: false,
+            report: CorruptionReport::default(),
+        }
+    }
+}
+
+/// Iterator over the entries of a log file that skips corrupt lines,
+/// returned by [`LogEntryIter::lenient`].
+///
+/// Invalid lines are skipped, while errors reading or decompressing the
+/// stream end iteration since nothing after them can be decoded.
+pub struct Lenient {
+    entries: LogEntryIter,
+    /// Number of lines read.
+    line: u64,
+    done: bool,
+    report: CorruptionReport,
+}
+
+impl Lenient {
+    /// Returns what has been skipped so far, which covers the whole file
+    /// once iteration has ended.
+    pub fn report(&self) -> &CorruptionReport {
+        &self.report
+    }
+
+    /// Returns what has been skipped.
+    pub fn into_report(self) -> CorruptionReport {
+        self.report
+    }
+}
+
+impl Iterator for Lenient {
+    type Item = LogEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let start = self.entries.consumed;
+            let result = self.entries.next_result()?;
+            self.line += 1;
+            match result {
+                Ok(entry) => return Some(entry),
+                Err(error) => {
+                    self.done = !error.is_recoverable();
+                    self.report.lines.push(CorruptLine {
+                        line: self.line,
+                        bytes: start..self.entries.consumed,
+                        error,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Lines skipped by a [`Lenient`] iterator, in file order.
+#[derive(Debug, Default)]
+pub struct CorruptionReport {
+    /// The skipped lines.
+    pub lines: Vec<CorruptLine>,
+}
+
+impl CorruptionReport {
+    /// Returns whether nothing was skipped.
+    pub fn is_clean(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+/// A line that could not be read.
+#[derive(Debug)]
+pub struct CorruptLine {
+    /// Number of the line, counting from 1.
+    pub line: u64,
+    /// Position of the line in the uncompressed stream.
+    pub bytes: Range<u64>,
+    /// Why the line could not be read. Only the last line of a report can
+    /// have an error other than [`ReadError::Json`] or, with the
+    /// `encryption` feature, `ReadError::Decrypt`.
+    pub error: ReadError,
+}
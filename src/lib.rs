@@ -1,15 +1,103 @@
-use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use flate2::read::MultiGzDecoder;
 use log::{Level, LevelFilter};
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Arguments,
     fs::File,
-    io::{BufRead, BufReader, Write},
-    sync::Mutex,
-    time::{Duration, Instant},
+    io::{BufRead, BufReader, Read},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
 };
 use thiserror::Error;
 
+mod archive;
+#[cfg(feature = "async-std")]
+pub mod async_std;
+mod builder;
+pub mod capture;
+#[cfg(feature = "hash-chain")]
+pub mod chain;
+pub mod checkpoint;
+pub mod clock;
+pub mod codec;
+#[cfg(unix)]
+pub mod collector;
+pub mod core;
+mod dest;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+mod facade;
+mod filter;
+pub mod fixture;
+pub mod follow;
+mod fork;
+mod format;
+mod handle;
+mod header;
+mod health;
+#[cfg(feature = "http")]
+pub mod http;
+mod hub;
+mod id;
+pub mod import;
+pub mod index;
+pub mod integrity;
+#[cfg(feature = "minimal-json")]
+mod json;
+mod kv;
+pub mod lenient;
+mod limit;
+pub mod lint;
+pub mod non_blocking;
+mod panic;
+#[cfg(feature = "pii")]
+pub mod pii;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+pub mod query;
+mod queue;
+mod redact;
+mod rfc3339;
+mod rotation;
+#[cfg(feature = "shm")]
+pub mod shm;
+#[cfg(all(unix, feature = "sighup"))]
+pub mod sighup;
+#[cfg(feature = "signing")]
+pub mod signing;
+#[cfg(feature = "slog")]
+pub mod slog;
+#[cfg(feature = "smol")]
+pub mod smol;
+pub mod tcp;
+mod template;
+pub mod test;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(feature = "tracing")]
+pub mod tracing;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod udp;
+#[cfg(feature = "websocket")]
+mod websocket;
+mod worker;
+mod writer;
+
+pub use builder::LoggerBuilder;
+pub use dest::SyncPolicy;
+pub use facade::{deinit, set_default, DefaultGuard, InitGuard};
+pub use filter::{set_filters, set_level, set_target_level, FilterError};
+pub use follow::follow;
+pub use fork::ForkPolicy;
+pub use handle::LoggerHandle;
+pub use header::{Header, FORMAT_VERSION};
+pub use health::Health;
+pub use index::{read_range, read_rev, tail};
+pub use panic::install_panic_hook;
+pub use queue::Overflow;
+pub use writer::LogWriter;
+
 /// A log from a log file.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LogEntry {
@@ -21,6 +109,42 @@ pub struct LogEntry {
     pub target: String,
     /// Message of this log entry.
     pub body: String,
+    /// ID of the logger initialization that wrote this entry, if enabled
+    /// with [`LoggerBuilder::session_id`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// ID of the process that wrote this entry, if enabled with
+    /// [`LoggerBuilder::pid`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// Wall-clock time this entry was logged at, if enabled with
+    /// [`LoggerBuilder::timestamps`]. Written as an RFC 3339 string in UTC.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "rfc3339::option"
+    )]
+    pub time: Option<SystemTime>,
+    /// Module path of the code that logged this entry, if recorded; see
+    /// [`LoggerBuilder::source_location`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module_path: Option<String>,
+    /// Source file of the code that logged this entry, if recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Line in [`file`](Self::file) of the code that logged this entry, if
+    /// recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    /// Structured key-values of this entry, written when the `kv` feature
+    /// is enabled and the record has any, and for some of this crate's own
+    /// entries.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub fields: serde_json::Map<String, serde_json::Value>,
+    /// Whether the body was cut short by
+    /// [`LoggerBuilder::max_body_bytes`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
 }
 
 /// Internal type that serializes the same as LogEntry.
@@ -30,12 +154,39 @@ struct LogEntryArgs<'a> {
     level: Level,
     target: &'a str,
     body: Arguments<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "rfc3339::option::serialize"
+    )]
+    time: Option<SystemTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module_path: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fields: Option<&'a serde_json::Map<String, serde_json::Value>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
 }
 
-/// Logger that logs to a .jsonl.gz file.
-struct Logger {
-    start: Instant,
-    dest: Mutex<GzEncoder<File>>,
+/// Logger that logs to a .jsonl.gz file, for combining with other loggers.
+///
+/// Built with [`LoggerBuilder::build`] or [`LoggerBuilder::build_writer`],
+/// it implements [`log::Log`] without installing itself, so it can be handed
+/// to a dispatcher such as `fern` or `multi_log`, or installed with
+/// [`log::set_boxed_logger`]. It then does not set `log`'s max level, which
+/// must allow the levels it should see. The file is finished when the logger
+/// is dropped.
+pub struct JsonlGzipLogger {
+    filter: filter::Filter,
+    format: format::Format,
+    dest: Arc<Mutex<fork::Owned>>,
 }
 
 /// Error type for [`init`].
@@ -47,77 +198,467 @@ pub enum InitError {
     /// Failed to globally install the logger.
     #[error("{0}")]
     SetLoggerError(#[from] log::SetLoggerError),
+    /// A logger from this crate is already installed.
+    #[error("a logger is already installed, call deinit() first")]
+    AlreadyInitialized,
+    /// The filter directives from the environment are invalid.
+    #[error("{0}")]
+    InvalidFilter(#[from] FilterError),
 }
 
 /// Creates and installs a global logger that logs to a new .jsonl.gz file at
 /// the given path.
-pub fn init<P: AsRef<std::path::Path>>(path: P, level: LevelFilter) -> Result<(), InitError> {
-    let logger = Box::new(Logger {
-        start: Instant::now(),
-        dest: Mutex::new(GzEncoder::new(File::create(path)?, Compression::fast())),
-    });
-    log::set_boxed_logger(logger)?;
-    log::set_max_level(level);
-    Ok(())
+///
+/// Only one logger can be installed at a time; drop the returned guard or
+/// call [`deinit`] to finish the file and allow installing another one.
+/// Use [`LoggerBuilder`] for more configuration options.
+pub fn init<P: AsRef<std::path::Path>>(
+    path: P,
+    level: LevelFilter,
+) -> Result<InitGuard, InitError> {
+    LoggerBuilder::new().level(level).init(path)
+}
+
+/// Creates and installs a global logger that logs to a new .jsonl.gz file at
+/// the given path, with levels from the `RUST_LOG` environment variable.
+///
+/// Logs at [`LevelFilter::Info`] if `RUST_LOG` is not set. See
+/// [`LoggerBuilder::parse_filters`] for the accepted directives.
+pub fn init_from_env<P: AsRef<std::path::Path>>(path: P) -> Result<InitGuard, InitError> {
+    LoggerBuilder::new().filters_from_env()?.init(path)
+}
+
+/// Creates and installs a global logger that writes a .jsonl.gz stream to
+/// the given writer, such as a socket, a pipe, or a custom sink.
+///
+/// The gzip stream is finished when the returned guard is dropped. Use
+/// [`LoggerBuilder::init_writer`] for more configuration options.
+pub fn init_with_writer<W: std::io::Write + Send + 'static>(
+    writer: W,
+    level: LevelFilter,
+) -> Result<InitGuard, InitError> {
+    LoggerBuilder::new().level(level).init_writer(writer)
+}
+
+impl JsonlGzipLogger {
+    /// Creates a logger that logs to a new .jsonl.gz file at the given path,
+    /// without installing it.
+    ///
+    /// Use [`LoggerBuilder::build`] for more configuration options.
+    pub fn new<P: AsRef<std::path::Path>>(path: P, level: LevelFilter) -> std::io::Result<Self> {
+        LoggerBuilder::new().level(level).build(path)
+    }
+
+    /// Returns the monitor recording the health of the destination.
+    pub(crate) fn monitor(&self) -> Arc<health::Monitor> {
+        let mut dest = self.dest.lock().unwrap_or_else(|error| error.into_inner());
+        dest.get()
+            .map(|dest| dest.monitor().clone())
+            .unwrap_or_default()
+    }
 }
 
-impl log::Log for Logger {
+impl log::Log for JsonlGzipLogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= log::max_level()
+        self.filter.enabled(metadata)
     }
 
     fn log(&self, record: &log::Record) {
-        if self.enabled(record.metadata()) {
-            let entry = LogEntryArgs {
-                offset: self.start.elapsed(),
-                level: record.level(),
-                target: record.target(),
-                body: *record.args(),
-            };
-            if let Ok(mut writer) = self.dest.lock() {
-                serde_json::to_writer(&mut *writer, &entry).unwrap();
-                writer.write_all(&[b'\n']).unwrap();
-            }
+        if !self.enabled(record.metadata()) {
+            return;
         }
+        self.format.entries(record, |line| {
+            if let Some(dest) = self.dest.lock().as_mut().ok().and_then(|dest| dest.get()) {
+                if let Err(error) = dest.write_entry(&line) {
+                    self.format.drops.count(limit::DropCause::Io);
+                    self.format.report(&error);
+                }
+            }
+        });
     }
 
     fn flush(&self) {
-        if let Ok(mut writer) = self.dest.lock() {
-            writer.flush().unwrap();
+        if let Some(dest) = self.dest.lock().as_mut().ok().and_then(|dest| dest.get()) {
+            self.format.pending(|line| {
+                if let Err(error) = dest.write_entry(&line) {
+                    self.format.drops.count(limit::DropCause::Io);
+                    self.format.report(&error);
+                }
+            });
+            if let Err(error) = dest.flush() {
+                self.format.report(&error);
+            }
         }
     }
 }
 
 /// Iterator that reads over the entries in a .jsonl.gz log file.
+///
+/// Iteration stops at the first entry that cannot be read; use
+/// [`results`](Self::results) to find out why.
 pub struct LogEntryIter {
-    source: BufReader<GzDecoder<File>>,
+    source: Box<dyn BufRead + Send>,
     buffer: Vec<u8>,
+    /// Uncompressed bytes read from the source.
+    consumed: u64,
+    /// Where the source's gzip members start, if it is gzip.
+    boundaries: Option<checkpoint::Boundaries>,
+    #[cfg(feature = "encryption")]
+    body_key: Option<encryption::EncryptionKey>,
 }
 
 impl Iterator for LogEntryIter {
     type Item = LogEntry;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.buffer.clear();
-        self.source.read_until(b'\n', &mut self.buffer).ok()?;
-        if self.buffer.last() != Some(&b'\n') {
-            // last line of the log was truncated, ignore it
+        self.next_result()?.ok()
+    }
+}
+
+/// Error reading an entry, returned by [`LogEntryResults`] and recorded in
+/// [`CorruptionReport`](lenient::CorruptionReport)s.
+#[derive(Error, Debug)]
+pub enum ReadError {
+    /// Reading the underlying file or stream failed.
+    #[error("failed to read log: {0}")]
+    Io(std::io::Error),
+    /// The compressed data is invalid, such as a damaged gzip header,
+    /// deflate block, or checksum.
+    #[error("log is corrupt: {0}")]
+    Corrupt(std::io::Error),
+    /// The log ends in the middle of an entry or of its compressed stream,
+    /// as files still being written or cut short by a crash do.
+    #[error("log ends in the middle of an entry")]
+    Truncated,
+    /// A line is not a valid entry.
+    #[error("invalid entry: {0}")]
+    Json(#[from] serde_json::Error),
+    /// An entry body failed to decrypt.
+    #[cfg(feature = "encryption")]
+    #[error("failed to decrypt entry body: {0}")]
+    Decrypt(std::io::Error),
+}
+
+impl ReadError {
+    /// Classifies an error from reading the decompressed stream.
+    fn from_source(error: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        match error.kind() {
+            ErrorKind::UnexpectedEof => Self::Truncated,
+            ErrorKind::InvalidInput | ErrorKind::InvalidData => Self::Corrupt(error),
+            _ => Self::Io(error),
+        }
+    }
+
+    /// Returns whether reading can continue with the next entry after this
+    /// error, which is only the case when the stream itself is intact.
+    fn is_recoverable(&self) -> bool {
+        match self {
+            Self::Json(_) => true,
+            #[cfg(feature = "encryption")]
+            Self::Decrypt(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Iterator over the entries of a log file that reports why entries cannot
+/// be read, returned by [`LogEntryIter::results`].
+///
+/// After an invalid line the next entry is read as usual, while errors
+/// reading or decompressing the stream end iteration.
+pub struct LogEntryResults {
+    entries: LogEntryIter,
+    done: bool,
+}
+
+impl LogEntryResults {
+    /// Returns the position after the last entry read, as
+    /// [`LogEntryIter::checkpoint`] does.
+    pub fn checkpoint(&self) -> Option<checkpoint::Checkpoint> {
+        self.entries.checkpoint()
+    }
+}
+
+impl Iterator for LogEntryResults {
+    type Item = Result<LogEntry, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
             return None;
         }
-        serde_json::from_slice(&self.buffer[..]).ok()
+        let result = self.entries.next_result();
+        if let Some(Err(error)) = &result {
+            self.done = !error.is_recoverable();
+        }
+        result
     }
 }
 
-/// Opens a .jsonl.gz log file to be read by a [`LogEntryIter`].
+/// Opens a log file to be read by a [`LogEntryIter`], recognizing gzip,
+/// zstd, LZ4 and uncompressed files from their first bytes.
+///
+/// Fails if the file is zstd or LZ4 compressed and the crate feature of the
+/// same name is not enabled. Files written with a custom
+/// [`Codec`](codec::Codec) must be read with [`read_with_codec`] instead.
 pub fn read<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<LogEntryIter> {
-    Ok(LogEntryIter {
-        source: BufReader::new(GzDecoder::new(File::open(path)?)),
-        buffer: Vec::new(),
-    })
+    LogEntryIter::detect(File::open(path)?)
+}
+
+/// Returns the first `n` entries of the log file at `path`, decompressing
+/// only as much of the file as they take up.
+///
+/// Like [`read`] this recognizes the file's compression.
+pub fn head<P: AsRef<std::path::Path>>(path: P, n: usize) -> std::io::Result<Vec<LogEntry>> {
+    Ok(read(path)?.take(n).collect())
+}
+
+/// Opens a .jsonl.lz4 log file written with [`LoggerBuilder::lz4`] to be
+/// read by a [`LogEntryIter`].
+#[cfg(feature = "lz4")]
+pub fn read_lz4<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<LogEntryIter> {
+    Ok(LogEntryIter::lz4(File::open(path)?))
+}
+
+/// Opens a log file written with [`LoggerBuilder::codec`] to be read by a
+/// [`LogEntryIter`], decompressing it with the same codec.
+pub fn read_with_codec<P: AsRef<std::path::Path>>(
+    path: P,
+    codec: &dyn codec::Codec,
+) -> std::io::Result<LogEntryIter> {
+    LogEntryIter::with_codec(File::open(path)?, codec)
+}
+
+/// Reads the entries of a .jsonl.gz log held in memory, such as the bytes of
+/// a [`MemorySink`](test::MemorySink).
+pub fn read_bytes(bytes: &[u8]) -> LogEntryIter {
+    LogEntryIter::new(std::io::Cursor::new(bytes.to_vec()))
+}
+
+/// Opens an encrypted .jsonl.gz log file to be read by a [`LogEntryIter`].
+///
+/// Iteration stops early if the file was modified, the key is wrong, or the
+/// file was truncated after the last entry returned.
+#[cfg(feature = "encryption")]
+pub fn read_encrypted<P: AsRef<std::path::Path>>(
+    path: P,
+    key: &encryption::EncryptionKey,
+) -> std::io::Result<LogEntryIter> {
+    Ok(LogEntryIter::new(encryption::DecryptingReader::new(
+        File::open(path)?,
+        key,
+    )))
+}
+
+/// Opens a .jsonl.gz log file written with [`LoggerBuilder::encryption_to`]
+/// to be read by a [`LogEntryIter`], decrypting it with the identity key of
+/// the recipient it was encrypted to.
+///
+/// Iteration stops early if the file was modified, the key is wrong, or the
+/// file was truncated after the last entry returned.
+#[cfg(feature = "encryption")]
+pub fn read_with_identity<P: AsRef<std::path::Path>>(
+    path: P,
+    identity: &encryption::IdentityKey,
+) -> std::io::Result<LogEntryIter> {
+    Ok(LogEntryIter::new(
+        encryption::DecryptingReader::with_identity(File::open(path)?, identity),
+    ))
+}
+
+/// Opens a .jsonl.gz log file that may have been written with any of the
+/// encryption options, decrypting it with the given key as needed.
+///
+/// Both files encrypted with [`LoggerBuilder::encryption`] and entries with
+/// bodies encrypted with [`LoggerBuilder::body_encryption`] are decrypted,
+/// and unencrypted files are read as with [`read`].
+#[cfg(feature = "encryption")]
+pub fn read_with_key<P: AsRef<std::path::Path>>(
+    path: P,
+    key: &encryption::EncryptionKey,
+) -> std::io::Result<LogEntryIter> {
+    use std::io::{Seek, SeekFrom};
+
+    let mut file = File::open(path)?;
+    let mut magic = [0; 4];
+    let encrypted = match file.read_exact(&mut magic) {
+        Ok(()) => &magic == encryption::MAGIC,
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => false,
+        Err(error) => return Err(error),
+    };
+    file.seek(SeekFrom::Start(0))?;
+    let entries = if encrypted {
+        LogEntryIter::new(encryption::DecryptingReader::new(file, key))
+    } else {
+        LogEntryIter::new(file)
+    };
+    Ok(entries.decrypt_bodies(key))
+}
+
+impl LogEntry {
+    /// Target of the entries this crate writes about logging itself.
+    pub(crate) const OWN_TARGET: &'static str = "jsonl_gzip_logger";
+
+    /// Body of session markers.
+    const SESSION_BODY: &'static str = "new session";
+
+    /// Creates the marker starting the entries of the run with the given ID
+    /// in an appended file.
+    pub(crate) fn session_marker(session: String) -> Self {
+        Self {
+            offset: Duration::ZERO,
+            level: Level::Info,
+            target: Self::OWN_TARGET.to_string(),
+            body: Self::SESSION_BODY.to_string(),
+            session: Some(session),
+            pid: None,
+            time: Some(SystemTime::now()),
+            module_path: None,
+            file: None,
+            line: None,
+            fields: serde_json::Map::new(),
+            truncated: false,
+        }
+    }
+
+    /// Returns the process metadata if this is the header entry starting a
+    /// file written with [`LoggerBuilder::header`].
+    pub fn header(&self) -> Option<Header> {
+        Header::from_entry(self)
+    }
+
+    /// Returns whether this entry marks the start of another run's entries
+    /// in a file written with [`LoggerBuilder::append`].
+    ///
+    /// Offsets start over after a marker, its `time` is when the run
+    /// started, and its `session` is the run's ID.
+    pub fn is_session_marker(&self) -> bool {
+        self.target == Self::OWN_TARGET && self.body == Self::SESSION_BODY
+    }
+}
+
+impl LogEntryIter {
+    /// Creates an iterator over the entries of an already decompressed
+    /// stream.
+    pub(crate) fn decoded<R: Read + Send + 'static>(decoded: R) -> Self {
+        Self {
+            source: Box::new(BufReader::new(decoded)),
+            buffer: Vec::new(),
+            consumed: 0,
+            boundaries: None,
+            #[cfg(feature = "encryption")]
+            body_key: None,
+        }
+    }
+
+    /// Creates an iterator over the entries of a compressed stream, such as
+    /// one written by [`LoggerBuilder::init_writer`].
+    pub fn new<R: Read + Send + 'static>(source: R) -> Self {
+        Self::decoded(MultiGzDecoder::new(source))
+    }
+
+    /// Creates an iterator over the entries of an LZ4 frame stream, such as
+    /// one written by [`LoggerBuilder::lz4`].
+    #[cfg(feature = "lz4")]
+    pub fn lz4<R: Read + Send + 'static>(source: R) -> Self {
+        Self::decoded(lz4_flex::frame::FrameDecoder::new(source))
+    }
+
+    /// Creates an iterator over the entries of a stream whose format is
+    /// recognized from its first bytes, as by [`read`].
+    pub fn detect<R: Read + Send + 'static>(source: R) -> std::io::Result<Self> {
+        Self::detect_at(source, 0)
+    }
+
+    /// Creates an iterator over the entries of a stream whose format is
+    /// recognized from its first bytes, starting at `position` in its file.
+    pub(crate) fn detect_at<R: Read + Send + 'static>(
+        source: R,
+        position: u64,
+    ) -> std::io::Result<Self> {
+        let (decoded, boundaries) = checkpoint::decode(Box::new(source), position)?;
+        let mut entries = Self::decoded(decoded);
+        entries.boundaries = boundaries;
+        Ok(entries)
+    }
+
+    /// Returns the position after the last entry returned, from which
+    /// [`checkpoint::resume`] continues reading.
+    ///
+    /// Only iterators over gzip streams opened with [`read`] or
+    /// [`detect`](Self::detect) have checkpoints.
+    pub fn checkpoint(&self) -> Option<checkpoint::Checkpoint> {
+        self.boundaries.as_ref()?.checkpoint(self.consumed)
+    }
+
+    /// Skips the given number of uncompressed bytes, which must end at the
+    /// end of an entry.
+    pub(crate) fn skip_bytes(&mut self, bytes: u64) -> std::io::Result<()> {
+        std::io::copy(&mut (&mut self.source).take(bytes), &mut std::io::sink())?;
+        self.consumed += bytes;
+        Ok(())
+    }
+
+    /// Creates an iterator over the entries of a stream compressed with the
+    /// given codec, such as one written with [`LoggerBuilder::codec`].
+    pub fn with_codec<R: Read + Send + 'static>(
+        source: R,
+        codec: &dyn codec::Codec,
+    ) -> std::io::Result<Self> {
+        Ok(Self::decoded(codec.decompress(Box::new(source))?))
+    }
+
+    /// Turns this into an iterator that returns why entries cannot be read,
+    /// instead of stopping at the first one.
+    pub fn results(self) -> LogEntryResults {
+        LogEntryResults {
+            entries: self,
+            done: false,
+        }
+    }
+
+    /// Reads the next line, returning `None` at the end of the stream.
+    fn next_result(&mut self) -> Option<Result<LogEntry, ReadError>> {
+        self.buffer.clear();
+        let read = self.source.read_until(b'\n', &mut self.buffer);
+        self.consumed += self.buffer.len() as u64;
+        if let Err(error) = read {
+            return Some(Err(ReadError::from_source(error)));
+        }
+        if self.buffer.is_empty() {
+            return None;
+        }
+        if self.buffer.last() != Some(&b'\n') {
+            return Some(Err(ReadError::Truncated));
+        }
+        #[allow(unused_mut)]
+        let mut entry: LogEntry = match serde_json::from_slice(&self.buffer[..]) {
+            Ok(entry) => entry,
+            Err(error) => return Some(Err(error.into())),
+        };
+        #[cfg(feature = "encryption")]
+        if let Some(key) = &self.body_key {
+            entry.body = match encryption::decrypt_body(&entry.body, key) {
+                Ok(body) => body,
+                Err(error) => return Some(Err(ReadError::Decrypt(error))),
+            };
+        }
+        Some(Ok(entry))
+    }
+
+    /// Decrypts entry bodies encrypted with
+    /// [`LoggerBuilder::body_encryption`] using the given key.
+    ///
+    /// Iteration stops early at a body that fails to decrypt.
+    #[cfg(feature = "encryption")]
+    pub fn decrypt_bodies(mut self, key: &encryption::EncryptionKey) -> Self {
+        self.body_key = Some(key.clone());
+        self
+    }
 }
 
 #[cfg(test)]
-mod test {
+mod tests {
     use super::*;
 
     /// The JSON representation of a LogEntry must be stable.
@@ -139,6 +680,14 @@ mod test {
             level: Level::Error,
             target: " my target 123 ".to_string(),
             body: "This is the body of the log. \nfoobarbaz ".to_string(),
+            session: Some("3f1c2a9e-5b7d-4c8e-9a0b-1d2e3f4a5b6c".to_string()),
+            pid: Some(4242),
+            time: Some(std::time::UNIX_EPOCH + Duration::new(1_714_566_896, 789)),
+            module_path: Some("my_crate::db".to_string()),
+            file: Some("src/db.rs".to_string()),
+            line: Some(42),
+            fields: serde_json::from_str(r#"{"user":"ann","attempt":2}"#).unwrap(),
+            truncated: false,
         };
         let json = serde_json::to_string(&entry).unwrap();
         let parsed: LogEntry = serde_json::from_str(&json).unwrap();
@@ -146,6 +695,12 @@ mod test {
         assert_eq!(entry.level, parsed.level);
         assert_eq!(entry.target, parsed.target);
         assert_eq!(entry.body, parsed.body);
+        assert_eq!(entry.session, parsed.session);
+        assert_eq!(entry.time, parsed.time);
+        assert_eq!(entry.module_path, parsed.module_path);
+        assert_eq!(entry.file, parsed.file);
+        assert_eq!(entry.line, parsed.line);
+        assert_eq!(entry.fields, parsed.fields);
     }
 
     /// A serialiazed LogEntryArgs must deserialize to an equivalent LogEntry.
@@ -156,6 +711,14 @@ mod test {
             level: Level::Debug,
             target: "test.foo.bar",
             body: format_args!("{} + {} == {}", 1, 2, 3),
+            session: None,
+            pid: None,
+            time: None,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: None,
+            truncated: false,
         })
         .unwrap();
         let entry: LogEntry = serde_json::from_str(&json).unwrap();
@@ -176,6 +739,14 @@ mod test {
                 level,
                 target: "target".to_string(),
                 body: "body".to_string(),
+                session: None,
+                pid: None,
+                time: None,
+                module_path: None,
+                file: None,
+                line: None,
+                fields: Default::default(),
+                truncated: false,
             };
             serde_json::to_value(entry).unwrap()
         }
@@ -227,6 +798,14 @@ mod test {
                 level,
                 target: "target",
                 body: format_args!("body"),
+                session: None,
+                pid: None,
+                time: None,
+                module_path: None,
+                file: None,
+                line: None,
+                fields: None,
+                truncated: false,
             })
             .unwrap()
         }
@@ -0,0 +1,368 @@
+//! Limits on how many entries are written.
+
+use log::Level;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// Why an entry was dropped.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum DropCause {
+    /// A queue in front of the destination was full.
+    Queue = 0,
+    /// Writing or sending the entry failed.
+    Io = 1,
+    /// The target's rate limit was exceeded.
+    RateLimit = 2,
+}
+
+/// Counts of entries dropped since they were last reported, by cause.
+#[derive(Default)]
+pub(crate) struct Drops {
+    counts: [AtomicU64; 3],
+    /// Minimum time between reports, or `None` if there are none.
+    interval: Option<Duration>,
+    /// Offset at which the last report was taken.
+    reported: Mutex<Duration>,
+}
+
+impl Drops {
+    pub(crate) fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            ..Self::default()
+        }
+    }
+
+    /// Counts an entry dropped for the cause.
+    pub(crate) fn count(&self, cause: DropCause) {
+        self.counts[cause as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the counts, indexed by [`DropCause`], and starts counting
+    /// again if a report is due.
+    ///
+    /// A report is due once entries were dropped and the interval has passed
+    /// since the last one, as of the offset, or whenever entries were
+    /// dropped if `flushing`.
+    pub(crate) fn take_report(
+        &self,
+        offset: impl FnOnce() -> Duration,
+        flushing: bool,
+    ) -> Option<[u64; 3]> {
+        let interval = self.interval?;
+        if self
+            .counts
+            .iter()
+            .all(|count| count.load(Ordering::Relaxed) == 0)
+        {
+            return None;
+        }
+        let offset = offset();
+        let mut reported = self
+            .reported
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        if !flushing && offset < *reported + interval {
+            return None;
+        }
+        *reported = offset;
+        let counts = self
+            .counts
+            .each_ref()
+            .map(|count| count.swap(0, Ordering::Relaxed));
+        counts.iter().any(|&count| count > 0).then_some(counts)
+    }
+}
+
+/// Keeps one in every so many entries of each level.
+///
+/// The rates are atomic so that a [`LoggerHandle`](crate::LoggerHandle) can
+/// change them while the logger is installed.
+#[derive(Default)]
+pub(crate) struct Sampler {
+    /// Entries of each level, indexed by [`index`], out of which one is
+    /// kept. Zero keeps all of them.
+    one_in: [AtomicU64; 5],
+    /// Entries of each level seen so far.
+    seen: [AtomicU64; 5],
+}
+
+impl Sampler {
+    /// Keeps one in every `one_in` entries of the level.
+    pub(crate) fn set(&self, level: Level, one_in: u32) {
+        self.one_in[index(level)].store(one_in.into(), Ordering::Relaxed);
+    }
+
+    /// Returns whether the next entry of the level is kept, which includes
+    /// the first one.
+    pub(crate) fn keep(&self, level: Level) -> bool {
+        let index = index(level);
+        match self.one_in[index].load(Ordering::Relaxed) {
+            0 | 1 => true,
+            one_in => self.seen[index]
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(one_in),
+        }
+    }
+}
+
+fn index(level: Level) -> usize {
+    level as usize - 1
+}
+
+/// Collapses consecutive entries with the same level, target and body into
+/// the first of them and a count of the rest.
+#[derive(Default)]
+pub(crate) struct Dedup {
+    last: Mutex<Option<Last>>,
+}
+
+/// Last entry written, and how many times it was repeated since.
+struct Last {
+    level: Level,
+    target: String,
+    body: String,
+    repeated: u64,
+}
+
+/// Repetitions of an entry that are to be reported.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Repeats {
+    pub(crate) level: Level,
+    pub(crate) target: String,
+    pub(crate) count: u64,
+}
+
+impl Dedup {
+    /// Returns whether an entry differs from the last one, and so is
+    /// written.
+    ///
+    /// A differing entry also returns the repetitions of the last one, if
+    /// there were any, to be reported before it. They are returned rather
+    /// than reported here so that nothing is written while holding the lock.
+    pub(crate) fn admit(&self, level: Level, target: &str, body: &str) -> (bool, Option<Repeats>) {
+        let mut last = self.last.lock().unwrap_or_else(|error| error.into_inner());
+        if let Some(last) = &mut *last {
+            if last.level == level && last.target == target && last.body == body {
+                last.repeated += 1;
+                return (false, None);
+            }
+        }
+        let previous = last.replace(Last {
+            level,
+            target: target.to_string(),
+            body: body.to_string(),
+            repeated: 0,
+        });
+        let repeats = previous
+            .filter(|last| last.repeated > 0)
+            .map(|last| Repeats {
+                level: last.level,
+                target: last.target,
+                count: last.repeated,
+            });
+        (true, repeats)
+    }
+
+    /// Returns the repetitions of the last entry that have not been reported
+    /// yet, such as before the log is flushed.
+    pub(crate) fn flush(&self) -> Option<Repeats> {
+        let mut last = self.last.lock().unwrap_or_else(|error| error.into_inner());
+        let last = last.as_mut().filter(|last| last.repeated > 0)?;
+        Some(Repeats {
+            level: last.level,
+            target: last.target.clone(),
+            count: std::mem::take(&mut last.repeated),
+        })
+    }
+}
+
+/// Limits how many entries of each target are written per second of the
+/// logger's clock.
+pub(crate) struct RateLimit {
+    /// Entries written per second for each target.
+    max: u32,
+    state: Mutex<RateState>,
+}
+
+#[derive(Default)]
+struct RateState {
+    /// Second of the logger's clock the windows are for.
+    second: u64,
+    /// Windows of the targets logged to within that second.
+    targets: HashMap<String, Window>,
+}
+
+/// Entries of a target within one second.
+#[derive(Default)]
+struct Window {
+    written: u32,
+    suppressed: u64,
+}
+
+impl RateLimit {
+    pub(crate) fn new(max: u32) -> Self {
+        Self {
+            max,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns whether an entry of the target logged at the offset is
+    /// within the limit.
+    ///
+    /// The first entry of a new second also returns the targets whose
+    /// entries were suppressed in the previous one, sorted, along with how
+    /// many were, to be reported before it. The windows of that second are
+    /// then discarded, so only targets logged to within the current second
+    /// are kept.
+    pub(crate) fn admit(&self, target: &str, offset: Duration) -> (bool, Vec<(String, u64)>) {
+        let second = offset.as_secs();
+        let mut state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+        let mut suppressed = Vec::new();
+        if state.second != second {
+            state.second = second;
+            suppressed.extend(
+                state
+                    .targets
+                    .drain()
+                    .filter(|(_, window)| window.suppressed > 0)
+                    .map(|(target, window)| (target, window.suppressed)),
+            );
+            suppressed.sort();
+        }
+        if !state.targets.contains_key(target) {
+            state.targets.insert(target.to_string(), Window::default());
+        }
+        let window = state.targets.get_mut(target).expect("inserted above");
+        if window.written < self.max {
+            window.written += 1;
+            return (true, suppressed);
+        }
+        window.suppressed += 1;
+        (false, suppressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sampled levels keep the first entry and every so many after it,
+    /// while other levels keep everything.
+    #[test]
+    fn samples_levels() {
+        let sampler = Sampler::default();
+        sampler.set(Level::Trace, 3);
+        let kept: Vec<_> = (0..7).map(|_| sampler.keep(Level::Trace)).collect();
+        assert_eq!(vec![true, false, false, true, false, false, true], kept);
+        assert!((0..7).all(|_| sampler.keep(Level::Warn)));
+    }
+
+    /// Repetitions are reported once a different entry is admitted or the
+    /// log is flushed, and later repetitions are counted again.
+    #[test]
+    fn collapses_repeats() {
+        let dedup = Dedup::default();
+        let mut reports = Vec::new();
+        let mut admit = |level, target, body| {
+            let (unique, repeats) = dedup.admit(level, target, body);
+            reports.extend(repeats);
+            unique
+        };
+        assert!(admit(Level::Info, "a", "retry"));
+        assert!(!admit(Level::Info, "a", "retry"));
+        assert!(!admit(Level::Info, "a", "retry"));
+        assert!(admit(Level::Warn, "a", "retry"));
+        assert!(admit(Level::Warn, "b", "retry"));
+        assert!(admit(Level::Warn, "b", "done"));
+        assert!(!admit(Level::Warn, "b", "done"));
+        reports.extend(dedup.flush());
+        reports.extend(dedup.flush());
+        let (unique, repeats) = dedup.admit(Level::Warn, "b", "done");
+        assert!(!unique && repeats.is_none());
+        reports.extend(dedup.flush());
+        let repeats = |level, target: &str, count| Repeats {
+            level,
+            target: target.to_string(),
+            count,
+        };
+        let expected = vec![
+            repeats(Level::Info, "a", 2),
+            repeats(Level::Warn, "b", 1),
+            repeats(Level::Warn, "b", 1),
+        ];
+        assert_eq!(expected, reports);
+    }
+
+    /// Drops are reported at most once per interval, and always when
+    /// flushing.
+    #[test]
+    fn reports_drops() {
+        let drops = Drops::new(Some(Duration::from_secs(10)));
+        let at = |secs| move || Duration::from_secs(secs);
+        assert_eq!(None, drops.take_report(at(0), true));
+        drops.count(DropCause::Io);
+        drops.count(DropCause::Io);
+        drops.count(DropCause::RateLimit);
+        assert_eq!(None, drops.take_report(at(5), false));
+        assert_eq!(Some([0, 2, 1]), drops.take_report(at(10), false));
+        drops.count(DropCause::Queue);
+        assert_eq!(None, drops.take_report(at(15), false));
+        assert_eq!(Some([1, 0, 0]), drops.take_report(at(15), true));
+        assert_eq!(None, drops.take_report(at(30), false));
+
+        let drops = Drops::new(None);
+        drops.count(DropCause::Io);
+        assert_eq!(None, drops.take_report(at(0), true));
+    }
+
+    /// Entries over the limit are suppressed until the next second, which
+    /// reports them in order of their targets.
+    #[test]
+    fn limits_each_target() {
+        let limit = RateLimit::new(2);
+        let mut reports = Vec::new();
+        let mut admit = |target: &str, secs| {
+            let (admitted, suppressed) = limit.admit(target, Duration::from_secs(secs));
+            reports.extend(suppressed);
+            admitted
+        };
+        for target in ["c", "a", "b"] {
+            assert!(admit(target, 0));
+            assert!(admit(target, 0));
+        }
+        assert!(!admit("c", 0));
+        assert!(!admit("a", 0));
+        assert!(!admit("a", 0));
+        assert!(admit("b", 1));
+        assert!(admit("a", 1));
+        assert!(admit("a", 1));
+        assert!(!admit("a", 1));
+        assert!(admit("c", 2));
+        let expected = vec![
+            ("a".to_string(), 2),
+            ("c".to_string(), 1),
+            ("a".to_string(), 1),
+        ];
+        assert_eq!(expected, reports);
+    }
+
+    /// Only targets logged to within the current second are kept.
+    #[test]
+    fn forgets_past_seconds() {
+        let limit = RateLimit::new(1);
+        for i in 0..100 {
+            assert!(limit.admit(&format!("target {i}"), Duration::ZERO).0);
+        }
+        assert!(limit.admit("next", Duration::from_secs(1)).1.is_empty());
+        let state = limit.state.lock().unwrap();
+        assert_eq!(vec!["next"], state.targets.keys().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,131 @@
+//! JSON Lines compliance checks for log files, including ones written by
+//! other programs.
+//!
+//! Entries written by this crate are always one JSON object per line, since
+//! serialization escapes every control character and
+//! [`Encoder::write_line`](crate::core::Encoder::write_line) rejects lines
+//! containing a newline. [`check`] finds the places where a file breaks
+//! those rules, which [`read`](crate::read) would otherwise stop at silently.
+
+use crate::LogEntry;
+use flate2::read::MultiGzDecoder;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+/// A line of a log file breaking the JSON Lines format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// What is wrong with the line.
+    pub kind: ViolationKind,
+}
+
+/// Kinds of [`Violation`]s.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The line is not valid UTF-8.
+    InvalidUtf8,
+    /// The line contains a raw control character at this byte offset, which
+    /// JSON only allows escaped.
+    ControlCharacter(usize),
+    /// The line is not valid JSON.
+    InvalidJson(String),
+    /// The line is valid JSON but not a log entry.
+    NotAnEntry(String),
+    /// The last line has no trailing newline, as if writing was interrupted.
+    MissingNewline,
+}
+
+/// Checks every line of the .jsonl.gz file at `path`.
+pub fn check<P: AsRef<Path>>(path: P) -> io::Result<Vec<Violation>> {
+    check_lines(BufReader::new(MultiGzDecoder::new(File::open(path)?)))
+}
+
+/// Checks every line of uncompressed JSON Lines data.
+///
+/// Lines may end with `\r\n` as well as `\n`.
+pub fn check_lines<R: BufRead>(mut reader: R) -> io::Result<Vec<Violation>> {
+    let mut violations = Vec::new();
+    let mut buffer = Vec::new();
+    for line in 1.. {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            break;
+        }
+        let mut violation = |kind| violations.push(Violation { line, kind });
+        let content = match buffer.strip_suffix(b"\n") {
+            Some(content) => content.strip_suffix(b"\r").unwrap_or(content),
+            None => {
+                violation(ViolationKind::MissingNewline);
+                &buffer
+            }
+        };
+        if let Some(kind) = check_line(content) {
+            violation(kind);
+        }
+    }
+    Ok(violations)
+}
+
+/// Checks one line without its line ending.
+fn check_line(line: &[u8]) -> Option<ViolationKind> {
+    let Ok(text) = std::str::from_utf8(line) else {
+        return Some(ViolationKind::InvalidUtf8);
+    };
+    // tabs are allowed as whitespace between tokens
+    if let Some(offset) = line.iter().position(|b| *b < 0x20 && *b != b'\t') {
+        return Some(ViolationKind::ControlCharacter(offset));
+    }
+    let value: serde_json::Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(error) => return Some(ViolationKind::InvalidJson(error.to_string())),
+    };
+    serde_json::from_value::<LogEntry>(value)
+        .err()
+        .map(|error| ViolationKind::NotAnEntry(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY: &str =
+        r#"{"offset":{"secs":1,"nanos":0},"level":"INFO","target":"app","body":"hi"}"#;
+
+    fn kinds(data: &[u8]) -> Vec<(usize, ViolationKind)> {
+        check_lines(data)
+            .unwrap()
+            .into_iter()
+            .map(|violation| (violation.line, violation.kind))
+            .collect()
+    }
+
+    #[test]
+    fn accepts_valid_lines() {
+        let data = format!("{ENTRY}\n{ENTRY}\r\n");
+        assert_eq!(Vec::<(usize, ViolationKind)>::new(), kinds(data.as_bytes()));
+    }
+
+    #[test]
+    fn flags_violations() {
+        let split = ENTRY.replace("hi", "h\ni");
+        let data = format!("{split}\n{{\"a\":1}}\n\u{7}\n{ENTRY}");
+        let found = kinds(data.as_bytes());
+        let lines: Vec<_> = found.iter().map(|(line, _)| *line).collect();
+        assert_eq!(vec![1, 2, 3, 4, 5], lines);
+        assert!(matches!(found[0].1, ViolationKind::InvalidJson(_)));
+        assert!(matches!(found[1].1, ViolationKind::InvalidJson(_)));
+        assert!(matches!(found[2].1, ViolationKind::NotAnEntry(_)));
+        assert_eq!(ViolationKind::ControlCharacter(0), found[3].1);
+        assert_eq!(ViolationKind::MissingNewline, found[4].1);
+    }
+
+    #[test]
+    fn flags_invalid_utf8() {
+        assert_eq!(vec![(1, ViolationKind::InvalidUtf8)], kinds(b"\xff\n"));
+    }
+}
@@ -0,0 +1,42 @@
+//! Non-blocking logging with a dedicated writer thread.
+
+use crate::{facade, worker, InitError, LoggerBuilder};
+use log::LevelFilter;
+use std::thread;
+
+pub use crate::worker::{Durability, Flushed, WorkerGuard};
+pub use crate::LoggerHandle;
+
+/// Creates and installs a global logger that logs to a new .jsonl.gz file at
+/// the given path without blocking the logging thread.
+///
+/// Compression and file IO happen on a dedicated writer thread, so logging
+/// threads only pay for formatting the entry and sending it over a channel.
+/// The returned [`WorkerGuard`] finishes the file when dropped and must
+/// outlive all logging.
+pub fn init<P: AsRef<std::path::Path>>(
+    path: P,
+    level: LevelFilter,
+) -> Result<WorkerGuard, InitError> {
+    LoggerBuilder::new().level(level).init_non_blocking(path)
+}
+
+impl LoggerBuilder {
+    /// Like [`non_blocking::init`](init), but with this builder's
+    /// configuration.
+    pub fn init_non_blocking<P: AsRef<std::path::Path>>(
+        self,
+        path: P,
+    ) -> Result<WorkerGuard, InitError> {
+        facade::ensure_uninstalled()?;
+        let dest = self.create(path)?;
+        let (filter, install, queue) = (self.filter.clone(), self.install_options(), self.queue);
+        let (logger, guard, worker) = worker::channel(dest, filter, self.format()?, queue);
+        // start the writer first so that an installed logger always has one
+        thread::Builder::new()
+            .name("jsonl_gzip_logger".to_string())
+            .spawn(worker)?;
+        let generation = facade::install(Box::new(logger), install)?;
+        Ok(guard.installed(generation))
+    }
+}
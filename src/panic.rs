@@ -0,0 +1,29 @@
+//! Panic hook that saves the log before a panic loses it.
+
+use std::{panic, sync::Once};
+
+/// Installs a panic hook that logs the panic as an error and flushes the
+/// global logger, then runs the previously installed hook.
+///
+/// When panics unwind the log is only flushed, since the panic may be
+/// caught; dropping the [`InitGuard`](crate::InitGuard) while unwinding out
+/// of `main` finishes the file. When panics abort, the file is finished
+/// right away as with [`deinit`](crate::deinit), since nothing else will
+/// get the chance to.
+///
+/// Installing the hook more than once has no further effect.
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            log::error!(target: "panic", "{}", info);
+            if cfg!(panic = "abort") {
+                crate::deinit();
+            } else {
+                log::logger().flush();
+            }
+            previous(info);
+        }));
+    });
+}
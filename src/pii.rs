@@ -0,0 +1,240 @@
+//! Detection and hashing of personally identifiable information.
+//!
+//! Email addresses, phone numbers and credit card numbers in entry bodies and
+//! in the strings of their fields, however deeply nested, are replaced with a
+//! token such as `<email:1f3a9c0b2d4e6f70>` holding a salted
+//! hash of the value. The same value always hashes to the same token with the
+//! same salt, so entries can still be correlated without the raw value ever
+//! being written. Detection is a best-effort heuristic, not a guarantee.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+
+/// Number of hex characters of the hash kept in a token.
+const HASH_LEN: usize = 16;
+
+/// Replaces PII in entry bodies with salted hashes.
+#[derive(Clone)]
+pub struct PiiHasher {
+    salt: Vec<u8>,
+}
+
+impl PiiHasher {
+    /// Creates a hasher using the given salt, which should be kept secret.
+    pub fn new(salt: impl Into<Vec<u8>>) -> Self {
+        Self { salt: salt.into() }
+    }
+
+    /// Returns the text with every detected PII value replaced by a token.
+    pub fn scrub<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let bytes = text.as_bytes();
+        let mut scrubbed = String::new();
+        // end of the text already copied into `scrubbed`
+        let mut copied = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let found = if bytes[i] == b'@' {
+                email_at(bytes, copied, i).map(|(start, end)| {
+                    let normalized = text[start..end].to_ascii_lowercase();
+                    (start, end, self.token("email", &normalized))
+                })
+            } else if starts_number(bytes, i) {
+                number_at(bytes, i).and_then(|(end, digits)| {
+                    let kind = if (13..=19).contains(&digits.len()) && luhn(&digits) {
+                        "card"
+                    } else if (10..=15).contains(&digits.len()) {
+                        "phone"
+                    } else {
+                        return None;
+                    };
+                    Some((i, end, self.token(kind, &digits)))
+                })
+            } else {
+                None
+            };
+            match found {
+                Some((start, end, token)) => {
+                    scrubbed.push_str(&text[copied..start]);
+                    scrubbed.push_str(&token);
+                    copied = end;
+                    i = end;
+                }
+                None => i += 1,
+            }
+        }
+        if copied == 0 {
+            return Cow::Borrowed(text);
+        }
+        scrubbed.push_str(&text[copied..]);
+        Cow::Owned(scrubbed)
+    }
+
+    /// Scrubs every string in the value.
+    pub(crate) fn scrub_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => {
+                if let Cow::Owned(scrubbed) = self.scrub(text) {
+                    *text = scrubbed;
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|value| self.scrub_value(value)),
+            Value::Object(fields) => fields
+                .values_mut()
+                .for_each(|value| self.scrub_value(value)),
+            _ => {}
+        }
+    }
+
+    /// Returns the token replacing a value of the given kind.
+    fn token(&self, kind: &str, value: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.salt);
+        hasher.update(value.as_bytes());
+        let hash = crate::dest::hex(&hasher.finalize());
+        format!("<{}:{}>", kind, &hash[..HASH_LEN])
+    }
+}
+
+impl std::fmt::Debug for PiiHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PiiHasher(..)")
+    }
+}
+
+/// Returns the span of the email address around the `@` at `at`, not
+/// starting before `min`.
+fn email_at(bytes: &[u8], min: usize, at: usize) -> Option<(usize, usize)> {
+    let local = |b: u8| b.is_ascii_alphanumeric() || b"._%+-".contains(&b);
+    let domain = |b: u8| b.is_ascii_alphanumeric() || b".-".contains(&b);
+    let mut start = at;
+    while start > min && local(bytes[start - 1]) {
+        start -= 1;
+    }
+    let mut end = at + 1;
+    while end < bytes.len() && domain(bytes[end]) {
+        end += 1;
+    }
+    // punctuation ending a sentence is not part of the domain
+    while end > at + 1 && b".-".contains(&bytes[end - 1]) {
+        end -= 1;
+    }
+    let host = &bytes[at + 1..end];
+    let tld = host.rsplit(|&b| b == b'.').next()?;
+    let valid = start < at
+        && host.contains(&b'.')
+        && tld.len() >= 2
+        && tld.iter().all(u8::is_ascii_alphabetic);
+    valid.then_some((start, end))
+}
+
+/// Returns whether a phone or card number may start at `i`.
+fn starts_number(bytes: &[u8], i: usize) -> bool {
+    let first = bytes[i].is_ascii_digit() || bytes[i] == b'+' || bytes[i] == b'(';
+    first && (i == 0 || !bytes[i - 1].is_ascii_alphanumeric())
+}
+
+/// Returns the end of the number starting at `start` and its digits.
+///
+/// Digits may be grouped with spaces, dashes, dots and parentheses.
+fn number_at(bytes: &[u8], start: usize) -> Option<(usize, String)> {
+    let mut digits = String::new();
+    let mut end = start;
+    let mut separators = 0;
+    let mut i = start;
+    if bytes[i] == b'+' {
+        i += 1;
+    }
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b.is_ascii_digit() {
+            digits.push(b as char);
+            separators = 0;
+            end = i + 1;
+        } else if b" -.()".contains(&b) && separators < 2 {
+            separators += 1;
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    // digits running into letters are part of an identifier
+    let attached = bytes.get(end).is_some_and(u8::is_ascii_alphanumeric);
+    (!digits.is_empty() && !attached).then_some((end, digits))
+}
+
+/// Returns whether the digits pass the Luhn checksum used by card numbers.
+fn luhn(digits: &str) -> bool {
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| {
+            let digit = u32::from(b - b'0');
+            match i % 2 {
+                0 => digit,
+                _ if digit > 4 => digit * 2 - 9,
+                _ => digit * 2,
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrub(text: &str) -> String {
+        PiiHasher::new("salt").scrub(text).into_owned()
+    }
+
+    /// Emails are replaced, case-insensitively, leaving punctuation.
+    #[test]
+    fn emails() {
+        let scrubbed = scrub("sent to Jane.Doe+x@Example.com.");
+        assert!(scrubbed.starts_with("sent to <email:"));
+        assert!(scrubbed.ends_with(">."));
+        assert_eq!(scrubbed, scrub("sent to jane.doe+x@example.com."));
+        assert_eq!("user@localhost", scrub("user@localhost"));
+        assert_eq!("@example.com", scrub("@example.com"));
+    }
+
+    /// Luhn-valid card numbers are replaced regardless of grouping.
+    #[test]
+    fn cards() {
+        let plain = scrub("card 4111111111111111 ok");
+        assert!(plain.starts_with("card <card:"));
+        assert!(plain.ends_with("> ok"));
+        assert_eq!(plain, scrub("card 4111-1111-1111-1111 ok"));
+        assert_eq!(plain, scrub("card 4111 1111 1111 1111 ok"));
+    }
+
+    /// Phone numbers are replaced, other numbers are left alone.
+    #[test]
+    fn phones() {
+        let scrubbed = scrub("call +1 (555) 123-4567 now");
+        assert!(scrubbed.starts_with("call <phone:"));
+        assert!(scrubbed.ends_with("> now"));
+        assert_eq!(scrubbed, scrub("call 15551234567 now"));
+        assert_eq!("took 1500 ms", scrub("took 1500 ms"));
+        assert_eq!("on 2024-01-05", scrub("on 2024-01-05"));
+        assert_eq!("id a5551234567", scrub("id a5551234567"));
+    }
+
+    /// Different salts produce different tokens.
+    #[test]
+    fn salted() {
+        let other = PiiHasher::new("pepper").scrub("a@b.io").into_owned();
+        assert_ne!(scrub("a@b.io"), other);
+    }
+
+    /// Text without PII is not copied.
+    #[test]
+    fn borrowed_when_clean() {
+        assert!(matches!(
+            PiiHasher::new("salt").scrub("nothing here"),
+            Cow::Borrowed(_)
+        ));
+    }
+}
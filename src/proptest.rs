@@ -0,0 +1,103 @@
+//! [`proptest`] support for property testing log processing.
+
+use crate::LogEntry;
+use ::proptest::{
+    arbitrary::{any, Arbitrary},
+    collection::vec,
+    prop_oneof,
+    strategy::{BoxedStrategy, Just, Strategy},
+};
+use flate2::{write::GzEncoder, Compression};
+use log::Level;
+use std::{fs::File, io::Write, path::Path, time::Duration};
+
+/// Strategy for any logging level.
+pub fn level() -> impl Strategy<Value = Level> {
+    prop_oneof![
+        Just(Level::Error),
+        Just(Level::Warn),
+        Just(Level::Info),
+        Just(Level::Debug),
+        Just(Level::Trace),
+    ]
+}
+
+/// Strategy for module-path-like targets such as `my_app::db`.
+pub fn target() -> impl Strategy<Value = String> {
+    "[a-z_][a-z0-9_]{0,7}(::[a-z_][a-z0-9_]{0,7}){0,3}"
+}
+
+/// Strategy for offsets within the first ten years of logging.
+pub fn offset() -> impl Strategy<Value = Duration> {
+    (0..315_360_000u64, 0..1_000_000_000u32).prop_map(|(secs, nanos)| Duration::new(secs, nanos))
+}
+
+impl Arbitrary for LogEntry {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (offset(), level(), target(), any::<String>())
+            .prop_map(|(offset, level, target, body)| LogEntry {
+                offset,
+                level,
+                target,
+                body,
+                session: None,
+                pid: None,
+                time: None,
+                module_path: None,
+                file: None,
+                line: None,
+                fields: Default::default(),
+                truncated: false,
+            })
+            .boxed()
+    }
+}
+
+/// Strategy for the entries of a valid log file: up to `max_entries`
+/// arbitrary entries with non-decreasing offsets.
+pub fn log_file(max_entries: usize) -> impl Strategy<Value = Vec<LogEntry>> {
+    vec(any::<LogEntry>(), 0..=max_entries).prop_map(|mut entries| {
+        entries.sort_by_key(|entry| entry.offset);
+        entries
+    })
+}
+
+/// Writes the given entries to a new .jsonl.gz file at the given path.
+pub fn write_log_file<P: AsRef<Path>>(path: P, entries: &[LogEntry]) -> std::io::Result<()> {
+    let mut dest = GzEncoder::new(File::create(path)?, Compression::fast());
+    for entry in entries {
+        serde_json::to_writer(&mut dest, entry)?;
+        dest.write_all(b"\n")?;
+    }
+    dest.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::proptest::proptest;
+
+    proptest! {
+        /// Generated files read back as the entries they were written from.
+        #[test]
+        fn log_file_round_trip(entries in log_file(20)) {
+            let path = std::env::temp_dir().join(format!(
+                "jsonl_gzip_logger_proptest_{}.jsonl.gz",
+                std::process::id()
+            ));
+            write_log_file(&path, &entries).unwrap();
+            let read: Vec<LogEntry> = crate::read(&path).unwrap().collect();
+            assert_eq!(entries.len(), read.len());
+            for (written, read) in entries.iter().zip(&read) {
+                assert_eq!(written.offset, read.offset);
+                assert_eq!(written.level, read.level);
+                assert_eq!(written.target, read.target);
+                assert_eq!(written.body, read.body);
+            }
+        }
+    }
+}
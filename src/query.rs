@@ -0,0 +1,647 @@
+//! Ad-hoc filtering of log files.
+//!
+//! A [`Query`] selects entries either from a small subset of jq syntax:
+//!
+//! ```no_run
+//! let errors = jsonl_gzip_logger::query::query(
+//!     "app.jsonl.gz",
+//!     r#"select(.level == "ERROR" and (.target | startswith("db")))"#,
+//! )
+//! .unwrap();
+//! for entry in errors {
+//!     println!("{}", entry.body);
+//! }
+//! ```
+//!
+//! or from the equivalent typed builder:
+//!
+//! ```
+//! use jsonl_gzip_logger::query::Query;
+//! use log::Level;
+//!
+//! let query = Query::level(Level::Error).and(Query::target_starts_with("db"));
+//! ```
+//!
+//! The supported syntax is an optional top-level `select(...)` around an
+//! expression made of:
+//!
+//! - the fields `.level`, `.target`, `.body`, `.session`, and `.offset`,
+//!   which is the entry's offset in seconds,
+//! - string, number, `true`, `false`, and `null` literals,
+//! - the comparisons `==`, `!=`, `<`, `<=`, `>`, and `>=`,
+//! - `and`, `or`, parentheses, and a trailing `| not`,
+//! - `FIELD | startswith("...")`, `endswith`, and `contains`.
+//!
+//! Levels are upper case as in the file, e.g. `"WARN"`.
+
+use crate::{LogEntry, LogEntryIter};
+use log::Level;
+use std::{fmt, io, path::Path, str::FromStr};
+use thiserror::Error;
+
+/// Predicate over log entries.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query(Expr);
+
+/// Error type for [`query`] and [`Query::parse`].
+#[derive(Error, Debug)]
+pub enum QueryError {
+    /// Opening the log file failed.
+    #[error("failed to open log file: {0}")]
+    Io(#[from] io::Error),
+    /// The query expression is malformed or unsupported.
+    #[error("invalid query at byte {position}: {message}")]
+    Parse {
+        /// Byte offset in the expression where the error was found.
+        position: usize,
+        /// Description of the problem.
+        message: String,
+    },
+}
+
+/// Returns the entries of the log file at `path` selected by `expression`.
+///
+/// See the [module documentation](self) for the supported syntax.
+pub fn query<P: AsRef<Path>>(
+    path: P,
+    expression: &str,
+) -> Result<impl Iterator<Item = LogEntry>, QueryError> {
+    let query = Query::parse(expression)?;
+    Ok(query.filter(crate::read(path)?))
+}
+
+impl Query {
+    /// Matches every entry.
+    pub fn all() -> Self {
+        Self(Expr::Literal(true))
+    }
+
+    /// Matches entries with exactly this level.
+    pub fn level(level: Level) -> Self {
+        Self::compare(Field::Level, Op::Eq, Value::String(level.as_str().into()))
+    }
+
+    /// Matches entries at or above this level, e.g. `Warn` also matches
+    /// `Error`.
+    pub fn min_level(level: Level) -> Self {
+        Level::iter()
+            .filter(|other| *other <= level)
+            .map(Self::level)
+            .reduce(Self::or)
+            .unwrap()
+    }
+
+    /// Matches entries with exactly this target.
+    pub fn target(target: impl Into<String>) -> Self {
+        Self::compare(Field::Target, Op::Eq, Value::String(target.into()))
+    }
+
+    /// Matches entries whose target starts with this prefix.
+    pub fn target_starts_with(prefix: impl Into<String>) -> Self {
+        Self(Expr::Call(Field::Target, Func::StartsWith, prefix.into()))
+    }
+
+    /// Matches entries whose body contains this text.
+    pub fn body_contains(text: impl Into<String>) -> Self {
+        Self(Expr::Call(Field::Body, Func::Contains, text.into()))
+    }
+
+    /// Matches entries written by this logger session.
+    pub fn session(session: impl Into<String>) -> Self {
+        Self::compare(Field::Session, Op::Eq, Value::String(session.into()))
+    }
+
+    /// Matches entries logged at or after this many seconds.
+    pub fn after(seconds: f64) -> Self {
+        Self::compare(Field::Offset, Op::Ge, Value::Number(seconds))
+    }
+
+    /// Matches entries logged before this many seconds.
+    pub fn before(seconds: f64) -> Self {
+        Self::compare(Field::Offset, Op::Lt, Value::Number(seconds))
+    }
+
+    /// Matches entries matching both queries.
+    pub fn and(self, other: Self) -> Self {
+        Self(Expr::And(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /// Matches entries matching either query.
+    pub fn or(self, other: Self) -> Self {
+        Self(Expr::Or(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /// Matches entries not matching this query.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Self(Expr::Not(Box::new(self.0)))
+    }
+
+    /// Parses a jq-style expression.
+    ///
+    /// See the [module documentation](self) for the supported syntax.
+    pub fn parse(expression: &str) -> Result<Self, QueryError> {
+        let mut parser = Parser {
+            tokens: tokenize(expression)?,
+            next: 0,
+            end: expression.len(),
+        };
+        let expr = parser.top()?;
+        if let Some((position, token)) = parser.tokens.get(parser.next) {
+            return Err(error(*position, format!("unexpected {token}")));
+        }
+        Ok(Self(expr))
+    }
+
+    /// Returns true if the entry matches this query.
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        self.0.eval(entry)
+    }
+
+    /// Returns the entries matching this query.
+    pub fn filter(self, entries: LogEntryIter) -> impl Iterator<Item = LogEntry> {
+        entries.filter(move |entry| self.matches(entry))
+    }
+
+    fn compare(field: Field, op: Op, value: Value) -> Self {
+        Self(Expr::Compare(
+            Operand::Field(field),
+            op,
+            Operand::Value(value),
+        ))
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(expression: &str) -> Result<Self, QueryError> {
+        Self::parse(expression)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Literal(bool),
+    /// True unless the operand is `null` or `false`, as in jq.
+    Truthy(Operand),
+    Compare(Operand, Op, Operand),
+    Call(Field, Func, String),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    Field(Field),
+    Value(Value),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Level,
+    Target,
+    Body,
+    Session,
+    Offset,
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Func {
+    StartsWith,
+    EndsWith,
+    Contains,
+}
+
+impl Expr {
+    fn eval(&self, entry: &LogEntry) -> bool {
+        match self {
+            Expr::Literal(value) => *value,
+            Expr::Truthy(operand) => {
+                !matches!(operand.eval(entry), Value::Null | Value::Bool(false))
+            }
+            Expr::Compare(left, op, right) => {
+                let (left, right) = (left.eval(entry), right.eval(entry));
+                match op {
+                    Op::Eq => left == right,
+                    Op::Ne => left != right,
+                    Op::Lt => left < right,
+                    Op::Le => left <= right,
+                    Op::Gt => left > right,
+                    Op::Ge => left >= right,
+                }
+            }
+            Expr::Call(field, func, argument) => match Operand::Field(*field).eval(entry) {
+                Value::String(text) => match func {
+                    Func::StartsWith => text.starts_with(argument.as_str()),
+                    Func::EndsWith => text.ends_with(argument.as_str()),
+                    Func::Contains => text.contains(argument.as_str()),
+                },
+                _ => false,
+            },
+            Expr::And(left, right) => left.eval(entry) && right.eval(entry),
+            Expr::Or(left, right) => left.eval(entry) || right.eval(entry),
+            Expr::Not(expr) => !expr.eval(entry),
+        }
+    }
+}
+
+impl Operand {
+    fn eval(&self, entry: &LogEntry) -> Value {
+        match self {
+            Operand::Field(Field::Level) => Value::String(entry.level.as_str().into()),
+            Operand::Field(Field::Target) => Value::String(entry.target.clone()),
+            Operand::Field(Field::Body) => Value::String(entry.body.clone()),
+            Operand::Field(Field::Session) => match &entry.session {
+                Some(session) => Value::String(session.clone()),
+                None => Value::Null,
+            },
+            Operand::Field(Field::Offset) => Value::Number(entry.offset.as_secs_f64()),
+            Operand::Value(value) => value.clone(),
+        }
+    }
+}
+
+fn error(position: usize, message: impl Into<String>) -> QueryError {
+    QueryError::Parse {
+        position,
+        message: message.into(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Dot,
+    Pipe,
+    Open,
+    Close,
+    Op(Op),
+    Ident(String),
+    String(String),
+    Number(f64),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Dot => write!(f, "`.`"),
+            Token::Pipe => write!(f, "`|`"),
+            Token::Open => write!(f, "`(`"),
+            Token::Close => write!(f, "`)`"),
+            Token::Op(op) => write!(f, "comparison {op:?}"),
+            Token::Ident(ident) => write!(f, "`{ident}`"),
+            Token::String(string) => write!(f, "string {string:?}"),
+            Token::Number(number) => write!(f, "number {number}"),
+        }
+    }
+}
+
+/// Splits an expression into tokens and their byte offsets.
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '.' => Token::Dot,
+            '|' => Token::Pipe,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' | '!' | '<' | '>' => {
+                let equals = chars.next_if(|(_, c)| *c == '=').is_some();
+                Token::Op(match (c, equals) {
+                    ('=', true) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    _ => return Err(error(start, format!("expected `=` after `{c}`"))),
+                })
+            }
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => string.push('\n'),
+                            Some((_, 't')) => string.push('\t'),
+                            Some((_, c @ ('"' | '\\' | '/'))) => string.push(c),
+                            _ => return Err(error(start, "unsupported escape in string")),
+                        },
+                        Some((_, c)) => string.push(c),
+                        None => return Err(error(start, "unterminated string")),
+                    }
+                }
+                Token::String(string)
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, c)) =
+                    chars.next_if(|(_, c)| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E'))
+                {
+                    end = index + c.len_utf8();
+                }
+                let number = expression[start..end]
+                    .parse()
+                    .map_err(|_| error(start, "invalid number"))?;
+                Token::Number(number)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, c)) =
+                    chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_')
+                {
+                    end = index + c.len_utf8();
+                }
+                Token::Ident(expression[start..end].to_string())
+            }
+            c => return Err(error(start, format!("unexpected character `{c}`"))),
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+/// Either an expression or an operand that may still be compared.
+enum Node {
+    Expr(Expr),
+    Operand(Operand),
+}
+
+impl Node {
+    fn into_expr(self) -> Expr {
+        match self {
+            Node::Expr(expr) => expr,
+            Node::Operand(operand) => Expr::Truthy(operand),
+        }
+    }
+}
+
+/// Recursive descent parser over tokens.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    /// Length of the expression, reported for errors at its end.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.end, |(position, _)| *position)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).map(|(_, token)| token.clone());
+        self.next += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn eat_ident(&mut self, ident: &str) -> bool {
+        self.eat(&Token::Ident(ident.to_string()))
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), QueryError> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("{token}")))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> QueryError {
+        let found = match self.peek() {
+            Some(token) => token.to_string(),
+            None => "end of query".to_string(),
+        };
+        error(
+            self.position(),
+            format!("expected {expected}, found {found}"),
+        )
+    }
+
+    /// `select(pipe)` or `pipe`.
+    fn top(&mut self) -> Result<Expr, QueryError> {
+        if self.eat_ident("select") {
+            self.expect(Token::Open)?;
+            let expr = self.pipe()?.into_expr();
+            self.expect(Token::Close)?;
+            Ok(expr)
+        } else {
+            Ok(self.pipe()?.into_expr())
+        }
+    }
+
+    /// `or (| filter)*`, where a filter is `not` or a string function.
+    fn pipe(&mut self) -> Result<Node, QueryError> {
+        let mut node = self.or()?;
+        while self.eat(&Token::Pipe) {
+            let position = self.position();
+            node = if self.eat_ident("not") {
+                Node::Expr(Expr::Not(Box::new(node.into_expr())))
+            } else {
+                let func = match self.advance() {
+                    Some(Token::Ident(name)) if name == "startswith" => Func::StartsWith,
+                    Some(Token::Ident(name)) if name == "endswith" => Func::EndsWith,
+                    Some(Token::Ident(name)) if name == "contains" => Func::Contains,
+                    _ => return Err(error(position, "unsupported filter after `|`")),
+                };
+                let Node::Operand(Operand::Field(field)) = node else {
+                    return Err(error(position, "string functions only apply to fields"));
+                };
+                self.expect(Token::Open)?;
+                let Some(Token::String(argument)) = self.advance() else {
+                    self.next -= 1;
+                    return Err(self.unexpected("a string"));
+                };
+                self.expect(Token::Close)?;
+                Node::Expr(Expr::Call(field, func, argument))
+            };
+        }
+        Ok(node)
+    }
+
+    fn or(&mut self) -> Result<Node, QueryError> {
+        let mut node = self.and()?;
+        while self.eat_ident("or") {
+            let right = self.and()?.into_expr();
+            node = Node::Expr(Expr::Or(Box::new(node.into_expr()), Box::new(right)));
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node, QueryError> {
+        let mut node = self.comparison()?;
+        while self.eat_ident("and") {
+            let right = self.comparison()?.into_expr();
+            node = Node::Expr(Expr::And(Box::new(node.into_expr()), Box::new(right)));
+        }
+        Ok(node)
+    }
+
+    fn comparison(&mut self) -> Result<Node, QueryError> {
+        let position = self.position();
+        let left = self.atom()?;
+        let Some(Token::Op(op)) = self.peek().cloned() else {
+            return Ok(left);
+        };
+        self.next += 1;
+        let right_position = self.position();
+        match (left, self.atom()?) {
+            (Node::Operand(left), Node::Operand(right)) => {
+                Ok(Node::Expr(Expr::Compare(left, op, right)))
+            }
+            (Node::Expr(_), _) => Err(error(position, "only fields and literals can be compared")),
+            (_, Node::Expr(_)) => Err(error(
+                right_position,
+                "only fields and literals can be compared",
+            )),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node, QueryError> {
+        let position = self.position();
+        let node = match self.advance() {
+            Some(Token::Open) => {
+                let node = self.pipe()?;
+                self.expect(Token::Close)?;
+                node
+            }
+            Some(Token::Dot) => {
+                let field = match self.advance() {
+                    Some(Token::Ident(name)) => match name.as_str() {
+                        "level" => Field::Level,
+                        "target" => Field::Target,
+                        "body" => Field::Body,
+                        "session" => Field::Session,
+                        "offset" => Field::Offset,
+                        _ => return Err(error(position, format!("unknown field `.{name}`"))),
+                    },
+                    _ => return Err(error(position, "expected a field name after `.`")),
+                };
+                Node::Operand(Operand::Field(field))
+            }
+            Some(Token::String(string)) => Node::Operand(Operand::Value(Value::String(string))),
+            Some(Token::Number(number)) => Node::Operand(Operand::Value(Value::Number(number))),
+            Some(Token::Ident(name)) if name == "true" || name == "false" => {
+                Node::Operand(Operand::Value(Value::Bool(name == "true")))
+            }
+            Some(Token::Ident(name)) if name == "null" => {
+                Node::Operand(Operand::Value(Value::Null))
+            }
+            _ => {
+                self.next -= 1;
+                return Err(self.unexpected("a field, literal, or `(`"));
+            }
+        };
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn entry(level: Level, target: &str, body: &str, offset: u64) -> LogEntry {
+        LogEntry {
+            offset: Duration::from_secs(offset),
+            level,
+            target: target.to_string(),
+            body: body.to_string(),
+            session: None,
+            pid: None,
+            time: None,
+            module_path: None,
+            file: None,
+            line: None,
+            fields: Default::default(),
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn parses_like_the_builder() {
+        let parsed =
+            Query::parse(r#"select(.level == "ERROR" and (.target|startswith("db")))"#).unwrap();
+        let built = Query::level(Level::Error).and(Query::target_starts_with("db"));
+        assert_eq!(built, parsed);
+    }
+
+    #[test]
+    fn evaluates() {
+        let entries = [
+            entry(Level::Error, "db::pool", "timeout", 1),
+            entry(Level::Warn, "db", "slow", 5),
+            entry(Level::Info, "http", "GET /", 10),
+        ];
+        let count = |expression: &str| {
+            let query = Query::parse(expression).unwrap();
+            entries.iter().filter(|entry| query.matches(entry)).count()
+        };
+        assert_eq!(3, count("true"));
+        assert_eq!(1, count(r#".level == "ERROR""#));
+        assert_eq!(2, count(r#".level == "ERROR" or .level == "WARN""#));
+        assert_eq!(2, count(r#"(.target | startswith("db"))"#));
+        assert_eq!(1, count(r#".body | contains("GET")"#));
+        assert_eq!(2, count(r#".target == "http" | not"#));
+        assert_eq!(2, count(".offset >= 5"));
+        assert_eq!(0, count(".session"));
+        assert_eq!(3, count(".session == null"));
+    }
+
+    #[test]
+    fn min_level() {
+        let query = Query::min_level(Level::Warn);
+        assert!(query.matches(&entry(Level::Error, "", "", 0)));
+        assert!(query.matches(&entry(Level::Warn, "", "", 0)));
+        assert!(!query.matches(&entry(Level::Info, "", "", 0)));
+    }
+
+    #[test]
+    fn reports_errors() {
+        let position = |expression: &str| match Query::parse(expression) {
+            Err(QueryError::Parse { position, .. }) => position,
+            other => panic!("expected a parse error, got {other:?}"),
+        };
+        assert_eq!(0, position(".nope == 1"));
+        assert_eq!(7, position(r#".level = "ERROR""#));
+        assert_eq!(14, position(r#"select(.level "#));
+        assert_eq!(9, position(".level | length"));
+        assert_eq!(6, position(r#""a" | startswith("b")"#));
+    }
+}
@@ -0,0 +1,252 @@
+//! Queue between the logging front end and the background writer.
+
+use crate::worker::Message;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+};
+
+/// What to do with an entry logged while the queue of a non-blocking logger
+/// is full, see [`LoggerBuilder::queue_capacity`](crate::LoggerBuilder::queue_capacity).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait in the logging thread until the writer makes room.
+    ///
+    /// Nothing is lost, but logging stalls while the destination is slow.
+    #[default]
+    Block,
+    /// Drop the entry being logged, keeping those already queued.
+    DropNewest,
+    /// Drop the oldest queued entry to make room, keeping the most recent
+    /// ones.
+    DropOldest,
+}
+
+/// Why a message was not queued as it is.
+pub(crate) enum SendError {
+    /// The writer is gone, so the message was dropped.
+    Disconnected,
+    /// The queue was full, so an entry was dropped following the overflow
+    /// policy.
+    Overflowed,
+}
+
+/// Creates a queue holding at most `capacity` entries, or any number of them
+/// if `None`.
+///
+/// Only entries count towards the capacity; other messages, such as flush
+/// requests, are always queued.
+pub(crate) fn channel(capacity: Option<usize>, overflow: Overflow) -> (Sender, Receiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            messages: VecDeque::new(),
+            lines: 0,
+            senders: 1,
+            closed: false,
+        }),
+        // an entry must fit for dropping the oldest to make room
+        capacity: capacity.map(|capacity| capacity.max(1)),
+        overflow,
+        queued: Condvar::new(),
+        freed: Condvar::new(),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+struct Shared {
+    state: Mutex<State>,
+    capacity: Option<usize>,
+    overflow: Overflow,
+    /// Signalled when a message is queued or the last sender is dropped.
+    queued: Condvar,
+    /// Signalled when an entry is taken or the receiver is dropped.
+    freed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+struct State {
+    messages: VecDeque<Message>,
+    /// Number of entries in `messages`.
+    lines: usize,
+    senders: usize,
+    /// Whether the receiver is gone.
+    closed: bool,
+}
+
+/// Sending side of the queue, used by the logger front end and its handles.
+pub(crate) struct Sender {
+    shared: Arc<Shared>,
+}
+
+impl Sender {
+    /// Queues a message, handling a full queue as the overflow policy says.
+    pub(crate) fn send(&self, message: Message) -> Result<(), SendError> {
+        let shared = &*self.shared;
+        let mut state = shared.lock();
+        let mut result = Ok(());
+        if let (Message::Line(_), Some(capacity)) = (&message, shared.capacity) {
+            while !state.closed && state.lines >= capacity {
+                match shared.overflow {
+                    Overflow::Block => {
+                        state = shared.freed.wait(state).unwrap_or_else(|e| e.into_inner());
+                    }
+                    Overflow::DropNewest => return Err(SendError::Overflowed),
+                    Overflow::DropOldest => {
+                        let oldest = state
+                            .messages
+                            .iter()
+                            .position(|message| matches!(message, Message::Line(_)));
+                        state
+                            .messages
+                            .remove(oldest.expect("the queue holds entries"));
+                        state.lines -= 1;
+                        result = Err(SendError::Overflowed);
+                    }
+                }
+            }
+        }
+        if state.closed {
+            return Err(SendError::Disconnected);
+        }
+        if let Message::Line(_) = message {
+            state.lines += 1;
+        }
+        state.messages.push_back(message);
+        shared.queued.notify_one();
+        result
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.queued.notify_all();
+        }
+    }
+}
+
+/// Receiving side of the queue, used by the writer.
+pub(crate) struct Receiver {
+    shared: Arc<Shared>,
+}
+
+impl Receiver {
+    /// Waits for the next message, returning `None` once every sender is gone
+    /// and the queue is empty.
+    pub(crate) fn recv(&self) -> Option<Message> {
+        let shared = &*self.shared;
+        let mut state = shared.lock();
+        loop {
+            if let Some(message) = state.messages.pop_front() {
+                if let Message::Line(_) = message {
+                    state.lines -= 1;
+                    shared.freed.notify_one();
+                }
+                return Some(message);
+            }
+            if state.senders == 0 {
+                return None;
+            }
+            state = shared.queued.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        // dropping queued flush requests completes them
+        let messages = {
+            let mut state = self.shared.lock();
+            state.closed = true;
+            state.lines = 0;
+            std::mem::take(&mut state.messages)
+        };
+        self.shared.freed.notify_all();
+        drop(messages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(byte: u8) -> Message {
+        Message::Line(vec![byte])
+    }
+
+    /// Drains the queue, returning the first byte of each entry.
+    fn drain(sender: Sender, receiver: Receiver) -> Vec<u8> {
+        drop(sender);
+        std::iter::from_fn(|| receiver.recv())
+            .filter_map(|message| match message {
+                Message::Line(line) => Some(line[0]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Full queues drop the entry being sent or the oldest queued one.
+    #[test]
+    fn drops_on_overflow() {
+        let (sender, receiver) = channel(Some(2), Overflow::DropNewest);
+        for byte in 0..4 {
+            let overflowed = matches!(sender.send(line(byte)), Err(SendError::Overflowed));
+            assert_eq!(byte >= 2, overflowed);
+        }
+        assert_eq!(vec![0, 1], drain(sender, receiver));
+
+        let (sender, receiver) = channel(Some(2), Overflow::DropOldest);
+        for byte in 0..4 {
+            let overflowed = matches!(sender.send(line(byte)), Err(SendError::Overflowed));
+            assert_eq!(byte >= 2, overflowed);
+        }
+        assert!(sender.send(Message::Shutdown).is_ok());
+        assert_eq!(vec![2, 3], drain(sender, receiver));
+    }
+
+    /// Blocked senders continue once the receiver takes an entry.
+    #[test]
+    fn blocks_on_overflow() {
+        let (sender, receiver) = channel(Some(1), Overflow::Block);
+        assert!(sender.send(line(0)).is_ok());
+        let thread = std::thread::spawn(move || {
+            assert!(sender.send(line(1)).is_ok());
+            sender
+        });
+        assert!(matches!(receiver.recv(), Some(Message::Line(line)) if line == [0]));
+        let sender = thread.join().unwrap();
+        assert_eq!(vec![1], drain(sender, receiver));
+    }
+
+    /// Senders are told when the receiver is gone, including blocked ones.
+    #[test]
+    fn disconnects() {
+        let (sender, receiver) = channel(Some(1), Overflow::Block);
+        assert!(sender.send(line(0)).is_ok());
+        let thread = std::thread::spawn(move || sender.send(line(1)));
+        drop(receiver);
+        let result = thread.join().unwrap();
+        assert!(matches!(result, Err(SendError::Disconnected)));
+    }
+}
@@ -0,0 +1,54 @@
+//! Redaction of secrets from entries before they are written.
+
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+/// Rule rewriting a body or string field value, such as to replace tokens
+/// with a placeholder.
+pub(crate) type Rule = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Applies the configured redaction rules, in the order they were added.
+#[derive(Clone, Default)]
+pub(crate) struct Redactor {
+    rules: Vec<Rule>,
+}
+
+impl Redactor {
+    /// Adds a rule applied after the existing ones.
+    pub(crate) fn push(&mut self, rule: Rule) {
+        self.rules.push(rule);
+    }
+
+    /// Returns whether there are no rules, so nothing is redacted.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns the text with every rule applied.
+    pub(crate) fn redact(&self, text: String) -> String {
+        self.rules.iter().fold(text, |text, rule| rule(&text))
+    }
+
+    /// Applies every rule to the string values of the fields, including
+    /// ones nested in arrays and objects. Keys are left as they are.
+    pub(crate) fn redact_fields(&self, fields: &mut Map<String, Value>) {
+        if self.is_empty() {
+            return;
+        }
+        fields
+            .values_mut()
+            .for_each(|value| self.redact_value(value));
+    }
+
+    /// Applies every rule to the strings in the value.
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.redact(std::mem::take(text)),
+            Value::Array(values) => values.iter_mut().for_each(|value| self.redact_value(value)),
+            Value::Object(fields) => fields
+                .values_mut()
+                .for_each(|value| self.redact_value(value)),
+            _ => {}
+        }
+    }
+}
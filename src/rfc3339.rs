@@ -0,0 +1,152 @@
+//! RFC 3339 timestamps such as `2024-05-01T12:34:56.789000000Z`.
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Formats a time in UTC with nanoseconds, e.g.
+/// `2024-05-01T12:34:56.789000000Z`.
+///
+/// Times before the Unix epoch are formatted as the epoch.
+pub(crate) fn format(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let secs_of_day = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_nanos()
+    )
+}
+
+/// Parses a timestamp such as `2024-05-01T12:34:56.789+02:00`.
+///
+/// Times before the Unix epoch are not supported.
+pub(crate) fn parse(text: &str) -> Option<SystemTime> {
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = text.get(range)?;
+        digits
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| digits.parse().ok())?
+    };
+    let bytes = text.as_bytes();
+    if bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || bytes[13] != b':' {
+        return None;
+    }
+    let days = days_from_civil(number(0..4)?, number(5..7)?, number(8..10)?);
+    let mut seconds = days * 86_400 + number(11..13)? * 3600 + number(14..16)? * 60;
+    seconds += number(17..19)?;
+    let mut rest = text.get(19..)?;
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        let digits = &fraction[..len.min(9)];
+        nanos = digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32);
+        rest = &fraction[len..];
+    }
+    match rest {
+        "Z" | "z" => {}
+        _ => {
+            let sign = match rest.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let hours: i64 = rest.get(1..3)?.parse().ok()?;
+            let minutes: i64 = rest.get(4..6)?.parse().ok()?;
+            seconds -= sign * (hours * 3600 + minutes * 60);
+        }
+    }
+    Some(UNIX_EPOCH + Duration::new(u64::try_from(seconds).ok()?, nanos))
+}
+
+/// Returns the number of days from 1970-01-01 to the given date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Howard Hinnant's algorithm, with years starting in March
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the date that is the given number of days after 1970-01-01.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    // the inverse of days_from_civil
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Serde adapter for optional times as RFC 3339 strings.
+pub(crate) mod option {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        time: &Option<SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => serializer.serialize_str(&format(*time)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SystemTime>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(text) => parse(&text)
+                .map(Some)
+                .ok_or_else(|| de::Error::custom("invalid RFC 3339 timestamp")),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let at = |secs, nanos| Some(UNIX_EPOCH + Duration::new(secs, nanos));
+        assert_eq!(at(0, 0), parse("1970-01-01T00:00:00Z"));
+        assert_eq!(
+            at(1_714_566_896, 789_000_000),
+            parse("2024-05-01T12:34:56.789Z")
+        );
+        assert_eq!(at(1_714_566_896, 0), parse("2024-05-01T14:34:56+02:00"));
+        assert_eq!(None, parse("INFO"));
+        assert_eq!(None, parse("2024-05-01T12:34:56"));
+    }
+
+    #[test]
+    fn round_trips() {
+        let time = UNIX_EPOCH + Duration::new(1_714_566_896, 789_000_001);
+        assert_eq!("2024-05-01T12:34:56.789000001Z", format(time));
+        for secs in [0, 951_782_400, 4_107_542_399, 1_709_164_800] {
+            let time = UNIX_EPOCH + Duration::from_secs(secs);
+            assert_eq!(Some(time), parse(&format(time)));
+        }
+    }
+}
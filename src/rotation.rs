@@ -0,0 +1,487 @@
+//! Rotation of log files once they grow too large or get too old.
+//!
+//! The current file is always at the configured path. Rotated files are
+//! renamed next to it with an increasing index before the extensions, so
+//! `app.jsonl.gz` is rotated to `app.1.jsonl.gz`, then `app.2.jsonl.gz`, and
+//! so on, unless a template names them. Each file is a complete gzip stream.
+
+use crate::{dest::DestOptions, index::index_path, template::Template, LoggerBuilder};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// How long to keep writing to the current file after rotating it failed
+/// before trying again.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+impl LoggerBuilder {
+    /// Rotates the log file once its compressed size reaches `bytes`.
+    ///
+    /// Sizes are checked after each entry and include only data the
+    /// compressor has already emitted, so files may end up somewhat larger.
+    /// Rotation only applies to loggers writing to a path. If rotating
+    /// fails, such as when the file cannot be renamed, entries keep being
+    /// written to the current file and rotating is tried again a minute
+    /// later.
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.dest.max_size = Some(bytes);
+        self
+    }
+
+    /// Rotates the log file at every multiple of `period` since the Unix
+    /// epoch, e.g. every hour on the hour or every day at midnight UTC.
+    ///
+    /// A file is rotated when the first entry of a new period is logged, so
+    /// periods without entries do not get files.
+    /// Rotation only applies to loggers writing to a path.
+    pub fn rotate_every(mut self, period: Duration) -> Self {
+        self.dest.period = Some(period).filter(|period| !period.is_zero());
+        self
+    }
+
+    /// Deletes rotated files last modified more than `age` ago.
+    ///
+    /// Old files are deleted when logging starts and after each rotation.
+    /// Only applies together with [`max_file_size`](Self::max_file_size) or
+    /// [`rotate_every`](Self::rotate_every), and never deletes the current
+    /// file.
+    pub fn max_age(mut self, age: Duration) -> Self {
+        self.dest.max_age = Some(age);
+        self
+    }
+
+    /// Deletes the oldest rotated files while the current and rotated files
+    /// together, including their indices, would take up more than `bytes`.
+    ///
+    /// Room is kept for the current file to grow to
+    /// [`max_file_size`](Self::max_file_size), so with it the files stay
+    /// within the budget, give or take how much larger than that size files
+    /// end up. Files are deleted when logging starts and after each
+    /// rotation, so only applies together with `max_file_size` or
+    /// [`rotate_every`](Self::rotate_every), and never deletes the current
+    /// file. Files waiting for [compression](Self::compress_on_rotation)
+    /// count with their uncompressed size.
+    pub fn max_total_size(mut self, bytes: u64) -> Self {
+        self.dest.max_total_size = Some(bytes);
+        self
+    }
+
+    /// Names rotated files after `template` instead of inserting the index
+    /// before the extensions, such as `app.{date}.{seq}.jsonl.gz` for
+    /// `app.2024-02-29.7.jsonl.gz`.
+    ///
+    /// `{seq}` is the file's index and must appear exactly once. `{date}` is
+    /// the date the file was started, like `%Y-%m-%d`, and the strftime-style
+    /// `%Y`, `%m`, `%d`, `%H`, `%M` and `%S` are parts of the time it was
+    /// started, all in UTC; `%%` is a literal `%`. Indices keep increasing
+    /// across dates. Rotated files stay in the directory of the current
+    /// file, and only names matching the template are numbered after and
+    /// deleted by [`max_age`](Self::max_age).
+    ///
+    /// Creating the logger fails if the template is invalid.
+    ///
+    /// ```no_run
+    /// # use jsonl_gzip_logger::LoggerBuilder;
+    /// let _guard = LoggerBuilder::new()
+    ///     .max_file_size(64 << 20)
+    ///     .rotated_name("app.{date}.{seq}.jsonl.gz")
+    ///     .init("logs/app.jsonl.gz")?;
+    /// # Ok::<(), jsonl_gzip_logger::InitError>(())
+    /// ```
+    pub fn rotated_name(mut self, template: impl Into<String>) -> Self {
+        self.dest.rotated_name = Some(template.into());
+        self
+    }
+
+    /// Writes the current file uncompressed, so that it can be followed with
+    /// tools like `tail -f` and `grep`, and compresses each file on a
+    /// background thread once it is rotated.
+    ///
+    /// Rotated files are compressed in place, with the configured
+    /// [`codec`](Self::codec) or gzip at the configured
+    /// [`compression_level`](Self::compression_level), and are
+    /// uncompressed until then. [`read`](crate::read) reads them either
+    /// way. Dropping the logger waits for the files rotated so far to be
+    /// compressed. [`max_file_size`](Self::max_file_size) limits the
+    /// uncompressed size of files.
+    ///
+    /// As the current file keeps its path, give it one without `.gz` and
+    /// name rotated files with [`rotated_name`](Self::rotated_name).
+    /// Creating the logger fails if files are also indexed or encrypted.
+    ///
+    /// ```no_run
+    /// # use jsonl_gzip_logger::LoggerBuilder;
+    /// let _guard = LoggerBuilder::new()
+    ///     .max_file_size(256 << 20)
+    ///     .compress_on_rotation(true)
+    ///     .rotated_name("app.{date}.{seq}.jsonl.gz")
+    ///     .init("logs/app.jsonl")?;
+    /// # Ok::<(), jsonl_gzip_logger::InitError>(())
+    /// ```
+    pub fn compress_on_rotation(mut self, enabled: bool) -> Self {
+        self.dest.archiver = enabled.then(Default::default);
+        self
+    }
+
+    /// Keeps a link with `latest` before the extensions, such as
+    /// `app.latest.jsonl.gz` for `app.jsonl.gz`, pointing at the most
+    /// recently rotated file, so that tools and people do not have to work
+    /// out which of the [named](Self::rotated_name) files is the newest.
+    ///
+    /// The link is a relative symbolic link, updated whenever a file is
+    /// rotated. Where symbolic links cannot be created, such as on Windows
+    /// without privileges, it falls back to a hard link, and then to a copy.
+    /// Failing to update the link does not stop logging.
+    pub fn latest_link(mut self, enabled: bool) -> Self {
+        self.dest.latest_link = enabled;
+        self
+    }
+}
+
+/// Rotation state of a destination writing to a path.
+pub(crate) struct Rotator {
+    path: PathBuf,
+    options: DestOptions,
+    /// Bytes written to the current file.
+    written: Arc<AtomicU64>,
+    /// Template naming rotated files, if they are not named after the index.
+    template: Option<Template>,
+    /// When the current file was started.
+    started: SystemTime,
+    /// Index the current file gets when rotated.
+    next_index: u64,
+    /// End of the period the current file covers.
+    period_end: Option<SystemTime>,
+    /// Until when rotating is put off after it failed.
+    retry_at: Option<Instant>,
+}
+
+impl Rotator {
+    /// Starts tracking the file just created at `path`, counting the bytes
+    /// passed through `written` and naming it after `template` once rotated.
+    pub(crate) fn new(
+        path: &Path,
+        options: &DestOptions,
+        written: Arc<AtomicU64>,
+        template: Option<Template>,
+    ) -> Self {
+        if options.rotates() {
+            prune(path, options, template.as_ref());
+        }
+        let newest = newest(path, template.as_ref());
+        if let Some((_, newest)) = newest.as_ref().filter(|_| options.latest_link) {
+            // the link is a convenience, so logging continues without it
+            let _ = link_latest(path, newest);
+        }
+        Self {
+            path: path.to_path_buf(),
+            options: options.clone(),
+            written,
+            next_index: newest.map_or(0, |(index, _)| index) + 1,
+            template,
+            started: SystemTime::now(),
+            period_end: options
+                .period
+                .map(|period| period_end(SystemTime::now(), period)),
+            retry_at: None,
+        }
+    }
+
+    /// Returns whether the current file should be rotated before writing
+    /// another entry.
+    pub(crate) fn due(&self) -> bool {
+        if self
+            .retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+        {
+            return false;
+        }
+        let full = self
+            .options
+            .max_size
+            .is_some_and(|max| self.written.load(Ordering::Relaxed) >= max);
+        full || self.period_end.is_some_and(|end| SystemTime::now() >= end)
+    }
+
+    /// Puts off rotating after it failed, so that entries keep being
+    /// written to the current file in the meantime.
+    pub(crate) fn postpone(&mut self) {
+        self.retry_at = Some(Instant::now() + RETRY_INTERVAL);
+    }
+
+    /// Returns the path of the current file.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path and options for creating a file replacing the
+    /// current one, once it was moved out of the way.
+    pub(crate) fn reopen(&self) -> (&Path, &DestOptions) {
+        (&self.path, &self.options)
+    }
+
+    /// Renames the current file out of the way and returns the path it was
+    /// renamed to.
+    pub(crate) fn rotate(&self) -> io::Result<PathBuf> {
+        let rotated = match &self.template {
+            Some(template) => self
+                .path
+                .with_file_name(template.name(self.next_index, self.started)),
+            None => rotated_path(&self.path, self.next_index),
+        };
+        fs::rename(&self.path, &rotated)?;
+        if self.options.index {
+            match fs::rename(index_path(&self.path), index_path(&rotated)) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                _ => {}
+            }
+        }
+        Ok(rotated)
+    }
+}
+
+/// Points the latest link of the file at `path` at `newest`, a file rotated
+/// from it, replacing the link atomically.
+fn link_latest(path: &Path, newest: &Path) -> io::Result<()> {
+    let link = crate::dest::tagged_path(path, "latest");
+    let temporary = crate::dest::tagged_path(path, "latest.tmp");
+    let _ = fs::remove_file(&temporary);
+    // rotated files are next to the file they were rotated from
+    let target = Path::new(newest.file_name().unwrap_or_default());
+    symlink(target, &temporary)
+        .or_else(|_| fs::hard_link(newest, &temporary))
+        .or_else(|_| fs::copy(newest, &temporary).map(drop))?;
+    fs::rename(temporary, link)
+}
+
+/// Creates a symbolic link at `link` pointing at the file `target`.
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (target, link);
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+/// Deletes the files rotated from `path` that are older than the options
+/// allow, then the oldest ones while they take up more than allowed.
+fn prune(path: &Path, options: &DestOptions, template: Option<&Template>) {
+    if options.max_age.is_none() && options.max_total_size.is_none() {
+        return;
+    }
+    let mut rotated = rotated_files(path, template);
+    if let Some(max_age) = options.max_age {
+        rotated.retain(|(_, rotated)| {
+            let expired = fs::metadata(rotated)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > max_age));
+            if expired {
+                remove(rotated);
+            }
+            !expired
+        });
+    }
+    let Some(max_total_size) = options.max_total_size else {
+        return;
+    };
+    rotated.sort();
+    let sizes: Vec<_> = rotated.iter().map(|(_, rotated)| size(rotated)).collect();
+    // room for the current file to grow into
+    let current = options.max_size.unwrap_or(0).max(size(path));
+    let mut total = sizes
+        .iter()
+        .fold(current, |total, &size| total.saturating_add(size));
+    for ((_, rotated), size) in rotated.iter().zip(sizes) {
+        if total <= max_total_size {
+            break;
+        }
+        total -= size;
+        remove(rotated);
+    }
+}
+
+/// Returns the bytes taken up by the file at `path` and its index, if any.
+fn size(path: &Path) -> u64 {
+    [path.to_path_buf(), index_path(path)]
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Deletes a rotated file and its index.
+fn remove(rotated: &Path) {
+    // another process may have deleted them already
+    let _ = fs::remove_file(index_path(rotated));
+    let _ = fs::remove_file(rotated);
+}
+
+/// Returns the end of the period containing `now`, periods being multiples
+/// of `period` since the Unix epoch.
+fn period_end(now: SystemTime, period: Duration) -> SystemTime {
+    let since_epoch = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let period = period.as_nanos();
+    let end = (since_epoch / period + 1) * period;
+    UNIX_EPOCH + Duration::from_nanos(end.try_into().unwrap_or(u64::MAX))
+}
+
+/// Writer counting the bytes written through it.
+pub(crate) struct Counting<W> {
+    pub(crate) inner: W,
+    pub(crate) written: Arc<AtomicU64>,
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.written.fetch_add(len as u64, Ordering::Relaxed);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns the path a file is renamed to when rotated with `index`.
+pub(crate) fn rotated_path(path: &Path, index: u64) -> PathBuf {
+    crate::dest::tagged_path(path, index)
+}
+
+/// Returns the file already rotated from `path` with the highest index, and
+/// that index.
+fn newest(path: &Path, template: Option<&Template>) -> Option<(u64, PathBuf)> {
+    rotated_files(path, template).into_iter().max()
+}
+
+/// Returns the files rotated from `path` with their indices, named after
+/// `template` if there is one.
+pub(crate) fn rotated_files(path: &Path, template: Option<&Template>) -> Vec<(u64, PathBuf)> {
+    let Some(name) = path.file_name().map(|name| name.to_string_lossy()) else {
+        return Vec::new();
+    };
+    let (stem, extensions) = name.split_once('.').unwrap_or((&name, ""));
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_name = entry.file_name();
+            if let Some(template) = template {
+                return Some((template.seq(file_name.to_str()?)?, entry.path()));
+            }
+            let rest = file_name.to_str()?.strip_prefix(stem)?.strip_prefix('.')?;
+            let index = match rest.split_once('.') {
+                Some((index, rest)) if rest == extensions => index,
+                None if extensions.is_empty() => rest,
+                _ => return None,
+            };
+            // only plain decimal indices, not e.g. `+1` or `01`
+            if index.is_empty()
+                || index.starts_with('0')
+                || !index.bytes().all(|b| b.is_ascii_digit())
+            {
+                return None;
+            }
+            Some((index.parse().ok()?, entry.path()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotated_names() {
+        assert_eq!(
+            Path::new("logs/app.3.jsonl.gz"),
+            rotated_path(Path::new("logs/app.jsonl.gz"), 3)
+        );
+        assert_eq!(Path::new("app.1"), rotated_path(Path::new("app"), 1));
+    }
+
+    #[test]
+    fn periods() {
+        let hour = Duration::from_secs(3600);
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(at(7200), period_end(at(3600), hour));
+        assert_eq!(at(7200), period_end(at(7199), hour));
+        assert_eq!(at(86_400), period_end(at(1), Duration::from_secs(86_400)));
+    }
+
+    #[test]
+    fn prunes_to_total_size() {
+        let dir = std::env::temp_dir().join("jsonl_gzip_logger_prunes_to_total_size");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.jsonl.gz");
+        fs::write(&path, [0; 100]).unwrap();
+        for index in 1..=5 {
+            fs::write(rotated_path(&path, index), [0; 100]).unwrap();
+        }
+        fs::write(index_path(rotated_path(&path, 3)), [0; 50]).unwrap();
+        let options = DestOptions {
+            max_total_size: Some(450),
+            max_size: Some(150),
+            ..DestOptions::default()
+        };
+        prune(&path, &options, None);
+        let mut indices: Vec<_> = rotated_files(&path, None)
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect();
+        indices.sort();
+        assert_eq!(vec![4, 5], indices);
+        assert!(!index_path(rotated_path(&path, 3)).exists());
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn finds_rotated_files() {
+        let dir = std::env::temp_dir().join("jsonl_gzip_logger_rotated_files");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "app.jsonl.gz",
+            "app.1.jsonl.gz",
+            "app.12.jsonl.gz",
+            "app.01.jsonl.gz",
+            "app.2.jsonl",
+            "other.3.jsonl.gz",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let path = dir.join("app.jsonl.gz");
+        let mut indices: Vec<_> = rotated_files(&path, None)
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect();
+        indices.sort();
+        assert_eq!(vec![1, 12], indices);
+        assert_eq!(Some((12, dir.join("app.12.jsonl.gz"))), newest(&path, None));
+    }
+}
@@ -0,0 +1,442 @@
+//! Logging through a shared-memory ring persisted by a separate process.
+//!
+//! The application logs into a ring buffer in a memory-mapped file (put it on
+//! a tmpfs such as `/dev/shm` to keep it in memory), and a companion flusher
+//! process compresses the entries and writes them to the log file. An entry
+//! is published to the ring only once it is complete, so even if the
+//! application is killed with SIGKILL every entry logged before that is still
+//! picked up by the flusher.
+//!
+//! ```no_run
+//! # use jsonl_gzip_logger::LoggerBuilder;
+//! // in the application, the flusher stops once the guard is dropped
+//! let _guard = LoggerBuilder::new()
+//!     .init_shm_ring("/dev/shm/app.ring", 1 << 20)
+//!     .unwrap();
+//!
+//! // in the companion process, returns once the application is gone
+//! LoggerBuilder::new()
+//!     .flush_shm_ring("/dev/shm/app.ring", "app.jsonl.gz")
+//!     .unwrap();
+//! ```
+//!
+//! The ring has a single writer. Entries logged while it is full are dropped
+//! and counted.
+
+use crate::{facade, filter::Filter, format::Format, limit::DropCause, InitError, LoggerBuilder};
+use memmap2::MmapMut;
+use std::{
+    fs::OpenOptions,
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Magic bytes at the start of a ring file.
+const MAGIC: &[u8; 4] = b"JGLR";
+/// Version of the ring file format.
+const VERSION: u8 = 1;
+/// Length of the header before the ring's data.
+const HEADER_LEN: usize = 64;
+/// Offset of the total number of bytes ever written.
+const WRITTEN: usize = 8;
+/// Offset of the total number of bytes ever read.
+const READ: usize = 16;
+/// Offset of the number of entries dropped because the ring was full.
+const DROPPED: usize = 24;
+/// Offset of the flag set once the writer has stopped logging.
+const CLOSED: usize = 32;
+/// Offset of the process ID of the writer.
+const PID: usize = 40;
+/// Offset of the capacity of the ring's data.
+const CAPACITY: usize = 48;
+/// How often the flusher checks the ring for new entries.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Ring buffer of length-prefixed entries in a memory-mapped file.
+struct Ring {
+    _map: MmapMut,
+    base: *mut u8,
+    capacity: u64,
+}
+
+// SAFETY: the mapping lives as long as the ring and all shared state is
+// accessed through atomics, with data only accessed by its single producer
+// or consumer
+unsafe impl Send for Ring {}
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    /// Creates a ring file with room for `capacity` bytes of entries.
+    fn create(path: &Path, capacity: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len((HEADER_LEN + capacity) as u64)?;
+        // SAFETY: the file was just created by this process, and other
+        // processes only access it through the ring's protocol
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[..4].copy_from_slice(MAGIC);
+        map[4] = VERSION;
+        let ring = Self::new(map);
+        ring.counter(PID)
+            .store(u64::from(std::process::id()), Ordering::Relaxed);
+        ring.counter(CAPACITY)
+            .store(capacity as u64, Ordering::Release);
+        Ok(Self {
+            capacity: capacity as u64,
+            ..ring
+        })
+    }
+
+    /// Opens an existing ring file.
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        // SAFETY: see `create`
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        if map.len() < HEADER_LEN || &map[..4] != MAGIC || map[4] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a log ring file",
+            ));
+        }
+        let ring = Self::new(map);
+        let capacity = ring.counter(CAPACITY).load(Ordering::Acquire);
+        if capacity as usize != ring._map.len() - HEADER_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "log ring file has the wrong size",
+            ));
+        }
+        Ok(Self { capacity, ..ring })
+    }
+
+    fn new(mut map: MmapMut) -> Self {
+        let base = map.as_mut_ptr();
+        Self {
+            _map: map,
+            base,
+            capacity: 0,
+        }
+    }
+
+    /// Returns the shared counter at the given header offset.
+    fn counter(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: header offsets are 8 byte aligned within the page aligned
+        // mapping, and the counters are only accessed atomically
+        unsafe { &*(self.base.add(offset) as *const AtomicU64) }
+    }
+
+    /// Copies bytes into the ring's data at the given total position.
+    fn copy_in(&self, position: u64, bytes: &[u8]) {
+        let start = (position % self.capacity) as usize;
+        let first = bytes.len().min(self.capacity as usize - start);
+        // SAFETY: both copies stay within the data, which is only written
+        // by the single producer in the region the consumer has released
+        unsafe {
+            let data = self.base.add(HEADER_LEN);
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), data.add(start), first);
+            std::ptr::copy_nonoverlapping(bytes[first..].as_ptr(), data, bytes.len() - first);
+        }
+    }
+
+    /// Copies bytes out of the ring's data at the given total position.
+    fn copy_out(&self, position: u64, bytes: &mut [u8]) {
+        let start = (position % self.capacity) as usize;
+        let first = bytes.len().min(self.capacity as usize - start);
+        // SAFETY: both copies stay within the data, in the region the
+        // producer has published
+        unsafe {
+            let data = self.base.add(HEADER_LEN);
+            std::ptr::copy_nonoverlapping(data.add(start), bytes.as_mut_ptr(), first);
+            std::ptr::copy_nonoverlapping(data, bytes[first..].as_mut_ptr(), bytes.len() - first);
+        }
+    }
+
+    /// Appends an entry, returning false if it was dropped for lack of room.
+    fn push(&self, entry: &[u8]) -> bool {
+        let written = self.counter(WRITTEN).load(Ordering::Relaxed);
+        let read = self.counter(READ).load(Ordering::Acquire);
+        let len = 4 + entry.len() as u64;
+        if written - read + len > self.capacity {
+            self.counter(DROPPED).fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        self.copy_in(written, &(entry.len() as u32).to_le_bytes());
+        self.copy_in(written + 4, entry);
+        // publishing only complete entries is what makes this crash safe
+        self.counter(WRITTEN)
+            .store(written + len, Ordering::Release);
+        true
+    }
+
+    /// Takes the oldest entry into `entry`, returning false if there is none.
+    ///
+    /// Fails if the ring's counters or the entry's length are corrupt, as the
+    /// file may have been changed by anyone.
+    fn pop(&self, entry: &mut Vec<u8>) -> io::Result<bool> {
+        let read = self.counter(READ).load(Ordering::Relaxed);
+        let written = self.counter(WRITTEN).load(Ordering::Acquire);
+        let available = match written.checked_sub(read) {
+            Some(0) => return Ok(false),
+            Some(available) if available <= self.capacity => available,
+            _ => return Err(corrupt()),
+        };
+        if available < 4 {
+            return Err(corrupt());
+        }
+        let mut len = [0; 4];
+        self.copy_out(read, &mut len);
+        let len = u64::from(u32::from_le_bytes(len));
+        if 4 + len > available {
+            return Err(corrupt());
+        }
+        entry.resize(len as usize, 0);
+        self.copy_out(read + 4, entry);
+        self.counter(READ).store(read + 4 + len, Ordering::Release);
+        Ok(true)
+    }
+
+    /// Returns whether the writer is done and has nothing left to read.
+    fn finished(&self) -> bool {
+        let drained = self.counter(READ).load(Ordering::Relaxed)
+            == self.counter(WRITTEN).load(Ordering::Acquire);
+        let closed = self.counter(CLOSED).load(Ordering::Acquire) != 0;
+        drained && (closed || !alive(self.counter(PID).load(Ordering::Relaxed)))
+    }
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "log ring file is corrupt")
+}
+
+/// Returns whether the process with the given ID is still running.
+///
+/// Only known on Linux; elsewhere the writer must close the ring.
+fn alive(pid: u64) -> bool {
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
+
+/// Logger that writes its entries into a shared-memory ring.
+struct RingLogger {
+    filter: Filter,
+    format: Format,
+    ring: Arc<Mutex<Ring>>,
+    /// The ring's single writer, see [`ForkPolicy`](crate::ForkPolicy).
+    pid: u32,
+}
+
+impl log::Log for RingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // a forked child writing too would break the single-writer ring
+        self.filter.enabled(metadata) && crate::fork::current_pid() == self.pid
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            self.format.entries(record, |line| {
+                if let Ok(ring) = self.ring.lock() {
+                    if !ring.push(&line) {
+                        self.format.drops.count(DropCause::Queue);
+                    }
+                }
+            });
+        }
+    }
+
+    // entries are visible to the flusher as soon as they are logged
+    fn flush(&self) {
+        if crate::fork::current_pid() != self.pid {
+            return;
+        }
+        self.format.pending(|line| {
+            if let Ok(ring) = self.ring.lock() {
+                if !ring.push(&line) {
+                    self.format.drops.count(DropCause::Queue);
+                }
+            }
+        });
+    }
+}
+
+impl Drop for RingLogger {
+    fn drop(&mut self) {
+        close(&self.ring, self.pid);
+    }
+}
+
+/// Tells the flusher that no more entries will be written, unless called in a
+/// forked child, whose exit must not stop the parent's logging.
+fn close(ring: &Mutex<Ring>, pid: u32) {
+    if crate::fork::current_pid() != pid {
+        return;
+    }
+    let ring = ring.lock().unwrap_or_else(|error| error.into_inner());
+    ring.counter(CLOSED).store(1, Ordering::Release);
+}
+
+/// Guard that uninstalls the logger and closes its ring when dropped, after
+/// which the flusher stops once it has written every entry.
+#[must_use = "dropping the guard immediately stops logging"]
+pub struct ShmGuard {
+    ring: Arc<Mutex<Ring>>,
+    /// Generation of the logger in the facade.
+    generation: u64,
+    /// The ring's single writer.
+    pid: u32,
+}
+
+impl Drop for ShmGuard {
+    fn drop(&mut self) {
+        facade::uninstall(Some(self.generation));
+        close(&self.ring, self.pid);
+    }
+}
+
+impl LoggerBuilder {
+    /// Creates and installs a global logger that writes into a new ring file
+    /// at `ring`, with room for `capacity` bytes of queued entries.
+    ///
+    /// Entries reach the log file only once a flusher is running, see
+    /// [`flush_shm_ring`](LoggerBuilder::flush_shm_ring). Destination options
+    /// such as encryption are configured on the flusher's builder.
+    ///
+    /// Dropping the returned guard uninstalls the logger and closes the ring.
+    pub fn init_shm_ring<P: AsRef<Path>>(
+        self,
+        ring: P,
+        capacity: usize,
+    ) -> Result<ShmGuard, InitError> {
+        facade::ensure_uninstalled()?;
+        let ring = Arc::new(Mutex::new(Ring::create(ring.as_ref(), capacity)?));
+        let (filter, install) = (self.filter.clone(), self.install_options());
+        let pid = crate::fork::current_pid();
+        let logger = RingLogger {
+            filter,
+            format: self.format()?,
+            ring: ring.clone(),
+            pid,
+        };
+        let generation = facade::install(Box::new(logger), install)?;
+        Ok(ShmGuard {
+            ring,
+            generation,
+            pid,
+        })
+    }
+
+    /// Moves entries from the ring file at `ring` into a new .jsonl.gz file
+    /// at `path` until the writer has uninstalled its logger or exited and
+    /// every entry has been written.
+    ///
+    /// Returns the number of entries the writer dropped because the ring was
+    /// full. Entry options such as the level are set on the writer's builder.
+    pub fn flush_shm_ring<P: AsRef<Path>, Q: AsRef<Path>>(
+        self,
+        ring: P,
+        path: Q,
+    ) -> io::Result<u64> {
+        let ring = Ring::open(ring.as_ref())?;
+        let mut dest = self.create(path)?;
+        let mut entry = Vec::new();
+        loop {
+            // checked first so that entries published before the writer
+            // finished are still written below
+            let finished = ring.finished();
+            let mut wrote = false;
+            while ring.pop(&mut entry)? {
+                dest.write_entry(&entry)?;
+                wrote = true;
+            }
+            if wrote {
+                dest.flush()?;
+            }
+            if finished {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        dest.finish()?;
+        Ok(ring.counter(DROPPED).load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a ring in a temporary file.
+    fn ring(name: &str, capacity: usize) -> Ring {
+        let path = std::env::temp_dir().join(format!("jsonl_gzip_logger_{}.ring", name));
+        Ring::create(&path, capacity).unwrap()
+    }
+
+    /// Entries come out in order, including across the end of the data.
+    #[test]
+    fn wraps_around() {
+        let ring = ring("wraps_around", 32);
+        let mut entry = Vec::new();
+        for i in 0..20 {
+            let pushed = format!("entry {}", i);
+            assert!(ring.push(pushed.as_bytes()));
+            assert!(ring.pop(&mut entry).unwrap());
+            assert_eq!(pushed.as_bytes(), &entry[..]);
+        }
+        assert!(!ring.pop(&mut entry).unwrap());
+    }
+
+    /// Entries that do not fit are dropped and counted.
+    #[test]
+    fn drops_when_full() {
+        let ring = ring("drops_when_full", 32);
+        assert!(ring.push(&[1; 20]));
+        assert!(!ring.push(&[2; 20]));
+        assert_eq!(1, ring.counter(DROPPED).load(Ordering::Relaxed));
+        let mut entry = Vec::new();
+        assert!(ring.pop(&mut entry).unwrap());
+        assert!(ring.push(&[3; 20]));
+    }
+
+    /// Lengths and counters pointing outside of what was written are
+    /// rejected rather than read.
+    #[test]
+    fn rejects_corrupt_entries() {
+        let ring = ring("rejects_corrupt_entries", 32);
+        let mut entry = Vec::new();
+        ring.push(b"hello");
+        ring.copy_in(0, &u32::MAX.to_le_bytes());
+        let error = ring.pop(&mut entry).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        ring.counter(WRITTEN).store(1000, Ordering::Release);
+        assert!(ring.pop(&mut entry).is_err());
+        ring.counter(WRITTEN).store(2, Ordering::Release);
+        assert!(ring.pop(&mut entry).is_err());
+    }
+
+    /// A second mapping of the file sees the entries of the first.
+    #[test]
+    fn shared_between_mappings() {
+        let path = std::env::temp_dir().join("jsonl_gzip_logger_shared.ring");
+        let writer = Ring::create(&path, 64).unwrap();
+        let reader = Ring::open(&path).unwrap();
+        writer.push(b"hello");
+        let mut entry = Vec::new();
+        assert!(reader.pop(&mut entry).unwrap());
+        assert_eq!(b"hello", &entry[..]);
+        assert!(!reader.finished());
+        writer.counter(CLOSED).store(1, Ordering::Release);
+        assert!(reader.finished());
+    }
+}
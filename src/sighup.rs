@@ -0,0 +1,64 @@
+//! Rotating the log file when the process receives `SIGHUP`, so that
+//! logrotate and similar tools can manage files written by this crate.
+//!
+//! Configure logrotate to rename the file and then send `SIGHUP`, for
+//! example with `postrotate kill -HUP $(cat app.pid) endscript`. The logger
+//! finishes the renamed file and continues in a new one at the original
+//! path. Without logrotate, `SIGHUP` rotates the file as
+//! [`LoggerHandle::rotate`] does.
+
+use crate::LoggerHandle;
+use signal_hook::{
+    consts::SIGHUP,
+    iterator::{backend::Handle, Signals},
+};
+use std::{
+    io,
+    thread::{self, JoinHandle},
+};
+
+impl LoggerHandle {
+    /// Rotates the logger's file whenever the process receives `SIGHUP`,
+    /// until the returned guard is dropped.
+    ///
+    /// The signal handler only wakes a background thread, which does the
+    /// rotating. Errors are passed to the logger's error hook and recorded
+    /// in its [`health`](Self::health), and the file is rotated again on
+    /// the next signal.
+    pub fn rotate_on_sighup(&self) -> io::Result<SighupGuard> {
+        let mut signals = Signals::new([SIGHUP])?;
+        let close = signals.handle();
+        let handle = self.clone();
+        let thread = thread::Builder::new()
+            .name("jsonl_gzip_logger sighup".to_string())
+            .spawn(move || {
+                for _ in signals.forever() {
+                    // failures are recorded by the destination
+                    let _ = handle.rotate();
+                }
+            })?;
+        Ok(SighupGuard {
+            close,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Guard that stops rotating on `SIGHUP` when dropped.
+///
+/// `SIGHUP` stays handled afterwards, so that it no longer terminates the
+/// process, but is ignored.
+#[must_use = "dropping the guard immediately stops rotating on SIGHUP"]
+pub struct SighupGuard {
+    close: Handle,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for SighupGuard {
+    fn drop(&mut self) {
+        self.close.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Writing `slog` records to the log, for services built on `slog` that want
+//! this crate's file format.
+//!
+//! [`JsonlGzipDrain`] turns each record into an entry for whichever logger
+//! is installed, like any other entry:
+//!
+//! ```no_run
+//! use jsonl_gzip_logger::slog::JsonlGzipDrain;
+//! use log::LevelFilter;
+//! use slog::{info, o, Drain, Logger};
+//!
+//! let _guard = jsonl_gzip_logger::init("app.jsonl.gz", LevelFilter::Info)?;
+//! let root = Logger::root(JsonlGzipDrain::new().fuse(), o!("service" => "billing"));
+//! info!(root, "charged"; "amount" => 1250, "currency" => "EUR");
+//! # Ok::<(), jsonl_gzip_logger::InitError>(())
+//! ```
+//!
+//! The record above is written with the body `charged` and the fields
+//! `{"service": "billing", "amount": 1250, "currency": "EUR"}`.
+
+use crate::kv::Fields;
+use ::slog::{Drain, Key, Never, OwnedKVList, Record, Serializer, KV};
+use log::Level;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Drain writing `slog` records as entries of the installed logger.
+///
+/// The key-value pairs of the record and of the loggers it was logged
+/// through become the entry's fields, with the record's taking precedence.
+/// Records are logged with their tag as the target, or their module if they
+/// have none, and filtered by the installed logger's level and filter, like
+/// entries logged with `log`. Critical records are written as errors.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonlGzipDrain {
+    _private: (),
+}
+
+impl JsonlGzipDrain {
+    /// Creates a drain writing to the installed logger.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Drain for JsonlGzipDrain {
+    type Ok = ();
+    type Err = Never;
+
+    fn log(&self, record: &Record<'_>, values: &OwnedKVList) -> Result<(), Never> {
+        let level = level(record.level());
+        let target = match record.tag() {
+            "" => record.module(),
+            tag => tag,
+        };
+        let logger = log::logger();
+        let enabled = level <= log::max_level()
+            && logger.enabled(&log::Metadata::builder().level(level).target(target).build());
+        if !enabled {
+            return Ok(());
+        }
+        let mut collect = Collect::default();
+        // collecting never fails, and the record's pairs go last to win
+        let _ = values.serialize(record, &mut collect);
+        let _ = record.kv().serialize(record, &mut collect);
+        logger.log(
+            &log::Record::builder()
+                .level(level)
+                .target(target)
+                .args(*record.msg())
+                .module_path(Some(record.module()))
+                .file(Some(record.file()))
+                .line(Some(record.line()))
+                .key_values(&Fields(&collect.0))
+                .build(),
+        );
+        Ok(())
+    }
+
+    fn is_enabled(&self, level: ::slog::Level) -> bool {
+        self::level(level) <= log::max_level()
+    }
+}
+
+/// Returns the `log` level of a `slog` level.
+fn level(level: ::slog::Level) -> Level {
+    match level {
+        ::slog::Level::Critical | ::slog::Level::Error => Level::Error,
+        ::slog::Level::Warning => Level::Warn,
+        ::slog::Level::Info => Level::Info,
+        ::slog::Level::Debug => Level::Debug,
+        ::slog::Level::Trace => Level::Trace,
+    }
+}
+
+/// Serializer collecting pairs as JSON values, keeping numbers, booleans and
+/// strings as they are and formatting everything else as text.
+#[derive(Default)]
+struct Collect(Map<String, Value>);
+
+impl Collect {
+    fn insert(&mut self, key: Key, value: impl Into<Value>) -> ::slog::Result {
+        self.0.insert(key.to_string(), value.into());
+        Ok(())
+    }
+}
+
+/// Implements `emit_*` methods inserting the value as it is.
+macro_rules! emit {
+    ($($method:ident: $type:ty),* $(,)?) => {
+        $(
+            fn $method(&mut self, key: Key, value: $type) -> ::slog::Result {
+                self.insert(key, value)
+            }
+        )*
+    };
+}
+
+impl Serializer for Collect {
+    emit! {
+        emit_usize: usize,
+        emit_isize: isize,
+        emit_bool: bool,
+        emit_u8: u8,
+        emit_i8: i8,
+        emit_u16: u16,
+        emit_i16: i16,
+        emit_u32: u32,
+        emit_i32: i32,
+        emit_f32: f32,
+        emit_u64: u64,
+        emit_i64: i64,
+        emit_f64: f64,
+        emit_str: &str,
+    }
+
+    fn emit_unit(&mut self, key: Key) -> ::slog::Result {
+        self.insert(key, Value::Null)
+    }
+
+    fn emit_none(&mut self, key: Key) -> ::slog::Result {
+        self.insert(key, Value::Null)
+    }
+
+    fn emit_arguments(&mut self, key: Key, value: &fmt::Arguments<'_>) -> ::slog::Result {
+        self.insert(key, value.to_string())
+    }
+}
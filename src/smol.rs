@@ -0,0 +1,34 @@
+//! Non-blocking logging for applications running on smol.
+
+use crate::{facade, worker, InitError, LoggerBuilder};
+use log::LevelFilter;
+
+pub use crate::worker::{Durability, Flushed, WorkerGuard};
+pub use crate::LoggerHandle;
+
+/// Creates and installs a global logger that logs to a new .jsonl.gz file at
+/// the given path without blocking the logging task.
+///
+/// Compression and file IO happen on smol's blocking thread pool, so async
+/// tasks only pay for formatting the entry. The returned [`WorkerGuard`]
+/// finishes the file when dropped and must outlive all logging.
+pub fn init<P: AsRef<std::path::Path>>(
+    path: P,
+    level: LevelFilter,
+) -> Result<WorkerGuard, InitError> {
+    LoggerBuilder::new().level(level).init_smol(path)
+}
+
+impl LoggerBuilder {
+    /// Like [`smol::init`](init), but with this builder's configuration.
+    pub fn init_smol<P: AsRef<std::path::Path>>(self, path: P) -> Result<WorkerGuard, InitError> {
+        facade::ensure_uninstalled()?;
+        let dest = self.create(path)?;
+        let (filter, install, queue) = (self.filter.clone(), self.install_options(), self.queue);
+        let (logger, guard, worker) = worker::channel(dest, filter, self.format()?, queue);
+        // start the writer first so that an installed logger always has one
+        ::smol::unblock(worker).detach();
+        let generation = facade::install(Box::new(logger), install)?;
+        Ok(guard.installed(generation))
+    }
+}
@@ -0,0 +1,432 @@
+//! Streaming entries to a remote collector over TCP.
+//!
+//! Entries are sent as JSON lines, one per entry, from a background thread.
+//! While the collector cannot be reached entries are appended to an optional
+//! spool file, which is replayed once the connection comes back, and entries
+//! that fail to be written are spooled too. Delivery is at least once:
+//! entries spooled during a replay that fails part way are sent again, and
+//! entries the OS accepted before noticing a broken connection can be lost.
+//! Entries that do not fit in the spool, or with no spool, are dropped and
+//! counted.
+
+use crate::{
+    facade,
+    filter::Filter,
+    format::Format,
+    limit::{DropCause, Drops},
+    InitError, LoggerBuilder,
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Shortest time between reconnection attempts.
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Longest time connecting to or writing to one address of the collector
+/// may take, so that an unresponsive collector does not stall flushes.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many bytes of lines are buffered before they are written out.
+const BUFFER_LEN: usize = 8 * 1024;
+
+/// Opens a connection to the collector.
+type Connector = Box<dyn FnMut() -> io::Result<Box<dyn Write + Send>> + Send>;
+
+/// Configuration of where and how entries are sent over TCP.
+pub struct TcpSink {
+    connector: Connector,
+    spool: Option<PathBuf>,
+    max_spool_size: u64,
+    max_backoff: Duration,
+}
+
+impl TcpSink {
+    /// Creates a sink sending to the given address, such as
+    /// `"collector.local:5170"`.
+    ///
+    /// Each address the name resolves to is tried in turn, giving up on it
+    /// after 5 seconds, and writes time out after as long.
+    pub fn new(address: impl Into<String>) -> Self {
+        let address = address.into();
+        Self::with_connector(move || {
+            let stream = connect(&address)?;
+            stream.set_nodelay(true)?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+            Ok(Box::new(stream) as Box<dyn Write + Send>)
+        })
+    }
+
+    /// Creates a sink that opens connections with the given function.
+    ///
+    /// This allows wrapping the connection, for example in TLS.
+    pub fn with_connector<F>(connector: F) -> Self
+    where
+        F: FnMut() -> io::Result<Box<dyn Write + Send>> + Send + 'static,
+    {
+        Self {
+            connector: Box::new(connector),
+            spool: None,
+            max_spool_size: 64 * 1024 * 1024,
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+
+    /// Appends entries to the file at the given path while disconnected,
+    /// instead of dropping them.
+    pub fn spool(mut self, path: impl Into<PathBuf>) -> Self {
+        self.spool = Some(path.into());
+        self
+    }
+
+    /// Sets how many bytes the spool may hold, 64 MiB by default. Entries
+    /// that do not fit are dropped.
+    pub fn max_spool_size(mut self, bytes: u64) -> Self {
+        self.max_spool_size = bytes;
+        self
+    }
+
+    /// Sets the longest time between reconnection attempts.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+}
+
+/// Connects to the first address the name resolves to that accepts a
+/// connection in time.
+fn connect(address: &str) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        let message = format!("{address:?} resolved to no addresses");
+        io::Error::new(io::ErrorKind::InvalidInput, message)
+    }))
+}
+
+/// Message sent from the logger to the sending thread.
+enum Message {
+    /// A serialized entry, including the trailing newline.
+    Line(Vec<u8>),
+    /// Flush the connection, then acknowledge.
+    Flush(SyncSender<()>),
+}
+
+/// Logger that hands entries to the sending thread.
+struct TcpLogger {
+    filter: Filter,
+    format: Format,
+    sender: Sender<Message>,
+    /// The process owning the sending thread, see
+    /// [`ForkPolicy`](crate::ForkPolicy).
+    pid: u32,
+}
+
+impl log::Log for TcpLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // the sending thread does not exist in forked children
+        self.filter.enabled(metadata) && crate::fork::current_pid() == self.pid
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            self.format.entries(record, |mut line| {
+                line.push(b'\n');
+                let _ = self.sender.send(Message::Line(line));
+            });
+        }
+    }
+
+    fn flush(&self) {
+        if crate::fork::current_pid() != self.pid {
+            return;
+        }
+        self.format.pending(|mut line| {
+            line.push(b'\n');
+            let _ = self.sender.send(Message::Line(line));
+        });
+        let (ack, done) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Flush(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+}
+
+/// Guard that stops the sending thread when dropped, after sending or
+/// spooling every queued entry.
+#[must_use = "dropping the guard immediately stops logging"]
+pub struct TcpGuard {
+    thread: Option<JoinHandle<()>>,
+    /// Generation of the logger in the facade.
+    generation: u64,
+    pid: u32,
+}
+
+impl Drop for TcpGuard {
+    fn drop(&mut self) {
+        // the thread stops once the logger, and with it the sender, is gone
+        facade::uninstall(Some(self.generation));
+        // forked children must not wait for a thread they do not have
+        if let Some(thread) = self
+            .thread
+            .take()
+            .filter(|_| crate::fork::current_pid() == self.pid)
+        {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl LoggerBuilder {
+    /// Creates and installs a global logger that streams its entries to a
+    /// remote collector.
+    ///
+    /// Dropping the returned guard uninstalls the logger.
+    pub fn init_tcp(self, sink: TcpSink) -> Result<TcpGuard, InitError> {
+        facade::ensure_uninstalled()?;
+        let spool = match &sink.spool {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .read(true)
+                    .append(true)
+                    .create(true)
+                    .open(path)?;
+                Some(Spool {
+                    len: file.metadata()?.len(),
+                    file,
+                    max_len: sink.max_spool_size,
+                })
+            }
+            None => None,
+        };
+        let (sender, receiver) = mpsc::channel();
+        let (filter, install) = (self.filter.clone(), self.install_options());
+        let pid = crate::fork::current_pid();
+        let logger = TcpLogger {
+            filter,
+            format: self.format()?,
+            sender,
+            pid,
+        };
+        let mut link = Link {
+            sink,
+            drops: logger.format.drops.clone(),
+            spool,
+            connection: None,
+            buffer: Vec::new(),
+            backoff: MIN_BACKOFF,
+            next_attempt: Instant::now(),
+        };
+        let thread = thread::spawn(move || link.run(receiver));
+        match facade::install(Box::new(logger), install) {
+            Ok(generation) => Ok(TcpGuard {
+                thread: Some(thread),
+                generation,
+                pid,
+            }),
+            Err(error) => {
+                // the thread stops as the logger was dropped
+                let _ = thread.join();
+                Err(error)
+            }
+        }
+    }
+}
+
+/// State of the sending thread.
+struct Link {
+    sink: TcpSink,
+    /// Counts lines that could neither be sent nor spooled.
+    drops: Arc<Drops>,
+    spool: Option<Spool>,
+    connection: Option<Box<dyn Write + Send>>,
+    /// Lines waiting to be written to the connection, kept until they are
+    /// so that they can be spooled if writing fails.
+    buffer: Vec<u8>,
+    backoff: Duration,
+    next_attempt: Instant,
+}
+
+/// Spool file and how much it holds.
+struct Spool {
+    file: File,
+    len: u64,
+    max_len: u64,
+}
+
+impl Link {
+    /// Sends messages until the logger is gone.
+    fn run(&mut self, receiver: Receiver<Message>) {
+        loop {
+            match receiver.recv_timeout(self.backoff) {
+                Ok(message) => {
+                    self.handle(message);
+                    // batch whatever else is queued into one write
+                    while let Ok(message) = receiver.try_recv() {
+                        self.handle(message);
+                    }
+                    self.flush();
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    self.connect();
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        self.flush();
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Line(line) => self.send(&line),
+            Message::Flush(ack) => {
+                self.flush();
+                let _ = ack.send(());
+            }
+        }
+    }
+
+    /// Sends a line, spooling it if there is no connection.
+    fn send(&mut self, line: &[u8]) {
+        self.connect();
+        if self.connection.is_none() {
+            return self.spool(line);
+        }
+        self.buffer.extend_from_slice(line);
+        if self.buffer.len() >= BUFFER_LEN {
+            self.write_buffer();
+        }
+    }
+
+    /// Writes the buffered lines to the connection, spooling them and
+    /// disconnecting if that fails.
+    ///
+    /// Lines that were only written in part are spooled whole, so they may be
+    /// received twice, the first time cut short.
+    fn write_buffer(&mut self) {
+        let Some(connection) = &mut self.connection else {
+            return;
+        };
+        let mut written = 0;
+        let result = loop {
+            if written == self.buffer.len() {
+                break Ok(());
+            }
+            match connection.write(&self.buffer[written..]) {
+                Ok(0) => break Err(io::ErrorKind::WriteZero.into()),
+                Ok(len) => written += len,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => break Err(error),
+            }
+        };
+        if result.is_ok() {
+            self.buffer.clear();
+            return;
+        }
+        let sent = self.buffer[..written]
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |newline| newline + 1);
+        self.buffer.drain(..sent);
+        self.disconnect();
+    }
+
+    /// Writes out and flushes the connection, if any.
+    fn flush(&mut self) {
+        self.write_buffer();
+        if let Some(connection) = &mut self.connection {
+            if connection.flush().is_err() {
+                self.disconnect();
+            }
+        }
+    }
+
+    /// Appends lines to the spool, dropping those that do not fit.
+    fn spool(&mut self, lines: &[u8]) {
+        let mut spooled = 0;
+        if let Some(spool) = &mut self.spool {
+            let room = spool.max_len.saturating_sub(spool.len);
+            for line in lines.split_inclusive(|byte| *byte == b'\n') {
+                if (spooled + line.len()) as u64 > room {
+                    break;
+                }
+                spooled += line.len();
+            }
+            if spool.file.write_all(&lines[..spooled]).is_ok() {
+                spool.len += spooled as u64;
+            } else {
+                spooled = 0;
+                spool.len = spool.file.metadata().map_or(spool.len, |meta| meta.len());
+            }
+        }
+        for _ in lines[spooled..].split_inclusive(|byte| *byte == b'\n') {
+            self.drops.count(DropCause::Io);
+        }
+    }
+
+    /// Connects if disconnected and an attempt is due, replaying the spool.
+    fn connect(&mut self) {
+        if self.connection.is_some() || Instant::now() < self.next_attempt {
+            return;
+        }
+        match (self.sink.connector)() {
+            Ok(connection) => {
+                self.connection = Some(connection);
+                self.backoff = MIN_BACKOFF;
+                if self.replay().is_err() {
+                    self.disconnect();
+                }
+            }
+            Err(_) => {
+                self.next_attempt = Instant::now() + self.backoff;
+                self.backoff = (self.backoff * 2).min(self.sink.max_backoff);
+            }
+        }
+    }
+
+    /// Drops the connection, spooling the lines not written to it.
+    fn disconnect(&mut self) {
+        self.connection = None;
+        self.next_attempt = Instant::now() + self.backoff;
+        let lines = std::mem::take(&mut self.buffer);
+        self.spool(&lines);
+    }
+
+    /// Sends the spooled entries over the new connection, one line at a
+    /// time, and empties the spool.
+    fn replay(&mut self) -> io::Result<()> {
+        let (spool, connection) = match (&mut self.spool, &mut self.connection) {
+            (Some(spool), Some(connection)) => (spool, connection),
+            _ => return Ok(()),
+        };
+        if spool.len == 0 {
+            return Ok(());
+        }
+        spool.file.seek(SeekFrom::Start(0))?;
+        let mut reader = BufReader::new(&mut spool.file);
+        let mut writer = BufWriter::new(connection);
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            writer.write_all(&line)?;
+            line.clear();
+        }
+        writer.flush()?;
+        spool.file.set_len(0)?;
+        spool.len = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+//! Templates naming rotated files, such as `app.{date}.{seq}.jsonl.gz`.
+
+use std::{
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Parsed template for the names of rotated files.
+///
+/// `{seq}` is replaced with the file's index and `{date}` with the date it
+/// was started, like `%Y-%m-%d`. The strftime-style `%Y`, `%m`, `%d`, `%H`,
+/// `%M` and `%S` are replaced with parts of the time it was started, in UTC,
+/// and `%%` with `%`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Seq,
+    /// Part of the start time, written with this many digits.
+    Time(Field, usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+impl Template {
+    /// Parses a template, which must contain `{seq}` exactly once so that
+    /// names are unique, and no path separators.
+    pub(crate) fn parse(template: &str) -> io::Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(c) = rest.chars().next() {
+            let (tokens, len) = if rest.starts_with("{seq}") {
+                (vec![Part::Seq], 5)
+            } else if rest.starts_with("{date}") {
+                let date = vec![
+                    Part::Time(Field::Year, 4),
+                    Part::Literal("-".to_string()),
+                    Part::Time(Field::Month, 2),
+                    Part::Literal("-".to_string()),
+                    Part::Time(Field::Day, 2),
+                ];
+                (date, 6)
+            } else if let Some(conversion) = rest.strip_prefix('%') {
+                let part = match conversion.chars().next() {
+                    Some('%') => Part::Literal("%".to_string()),
+                    Some('Y') => Part::Time(Field::Year, 4),
+                    Some('m') => Part::Time(Field::Month, 2),
+                    Some('d') => Part::Time(Field::Day, 2),
+                    Some('H') => Part::Time(Field::Hour, 2),
+                    Some('M') => Part::Time(Field::Minute, 2),
+                    Some('S') => Part::Time(Field::Second, 2),
+                    _ => {
+                        let message =
+                            format!("unsupported conversion in file name template {template:?}");
+                        return Err(invalid(message));
+                    }
+                };
+                (vec![part], 2)
+            } else if c == '{' {
+                let message = format!("unknown placeholder in file name template {template:?}");
+                return Err(invalid(message));
+            } else if c == '/' || c == '\\' {
+                let message = format!("file name template {template:?} contains a path separator");
+                return Err(invalid(message));
+            } else {
+                (vec![Part::Literal(c.to_string())], c.len_utf8())
+            };
+            for token in tokens {
+                match (parts.last_mut(), token) {
+                    (Some(Part::Literal(literal)), Part::Literal(more)) => literal.push_str(&more),
+                    (_, token) => parts.push(token),
+                }
+            }
+            rest = &rest[len..];
+        }
+        if parts.iter().filter(|part| **part == Part::Seq).count() != 1 {
+            let message = format!("file name template {template:?} must contain {{seq}} once");
+            return Err(invalid(message));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Returns the name of the file with index `seq` started at `started`.
+    pub(crate) fn name(&self, seq: u64, started: SystemTime) -> String {
+        let fields = fields(started);
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(literal) => literal.clone(),
+                Part::Seq => seq.to_string(),
+                Part::Time(field, width) => format!("{:0width$}", fields[*field as usize]),
+            })
+            .collect()
+    }
+
+    /// Returns the index of the file with the name if the template could
+    /// have produced it.
+    pub(crate) fn seq(&self, name: &str) -> Option<u64> {
+        let mut rest = name;
+        let mut seq = None;
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rest = rest.strip_prefix(literal.as_str())?,
+                Part::Seq => {
+                    let len = rest.bytes().take_while(u8::is_ascii_digit).count();
+                    let digits = &rest[..len];
+                    // only plain decimal indices, not e.g. `01`
+                    if digits.is_empty() || digits.starts_with('0') {
+                        return None;
+                    }
+                    seq = Some(digits.parse().ok()?);
+                    rest = &rest[len..];
+                }
+                Part::Time(_, width) => {
+                    let digits = rest.get(..*width)?;
+                    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+                        return None;
+                    }
+                    rest = &rest[*width..];
+                }
+            }
+        }
+        seq.filter(|_| rest.is_empty())
+    }
+}
+
+/// Returns the year, month, day, hour, minute and second of the time in UTC,
+/// indexed by [`Field`].
+fn fields(time: SystemTime) -> [i64; 6] {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64);
+    let (year, month, day) = crate::rfc3339::civil_from_days(secs.div_euclid(86_400));
+    let secs = secs.rem_euclid(86_400);
+    [year, month, day, secs / 3600, secs / 60 % 60, secs % 60]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// 2024-02-29T13:05:09Z
+    fn leap_day() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_709_211_909)
+    }
+
+    #[test]
+    fn names_files() {
+        let template = Template::parse("app.{date}.{seq}.jsonl.gz").unwrap();
+        assert_eq!("app.2024-02-29.7.jsonl.gz", template.name(7, leap_day()));
+        let template = Template::parse("app-%Y%m%dT%H%M%S-{seq}%%.log").unwrap();
+        assert_eq!("app-20240229T130509-12%.log", template.name(12, leap_day()));
+    }
+
+    #[test]
+    fn matches_names() {
+        let template = Template::parse("app.{date}.{seq}.jsonl.gz").unwrap();
+        assert_eq!(Some(7), template.seq("app.2024-02-29.7.jsonl.gz"));
+        assert_eq!(Some(12), template.seq("app.1999-12-31.12.jsonl.gz"));
+        assert_eq!(None, template.seq("app.2024-02-29.07.jsonl.gz"));
+        assert_eq!(None, template.seq("app.2024-2-29.7.jsonl.gz"));
+        assert_eq!(None, template.seq("app.2024-02-29.7.jsonl"));
+        assert_eq!(None, template.seq("app.2024-02-29..jsonl.gz"));
+        assert_eq!(None, template.seq("app.jsonl.gz"));
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for template in [
+            "app.jsonl.gz",
+            "app.{seq}.{seq}.jsonl.gz",
+            "app.{index}.{seq}.jsonl.gz",
+            "app.%j.{seq}.jsonl.gz",
+            "app.{seq}.jsonl.gz%",
+            "old/app.{seq}.jsonl.gz",
+        ] {
+            let error = Template::parse(template).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, error.kind(), "{template}");
+        }
+    }
+}
@@ -0,0 +1,442 @@
+//! Helpers for asserting on what code logs, without temp files or forking.
+
+use crate::{
+    clock::{Clock, DefaultClock},
+    facade, DefaultGuard, InitError, LogEntry,
+};
+use log::LevelFilter;
+use std::{
+    fmt, io,
+    sync::{Arc, Mutex},
+};
+
+pub use log::Level;
+
+/// Logger that records every entry into a shared in-memory list.
+///
+/// Clones share the same list, so a clone can be installed globally (or
+/// handed to a dispatcher) while the original is kept for assertions.
+#[derive(Clone)]
+pub struct TestLogger {
+    clock: Arc<dyn Clock>,
+    level: LevelFilter,
+    entries: Arc<Mutex<Vec<LogEntry>>>,
+}
+
+impl TestLogger {
+    /// Creates a logger that captures entries at or above the given level.
+    pub fn new(level: LevelFilter) -> Self {
+        Self::with_clock(level, DefaultClock::new())
+    }
+
+    /// Creates a logger whose entry offsets come from the given clock.
+    pub fn with_clock<C: Clock + 'static>(level: LevelFilter, clock: C) -> Self {
+        Self {
+            clock: Arc::new(clock),
+            level,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Installs a clone of this logger as the global logger.
+    ///
+    /// Fails if a logger is already installed; use
+    /// [`deinit`](crate::deinit) to uninstall it first.
+    pub fn install(&self) -> Result<(), InitError> {
+        facade::install(
+            Box::new(self.clone()),
+            facade::InstallOptions::new(self.level),
+        )?;
+        Ok(())
+    }
+
+    /// Captures the entries logged on the current thread with a clone of this
+    /// logger until the returned guard is dropped.
+    ///
+    /// Unlike [`install`](Self::install) this can be used by many tests
+    /// running concurrently in one process; see
+    /// [`set_default`](crate::set_default).
+    pub fn set_default(&self) -> Result<DefaultGuard, InitError> {
+        facade::set_default(self.clone())
+    }
+
+    /// Returns a copy of all entries captured so far, oldest first.
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// Removes all captured entries.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+impl log::Log for TestLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let entry = LogEntry {
+                offset: self.clock.offset(),
+                level: record.level(),
+                target: record.target().to_string(),
+                body: record.args().to_string(),
+                session: None,
+                pid: None,
+                time: None,
+                module_path: record.module_path().map(str::to_string),
+                file: record.file().map(str::to_string),
+                line: record.line(),
+                fields: crate::kv::fields(record).unwrap_or_default(),
+                truncated: false,
+            };
+            self.entries.lock().unwrap().push(entry);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Writer that keeps the compressed log in memory, for asserting on what a
+/// real logger produced without touching the filesystem.
+///
+/// Clones share the same buffer, so a clone can be passed to
+/// [`LoggerBuilder::init_writer`](crate::LoggerBuilder::init_writer) or
+/// [`LoggerBuilder::build_writer`](crate::LoggerBuilder::build_writer) while
+/// the original is kept for assertions. Entries only show up once the logger
+/// has flushed them.
+///
+/// ```
+/// # use jsonl_gzip_logger::{assert_logged, test::MemorySink, LoggerBuilder};
+/// # use log::Log;
+/// let sink = MemorySink::new();
+/// let logger = LoggerBuilder::new().build_writer(sink.clone()).unwrap();
+/// logger.log(&log::Record::builder()
+///     .level(log::Level::Info)
+///     .args(format_args!("started"))
+///     .build());
+/// logger.flush();
+/// assert_logged!(sink, body: "started");
+/// ```
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    bytes: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MemorySink {
+    /// Creates an empty sink.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of the compressed bytes written so far.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.lock().unwrap().clone()
+    }
+
+    /// Returns the entries written so far, oldest first.
+    pub fn entries(&self) -> Vec<LogEntry> {
+        crate::read_bytes(&self.bytes.lock().unwrap()).collect()
+    }
+}
+
+impl io::Write for MemorySink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Description of log entries to look for in captured entries.
+///
+/// Usually built by [`assert_logged!`](crate::assert_logged) and
+/// [`assert_not_logged!`](crate::assert_not_logged), but can also be used
+/// directly.
+#[derive(Clone, Debug, Default)]
+pub struct Expectation {
+    level: Option<Level>,
+    target: Option<String>,
+    body: Option<String>,
+    contains: Vec<String>,
+}
+
+impl Expectation {
+    /// Creates an expectation that matches any entry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only matches entries with exactly this level.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Only matches entries with exactly this target.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Only matches entries with exactly this body.
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Only matches entries whose body contains this text.
+    pub fn contains(mut self, text: impl Into<String>) -> Self {
+        self.contains.push(text.into());
+        self
+    }
+
+    /// Returns true if the entry matches this expectation.
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        self.level.is_none_or(|level| entry.level == level)
+            && self.target.as_ref().is_none_or(|t| entry.target == *t)
+            && self.body.as_ref().is_none_or(|b| entry.body == *b)
+            && self.contains.iter().all(|text| entry.body.contains(text))
+    }
+
+    /// Panics unless at least one entry matches this expectation.
+    #[track_caller]
+    pub fn assert_logged(&self, entries: &[LogEntry]) {
+        if !entries.iter().any(|entry| self.matches(entry)) {
+            panic!(
+                "expected an entry matching {} but none was logged\n{}",
+                self,
+                Listing(entries)
+            );
+        }
+    }
+
+    /// Panics if any entry matches this expectation.
+    #[track_caller]
+    pub fn assert_not_logged(&self, entries: &[LogEntry]) {
+        if entries.iter().any(|entry| self.matches(entry)) {
+            panic!(
+                "expected no entry matching {} but one was logged\n{}",
+                self,
+                Listing(entries)
+            );
+        }
+    }
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(level) = self.level {
+            parts.push(format!("level: {}", level));
+        }
+        if let Some(target) = &self.target {
+            parts.push(format!("target: {:?}", target));
+        }
+        if let Some(body) = &self.body {
+            parts.push(format!("body: {:?}", body));
+        }
+        for text in &self.contains {
+            parts.push(format!("contains: {:?}", text));
+        }
+        write!(f, "{{{}}}", parts.join(", "))
+    }
+}
+
+/// Formats captured entries for assertion failure messages.
+struct Listing<'a>(&'a [LogEntry]);
+
+impl fmt::Display for Listing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "captured entries:")?;
+        for entry in self.0 {
+            writeln!(f, "  {} {}: {}", entry.level, entry.target, entry.body)?;
+        }
+        Ok(())
+    }
+}
+
+/// Asserts that a [`TestLogger`](crate::test::TestLogger) captured an entry
+/// matching all of the given properties.
+///
+/// Supported properties are `level`, `target`, `body` (exact match) and
+/// `contains` (substring of the body, may be repeated).
+///
+/// ```
+/// # use jsonl_gzip_logger::{assert_logged, test::TestLogger};
+/// # use log::{Log, LevelFilter};
+/// let logger = TestLogger::new(LevelFilter::Info);
+/// logger.log(&log::Record::builder()
+///     .level(log::Level::Warn)
+///     .target("db")
+///     .args(format_args!("will retry in 5s"))
+///     .build());
+/// assert_logged!(logger, level: Warn, target: "db", contains: "retry");
+/// ```
+#[macro_export]
+macro_rules! assert_logged {
+    ($logger:expr, $($properties:tt)+) => {
+        $crate::__expectation!($crate::test::Expectation::new(); $($properties)+)
+            .assert_logged(&$logger.entries())
+    };
+}
+
+/// Asserts that a [`TestLogger`](crate::test::TestLogger) captured no entry
+/// matching all of the given properties.
+///
+/// Takes the same properties as [`assert_logged!`](crate::assert_logged).
+#[macro_export]
+macro_rules! assert_not_logged {
+    ($logger:expr, $($properties:tt)+) => {
+        $crate::__expectation!($crate::test::Expectation::new(); $($properties)+)
+            .assert_not_logged(&$logger.entries())
+    };
+}
+
+/// Builds an [`Expectation`] from `key: value` properties.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __expectation {
+    ($expectation:expr; $(,)?) => {
+        $expectation
+    };
+    ($expectation:expr; level: $level:ident $(, $($rest:tt)*)?) => {
+        $crate::__expectation!(
+            $expectation.level($crate::test::Level::$level); $($($rest)*)?
+        )
+    };
+    ($expectation:expr; $key:ident: $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__expectation!($expectation.$key($value); $($($rest)*)?)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::{Level, Log, Record};
+
+    /// Logs a record directly to the given logger.
+    fn log(logger: &dyn Log, level: Level, target: &str, body: &str) {
+        logger.log(
+            &Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{}", body))
+                .build(),
+        );
+    }
+
+    /// Entries are captured in order and shared between clones.
+    #[test]
+    fn captures_entries() {
+        let logger = TestLogger::new(LevelFilter::Debug);
+        log(&logger.clone(), Level::Info, "foo", "first");
+        log(&logger, Level::Debug, "bar", "second");
+
+        let entries = logger.entries();
+        assert_eq!(2, entries.len());
+        assert_eq!(Level::Info, entries[0].level);
+        assert_eq!("foo", entries[0].target);
+        assert_eq!("first", entries[0].body);
+        assert_eq!("second", entries[1].body);
+        assert!(entries[0].offset <= entries[1].offset);
+    }
+
+    /// Entries below the logger's level are not captured.
+    #[test]
+    fn filters_by_level() {
+        let logger = TestLogger::new(LevelFilter::Warn);
+        log(&logger, Level::Info, "foo", "ignored");
+        log(&logger, Level::Error, "foo", "kept");
+
+        let entries = logger.entries();
+        assert_eq!(1, entries.len());
+        assert_eq!("kept", entries[0].body);
+    }
+
+    /// Clearing removes everything captured so far.
+    #[test]
+    fn clear() {
+        let logger = TestLogger::new(LevelFilter::Trace);
+        log(&logger, Level::Trace, "foo", "gone");
+        logger.clear();
+        assert!(logger.entries().is_empty());
+    }
+
+    /// Offsets come from the configured clock.
+    #[test]
+    fn deterministic_offsets() {
+        let clock = crate::clock::ManualClock::new();
+        let logger = TestLogger::with_clock(LevelFilter::Info, clock.clone());
+        log(&logger, Level::Info, "foo", "start");
+        clock.advance(std::time::Duration::from_secs(3));
+        log(&logger, Level::Info, "foo", "later");
+
+        let entries = logger.entries();
+        assert_eq!(std::time::Duration::ZERO, entries[0].offset);
+        assert_eq!(std::time::Duration::from_secs(3), entries[1].offset);
+    }
+
+    /// A sink holds the entries of a real logger, readable once flushed.
+    #[test]
+    fn memory_sink() {
+        let sink = MemorySink::new();
+        let logger = crate::LoggerBuilder::new()
+            .level(LevelFilter::Info)
+            .build_writer(sink.clone())
+            .unwrap();
+        log(&logger, Level::Info, "foo", "first");
+        log(&logger, Level::Debug, "foo", "filtered");
+        logger.flush();
+        crate::assert_logged!(sink, level: Info, body: "first");
+
+        log(&logger, Level::Warn, "bar", "second");
+        drop(logger);
+        let bodies: Vec<_> = sink.entries().into_iter().map(|e| e.body).collect();
+        assert_eq!(vec!["first", "second"], bodies);
+    }
+
+    /// Logs a set of entries for the assertion tests.
+    fn logged() -> TestLogger {
+        let logger = TestLogger::new(LevelFilter::Trace);
+        log(&logger, Level::Warn, "db", "connection lost, will retry");
+        log(&logger, Level::Info, "http", "GET /index.html");
+        logger
+    }
+
+    /// Matching properties pass the assertion.
+    #[test]
+    fn assert_logged_matches() {
+        let logger = logged();
+        crate::assert_logged!(logger, level: Warn, target: "db", contains: "retry");
+        crate::assert_logged!(logger, level: Level::Info, body: "GET /index.html");
+        crate::assert_logged!(logger, contains: "connection", contains: "retry",);
+    }
+
+    /// Properties must all match the same entry.
+    #[test]
+    #[should_panic(expected = "expected an entry matching")]
+    fn assert_logged_requires_single_entry() {
+        crate::assert_logged!(logged(), level: Warn, target: "http");
+    }
+
+    /// Entries that were not logged pass the negative assertion.
+    #[test]
+    fn assert_not_logged_matches() {
+        let logger = logged();
+        crate::assert_not_logged!(logger, level: Error);
+        crate::assert_not_logged!(logger, target: "db", contains: "GET");
+    }
+
+    /// Logged entries fail the negative assertion.
+    #[test]
+    #[should_panic(expected = "expected no entry matching")]
+    fn assert_not_logged_fails() {
+        crate::assert_not_logged!(logged(), target: "http");
+    }
+}
@@ -0,0 +1,192 @@
+//! Non-blocking logging for applications running on a tokio runtime.
+
+use crate::{facade, worker, InitError, LoggerBuilder};
+use ::tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::mpsc::{self, UnboundedSender},
+};
+use log::LevelFilter;
+use std::{
+    future::Future,
+    io::{self, Write},
+    sync::mpsc::{sync_channel, SyncSender},
+};
+
+pub use crate::worker::{Durability, Flushed, WorkerGuard};
+pub use crate::LoggerHandle;
+
+/// Creates and installs a global logger that logs to a new .jsonl.gz file at
+/// the given path without blocking the logging task.
+///
+/// Compression and file IO happen on a tokio blocking task, so async tasks
+/// only pay for formatting the entry. The returned [`WorkerGuard`] finishes
+/// the file when dropped and must outlive all logging.
+///
+/// # Panics
+/// Panics if called outside of a tokio runtime.
+pub fn init<P: AsRef<std::path::Path>>(
+    path: P,
+    level: LevelFilter,
+) -> Result<WorkerGuard, InitError> {
+    LoggerBuilder::new().level(level).init_tokio(path)
+}
+
+/// Creates and installs a global logger that writes a .jsonl.gz stream to
+/// the given async writer, such as a TLS stream to a log collector.
+///
+/// Compression happens on a tokio blocking task and the compressed bytes are
+/// written by a regular tokio task, so neither blocks the logging task.
+/// Flushes and the [`WorkerGuard`]'s drop wait on the async writer, so the
+/// guard must be dropped where blocking is allowed and the runtime must have
+/// other worker threads to drive the writer (a multi-threaded runtime).
+///
+/// # Panics
+/// Panics if called outside of a tokio runtime.
+pub fn init_with_writer<W>(writer: W, level: LevelFilter) -> Result<WorkerGuard, InitError>
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    LoggerBuilder::new()
+        .level(level)
+        .init_tokio_with_writer(writer)
+}
+
+impl LoggerBuilder {
+    /// Like [`tokio::init`](init), but with this builder's configuration.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    pub fn init_tokio<P: AsRef<std::path::Path>>(self, path: P) -> Result<WorkerGuard, InitError> {
+        facade::ensure_uninstalled()?;
+        let dest = self.create(path)?;
+        let (filter, install, queue) = (self.filter.clone(), self.install_options(), self.queue);
+        let (logger, guard, worker) = worker::channel(dest, filter, self.format()?, queue);
+        // start the writer first so that an installed logger always has one
+        ::tokio::task::spawn_blocking(worker);
+        let generation = facade::install(Box::new(logger), install)?;
+        Ok(guard.installed(generation))
+    }
+
+    /// Like [`tokio::init_with_writer`](init_with_writer), but with this
+    /// builder's configuration.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    pub fn init_tokio_with_writer<W>(self, writer: W) -> Result<WorkerGuard, InitError>
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        facade::ensure_uninstalled()?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let dest = self.dest_options().wrap(AsyncBridge { sender })?;
+        let (filter, install, queue) = (self.filter.clone(), self.install_options(), self.queue);
+        let (logger, guard, worker) = worker::channel(dest, filter, self.format()?, queue);
+        ::tokio::spawn(drive(writer, receiver));
+        ::tokio::task::spawn_blocking(worker);
+        let generation = facade::install(Box::new(logger), install)?;
+        Ok(guard.installed(generation))
+    }
+}
+
+/// Request from the blocking writer to the task driving an async writer.
+enum Chunk {
+    /// Compressed bytes to write.
+    Data(Vec<u8>),
+    /// Flush the async writer, then acknowledge with the result.
+    Flush(SyncSender<io::Result<()>>),
+    /// Shut down the async writer, then acknowledge.
+    Shutdown(SyncSender<()>),
+}
+
+/// Blocking [`Write`] implementation that forwards to an async writer task.
+struct AsyncBridge {
+    sender: UnboundedSender<Chunk>,
+}
+
+impl AsyncBridge {
+    /// Error for when the writer task has stopped.
+    fn closed() -> io::Error {
+        io::Error::new(io::ErrorKind::BrokenPipe, "async writer task stopped")
+    }
+}
+
+impl Write for AsyncBridge {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sender
+            .send(Chunk::Data(buf.to_vec()))
+            .map_err(|_| Self::closed())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let (ack, done) = sync_channel(1);
+        self.sender
+            .send(Chunk::Flush(ack))
+            .map_err(|_| Self::closed())?;
+        done.recv().map_err(|_| Self::closed())?
+    }
+}
+
+impl Drop for AsyncBridge {
+    fn drop(&mut self) {
+        let (ack, done) = sync_channel(1);
+        if self.sender.send(Chunk::Shutdown(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+}
+
+/// Writes chunks from the bridge into the async writer.
+async fn drive<W: AsyncWrite + Unpin>(mut writer: W, mut receiver: mpsc::UnboundedReceiver<Chunk>) {
+    let mut result = Ok(());
+    while let Some(chunk) = receiver.recv().await {
+        match chunk {
+            Chunk::Data(data) => {
+                if result.is_ok() {
+                    result = writer.write_all(&data).await;
+                }
+            }
+            Chunk::Flush(ack) => {
+                if result.is_ok() {
+                    result = writer.flush().await;
+                }
+                let _ = ack.send(match &result {
+                    Ok(()) => Ok(()),
+                    Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+                });
+            }
+            Chunk::Shutdown(ack) => {
+                let _ = writer.shutdown().await;
+                let _ = ack.send(());
+                return;
+            }
+        }
+    }
+}
+
+/// Waits for `shutdown` to resolve, then finishes the log file.
+///
+/// Intended for graceful-shutdown patterns where the service already has a
+/// shutdown future (a cancellation token, a broadcast receiver, ...); spawn
+/// this or `select!` on it so the gzip stream is always properly closed.
+pub async fn finish_on_shutdown<F: Future>(guard: WorkerGuard, shutdown: F) {
+    shutdown.await;
+    guard.finish_async().await;
+}
+
+/// Waits for ctrl-c (or SIGTERM on unix), then finishes the log file.
+pub async fn finish_on_signal(guard: WorkerGuard) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use ::tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        ::tokio::select! {
+            result = ::tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    ::tokio::signal::ctrl_c().await?;
+    guard.finish_async().await;
+    Ok(())
+}
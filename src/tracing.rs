@@ -0,0 +1,186 @@
+//! Writing `tracing` events to the log, for applications moving from `log`
+//! to `tracing` that want to keep one file format.
+//!
+//! [`JsonlGzipLayer`] turns each event into an entry for whichever logger is
+//! installed, like any other entry, so entries logged with `log` and events
+//! end up in the same file:
+//!
+//! ```no_run
+//! use jsonl_gzip_logger::tracing::JsonlGzipLayer;
+//! use log::LevelFilter;
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! let _guard = jsonl_gzip_logger::init("app.jsonl.gz", LevelFilter::Info)?;
+//! let subscriber = tracing_subscriber::registry().with(JsonlGzipLayer::new());
+//! tracing::subscriber::set_global_default(subscriber)?;
+//!
+//! let span = tracing::info_span!("request", id = 7);
+//! let _entered = span.enter();
+//! tracing::info!(status = 200, "handled");
+//! log::info!("still logged to the same file");
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! The event above is written with the body `handled` and the fields
+//! `{"status": 200, "spans": [{"name": "request", "id": 7}]}`.
+
+use crate::kv::Fields;
+use log::Level;
+use serde_json::{Map, Value};
+use std::fmt;
+use tracing_core::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// Layer writing `tracing` events as entries of the installed logger.
+///
+/// An event's `message` becomes the body and its other fields the entry's
+/// fields. The spans the event happened in are added as the `spans` field,
+/// outermost first, each an object of its name and fields. Events are
+/// filtered by the installed logger's level and filter, like entries logged
+/// with `log`.
+///
+/// Do not also forward `log` records to `tracing`, such as with
+/// `tracing-log`'s `LogTracer`, as entries would then go round in circles.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonlGzipLayer {
+    _private: (),
+}
+
+impl JsonlGzipLayer {
+    /// Creates a layer writing to the installed logger.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Fields recorded for a span, kept in its extensions.
+struct SpanFields(Map<String, Value>);
+
+impl<S> Layer<S> for JsonlGzipLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Collect::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(SpanFields(fields.0));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            let mut collect = Collect(std::mem::take(fields));
+            values.record(&mut collect);
+            *fields = collect.0;
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = level(*metadata.level());
+        let logger = log::logger();
+        let enabled = level <= log::max_level()
+            && logger.enabled(
+                &log::Metadata::builder()
+                    .level(level)
+                    .target(metadata.target())
+                    .build(),
+            );
+        if !enabled {
+            return;
+        }
+        let mut fields = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            let spans = scope.from_root().map(|span| {
+                let mut object = Map::new();
+                object.insert("name".to_string(), span.name().into());
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                    object.extend(fields.clone());
+                }
+                Value::Object(object)
+            });
+            fields.insert("spans".to_string(), Value::Array(spans.collect()));
+        }
+        let mut collect = Collect(fields);
+        event.record(&mut collect);
+        let mut fields = collect.0;
+        let body = match fields.remove("message") {
+            Some(Value::String(message)) => message,
+            Some(message) => message.to_string(),
+            None => String::new(),
+        };
+        logger.log(
+            &log::Record::builder()
+                .level(level)
+                .target(metadata.target())
+                .args(format_args!("{}", body))
+                .module_path(metadata.module_path())
+                .file(metadata.file())
+                .line(metadata.line())
+                .key_values(&Fields(&fields))
+                .build(),
+        );
+    }
+}
+
+/// Returns the `log` level of a `tracing` level.
+fn level(level: tracing_core::Level) -> Level {
+    match level {
+        tracing_core::Level::ERROR => Level::Error,
+        tracing_core::Level::WARN => Level::Warn,
+        tracing_core::Level::INFO => Level::Info,
+        tracing_core::Level::DEBUG => Level::Debug,
+        tracing_core::Level::TRACE => Level::Trace,
+    }
+}
+
+/// Visitor collecting fields as JSON values, keeping numbers and booleans
+/// as they are and formatting everything else as text.
+#[derive(Default)]
+struct Collect(Map<String, Value>);
+
+impl Collect {
+    fn insert(&mut self, field: &Field, value: impl Into<Value>) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+}
+
+impl Visit for Collect {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value);
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value);
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.insert(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{:?}", value));
+    }
+}
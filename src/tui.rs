@@ -0,0 +1,195 @@
+//! Log pane widget for terminal user interfaces built with `ratatui`.
+//!
+//! A [`LogView`] holds entries fed from a [`LogEntryIter`](crate::LogEntryIter)
+//! or any other source of [`LogEntry`]s, and renders the ones passing its
+//! filters, newest at the bottom. Applications drive scrolling and filtering
+//! from their own key handling.
+//!
+//! ```no_run
+//! use jsonl_gzip_logger::tui::LogView;
+//! use log::LevelFilter;
+//!
+//! let mut view = LogView::new();
+//! view.extend(jsonl_gzip_logger::read("app.jsonl.gz").unwrap());
+//! view.set_level(LevelFilter::Warn);
+//! // later, inside `Terminal::draw`: frame.render_widget(&view, area);
+//! ```
+
+use crate::LogEntry;
+use log::{Level, LevelFilter};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Widget},
+};
+use std::collections::VecDeque;
+
+/// Number of entries kept by [`LogView::new`].
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Scrollable, filterable pane of log entries.
+///
+/// Rendering `&LogView` draws the visible entries, one per line.
+#[derive(Clone, Debug)]
+pub struct LogView<'a> {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    level: LevelFilter,
+    target: String,
+    search: String,
+    /// Number of matching entries hidden below the bottom of the pane.
+    scroll: usize,
+    block: Option<Block<'a>>,
+}
+
+impl Default for LogView<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> LogView<'a> {
+    /// Creates an empty view keeping the last 10 000 entries.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates an empty view keeping the last `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+            level: LevelFilter::Trace,
+            target: String::new(),
+            search: String::new(),
+            scroll: 0,
+            block: None,
+        }
+    }
+
+    /// Surrounds the pane with a block, for example to give it a border and
+    /// a title.
+    pub fn block(mut self, block: Block<'a>) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    /// Appends an entry, dropping the oldest one if the view is full.
+    ///
+    /// A view scrolled away from the bottom stays on the entries it shows.
+    pub fn push(&mut self, entry: LogEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        if self.scroll > 0 && self.matches(&entry) {
+            self.scroll += 1;
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Only shows entries at or above `level`.
+    pub fn set_level(&mut self, level: LevelFilter) {
+        self.level = level;
+        self.scroll = 0;
+    }
+
+    /// Only shows entries whose target starts with `target`.
+    pub fn set_target(&mut self, target: impl Into<String>) {
+        self.target = target.into();
+        self.scroll = 0;
+    }
+
+    /// Only shows entries whose body contains `text`.
+    pub fn set_search(&mut self, text: impl Into<String>) {
+        self.search = text.into();
+        self.scroll = 0;
+    }
+
+    /// Scrolls towards older entries.
+    pub fn scroll_up(&mut self, lines: usize) {
+        let max = self.visible().count().saturating_sub(1);
+        self.scroll = (self.scroll + lines).min(max);
+    }
+
+    /// Scrolls towards newer entries.
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    /// Scrolls to the newest entry, after which new entries stay in view.
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll = 0;
+    }
+
+    /// Returns whether the newest entry is in view.
+    pub fn is_at_bottom(&self) -> bool {
+        self.scroll == 0
+    }
+
+    /// Returns the entries passing the filters, oldest first.
+    pub fn visible(&self) -> impl DoubleEndedIterator<Item = &LogEntry> {
+        self.entries.iter().filter(|entry| self.matches(entry))
+    }
+
+    /// Returns whether an entry passes the filters.
+    fn matches(&self, entry: &LogEntry) -> bool {
+        entry.level <= self.level
+            && entry.target.starts_with(&self.target)
+            && entry.body.contains(&self.search)
+    }
+}
+
+impl Extend<LogEntry> for LogView<'_> {
+    fn extend<I: IntoIterator<Item = LogEntry>>(&mut self, entries: I) {
+        for entry in entries {
+            self.push(entry);
+        }
+    }
+}
+
+impl Widget for &LogView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let inner = match &self.block {
+            Some(block) => {
+                let inner = block.inner(area);
+                block.render(area, buf);
+                inner
+            }
+            None => area,
+        };
+        let height = usize::from(inner.height);
+        let mut lines: Vec<_> = self
+            .visible()
+            .rev()
+            .skip(self.scroll)
+            .take(height)
+            .collect();
+        lines.reverse();
+        for (entry, y) in lines.into_iter().zip(inner.top()..) {
+            buf.set_line(inner.left(), y, &line(entry), inner.width);
+        }
+    }
+}
+
+/// Formats an entry as one line of the pane.
+fn line(entry: &LogEntry) -> Line<'_> {
+    let style = Style::new().fg(match entry.level {
+        Level::Error => Color::Red,
+        Level::Warn => Color::Yellow,
+        Level::Info => Color::Green,
+        Level::Debug => Color::Blue,
+        Level::Trace => Color::DarkGray,
+    });
+    Line::from(vec![
+        Span::raw(format!("{:>10.3} ", entry.offset.as_secs_f64())),
+        Span::styled(format!("{:<5} ", entry.level), style),
+        Span::raw(&entry.target),
+        Span::raw(": "),
+        Span::raw(&entry.body),
+    ])
+}
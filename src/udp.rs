@@ -0,0 +1,86 @@
+//! Fire-and-forget delivery of entries over UDP.
+
+use crate::{facade, filter::Filter, format::Format, limit::DropCause, InitError, LoggerBuilder};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+/// Logger that sends every entry as one datagram.
+struct UdpLogger {
+    filter: Filter,
+    format: Format,
+    socket: UdpSocket,
+    /// The process owning the socket, see [`ForkPolicy`](crate::ForkPolicy).
+    pid: u32,
+}
+
+impl log::Log for UdpLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // forked children share the parent's socket
+        self.filter.enabled(metadata) && crate::fork::current_pid() == self.pid
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            // delivery is best effort, so errors are ignored
+            self.format.entries(record, |line| {
+                if self.socket.send(&line).is_err() {
+                    self.format.drops.count(DropCause::Io);
+                }
+            });
+        }
+    }
+
+    fn flush(&self) {
+        if crate::fork::current_pid() != self.pid {
+            return;
+        }
+        self.format.pending(|line| {
+            if self.socket.send(&line).is_err() {
+                self.format.drops.count(DropCause::Io);
+            }
+        });
+    }
+}
+
+/// Guard that uninstalls the logger when dropped.
+#[must_use = "dropping the guard immediately stops logging"]
+pub struct UdpGuard {
+    /// Generation of the logger in the facade.
+    generation: u64,
+}
+
+impl Drop for UdpGuard {
+    fn drop(&mut self) {
+        facade::uninstall(Some(self.generation));
+    }
+}
+
+impl LoggerBuilder {
+    /// Creates and installs a global logger that sends each entry as a JSON
+    /// datagram to the given address, such as a local fluentd agent.
+    ///
+    /// Delivery is lossy: entries are dropped if the receiver is not
+    /// listening, the network drops them, or they do not fit in a datagram.
+    ///
+    /// Dropping the returned guard uninstalls the logger.
+    pub fn init_udp<A: ToSocketAddrs>(self, address: A) -> Result<UdpGuard, InitError> {
+        facade::ensure_uninstalled()?;
+        let address = address.to_socket_addrs()?.next().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "no address to send to")
+        })?;
+        let local: SocketAddr = match address {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0; 8], 0).into(),
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(address)?;
+        let (filter, install) = (self.filter.clone(), self.install_options());
+        let logger = UdpLogger {
+            filter,
+            format: self.format()?,
+            socket,
+            pid: crate::fork::current_pid(),
+        };
+        let generation = facade::install(Box::new(logger), install)?;
+        Ok(UdpGuard { generation })
+    }
+}
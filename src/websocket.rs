@@ -0,0 +1,112 @@
+//! WebSocket variant of the live entry stream served by [`http`](crate::http).
+
+use crate::{
+    http::{Filter, Request},
+    hub::Hub,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+use std::{
+    io::{self, Write},
+    net::TcpStream,
+    sync::mpsc::RecvTimeoutError,
+    time::Duration,
+};
+
+/// GUID appended to the client's key when accepting a connection.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// How often an idle connection is pinged to keep it open.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+/// Opcode of a final text frame.
+const TEXT: u8 = 0x81;
+/// Opcode of a ping frame.
+const PING: u8 = 0x89;
+
+/// Returns the `Sec-WebSocket-Accept` value for a client's key.
+fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(GUID.as_bytes());
+    STANDARD.encode(sha1.finalize())
+}
+
+/// Writes one unmasked frame, as sent by servers.
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut header = vec![opcode];
+    match payload.len() {
+        len @ 0..=125 => header.push(len as u8),
+        len @ 126..=0xffff => {
+            header.push(126);
+            header.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            header.push(127);
+            header.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    stream.write_all(&header)?;
+    stream.write_all(payload)?;
+    stream.flush()
+}
+
+/// Upgrades the connection and streams matching entries until the client
+/// goes away or the hub is closed.
+///
+/// Messages from the client are never read; a closed connection is noticed
+/// when the next entry or ping fails to send.
+pub(crate) fn serve(
+    mut stream: TcpStream,
+    request: &Request,
+    filter: Filter,
+    hub: &Hub,
+) -> io::Result<()> {
+    let key = match request.header("sec-websocket-key") {
+        Some(key) => key,
+        None => {
+            return stream.write_all(
+                b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+        }
+    };
+    let entries = hub.subscribe();
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )?;
+    loop {
+        match entries.recv_timeout(KEEP_ALIVE) {
+            Ok(entry) if filter.matches(&entry) => write_frame(&mut stream, TEXT, &entry)?,
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => write_frame(&mut stream, PING, &[])?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The accept key matches the example from RFC 6455.
+    #[test]
+    fn rfc_accept_key() {
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            accept_key("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+    }
+
+    /// Payload lengths use the shortest encoding.
+    #[test]
+    fn frame_lengths() {
+        let mut frame = Vec::new();
+        write_frame(&mut frame, TEXT, b"hi").unwrap();
+        assert_eq!(vec![0x81, 2, b'h', b'i'], frame);
+
+        let mut frame = Vec::new();
+        write_frame(&mut frame, TEXT, &[0; 300]).unwrap();
+        assert_eq!(&[0x81, 126, 1, 44], &frame[..4]);
+        assert_eq!(304, frame.len());
+    }
+}
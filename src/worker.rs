@@ -0,0 +1,309 @@
+//! Background writer shared by the non-blocking loggers.
+//!
+//! The logging front end only serializes entries; compression and IO are
+//! done by [`run`] on whatever thread or blocking task the caller provides.
+
+use crate::{
+    dest::Dest,
+    filter::Filter,
+    format::{ErrorHook, Format},
+    handle::{LoggerHandle, Target},
+    health::Monitor,
+    hub::Hub,
+    limit::{DropCause, Drops, Sampler},
+    queue::{self, Overflow, Receiver, SendError, Sender},
+};
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{mpsc, Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// Message sent from the logging front end to the writer.
+pub(crate) enum Message {
+    /// A serialized log entry, without a trailing newline.
+    Line(Vec<u8>),
+    /// Flush everything written so far, then complete the notifier.
+    Flush(Notifier, Durability),
+    /// Rotate the file, then send the result.
+    Rotate(mpsc::Sender<io::Result<()>>),
+    /// Finish the gzip stream and stop the writer.
+    Shutdown,
+}
+
+/// Logger that hands serialized entries to a background writer.
+pub(crate) struct NonBlockingLogger {
+    filter: Filter,
+    format: Format,
+    sender: Sender,
+    /// The process owning the writer, see [`ForkPolicy`](crate::ForkPolicy).
+    pid: u32,
+}
+
+impl log::Log for NonBlockingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // the writer thread does not exist in forked children
+        self.filter.enabled(metadata) && crate::fork::current_pid() == self.pid
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            self.format.entries(record, |line| self.send(line));
+        }
+    }
+
+    fn flush(&self) {
+        if crate::fork::current_pid() == self.pid {
+            self.format.pending(|line| self.send(line));
+            flush(&self.sender, Durability::Written).wait();
+        }
+    }
+}
+
+impl NonBlockingLogger {
+    /// Queues an entry for the writer, counting it if the queue overflows.
+    fn send(&self, line: Vec<u8>) {
+        if let Err(SendError::Overflowed) = self.sender.send(Message::Line(line)) {
+            self.format.drops.count(DropCause::Queue);
+        }
+    }
+}
+
+/// How far entries must have progressed before a flush completes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Durability {
+    /// Entries have been compressed and handed to the OS.
+    Written,
+    /// Entries have additionally been fsynced to the storage device.
+    ///
+    /// Destinations that are not files are only flushed.
+    Synced,
+}
+
+/// Queues a flush behind every entry sent so far.
+pub(crate) fn flush(sender: &Sender, durability: Durability) -> Flushed {
+    let (notifier, flushed) = completion();
+    // if the writer is gone the notifier is dropped, completing the flush
+    let _ = sender.send(Message::Flush(notifier, durability));
+    flushed
+}
+
+/// Returns a future that has already resolved.
+pub(crate) fn completed() -> Flushed {
+    let (notifier, flushed) = completion();
+    drop(notifier);
+    flushed
+}
+
+/// State shared between a [`Notifier`] and its [`Flushed`] future.
+#[derive(Default)]
+struct Completion {
+    state: Mutex<(bool, Option<Waker>)>,
+    condvar: Condvar,
+}
+
+/// Creates a connected notifier and future pair.
+fn completion() -> (Notifier, Flushed) {
+    let shared = Arc::new(Completion::default());
+    (Notifier(shared.clone()), Flushed(shared))
+}
+
+/// Writer side of a flush request; completes the [`Flushed`] when dropped.
+pub(crate) struct Notifier(Arc<Completion>);
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.0 = true;
+        if let Some(waker) = state.1.take() {
+            waker.wake();
+        }
+        self.0.condvar.notify_all();
+    }
+}
+
+/// Future that resolves once a requested flush (or finish) has completed.
+///
+/// Works with any async runtime, and can also be waited on synchronously
+/// with [`Flushed::wait`].
+#[must_use = "a flush is only awaited if the future is polled or waited on"]
+pub struct Flushed(Arc<Completion>);
+
+impl Flushed {
+    /// Blocks the current thread until the flush has completed.
+    pub fn wait(self) {
+        let mut state = self.0.state.lock().unwrap();
+        while !state.0 {
+            state = self.0.condvar.wait(state).unwrap();
+        }
+    }
+}
+
+impl Future for Flushed {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.0.state.lock().unwrap();
+        if state.0 {
+            Poll::Ready(())
+        } else {
+            state.1 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Guard that finishes the log file when dropped.
+///
+/// Dropping the guard waits for every queued entry to be written and for the
+/// gzip stream to be finished, so keep it alive for as long as logging should
+/// continue (typically by binding it in `main`).
+#[must_use = "dropping the guard immediately stops logging"]
+pub struct WorkerGuard {
+    sender: Sender,
+    done: Option<Flushed>,
+    pid: u32,
+    hub: Option<Arc<Hub>>,
+    sampler: Arc<Sampler>,
+    monitor: Arc<Monitor>,
+    /// Generation of the logger in the facade, once installed.
+    generation: u64,
+}
+
+impl WorkerGuard {
+    /// Returns the guard for the logger installed as the given generation.
+    pub(crate) fn installed(mut self, generation: u64) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// Returns a handle for controlling the logger.
+    pub fn handle(&self) -> LoggerHandle {
+        let target = Target::Worker(self.sender.clone(), self.pid);
+        LoggerHandle::new(
+            self.generation,
+            target,
+            self.hub.clone(),
+            self.sampler.clone(),
+            self.monitor.clone(),
+        )
+    }
+
+    /// Stops logging without blocking, returning a future that resolves once
+    /// every queued entry is written and the gzip stream is finished.
+    ///
+    /// This is the async counterpart of dropping the guard.
+    pub fn finish_async(mut self) -> Flushed {
+        self.uninstall();
+        let _ = self.sender.send(Message::Shutdown);
+        self.done.take().unwrap()
+    }
+
+    /// Uninstalls the logger from the facade, unless another logger has
+    /// been installed since, so that a new one can be installed and entries
+    /// stop being queued for a writer that is shutting down.
+    fn uninstall(&self) {
+        if self.generation != 0 {
+            crate::facade::uninstall(Some(self.generation));
+        }
+    }
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        if self.done.is_some() {
+            self.uninstall();
+        }
+        // forked children must not wait for a writer thread they do not have
+        if let Some(done) = self
+            .done
+            .take()
+            .filter(|_| crate::fork::current_pid() == self.pid)
+        {
+            let _ = self.sender.send(Message::Shutdown);
+            done.wait();
+        }
+    }
+}
+
+/// Creates the logger front end, its guard, and the writer loop.
+///
+/// `filter` and `format` configure the logger front end, and `queue` the
+/// capacity of the queue in front of the writer, if bounded.
+/// The returned closure must be run on a thread that may block; it returns
+/// once the guard is dropped.
+pub(crate) fn channel(
+    dest: Dest,
+    filter: Filter,
+    format: Format,
+    queue: Option<(usize, Overflow)>,
+) -> (
+    NonBlockingLogger,
+    WorkerGuard,
+    impl FnOnce() + Send + 'static,
+) {
+    let (capacity, overflow) = match queue {
+        Some((capacity, overflow)) => (Some(capacity), overflow),
+        None => (None, Overflow::default()),
+    };
+    let (sender, receiver) = queue::channel(capacity, overflow);
+    let (finished, done) = completion();
+    let pid = crate::fork::current_pid();
+    let hub = format.hub.clone();
+    let sampler = format.sampler.clone();
+    let on_error = format.on_error.clone();
+    let drops = format.drops.clone();
+    let logger = NonBlockingLogger {
+        filter,
+        format,
+        sender: sender.clone(),
+        pid,
+    };
+    let guard = WorkerGuard {
+        sender,
+        done: Some(done),
+        pid,
+        hub,
+        sampler,
+        monitor: dest.monitor().clone(),
+        generation: 0,
+    };
+    let worker = move || {
+        run(receiver, dest, on_error, drops);
+        drop(finished);
+    };
+    (logger, guard, worker)
+}
+
+/// Writes messages to the destination until shut down, passing errors to
+/// `on_error` and counting entries that fail to be written in `drops`.
+fn run(receiver: Receiver, mut dest: Dest, on_error: ErrorHook, drops: Arc<Drops>) {
+    while let Some(message) = receiver.recv() {
+        let result = match message {
+            Message::Line(line) => dest
+                .write_entry(&line)
+                .inspect_err(|_| drops.count(DropCause::Io)),
+            Message::Flush(notifier, durability) => {
+                let result = match durability {
+                    Durability::Written => dest.flush(),
+                    Durability::Synced => dest.sync(),
+                };
+                drop(notifier);
+                result
+            }
+            Message::Rotate(reply) => {
+                let _ = reply.send(dest.rotate());
+                Ok(())
+            }
+            Message::Shutdown => break,
+        };
+        if let Err(error) = result {
+            on_error(&error);
+        }
+    }
+    if let Err(error) = dest.finish() {
+        on_error(&error);
+    }
+}
@@ -0,0 +1,54 @@
+//! Writing entries directly, without going through `log`.
+
+use crate::{dest::Dest, LogEntry, LoggerBuilder};
+use std::{io, path::Path};
+
+/// Writer appending existing entries to a new .jsonl.gz file.
+///
+/// Useful for tools that import events from other systems or generate
+/// synthetic logs, since the result can be read with the same tooling as a
+/// file written by a logger. Entries are written exactly as given, so their
+/// offsets need not be increasing.
+///
+/// Created with [`LogWriter::create`], or with [`LoggerBuilder::writer`] to
+/// apply the builder's file options such as signing. The file is finished
+/// when the writer is dropped, but only [`LogWriter::finish`] reports
+/// errors from doing so.
+pub struct LogWriter {
+    dest: Dest,
+}
+
+impl LogWriter {
+    /// Creates a new .jsonl.gz file at the given path.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        LoggerBuilder::new().writer(path)
+    }
+
+    /// Writes one entry.
+    pub fn write_entry(&mut self, entry: &LogEntry) -> io::Result<()> {
+        self.dest.write_entry(&crate::core::serialize_entry(entry)?)
+    }
+
+    /// Flushes everything written so far to the file, so that readers can
+    /// see it.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.dest.flush()
+    }
+
+    /// Finishes the file.
+    pub fn finish(self) -> io::Result<()> {
+        self.dest.finish()
+    }
+}
+
+impl LoggerBuilder {
+    /// Creates a [`LogWriter`] for a new .jsonl.gz file at the given path.
+    ///
+    /// The file is encrypted, signed, or hash chained as configured, while
+    /// options applying to log records, such as the level and clock, are
+    /// ignored.
+    pub fn writer<P: AsRef<Path>>(&self, path: P) -> io::Result<LogWriter> {
+        let dest = self.create(path)?;
+        Ok(LogWriter { dest })
+    }
+}
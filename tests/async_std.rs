@@ -0,0 +1,33 @@
+#![cfg(feature = "async-std")]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::read;
+use log::{Level, LevelFilter};
+use rusty_fork::rusty_fork_test;
+
+// init() can only be called once per process, so
+// have to run each of these tests in their own process.
+rusty_fork_test! {
+
+#[test]
+fn guard_finishes_file() {
+    let path = path("async_std_guard");
+    async_std::task::block_on(async {
+        let _guard = jsonl_gzip_logger::async_std::init(&path, LevelFilter::Info).unwrap();
+        log::info!(target: "foo", "This is a log!");
+        log::debug!(target: "foo", "This is filtered!");
+    });
+
+    let mut iter = read(&path).unwrap();
+
+    let entry = iter.next().unwrap();
+    assert_eq!(Level::Info, entry.level);
+    assert_eq!("foo", entry.target);
+    assert_eq!("This is a log!", entry.body);
+
+    assert_eq!(0, iter.count());
+}
+
+}
@@ -0,0 +1,42 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{capture::Capture, deinit, init, read};
+use log::{Level, LevelFilter};
+use rusty_fork::rusty_fork_test;
+use std::process::Command;
+
+// init() can only be called once per process, so
+// have to run each of these tests in their own process.
+rusty_fork_test! {
+
+/// Each line of output becomes an entry at its stream's level.
+#[test]
+fn captures_stdout_and_stderr() {
+    let path = path("capture");
+    let _guard = init(&path, LevelFilter::Info).unwrap();
+    let status = Capture::new("tool")
+        .stderr_level(Level::Error)
+        .run(Command::new("sh").args(["-c", "echo one; echo two >&2; printf three"]))
+        .unwrap();
+    assert!(status.success());
+    deinit();
+
+    let mut entries: Vec<_> = read(&path)
+        .unwrap()
+        .map(|entry| (entry.body, entry.level, entry.target))
+        .collect();
+    entries.sort();
+    assert_eq!(
+        vec![
+            ("one".to_string(), Level::Info, "tool".to_string()),
+            ("three".to_string(), Level::Info, "tool".to_string()),
+            ("two".to_string(), Level::Error, "tool".to_string()),
+        ],
+        entries
+    );
+}
+
+}
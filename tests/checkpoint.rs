@@ -0,0 +1,62 @@
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{
+    checkpoint::{resume, Checkpoint},
+    read, LoggerBuilder,
+};
+use rusty_fork::rusty_fork_test;
+use std::path::Path;
+
+/// Returns the bodies of the entries after a checkpoint, after passing it
+/// through JSON.
+fn resumed(path: &Path, checkpoint: Checkpoint) -> Vec<String> {
+    let json = serde_json::to_string(&checkpoint).unwrap();
+    let checkpoint: Checkpoint = serde_json::from_str(&json).unwrap();
+    resume(path, &checkpoint)
+        .unwrap()
+        .map(|entry| entry.body)
+        .collect()
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Reading resumes after any entry, within members and at their ends.
+#[test]
+fn resumes_after_every_entry() {
+    let path = path("checkpoints");
+    let guard = LoggerBuilder::new().gzip_member_entries(10).init(&path).unwrap();
+    for i in 0..25 {
+        log::info!("entry {}", i);
+    }
+    drop(guard);
+
+    let bodies: Vec<_> = (0..25).map(|i| format!("entry {}", i)).collect();
+    let mut entries = read(&path).unwrap();
+    for read in 0..=25 {
+        assert_eq!(bodies[read..], resumed(&path, entries.checkpoint().unwrap()));
+        entries.next();
+    }
+}
+
+/// Checkpoints stay valid when a file is appended to.
+#[test]
+fn resumes_appended_file() {
+    let path = path("checkpoints_append");
+    let guard = LoggerBuilder::new().init(&path).unwrap();
+    log::info!("first");
+    log::info!("second");
+    drop(guard);
+    let mut entries = read(&path).unwrap();
+    entries.next();
+    let checkpoint = entries.checkpoint().unwrap();
+
+    let guard = LoggerBuilder::new().append(true).init(&path).unwrap();
+    log::info!("third");
+    drop(guard);
+    assert_eq!(vec!["second", "new session", "third"], resumed(&path, checkpoint));
+}
+
+}
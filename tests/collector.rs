@@ -0,0 +1,67 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{read, LoggerBuilder};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+use std::{io::Write, os::unix::net::UnixStream};
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Entries from several clients end up in one well-formed file.
+#[test]
+fn collects_from_many_clients() {
+    let socket = path("collector_socket");
+    let _ = std::fs::remove_file(&socket);
+    let path = path("collector");
+    let collector = LoggerBuilder::new().collector(&socket, &path).unwrap();
+
+    // a raw client that exits in the middle of an entry
+    let mut raw = UnixStream::connect(&socket).unwrap();
+    raw.write_all(b"{\"offset\":{\"secs\":0,\"nanos\":0},\"level\":\"INFO\",\"target\":\"raw\",\"body\":\"whole\"}\n{\"offset\":").unwrap();
+    drop(raw);
+
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .init_client(&socket)
+        .unwrap();
+    log::info!(target: "client", "first");
+    log::debug!(target: "client", "filtered");
+    log::warn!(target: "client", "second");
+    drop(guard);
+
+    collector.finish().unwrap();
+    assert!(!socket.exists());
+
+    let mut entries: Vec<_> = read(&path).unwrap().collect();
+    entries.sort_by(|a, b| a.body.cmp(&b.body));
+    let bodies: Vec<_> = entries.iter().map(|entry| entry.body.as_str()).collect();
+    assert_eq!(vec!["first", "second", "whole"], bodies);
+}
+
+/// Lines too long to be entries are skipped without losing the entries
+/// after them.
+#[test]
+fn skips_long_lines() {
+    let socket = path("collector_long_socket");
+    let _ = std::fs::remove_file(&socket);
+    let path = path("collector_long");
+    let collector = LoggerBuilder::new().collector(&socket, &path).unwrap();
+
+    let mut raw = UnixStream::connect(&socket).unwrap();
+    let mut long = vec![b'x'; 3 << 20];
+    long.push(b'\n');
+    raw.write_all(&long).unwrap();
+    raw.write_all(b"{\"offset\":{\"secs\":0,\"nanos\":0},\"level\":\"INFO\",\"target\":\"raw\",\"body\":\"after\"}\n").unwrap();
+    drop(raw);
+    collector.finish().unwrap();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["after"], bodies);
+}
+
+}
@@ -0,0 +1,9 @@
+use std::path::{Path, PathBuf};
+
+/// Creates a path for a test.
+/// The given name must be unique across all tests.
+pub fn path(name: &str) -> PathBuf {
+    let mut path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    path.set_extension("jsonl.gzip");
+    path
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{read, JsonlGzipLogger, LoggerBuilder};
+use log::{Level, LevelFilter, Log, Record};
+
+/// Logs one record directly, without going through the global logger.
+fn log(logger: &dyn Log, level: Level, target: &str, body: &str) {
+    logger.log(
+        &Record::builder()
+            .level(level)
+            .target(target)
+            .args(format_args!("{}", body))
+            .build(),
+    );
+}
+
+/// A logger that is never installed writes and finishes its file.
+#[test]
+fn logs_without_installing() {
+    let path = path("composable");
+    let logger = JsonlGzipLogger::new(&path, LevelFilter::Info).unwrap();
+    log(&logger, Level::Info, "app", "kept");
+    log(&logger, Level::Debug, "app", "filtered");
+    assert!(!logger.enabled(&log::Metadata::builder().level(Level::Trace).build()));
+    drop(logger);
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    assert_eq!(1, entries.len());
+    assert_eq!("kept", entries[0].body);
+    assert_eq!("app", entries[0].target);
+}
+
+/// Built loggers can be combined by a dispatcher.
+#[test]
+fn dispatches_to_several_loggers() {
+    let (quiet, verbose) = (path("composable_quiet"), path("composable_verbose"));
+    let loggers: Vec<Box<dyn Log>> = vec![
+        Box::new(
+            LoggerBuilder::new()
+                .level(LevelFilter::Warn)
+                .build(&quiet)
+                .unwrap(),
+        ),
+        Box::new(
+            LoggerBuilder::new()
+                .level(LevelFilter::Trace)
+                .build(&verbose)
+                .unwrap(),
+        ),
+    ];
+    for logger in &loggers {
+        log(logger.as_ref(), Level::Error, "app", "error");
+        log(logger.as_ref(), Level::Trace, "app", "trace");
+    }
+    drop(loggers);
+
+    let bodies = |path| -> Vec<_> { read(path).unwrap().map(|entry| entry.body).collect() };
+    assert_eq!(vec!["error"], bodies(&quiet));
+    assert_eq!(vec!["error", "trace"], bodies(&verbose));
+}
@@ -0,0 +1,66 @@
+use jsonl_gzip_logger::read;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Uncompressed JSON lines holding two entries.
+const LINES: &[u8] = br#"{"offset":{"secs":0,"nanos":0},"level":"INFO","target":"a","body":"one"}
+{"offset":{"secs":1,"nanos":0},"level":"WARN","target":"b","body":"two"}
+"#;
+
+/// Writes the bytes to a file named for the test and returns its path.
+fn file(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+/// Reads the bodies of a file.
+fn bodies(path: &Path) -> Vec<String> {
+    read(path).unwrap().map(|entry| entry.body).collect()
+}
+
+/// Uncompressed files are read as they are.
+#[test]
+fn reads_plain_text() {
+    assert_eq!(vec!["one", "two"], bodies(&file("detect.jsonl", LINES)));
+    assert!(bodies(&file("detect_empty.jsonl", b"")).is_empty());
+}
+
+/// Zstd files, including ones of several frames, are recognized.
+#[cfg(feature = "zstd")]
+#[test]
+fn reads_zstd() {
+    let (first, second) = LINES.split_at(LINES.iter().position(|&b| b == b'\n').unwrap() + 1);
+    let mut bytes = zstd::encode_all(first, 3).unwrap();
+    bytes.extend(zstd::encode_all(second, 3).unwrap());
+    assert_eq!(
+        vec!["one", "two"],
+        bodies(&file("detect.jsonl.zst", &bytes))
+    );
+}
+
+/// Zstd files cannot be read without the `zstd` feature.
+#[cfg(not(feature = "zstd"))]
+#[test]
+fn rejects_zstd_without_feature() {
+    let path = file("detect.jsonl.zst", &[0x28, 0xb5, 0x2f, 0xfd, 0]);
+    let error = read(path).err().unwrap();
+    assert_eq!(std::io::ErrorKind::Unsupported, error.kind());
+}
+
+/// LZ4 files are recognized.
+#[cfg(feature = "lz4")]
+#[test]
+fn reads_lz4() {
+    use std::io::Write;
+
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+    encoder.write_all(LINES).unwrap();
+    let bytes = encoder.finish().unwrap();
+    assert_eq!(
+        vec!["one", "two"],
+        bodies(&file("detect.jsonl.lz4", &bytes))
+    );
+}
@@ -0,0 +1,135 @@
+#![cfg(feature = "encryption")]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{
+    deinit,
+    encryption::{EncryptionKey, IdentityKey},
+    read, read_encrypted, read_with_identity, read_with_key, LoggerBuilder,
+};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Encrypted logs can only be read back with the key.
+#[test]
+fn encrypted_log_round_trip() {
+    let path = path("encrypted");
+    let key = EncryptionKey::generate().unwrap();
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .encryption(key.clone())
+        .init(&path)
+        .unwrap();
+    log::info!(target: "user", "email=someone@example.com");
+    log::logger().flush();
+
+    // entries flushed so far are readable before the file is finished
+    let entries: Vec<_> = read_encrypted(&path, &key).unwrap().collect();
+    assert_eq!(1, entries.len());
+
+    log::warn!(target: "user", "second");
+    deinit();
+
+    let entries: Vec<_> = read_encrypted(&path, &key).unwrap().collect();
+    assert_eq!(2, entries.len());
+    assert_eq!("email=someone@example.com", entries[0].body);
+    assert_eq!("second", entries[1].body);
+
+    let contents = std::fs::read(&path).unwrap();
+    assert_eq!(b"JGLE", &contents[..4]);
+    assert_eq!(0, read(&path).unwrap().count());
+    let wrong = EncryptionKey::from_bytes([0; 32]);
+    assert_eq!(0, read_encrypted(&path, &wrong).unwrap().count());
+}
+
+/// Logs encrypted to a recipient can only be read back with its identity.
+#[test]
+fn recipient_round_trip() {
+    let path = path("encrypted_to");
+    let identity = IdentityKey::generate().unwrap();
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .encryption_to(identity.recipient())
+        .init(&path)
+        .unwrap();
+    log::info!("first");
+    log::info!("second");
+    deinit();
+
+    let bodies: Vec<_> = read_with_identity(&path, &identity)
+        .unwrap()
+        .map(|entry| entry.body)
+        .collect();
+    assert_eq!(vec!["first", "second"], bodies);
+    let other = IdentityKey::generate().unwrap();
+    assert_eq!(0, read_with_identity(&path, &other).unwrap().count());
+    let key = EncryptionKey::from_bytes(*identity.as_bytes());
+    assert_eq!(0, read_encrypted(&path, &key).unwrap().count());
+}
+
+/// Body encryption leaves the other fields readable.
+#[test]
+fn body_encryption_round_trip() {
+    let path = path("body_encrypted");
+    let key = EncryptionKey::generate().unwrap();
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .body_encryption(key.clone())
+        .init(&path)
+        .unwrap();
+    log::warn!(target: "payments", "card=4111 1111 1111 1111");
+    deinit();
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    assert_eq!(1, entries.len());
+    assert_eq!(log::Level::Warn, entries[0].level);
+    assert_eq!("payments", entries[0].target);
+    assert!(!entries[0].body.contains("4111"));
+
+    let entries: Vec<_> = read(&path).unwrap().decrypt_bodies(&key).collect();
+    assert_eq!("card=4111 1111 1111 1111", entries[0].body);
+}
+
+/// One key file reads encrypted, body encrypted and plain files alike.
+#[test]
+fn read_with_key_file() {
+    let key_path = path("read_with_key.key");
+    let key = EncryptionKey::generate().unwrap();
+    std::fs::write(&key_path, key.to_hex()).unwrap();
+    let key = EncryptionKey::from_file(&key_path).unwrap();
+
+    let builders = [
+        (
+            "read_with_key_file",
+            LoggerBuilder::new().encryption(key.clone()),
+        ),
+        (
+            "read_with_key_body",
+            LoggerBuilder::new().body_encryption(key.clone()),
+        ),
+        (
+            "read_with_key_both",
+            LoggerBuilder::new()
+                .encryption(key.clone())
+                .body_encryption(key.clone()),
+        ),
+        ("read_with_key_plain", LoggerBuilder::new()),
+    ];
+    for (name, builder) in builders {
+        let path = path(name);
+        let _guard = builder.level(LevelFilter::Info).init(&path).unwrap();
+        log::info!("secret {}", name);
+        deinit();
+
+        let entries: Vec<_> = read_with_key(&path, &key).unwrap().collect();
+        assert_eq!(1, entries.len(), "{}", name);
+        assert_eq!(format!("secret {}", name), entries[0].body);
+    }
+}
+
+}
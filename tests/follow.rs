@@ -0,0 +1,68 @@
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{follow, LoggerBuilder};
+use rusty_fork::rusty_fork_test;
+use std::{sync::mpsc, thread, time::Duration};
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Following a file yields its entries and then the ones flushed later.
+#[test]
+fn follows_live_file() {
+    let path = path("follow");
+    let _guard = LoggerBuilder::new().gzip_member_entries(2).init(&path).unwrap();
+    log::info!("before");
+    log::logger().flush();
+
+    let (sender, bodies) = mpsc::channel();
+    let entries = follow(&path).unwrap();
+    thread::spawn(move || {
+        for entry in entries.take(4) {
+            sender.send(entry.body).unwrap();
+        }
+    });
+    let next = || bodies.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!("before", next());
+    for body in ["first", "second", "third"] {
+        log::info!("{}", body);
+        log::logger().flush();
+        assert_eq!(body, next());
+    }
+}
+
+/// Following continues in the new file after a rotation.
+#[test]
+fn follows_rotations() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("follows_rotations");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.jsonl.gz");
+    let guard = LoggerBuilder::new().init(&path).unwrap();
+    log::info!("first");
+    log::logger().flush();
+
+    let (sender, bodies) = mpsc::channel();
+    let entries = follow(&path).unwrap();
+    thread::spawn(move || {
+        for entry in entries.take(4) {
+            sender.send(entry.body).unwrap();
+        }
+    });
+    let next = || bodies.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!("first", next());
+    log::info!("second");
+    guard.handle().rotate().unwrap();
+    log::info!("third");
+    log::logger().flush();
+    assert_eq!("second", next());
+    assert_eq!("third", next());
+    guard.handle().rotate().unwrap();
+    log::info!("fourth");
+    log::logger().flush();
+    assert_eq!("fourth", next());
+}
+
+}
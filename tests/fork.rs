@@ -0,0 +1,131 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{deinit, read, tcp::TcpSink, ForkPolicy, LogEntry, LoggerBuilder};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+use std::{
+    io::{BufRead, BufReader},
+    net::TcpListener,
+};
+
+/// Runs `child` in a forked child process and waits for it to exit.
+///
+/// Returns the process ID of the child.
+fn fork(child: impl FnOnce()) -> u32 {
+    // SAFETY: the tests are single threaded when forking, and the child
+    // exits without returning into the test harness
+    unsafe {
+        let pid = libc::fork();
+        assert!(pid >= 0);
+        if pid == 0 {
+            child();
+            libc::_exit(0);
+        }
+        let mut status = 0;
+        libc::waitpid(pid, &mut status, 0);
+        pid as u32
+    }
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Children discard their entries and leave the parent's file intact.
+#[test]
+fn child_entries_discarded() {
+    let path = path("fork_disable");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .init(&path)
+        .unwrap();
+    log::info!("before");
+    fork(|| {
+        log::info!("child");
+        log::logger().flush();
+        deinit();
+    });
+    log::info!("after");
+    deinit();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["before", "after"], bodies);
+}
+
+/// Children log to their own file next to the parent's.
+#[test]
+fn child_reopens_own_file() {
+    let path = path("fork_reopen");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .fork_policy(ForkPolicy::Reopen)
+        .init(&path)
+        .unwrap();
+    log::info!("parent");
+    let pid = fork(|| {
+        log::info!("child");
+        deinit();
+    });
+    deinit();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["parent"], bodies);
+    let child = path.with_file_name(format!("fork_reopen.{}.jsonl.gzip", pid));
+    let bodies: Vec<_> = read(&child).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["child"], bodies);
+}
+
+/// Entries record the process that wrote them when enabled.
+#[test]
+fn entries_record_pid() {
+    let path = path("fork_pid");
+    let _guard = LoggerBuilder::new()
+        .pid(true)
+        .fork_policy(ForkPolicy::Reopen)
+        .init(&path)
+        .unwrap();
+    log::info!("parent");
+    let pid = fork(|| {
+        log::info!("child");
+        deinit();
+    });
+    deinit();
+
+    let parent: Vec<_> = read(&path).unwrap().map(|entry| entry.pid).collect();
+    assert_eq!(vec![Some(std::process::id())], parent);
+    let child = path.with_file_name(format!("fork_pid.{}.jsonl.gzip", pid));
+    let child: Vec<_> = read(&child).unwrap().map(|entry| entry.pid).collect();
+    assert_eq!(vec![Some(pid)], child);
+}
+
+/// Children of a TCP logger discard their entries instead of blocking on
+/// the parent's sending thread.
+#[test]
+fn tcp_child_entries_discarded() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .init_tcp(TcpSink::new(address))
+        .unwrap();
+    log::info!("before");
+    fork(|| {
+        log::info!("child");
+        log::logger().flush();
+    });
+    log::info!("after");
+    log::logger().flush();
+    let (stream, _) = listener.accept().unwrap();
+    let bodies: Vec<_> = BufReader::new(stream)
+        .lines()
+        .take(2)
+        .map(|line| serde_json::from_str::<LogEntry>(&line.unwrap()).unwrap().body)
+        .collect();
+    assert_eq!(vec!["before", "after"], bodies);
+    drop(guard);
+}
+
+}
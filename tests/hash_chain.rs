@@ -0,0 +1,93 @@
+#![cfg(feature = "hash-chain")]
+
+mod common;
+
+use common::path;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use jsonl_gzip_logger::{chain::verify, deinit, read, LoggerBuilder};
+use log::LevelFilter;
+use std::io::{Read, Write};
+
+/// Replaces the decompressed lines of a log file.
+fn rewrite(path: &std::path::Path, edit: impl FnOnce(&mut Vec<&str>)) {
+    let mut contents = String::new();
+    GzDecoder::new(std::fs::File::open(path).unwrap())
+        .read_to_string(&mut contents)
+        .unwrap();
+    let mut lines: Vec<_> = contents.lines().collect();
+    edit(&mut lines);
+    let mut encoder = GzEncoder::new(std::fs::File::create(path).unwrap(), Compression::fast());
+    for line in lines {
+        writeln!(encoder, "{}", line).unwrap();
+    }
+    encoder.finish().unwrap();
+}
+
+/// Deleted and reordered entries break the chain.
+#[test]
+fn removed_and_reordered_entries_detected() {
+    let path = path("hash_chain");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .hash_chain(true)
+        .init(&path)
+        .unwrap();
+    for i in 0..4 {
+        log::info!("entry {}", i);
+    }
+    deinit();
+
+    assert_eq!(4, read(&path).unwrap().count());
+    let verification = verify(&path).unwrap();
+    assert!(verification.is_valid());
+    assert_eq!(4, verification.valid);
+
+    rewrite(&path, |lines| lines.swap(2, 3));
+    assert_eq!(vec![3, 4], verify(&path).unwrap().broken);
+
+    rewrite(&path, |lines| {
+        lines.swap(2, 3);
+        lines.remove(1);
+    });
+    let verification = verify(&path).unwrap();
+    assert_eq!(2, verification.valid);
+    assert_eq!(vec![2], verification.broken);
+}
+
+/// Keyed chains only verify with their key, so recomputing the links of an
+/// edited file does not help without it.
+#[cfg(feature = "signing")]
+#[test]
+fn keyed_chain_needs_key() {
+    use jsonl_gzip_logger::{chain::verify_keyed, signing::SigningKey};
+    use log::Log;
+
+    let path = path("keyed_hash_chain");
+    let key = SigningKey::from_bytes(b"chain key".to_vec());
+    let logger = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .keyed_hash_chain(key.clone())
+        .build(&path)
+        .unwrap();
+    for i in 0..3 {
+        logger.log(
+            &log::Record::builder()
+                .args(format_args!("entry {}", i))
+                .level(log::Level::Info)
+                .build(),
+        );
+    }
+    drop(logger);
+
+    let verification = verify_keyed(&path, &key).unwrap();
+    assert!(verification.is_valid());
+    assert_eq!(3, verification.valid);
+    let other = SigningKey::from_bytes(b"other key".to_vec());
+    assert_eq!(vec![2, 3], verify_keyed(&path, &other).unwrap().broken);
+    assert_eq!(vec![2, 3], verify(&path).unwrap().broken);
+
+    rewrite(&path, |lines| {
+        lines.remove(1);
+    });
+    assert_eq!(vec![2], verify_keyed(&path, &key).unwrap().broken);
+}
@@ -0,0 +1,127 @@
+#![cfg(feature = "http")]
+
+use jsonl_gzip_logger::{http::HttpServer, InitGuard, LogEntry, LoggerBuilder};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+};
+
+/// Installs a logger serving HTTP on a free port, returning its guard and
+/// address.
+fn init(name: &str) -> (InitGuard, SocketAddr) {
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .http_server(HttpServer::new(address).unwrap())
+        .init(path)
+        .unwrap();
+    (guard, address)
+}
+
+/// Sends a GET request, returning a reader positioned after the headers.
+fn get(address: SocketAddr, target: &str) -> BufReader<TcpStream> {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target).unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert!(line.starts_with("HTTP/1.1 200"), "{}", line);
+    while line != "\r\n" {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+    reader
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Recent entries are returned as JSON lines, newest last.
+#[test]
+fn recent_entries() {
+    let (_guard, address) = init("http_recent");
+    for i in 0..3 {
+        log::info!("entry {}", i);
+    }
+    let mut body = String::new();
+    get(address, "/logs/recent?n=2").read_to_string(&mut body).unwrap();
+    let bodies: Vec<_> = body
+        .lines()
+        .map(|line| serde_json::from_str::<LogEntry>(line).unwrap().body)
+        .collect();
+    assert_eq!(vec!["entry 1", "entry 2"], bodies);
+}
+
+/// New entries are streamed as Server-Sent Events.
+#[test]
+fn event_stream() {
+    let (_guard, address) = init("http_stream");
+    let mut events = get(address, "/logs/stream");
+    log::warn!(target: "device", "overheating");
+    let mut line = String::new();
+    events.read_line(&mut line).unwrap();
+    let entry: LogEntry = serde_json::from_str(line.strip_prefix("data: ").unwrap()).unwrap();
+    assert_eq!("device", entry.target);
+    assert_eq!("overheating", entry.body);
+}
+
+/// Streams only send entries matching their level and target filters.
+#[test]
+fn filtered_event_stream() {
+    let (_guard, address) = init("http_filtered");
+    let mut events = get(address, "/logs/stream?level=warn&target=app::db");
+    log::error!(target: "app::web", "wrong target");
+    log::info!(target: "app::db", "too verbose");
+    log::warn!(target: "app::db::pool", "exhausted");
+    let mut line = String::new();
+    events.read_line(&mut line).unwrap();
+    let entry: LogEntry = serde_json::from_str(line.strip_prefix("data: ").unwrap()).unwrap();
+    assert_eq!("exhausted", entry.body);
+}
+
+/// New entries are streamed as WebSocket text messages.
+#[cfg(feature = "websocket")]
+#[test]
+fn websocket_stream() {
+    let (_guard, address) = init("http_websocket");
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "GET /logs/ws?level=info HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n"
+    )
+    .unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut response = String::new();
+    while !response.ends_with("\r\n\r\n") {
+        reader.read_line(&mut response).unwrap();
+    }
+    assert!(response.starts_with("HTTP/1.1 101"), "{}", response);
+    assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+
+    log::debug!("filtered");
+    log::info!(target: "dashboard", "hello");
+    let mut header = [0; 2];
+    reader.read_exact(&mut header).unwrap();
+    assert_eq!(0x81, header[0]);
+    let len = match header[1] {
+        126 => {
+            let mut extended = [0; 2];
+            reader.read_exact(&mut extended).unwrap();
+            u16::from_be_bytes(extended) as usize
+        }
+        len => len as usize,
+    };
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload).unwrap();
+    let entry: LogEntry = serde_json::from_slice(&payload).unwrap();
+    assert_eq!("hello", entry.body);
+}
+
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{
+    import::{import_jsonl, import_with, EnvLoggerParser, Import},
+    read,
+};
+use log::Level;
+use std::{fs, time::Duration};
+
+/// Entries in plain JSON lines are converted, and other lines skipped.
+#[test]
+fn jsonl() {
+    let src = path("import_jsonl_src").with_extension("jsonl");
+    let dst = path("import_jsonl_dst");
+    fs::write(
+        &src,
+        concat!(
+            r#"{"offset":{"secs":2,"nanos":0},"level":"WARN","target":"db","body":"slow"}"#,
+            "\n\nnot json\r\n",
+            r#"{"offset":{"secs":3,"nanos":0},"level":"INFO","target":"db","body":"ok"}"#,
+            "\r\n",
+        ),
+    )
+    .unwrap();
+
+    let import = import_jsonl(&src, &dst).unwrap();
+    assert_eq!(
+        Import {
+            imported: 2,
+            skipped: 2
+        },
+        import
+    );
+    let entries: Vec<_> = read(&dst).unwrap().collect();
+    assert_eq!(Level::Warn, entries[0].level);
+    assert_eq!("slow", entries[0].body);
+    assert_eq!(Duration::from_secs(3), entries[1].offset);
+}
+
+/// Text logs are converted by a line parser.
+#[test]
+fn env_logger_text() {
+    let src = path("import_text_src").with_extension("log");
+    let dst = path("import_text_dst");
+    fs::write(
+        &src,
+        "[2024-05-01T12:00:00Z INFO  app] started\n\
+         [2024-05-01T12:00:01Z ERROR app::db] failed\n",
+    )
+    .unwrap();
+
+    let import = import_with(&src, &dst, EnvLoggerParser::new()).unwrap();
+    assert_eq!(2, import.imported);
+    let entries: Vec<_> = read(&dst).unwrap().collect();
+    assert_eq!("started", entries[0].body);
+    assert_eq!(Duration::from_secs(1), entries[1].offset);
+    assert_eq!("app::db", entries[1].target);
+}
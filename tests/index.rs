@@ -0,0 +1,165 @@
+use jsonl_gzip_logger::{
+    clock::ManualClock,
+    index::{index_path, read_from_entry, read_index},
+    read_range, read_rev, tail, LoggerBuilder,
+};
+use rusty_fork::rusty_fork_test;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Creates an empty directory for a test.
+fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Logs 25 entries a second apart to an indexed file with members of 10.
+fn log_entries(builder: LoggerBuilder, path: &Path) {
+    let clock = ManualClock::new();
+    let guard = builder
+        .clock(clock.clone())
+        .gzip_member_entries(10)
+        .index(true)
+        .init(path)
+        .unwrap();
+    for i in 0..25 {
+        clock.set(Duration::from_secs(i));
+        log::info!("entry {}", i);
+    }
+    drop(guard);
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// The index records where each member starts.
+#[test]
+fn indexes_members() {
+    let path = dir("indexes_members").join("app.jsonl.gz");
+    log_entries(LoggerBuilder::new(), &path);
+
+    let index = read_index(&path).unwrap();
+    let entries: Vec<_> = index.iter().map(|member| member.entry).collect();
+    assert_eq!(vec![0, 10, 20], entries);
+    let offsets: Vec<_> = index.iter().map(|member| member.offset.as_secs()).collect();
+    assert_eq!(vec![0, 10, 20], offsets);
+    assert_eq!(0, index[0].position);
+    assert!(index.windows(2).all(|pair| pair[0].position < pair[1].position));
+}
+
+/// Reading from an entry starts at the member containing it.
+#[test]
+fn reads_from_entry() {
+    let path = dir("reads_from_entry").join("app.jsonl.gz");
+    log_entries(LoggerBuilder::new(), &path);
+
+    for start in [0, 9, 10, 13, 24, 25] {
+        let bodies: Vec<_> = read_from_entry(&path, start)
+            .unwrap()
+            .map(|entry| entry.body)
+            .collect();
+        let expected: Vec<_> = (start..25).map(|i| format!("entry {}", i)).collect();
+        assert_eq!(expected, bodies);
+    }
+
+    // without the index the file is read from the start
+    fs::remove_file(index_path(&path)).unwrap();
+    let entry = read_from_entry(&path, 13).unwrap().next().unwrap();
+    assert_eq!("entry 13", entry.body);
+}
+
+/// Time ranges are read from the members holding them, or from the whole
+/// file without an index.
+#[test]
+fn reads_range() {
+    let path = dir("reads_range").join("app.jsonl.gz");
+    log_entries(LoggerBuilder::new(), &path);
+
+    let range = |from, to| -> Vec<_> {
+        read_range(&path, Duration::from_secs(from), Duration::from_secs(to))
+            .unwrap()
+            .map(|entry| entry.offset.as_secs())
+            .collect()
+    };
+    assert_eq!((12..17).collect::<Vec<_>>(), range(12, 17));
+    assert_eq!((8..22).collect::<Vec<_>>(), range(8, 22));
+    assert_eq!((20..25).collect::<Vec<_>>(), range(20, 100));
+    assert!(range(5, 5).is_empty());
+
+    fs::remove_file(index_path(&path)).unwrap();
+    assert_eq!((12..17).collect::<Vec<_>>(), range(12, 17));
+}
+
+/// The last entries are read from the last members, or from the whole file
+/// without an index.
+#[test]
+fn reads_tail() {
+    let path = dir("reads_tail").join("app.jsonl.gz");
+    log_entries(LoggerBuilder::new(), &path);
+
+    let last = |n| -> Vec<_> {
+        tail(&path, n)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.offset.as_secs())
+            .collect()
+    };
+    for n in [0, 3, 5, 12, 25, 100] {
+        let expected: Vec<_> = (25u64.saturating_sub(n as u64)..25).collect();
+        assert_eq!(expected, last(n));
+    }
+
+    fs::remove_file(index_path(&path)).unwrap();
+    assert_eq!(vec![22, 23, 24], last(3));
+}
+
+/// Entries are read newest first, one member at a time or from the whole
+/// file without an index.
+#[test]
+fn reads_reverse() {
+    let path = dir("reads_reverse").join("app.jsonl.gz");
+    log_entries(LoggerBuilder::new(), &path);
+
+    let reversed = || -> Vec<_> {
+        read_rev(&path)
+            .unwrap()
+            .map(|entry| entry.offset.as_secs())
+            .collect()
+    };
+    assert_eq!((0..25).rev().collect::<Vec<_>>(), reversed());
+
+    fs::remove_file(index_path(&path)).unwrap();
+    assert_eq!((0..25).rev().collect::<Vec<_>>(), reversed());
+}
+
+/// Rotated files keep their indices.
+#[test]
+fn rotates_index() {
+    let dir = dir("rotates_index");
+    let path = dir.join("app.jsonl.gz");
+    let guard = LoggerBuilder::new().index(true).init(&path).unwrap();
+    log::info!("first");
+    guard.handle().rotate().unwrap();
+    log::info!("second");
+    drop(guard);
+
+    let rotated = dir.join("app.1.jsonl.gz");
+    assert_eq!(1, read_index(&rotated).unwrap().len());
+    assert_eq!("first", read_from_entry(&rotated, 0).unwrap().next().unwrap().body);
+    assert_eq!("second", read_from_entry(&path, 0).unwrap().next().unwrap().body);
+}
+
+/// Indexed files cannot be appended to.
+#[test]
+fn refuses_to_append() {
+    let path = dir("refuses_to_append").join("app.jsonl.gz");
+    assert!(LoggerBuilder::new().index(true).append(true).init(&path).is_err());
+}
+
+}
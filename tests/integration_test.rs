@@ -1,15 +1,13 @@
-use jsonl_gzip_logger::{init, read};
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{
+    clock::ManualClock, codec::Gzip, head, init, init_from_env, install_panic_hook, read,
+    read_with_codec, InitError, LoggerBuilder, SyncPolicy,
+};
 use log::{Level, LevelFilter};
 use rusty_fork::rusty_fork_test;
-use std::path::{Path, PathBuf};
-
-/// Creates a path for a test.
-/// The given name must be unique across all tests.
-fn path(name: &str) -> PathBuf {
-    let mut path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
-    path.set_extension("jsonl.gzip");
-    path
-}
+use std::time::{Duration, SystemTime};
 
 // init() can only be called once per process, so
 // have to run each of these tests in their own process.
@@ -18,7 +16,7 @@ rusty_fork_test! {
 #[test]
 fn empty_log() {
     let path = path("empty");
-    init(&path, LevelFilter::Info).unwrap();
+    let _guard = init(&path, LevelFilter::Info).unwrap();
     log::logger().flush();
 
     let iter = read(&path).unwrap();
@@ -29,7 +27,7 @@ fn empty_log() {
 #[test]
 fn one_log() {
     let path = path("one");
-    init(&path, LevelFilter::Info).unwrap();
+    let _guard = init(&path, LevelFilter::Info).unwrap();
     log::info!(target: "foo", "This is a log!");
     log::logger().flush();
 
@@ -47,7 +45,7 @@ fn one_log() {
 #[test]
 fn two_logs() {
     let path = path("two");
-    init(&path, LevelFilter::Trace).unwrap();
+    let _guard = init(&path, LevelFilter::Trace).unwrap();
     log::info!(target: "foo", "This is foo log!");
     log::debug!(target: "bar", "This is bar log!");
     log::logger().flush();
@@ -67,4 +65,708 @@ fn two_logs() {
     assert_eq!(0, iter.count());
 }
 
+#[test]
+fn manual_clock_offsets() {
+    let path = path("manual_clock");
+    let clock = ManualClock::new();
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .clock(clock.clone())
+        .init(&path)
+        .unwrap();
+    clock.set(Duration::from_secs(90));
+    log::info!(target: "replay", "Historical entry");
+    log::logger().flush();
+
+    let entry = read(&path).unwrap().next().unwrap();
+    assert_eq!(Duration::from_secs(90), entry.offset);
+    assert_eq!("Historical entry", entry.body);
+}
+
+#[test]
+fn session_id_in_every_entry() {
+    let path = path("session_id");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .session_id(true)
+        .init(&path)
+        .unwrap();
+    log::info!("first");
+    log::info!("second");
+    log::logger().flush();
+
+    let sessions: Vec<_> = read(&path).unwrap().map(|entry| entry.session.unwrap()).collect();
+    assert_eq!(2, sessions.len());
+    assert_eq!(36, sessions[0].len());
+    assert_eq!(sessions[0], sessions[1]);
+}
+
+#[test]
+fn timestamps_in_every_entry() {
+    let path = path("timestamps");
+    let before = SystemTime::now();
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .timestamps(true)
+        .init(&path)
+        .unwrap();
+    log::info!("stamped");
+    log::logger().flush();
+    let after = SystemTime::now();
+
+    let time = read(&path).unwrap().next().unwrap().time.unwrap();
+    assert!(before <= time && time <= after);
+}
+
+#[test]
+fn source_location() {
+    let path = path("source_location");
+    let _guard = init(&path, LevelFilter::Info).unwrap();
+    log::info!("located");
+    log::logger().flush();
+
+    let entry = read(&path).unwrap().next().unwrap();
+    assert_eq!(Some(module_path!()), entry.module_path.as_deref());
+    assert_eq!(Some(file!()), entry.file.as_deref());
+    assert!(entry.line.is_some());
+}
+
+#[test]
+fn source_location_disabled() {
+    let path = path("source_location_disabled");
+    let _guard = LoggerBuilder::new()
+        .source_location(false)
+        .init(&path)
+        .unwrap();
+    log::info!("unlocated");
+    log::logger().flush();
+
+    let entry = read(&path).unwrap().next().unwrap();
+    assert_eq!(None, entry.module_path);
+    assert_eq!(None, entry.file);
+    assert_eq!(None, entry.line);
+}
+
+#[test]
+fn target_levels() {
+    let path = path("target_levels");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .target_level("hyper", LevelFilter::Warn)
+        .target_level("my_app", LevelFilter::Trace)
+        .init(&path)
+        .unwrap();
+    log::info!(target: "hyper::client", "quiet");
+    log::warn!(target: "hyper::client", "loud");
+    log::trace!(target: "my_app::db", "verbose");
+    log::debug!(target: "other", "filtered");
+    log::logger().flush();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["loud", "verbose"], bodies);
+}
+
+#[test]
+fn levels_change_at_runtime() {
+    let path = path("levels_change_at_runtime");
+    let _guard = init(&path, LevelFilter::Info).unwrap();
+    log::debug!("filtered");
+    jsonl_gzip_logger::set_level(LevelFilter::Debug);
+    log::debug!("debug");
+    jsonl_gzip_logger::set_target_level("noisy", LevelFilter::Off);
+    log::info!(target: "noisy", "silenced");
+    jsonl_gzip_logger::set_filters("warn,noisy=trace").unwrap();
+    assert!(jsonl_gzip_logger::set_filters("noisy=loud").is_err());
+    log::info!("filtered again");
+    log::trace!(target: "noisy", "trace");
+    log::logger().flush();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["debug", "trace"], bodies);
+}
+
+#[test]
+fn handle_controls_logger() {
+    let path = path("handle_controls_logger");
+    let guard = init(&path, LevelFilter::Info).unwrap();
+    let handle = guard.handle();
+    log::debug!("filtered");
+    handle.set_level(LevelFilter::Debug);
+    log::debug!("debug");
+    handle.flush();
+    assert_eq!("debug", read(&path).unwrap().next().unwrap().body);
+
+    // a handle only controls the logger it was returned for
+    drop(guard);
+    let _guard = init(&path, LevelFilter::Info).unwrap();
+    handle.set_level(LevelFilter::Trace);
+    log::debug!("filtered again");
+    log::logger().flush();
+    assert_eq!(0, read(&path).unwrap().count());
+}
+
+#[test]
+fn append_marks_sessions() {
+    let path = path("append_marks_sessions");
+    let _ = std::fs::remove_file(&path);
+    for body in ["first", "second"] {
+        let _guard = LoggerBuilder::new().append(true).init(&path).unwrap();
+        log::info!("{}", body);
+    }
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    let markers: Vec<_> = entries.iter().map(|entry| entry.is_session_marker()).collect();
+    assert_eq!(vec![true, false, true, false], markers);
+    assert_eq!("first", entries[1].body);
+    assert_eq!("second", entries[3].body);
+    assert!(entries[0].time.unwrap() <= entries[2].time.unwrap());
+    assert!(entries[0].session.is_some());
+    assert_ne!(entries[0].session, entries[2].session);
+}
+
+#[test]
+fn header_describes_process() {
+    let path = path("header_describes_process");
+    let before = SystemTime::now();
+    let _guard = LoggerBuilder::new().header(true).init(&path).unwrap();
+    log::info!("after header");
+    log::logger().flush();
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    let header = entries[0].header().unwrap();
+    assert_eq!(std::process::id(), header.pid);
+    assert_eq!(jsonl_gzip_logger::FORMAT_VERSION, header.format_version);
+    assert!(header.executable.is_some());
+    assert!(header.start <= SystemTime::now() && header.start >= before - Duration::from_secs(1));
+    assert_eq!("after header", entries[1].body);
+    assert!(entries[1].header().is_none());
+    assert_eq!(None, entries[1].session);
+}
+
+#[test]
+fn run_id_in_entries_and_header() {
+    let path = path("run_id_in_entries_and_header");
+    let _guard = LoggerBuilder::new()
+        .header(true)
+        .session_id(true)
+        .init(&path)
+        .unwrap();
+    log::info!("entry");
+    log::logger().flush();
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    let session = entries[0].header().unwrap().session;
+    assert_eq!(Some(session), entries[1].session);
+}
+
+#[test]
+fn levels_from_env() {
+    let path = path("levels_from_env");
+    std::env::set_var("RUST_LOG", "warn,app::db=debug");
+    let _guard = init_from_env(&path).unwrap();
+    log::info!(target: "app", "filtered");
+    log::debug!(target: "app::db", "query");
+    log::logger().flush();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["query"], bodies);
+}
+
+#[test]
+fn guard_finishes_file() {
+    let first = path("guard_first");
+    let guard = init(&first, LevelFilter::Info).unwrap();
+    log::info!("first");
+    drop(guard);
+    assert_eq!("first", read(&first).unwrap().next().unwrap().body);
+
+    // a guard whose logger is already gone leaves later loggers alone
+    let stale = init(&first, LevelFilter::Info).unwrap();
+    jsonl_gzip_logger::deinit();
+    let second = path("guard_second");
+    let _guard = init(&second, LevelFilter::Info).unwrap();
+    drop(stale);
+    log::info!("second");
+    log::logger().flush();
+    assert_eq!("second", read(&second).unwrap().next().unwrap().body);
+}
+
+#[test]
+fn panic_hook_flushes() {
+    let path = path("panic_hook");
+    let _guard = init(&path, LevelFilter::Info).unwrap();
+    install_panic_hook();
+    std::thread::spawn(|| {
+        log::info!("before the panic");
+        panic!("boom");
+    })
+    .join()
+    .unwrap_err();
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    assert_eq!("before the panic", entries[0].body);
+    assert_eq!("panic", entries[1].target);
+    assert!(entries[1].body.contains("boom"));
+}
+
+#[test]
+fn flush_interval() {
+    let path = path("flush_interval");
+    let _guard = LoggerBuilder::new()
+        .flush_interval(Duration::from_millis(20))
+        .init(&path)
+        .unwrap();
+    log::info!("eventually visible");
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while read(&path).unwrap().next().is_none() {
+        assert!(std::time::Instant::now() < deadline, "never flushed");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn sync_every_interval_flushes() {
+    let path = path("sync_every");
+    let _guard = LoggerBuilder::new()
+        .sync_policy(SyncPolicy::Every(Duration::from_millis(20)))
+        .init(&path)
+        .unwrap();
+    log::info!("eventually synced");
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while read(&path).unwrap().next().is_none() {
+        assert!(std::time::Instant::now() < deadline, "never flushed");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn sync_policies_keep_entries() {
+    for (i, policy) in [SyncPolicy::OnFlush, SyncPolicy::OnRotation].into_iter().enumerate() {
+        let path = path(&format!("sync_policy_{}", i));
+        let guard = LoggerBuilder::new()
+            .sync_policy(policy)
+            .init(&path)
+            .unwrap();
+        log::info!("first");
+        guard.handle().flush();
+        log::info!("second");
+        guard.handle().rotate().unwrap();
+        log::info!("third");
+        drop(guard);
+        assert_eq!(vec!["third"], read(&path).unwrap().map(|entry| entry.body).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn flush_every_entries() {
+    let path = path("flush_every_entries");
+    let _guard = LoggerBuilder::new()
+        .flush_every_entries(2)
+        .init(&path)
+        .unwrap();
+    log::info!("first");
+    assert_eq!(0, read(&path).unwrap().count());
+    log::info!("second");
+    assert_eq!(2, read(&path).unwrap().count());
+}
+
+#[test]
+fn flush_every_bytes() {
+    let path = path("flush_every_bytes");
+    let _guard = LoggerBuilder::new()
+        .flush_every_bytes(1)
+        .init(&path)
+        .unwrap();
+    log::info!("first");
+    assert_eq!(1, read(&path).unwrap().count());
+}
+
+#[test]
+fn long_path() {
+    // longer than the 260 characters Windows allows without a `\\?\` prefix
+    let mut dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    for _ in 0..4 {
+        dir.push("a".repeat(80));
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("long.jsonl.gz");
+    let _guard = init(&path, LevelFilter::Info).unwrap();
+    log::info!("deep");
+    log::logger().flush();
+
+    assert_eq!("deep", read(&path).unwrap().next().unwrap().body);
+}
+
+#[test]
+fn compression_and_buffer_options() {
+    let path = path("options");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .compression_level(9)
+        .buffer_size(64 * 1024)
+        .init(&path)
+        .unwrap();
+    for i in 0..100 {
+        log::info!("entry {}", i);
+    }
+    log::logger().flush();
+
+    assert_eq!(100, read(&path).unwrap().count());
+}
+
+#[test]
+fn refuses_to_overwrite() {
+    let path = path("no_overwrite");
+    std::fs::write(&path, "existing").unwrap();
+    let result = LoggerBuilder::new().overwrite(false).init(&path);
+    assert!(matches!(result, Err(InitError::CreateFileError(_))));
+    assert_eq!("existing", std::fs::read_to_string(&path).unwrap());
+}
+
+#[test]
+fn custom_codec() {
+    let path = path("codec");
+    let codec = Gzip { level: 9 };
+    let guard = LoggerBuilder::new().codec(codec).init(&path).unwrap();
+    log::info!("compressed");
+    drop(guard);
+
+    let entries: Vec<_> = read_with_codec(&path, &codec).unwrap().collect();
+    assert_eq!("compressed", entries[0].body);
+}
+
+#[test]
+fn live_file_readable_after_flush() {
+    let path = path("live");
+    let _guard = LoggerBuilder::new().level(LevelFilter::Info).init(&path).unwrap();
+    let mut flushed = 0;
+    for round in 0..5 {
+        for i in 0..1000 * round + 1 {
+            log::info!("flushed {} {}", i, i * 7919 % 10007);
+        }
+        flushed += 1000 * round + 1;
+        log::logger().flush();
+        // entries after the flush may have been partially compressed and
+        // written, leaving the stream mid-block
+        for i in 0..1000 * round {
+            log::info!("pending {} {}", i, i * 7919 % 10007);
+        }
+
+        let entries: Vec<_> = read(&path).unwrap().collect();
+        assert!(entries.len() >= flushed, "{} < {}", entries.len(), flushed);
+        assert!(entries.iter().all(|entry| !entry.body.is_empty()));
+        log::logger().flush();
+        flushed = read(&path).unwrap().count();
+    }
+}
+
+#[test]
+fn gzip_members() {
+    let path = path("members");
+    let guard = LoggerBuilder::new()
+        .gzip_member_entries(10)
+        .init(&path)
+        .unwrap();
+    for i in 0..25 {
+        log::info!("entry {}", i);
+    }
+    drop(guard);
+
+    assert_eq!(25, read(&path).unwrap().count());
+    // each member decodes on its own, starting at an entry
+    let bytes = std::fs::read(&path).unwrap();
+    let mut rest = &bytes[..];
+    let mut members = Vec::new();
+    while !rest.is_empty() {
+        let mut decoder = flate2::bufread::GzDecoder::new(rest);
+        let mut member = String::new();
+        std::io::Read::read_to_string(&mut decoder, &mut member).unwrap();
+        rest = decoder.into_inner();
+        members.push(member.lines().count());
+    }
+    assert_eq!(vec![10, 10, 5], members);
+}
+
+#[test]
+fn head_stops_early() {
+    let path = path("head");
+    let guard = LoggerBuilder::new()
+        .gzip_member_entries(100)
+        .init(&path)
+        .unwrap();
+    for i in 0..1000 {
+        log::info!("entry {} {}", i, i * 7919 % 10007);
+    }
+    drop(guard);
+    // damage the end, which reading the first entries must not reach
+    let mut bytes = std::fs::read(&path).unwrap();
+    let len = bytes.len();
+    bytes[len / 2..].fill(0xff);
+    std::fs::write(&path, bytes).unwrap();
+
+    let bodies: Vec<_> = head(&path, 3)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.body)
+        .collect();
+    assert_eq!(vec!["entry 0 0", "entry 1 7919", "entry 2 5831"], bodies);
+    assert!(read(&path).unwrap().count() < 1000);
+}
+
+#[test]
+fn max_body_bytes() {
+    let path = path("max_body");
+    let guard = LoggerBuilder::new().max_body_bytes(8).init(&path).unwrap();
+    log::info!("short");
+    log::info!("exactly8");
+    log::info!("much longer than allowed");
+    // cut before the multi-byte character instead of through it
+    log::info!("1234567é");
+    drop(guard);
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    let bodies: Vec<_> = entries.iter().map(|entry| entry.body.as_str()).collect();
+    assert_eq!(vec!["short", "exactly8", "much lon", "1234567"], bodies);
+    let truncated: Vec<_> = entries.iter().map(|entry| entry.truncated).collect();
+    assert_eq!(vec![false, false, true, true], truncated);
+}
+
+#[test]
+fn sample_verbose_levels() {
+    let path = path("sample");
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Trace)
+        .sample(Level::Trace, 4)
+        .init(&path)
+        .unwrap();
+    for i in 0..10 {
+        log::trace!("trace {}", i);
+        log::warn!("warn {}", i);
+    }
+    drop(guard);
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    let traces: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.level == Level::Trace)
+        .map(|entry| entry.body.as_str())
+        .collect();
+    assert_eq!(vec!["trace 0", "trace 4", "trace 8"], traces);
+    assert_eq!(10, entries.iter().filter(|entry| entry.level == Level::Warn).count());
+}
+
+#[test]
+fn handle_changes_sampling() {
+    let path = path("handle_sampling");
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Debug)
+        .init(&path)
+        .unwrap();
+    let handle = guard.handle();
+    for i in 0..4 {
+        log::debug!("before {}", i);
+    }
+    handle.set_sampling(Level::Debug, 2);
+    for i in 0..4 {
+        log::debug!("sampled {}", i);
+    }
+    handle.set_sampling(Level::Debug, 1);
+    log::debug!("after");
+    drop(guard);
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(
+        vec!["before 0", "before 1", "before 2", "before 3", "sampled 0", "sampled 2", "after"],
+        bodies
+    );
+}
+
+#[test]
+fn collapse_repeats() {
+    let path = path("collapse_repeats");
+    let guard = LoggerBuilder::new()
+        .collapse_repeats(true)
+        .init(&path)
+        .unwrap();
+    for attempt in 0..4 {
+        log::warn!(target: "db", "connection refused");
+        log::info!(target: "db", "attempt {}", attempt % 2);
+    }
+    for _ in 0..3 {
+        log::warn!(target: "db", "connection refused");
+    }
+    log::error!(target: "db", "giving up");
+    log::error!(target: "db", "giving up");
+    drop(guard);
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    let bodies: Vec<_> = entries.iter().map(|entry| entry.body.as_str()).collect();
+    assert_eq!(
+        vec![
+            "connection refused",
+            "attempt 0",
+            "connection refused",
+            "attempt 1",
+            "connection refused",
+            "attempt 0",
+            "connection refused",
+            "attempt 1",
+            "connection refused",
+            "message repeated 2 times",
+            "giving up",
+            "message repeated 1 times",
+        ],
+        bodies
+    );
+    let notice = &entries[9];
+    assert_eq!(Level::Warn, notice.level);
+    assert_eq!(2, notice.fields["repeated"]);
+    assert_eq!("db", notice.fields["target"]);
+    assert_eq!(Level::Error, entries[11].level);
+}
+
+#[test]
+fn collapse_repeats_while_flushing() {
+    let path = path("collapse_repeats_while_flushing");
+    let guard = LoggerBuilder::new()
+        .collapse_repeats(true)
+        .init(&path)
+        .unwrap();
+    let flusher = std::thread::spawn(|| {
+        for _ in 0..10_000 {
+            log::logger().flush();
+        }
+    });
+    for attempt in 0..10_000 {
+        log::warn!("attempt {}", attempt / 2);
+    }
+    flusher.join().unwrap();
+    drop(guard);
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    assert!(entries.iter().any(|entry| entry.body == "attempt 4999"));
+}
+
+#[test]
+fn health() {
+    let path = path("health");
+    let before = SystemTime::now();
+    let guard = LoggerBuilder::new().init(&path).unwrap();
+    let handle = guard.handle();
+    assert_eq!(Some(path.clone()), handle.health().path);
+    assert_eq!(None, handle.health().last_write);
+
+    log::info!("written");
+    handle.flush();
+    let health = handle.health();
+    assert!(health.last_write.unwrap() >= before);
+    assert_eq!(Some(std::fs::metadata(&path).unwrap().len()), health.size);
+    assert!(health.size.unwrap() > 0);
+    assert_eq!(None, health.last_error);
+    drop(guard);
+    assert_eq!(health.last_write, handle.health().last_write);
+}
+
+#[test]
+fn health_reports_errors() {
+    struct Broken;
+    impl std::io::Write for Broken {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disk on fire"))
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let guard = LoggerBuilder::new()
+        .on_error(|_| {})
+        .init_writer(Broken)
+        .unwrap();
+    log::info!("lost");
+    guard.handle().flush();
+    let health = guard.handle().health();
+    assert_eq!(Some("disk on fire".to_string()), health.last_error);
+    assert_eq!(None, health.path);
+}
+
+#[test]
+fn rate_limit_per_target() {
+    let path = path("rate_limit");
+    let clock = ManualClock::new();
+    let guard = LoggerBuilder::new()
+        .clock(clock.clone())
+        .rate_limit(2)
+        .init(&path)
+        .unwrap();
+    for i in 0..5 {
+        log::info!(target: "chatty", "chatty {}", i);
+    }
+    log::info!(target: "quiet", "quiet");
+    clock.set(Duration::from_secs(1));
+    log::info!(target: "quiet", "next second");
+    log::info!(target: "chatty", "chatty again");
+    drop(guard);
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    let bodies: Vec<_> = entries.iter().map(|entry| entry.body.as_str()).collect();
+    assert_eq!(
+        vec![
+            "chatty 0",
+            "chatty 1",
+            "quiet",
+            "suppressed 3 entries from chatty",
+            "next second",
+            "chatty again",
+        ],
+        bodies
+    );
+    let notice = &entries[3];
+    assert_eq!(Level::Warn, notice.level);
+    assert_eq!("jsonl_gzip_logger", notice.target);
+    assert_eq!(Duration::from_secs(1), notice.offset);
+    assert_eq!(3, notice.fields["suppressed"]);
+    assert_eq!("chatty", notice.fields["target"]);
+}
+
+#[test]
+fn report_drops() {
+    let path = path("report_drops");
+    let clock = ManualClock::new();
+    let guard = LoggerBuilder::new()
+        .clock(clock.clone())
+        .rate_limit(1)
+        .report_drops(Duration::from_secs(10))
+        .init(&path)
+        .unwrap();
+    for i in 0..3 {
+        log::info!("burst {}", i);
+    }
+    clock.set(Duration::from_secs(5));
+    log::info!("too soon");
+    clock.set(Duration::from_secs(10));
+    log::info!("later");
+    drop(guard);
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    let bodies: Vec<_> = entries.iter().map(|entry| entry.body.as_str()).collect();
+    assert_eq!(
+        vec![
+            "burst 0",
+            "suppressed 2 entries from integration_test",
+            "too soon",
+            "dropped 2 entries since last report",
+            "later",
+        ],
+        bodies
+    );
+    let report = &entries[3];
+    assert_eq!(Level::Warn, report.level);
+    assert_eq!(2, report.fields["dropped"]);
+    assert_eq!(2, report.fields["rate_limit"]);
+    assert_eq!(0, report.fields["io"]);
+}
+
 }
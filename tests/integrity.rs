@@ -0,0 +1,184 @@
+use flate2::{write::GzEncoder, Compression};
+use jsonl_gzip_logger::{
+    index::{read_from_entry, read_index},
+    integrity::{repair, verify},
+    read, LoggerBuilder,
+};
+use rusty_fork::rusty_fork_test;
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Writes `count` entries numbered from `first`.
+fn lines(first: u64, count: u64) -> Vec<u8> {
+    let mut lines = Vec::new();
+    for i in first..first + count {
+        writeln!(
+            lines,
+            r#"{{"offset":{{"secs":{},"nanos":0}},"level":"INFO","target":"a","body":"entry {}"}}"#,
+            i, i
+        )
+        .unwrap();
+    }
+    lines
+}
+
+/// Compresses the bytes into a gzip member.
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Writes the bytes to a file named for the test and returns its path.
+fn file(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.jsonl.gz", name));
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+/// Returns whether every line of the file can be read.
+fn is_intact(path: &Path) -> bool {
+    read(path).unwrap().results().all(|result| result.is_ok())
+}
+
+/// Intact files are left alone.
+#[test]
+fn keeps_intact_files() {
+    let mut bytes = gzip(&lines(0, 5));
+    bytes.extend(gzip(&lines(5, 5)));
+    let path = file("repair_intact", &bytes);
+    assert!(!repair(&path).unwrap());
+    assert_eq!(bytes, fs::read(&path).unwrap());
+
+    let path = file("repair_empty", b"");
+    assert!(!repair(&path).unwrap());
+}
+
+/// A file cut short keeps the entries before the cut.
+#[test]
+fn repairs_truncated_file() {
+    let mut bytes = gzip(&lines(0, 5));
+    let second = gzip(&lines(5, 20));
+    bytes.extend(&second[..second.len() / 2]);
+    let path = file("repair_truncated", &bytes);
+    let readable = read(&path).unwrap().count();
+    assert!(!is_intact(&path));
+
+    assert!(repair(&path).unwrap());
+    assert!(is_intact(&path));
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    let expected: Vec<_> = (0..readable).map(|i| format!("entry {}", i)).collect();
+    assert_eq!(expected, bodies);
+    assert!(!repair(&path).unwrap());
+}
+
+/// Lines after the last entry are removed, while invalid lines before it
+/// are kept.
+#[test]
+fn removes_trailing_lines() {
+    let mut lines = lines(0, 2);
+    lines.extend(b"garbage\n");
+    lines.extend(self::lines(2, 1));
+    lines.extend(b"{\"offset\":\n{\"off");
+    let path = file("repair_trailing", &gzip(&lines));
+
+    assert!(repair(&path).unwrap());
+    let results: Vec<_> = read(&path).unwrap().results().collect();
+    assert_eq!(4, results.len());
+    assert!(results[2].is_err());
+    assert_eq!("entry 2", results[3].as_ref().unwrap().body);
+}
+
+/// Files that are not gzip compressed are refused.
+#[test]
+fn refuses_other_formats() {
+    let path = file("repair_plain", &lines(0, 2)[..50]);
+    assert!(repair(&path).is_err());
+    assert_eq!(&lines(0, 2)[..50], &fs::read(&path).unwrap()[..]);
+}
+
+/// Intact files verify.
+#[test]
+fn verifies_intact_files() {
+    let mut bytes = gzip(&lines(0, 5));
+    bytes.extend(gzip(&lines(5, 5)));
+    let verification = verify(file("verify_intact", &bytes)).unwrap();
+    assert!(verification.is_valid());
+    assert_eq!(10, verification.entries);
+}
+
+/// Files cut short are missing their trailer, and possibly the end of
+/// their last line.
+#[test]
+fn verifies_truncated_files() {
+    let bytes = gzip(&lines(0, 20));
+    let verification = verify(file("verify_truncated", &bytes[..bytes.len() - 4])).unwrap();
+    assert!(!verification.is_valid());
+    assert!(!verification.trailer_intact);
+    assert!(verification.last_line_complete);
+    assert_eq!(20, verification.entries);
+
+    let mut lines = lines(0, 2);
+    lines.extend(b"{\"off");
+    let verification = verify(file("verify_cut_line", &gzip(&lines))).unwrap();
+    assert!(verification.trailer_intact);
+    assert!(!verification.last_line_complete);
+    assert_eq!(2, verification.entries);
+}
+
+/// Invalid lines and offsets going back are reported, while offsets
+/// starting over at session markers are fine.
+#[test]
+fn verifies_entries() {
+    let marker = br#"{"offset":{"secs":0,"nanos":0},"level":"INFO","target":"jsonl_gzip_logger","body":"new session"}
+"#;
+    let mut appended = lines(0, 3);
+    appended.extend(marker);
+    appended.extend(lines(0, 3));
+    let verification = verify(file("verify_appended", &gzip(&appended))).unwrap();
+    assert!(verification.is_valid());
+    assert_eq!(7, verification.entries);
+
+    let mut unordered = lines(5, 1);
+    unordered.extend(b"garbage\n");
+    unordered.extend(lines(4, 1));
+    let verification = verify(file("verify_unordered", &gzip(&unordered))).unwrap();
+    assert!(!verification.offsets_monotonic);
+    assert_eq!(vec![2], verification.invalid);
+    assert_eq!(2, verification.entries);
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// The index of a repaired file only lists the members that are left.
+#[test]
+fn repairs_index() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("repairs_index");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.jsonl.gz");
+    let guard = LoggerBuilder::new()
+        .gzip_member_entries(10)
+        .index(true)
+        .init(&path)
+        .unwrap();
+    for i in 0..25 {
+        log::info!("entry {}", i);
+    }
+    drop(guard);
+    let index = read_index(&path).unwrap();
+    let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(index[2].position + 10).unwrap();
+
+    assert!(repair(&path).unwrap());
+    assert_eq!(&index[..2], &read_index(&path).unwrap()[..]);
+    assert_eq!("entry 19", read_from_entry(&path, 19).unwrap().next().unwrap().body);
+    assert!(is_intact(&path));
+}
+
+}
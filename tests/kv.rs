@@ -0,0 +1,41 @@
+#![cfg(feature = "kv")]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{deinit, read, test::TestLogger, LoggerBuilder};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Key-values are written as a map next to the formatted body.
+#[test]
+fn writes_fields() {
+    let path = path("kv");
+    let _guard = LoggerBuilder::new().init(&path).unwrap();
+    log::info!(user = "ann", attempt = 2, admin = false; "login");
+    log::info!("plain");
+    deinit();
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    assert_eq!("login", entries[0].body);
+    assert_eq!("ann", entries[0].fields["user"]);
+    assert_eq!(2, entries[0].fields["attempt"]);
+    assert_eq!(false, entries[0].fields["admin"]);
+    assert!(entries[1].fields.is_empty());
+}
+
+/// The test logger captures key-values too.
+#[test]
+fn test_logger_fields() {
+    let logger = TestLogger::new(LevelFilter::Info);
+    logger.install().unwrap();
+    log::warn!(retries = 3; "slow");
+
+    assert_eq!(3, logger.entries()[0].fields["retries"]);
+}
+
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{read, LogEntry, LogWriter};
+use log::Level;
+use std::time::Duration;
+
+fn entry(secs: u64, body: &str) -> LogEntry {
+    LogEntry {
+        offset: Duration::from_secs(secs),
+        level: Level::Info,
+        target: "import".to_string(),
+        body: body.to_string(),
+        session: None,
+        pid: None,
+        time: None,
+        module_path: None,
+        file: None,
+        line: None,
+        fields: Default::default(),
+        truncated: false,
+    }
+}
+
+/// Written entries are read back unchanged, in the order written.
+#[test]
+fn round_trip() {
+    let path = path("log_writer_round_trip");
+    let mut writer = LogWriter::create(&path).unwrap();
+    writer.write_entry(&entry(5, "later")).unwrap();
+    writer.write_entry(&entry(1, "line\nbreak")).unwrap();
+    writer.finish().unwrap();
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    assert_eq!(2, entries.len());
+    assert_eq!(Duration::from_secs(5), entries[0].offset);
+    assert_eq!("later", entries[0].body);
+    assert_eq!("line\nbreak", entries[1].body);
+    assert!(jsonl_gzip_logger::lint::check(&path).unwrap().is_empty());
+}
+
+/// Flushed entries can be read before the file is finished.
+#[test]
+fn flush() {
+    let path = path("log_writer_flush");
+    let mut writer = LogWriter::create(&path).unwrap();
+    writer.write_entry(&entry(0, "first")).unwrap();
+    writer.flush().unwrap();
+
+    assert_eq!("first", read(&path).unwrap().next().unwrap().body);
+}
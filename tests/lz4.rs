@@ -0,0 +1,52 @@
+#![cfg(feature = "lz4")]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{core::Encoder, deinit, read_lz4, LogEntryIter, LoggerBuilder};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+use std::io::Cursor;
+
+/// Encoders write a complete LZ4 frame that readers decode line by line.
+#[test]
+fn encoder_round_trips() {
+    let mut encoder = Encoder::lz4(Vec::new());
+    encoder
+        .write_line(br#"{"offset":{"secs":0,"nanos":0},"level":"INFO","target":"a","body":"one"}"#)
+        .unwrap();
+    encoder.flush().unwrap();
+    encoder
+        .write_line(br#"{"offset":{"secs":1,"nanos":0},"level":"WARN","target":"b","body":"two"}"#)
+        .unwrap();
+    let bytes = encoder.finish().unwrap();
+
+    let bodies: Vec<_> = LogEntryIter::lz4(Cursor::new(bytes))
+        .map(|entry| entry.body)
+        .collect();
+    assert_eq!(vec!["one", "two"], bodies);
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Loggers write LZ4 files that are finished when uninstalled.
+#[test]
+fn writes_lz4_files() {
+    let path = path("lz4");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .lz4(true)
+        .init(&path)
+        .unwrap();
+    log::info!("kept");
+    log::debug!("filtered");
+    log::warn!("also kept");
+    deinit();
+
+    let bodies: Vec<_> = read_lz4(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["kept", "also kept"], bodies);
+}
+
+}
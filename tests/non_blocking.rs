@@ -0,0 +1,105 @@
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{non_blocking::Durability, read, LoggerBuilder, Overflow};
+use log::{Level, LevelFilter};
+use rusty_fork::rusty_fork_test;
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Dropping the guard writes every queued entry and finishes the file.
+#[test]
+fn guard_finishes_file() {
+    let path = path("non_blocking_guard");
+    let guard = jsonl_gzip_logger::non_blocking::init(&path, LevelFilter::Info).unwrap();
+    for i in 0..100 {
+        log::info!(target: "foo", "entry {}", i);
+    }
+    log::debug!(target: "foo", "This is filtered!");
+    drop(guard);
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    assert_eq!(100, entries.len());
+    assert_eq!(Level::Info, entries[0].level);
+    assert_eq!("entry 99", entries[99].body);
+}
+
+/// Dropping the guard uninstalls the logger, so another can be installed.
+#[test]
+fn guard_uninstalls_logger() {
+    let first = path("non_blocking_uninstall_first");
+    let second = path("non_blocking_uninstall_second");
+    let guard = LoggerBuilder::new()
+        .flush_interval(std::time::Duration::from_millis(1))
+        .init_non_blocking(&first)
+        .unwrap();
+    log::info!("first");
+    drop(guard);
+    log::info!("discarded");
+
+    let guard = jsonl_gzip_logger::init(&second, LevelFilter::Info).unwrap();
+    log::info!("second");
+    drop(guard);
+
+    let bodies = |path| read(path).unwrap().map(|entry| entry.body).collect::<Vec<_>>();
+    assert_eq!(vec!["first"], bodies(&first));
+    assert_eq!(vec!["second"], bodies(&second));
+}
+
+/// Flushing through the handle waits for the writer thread.
+#[test]
+fn flush_waits_for_writer() {
+    let path = path("non_blocking_flush");
+    let guard = LoggerBuilder::new().init_non_blocking(&path).unwrap();
+    log::error!("written");
+    log::logger().flush();
+    assert_eq!("written", read(&path).unwrap().next().unwrap().body);
+
+    log::warn!("synced");
+    guard.handle().written_up_to_now(Durability::Synced).wait();
+    assert_eq!(2, read(&path).unwrap().count());
+}
+
+/// Blocking on a full queue loses nothing.
+#[test]
+fn bounded_queue_blocks() {
+    let path = path("non_blocking_block");
+    let guard = LoggerBuilder::new()
+        .queue_capacity(4, Overflow::Block)
+        .init_non_blocking(&path)
+        .unwrap();
+    for i in 0..1000 {
+        log::info!("entry {}", i);
+    }
+    drop(guard);
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    let expected: Vec<_> = (0..1000).map(|i| format!("entry {}", i)).collect();
+    assert_eq!(expected, bodies);
+}
+
+/// Dropping the oldest entries on a full queue keeps the newest ones in
+/// order.
+#[test]
+fn bounded_queue_drops_oldest() {
+    let path = path("non_blocking_drop_oldest");
+    let guard = LoggerBuilder::new()
+        .queue_capacity(1, Overflow::DropOldest)
+        .init_non_blocking(&path)
+        .unwrap();
+    for i in 0..1000 {
+        log::info!("entry {}", i);
+    }
+    drop(guard);
+
+    let numbers: Vec<usize> = read(&path)
+        .unwrap()
+        .map(|entry| entry.body["entry ".len()..].parse().unwrap())
+        .collect();
+    assert_eq!(Some(&999), numbers.last());
+    assert!(numbers.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "pii")]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{deinit, read, LoggerBuilder};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Raw PII never reaches the file, but equal values stay correlated.
+#[test]
+fn pii_is_hashed() {
+    let path = path("pii");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .pii_hashing("salt")
+        .init(&path)
+        .unwrap();
+    log::info!("signup by bob@example.com from +44 20 7946 0958");
+    log::info!("payment by bob@example.com with 5500 0000 0000 0004");
+    deinit();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(2, bodies.len());
+    for body in &bodies {
+        assert!(!body.contains("bob@"), "{}", body);
+        assert!(!body.contains("7946"), "{}", body);
+        assert!(!body.contains("5500"), "{}", body);
+    }
+    let token = |body: &str| body.split(' ').nth(2).unwrap().to_string();
+    assert!(token(&bodies[0]).starts_with("<email:"));
+    assert_eq!(token(&bodies[0]), token(&bodies[1]));
+    assert!(bodies[0].contains("<phone:"));
+    assert!(bodies[1].contains("<card:"));
+}
+
+/// Raw PII in fields, however nested, never reaches the file either.
+#[cfg(feature = "kv")]
+#[test]
+fn pii_in_fields_is_hashed() {
+    let path = path("pii_fields");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .pii_hashing("salt")
+        .init(&path)
+        .unwrap();
+    let contacts = vec!["+44 20 7946 0958", "ann@example.org"];
+    log::info!(email = "bob@example.com", contacts:serde = contacts, count = 3; "signup");
+    log::info!("by bob@example.com");
+    deinit();
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    let fields = serde_json::to_string(&entries[0].fields).unwrap();
+    assert!(!fields.contains("bob@") && !fields.contains("ann@"), "{}", fields);
+    assert!(!fields.contains("7946"), "{}", fields);
+    assert!(entries[0].fields["contacts"][0].as_str().unwrap().starts_with("<phone:"));
+    assert_eq!(3, entries[0].fields["count"]);
+    let email = entries[0].fields["email"].as_str().unwrap();
+    assert_eq!(Some(email), entries[1].body.split(' ').nth(1));
+}
+
+}
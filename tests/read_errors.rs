@@ -0,0 +1,111 @@
+use flate2::{write::GzEncoder, Compression};
+use jsonl_gzip_logger::{LogEntryIter, ReadError};
+use std::io::{self, Read, Write};
+
+/// Uncompressed JSON lines holding two entries.
+const LINES: &[u8] = br#"{"offset":{"secs":0,"nanos":0},"level":"INFO","target":"a","body":"one"}
+{"offset":{"secs":1,"nanos":0},"level":"WARN","target":"b","body":"two"}
+"#;
+
+/// Compresses the bytes into a gzip stream.
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Reads the results of a stream whose format is recognized.
+fn read_results(bytes: Vec<u8>) -> Vec<Result<String, ReadError>> {
+    LogEntryIter::detect(io::Cursor::new(bytes))
+        .unwrap()
+        .results()
+        .map(|result| result.map(|entry| entry.body))
+        .collect()
+}
+
+/// Invalid lines are reported and skipped.
+#[test]
+fn reports_invalid_lines() {
+    let mut lines = b"not json\n".to_vec();
+    lines.extend(LINES);
+    let results = read_results(gzip(&lines));
+    assert_eq!(3, results.len());
+    assert!(matches!(results[0], Err(ReadError::Json(_))));
+    assert_eq!("one", results[1].as_ref().unwrap());
+    assert_eq!("two", results[2].as_ref().unwrap());
+}
+
+/// A line cut short and a gzip stream cut short are both truncation.
+#[test]
+fn reports_truncation() {
+    let results = read_results(LINES[..LINES.len() - 10].to_vec());
+    assert_eq!("one", results[0].as_ref().unwrap());
+    assert!(matches!(results[1], Err(ReadError::Truncated)));
+
+    let compressed = gzip(LINES);
+    for len in [compressed.len() / 2, compressed.len() - 4] {
+        let results = read_results(compressed[..len].to_vec());
+        assert!(matches!(results.last(), Some(Err(ReadError::Truncated))));
+    }
+}
+
+/// Damaged compressed data is corruption, and ends iteration.
+#[test]
+fn reports_corruption() {
+    let mut compressed = gzip(LINES);
+    // the CRC-32 of the trailer
+    let crc = compressed.len() - 8;
+    compressed[crc] ^= 0xff;
+    let results = read_results(compressed);
+    assert!(matches!(results.last(), Some(Err(ReadError::Corrupt(_)))));
+    assert_eq!(1, results.iter().filter(|result| result.is_err()).count());
+}
+
+/// Errors from the underlying stream are passed on.
+#[test]
+fn reports_io_errors() {
+    struct Failing;
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+
+    let mut results = LogEntryIter::new(Failing).results();
+    assert!(matches!(results.next(), Some(Err(ReadError::Io(_)))));
+    assert!(results.next().is_none());
+}
+
+/// Lenient reading skips corrupt lines and reports where they are.
+#[test]
+fn skips_corrupt_lines() {
+    let mut lines = LINES.to_vec();
+    lines.splice(0..0, b"{\"offset\":\n".iter().copied());
+    lines.extend(b"{\"offset\":{\"se");
+    let mut entries = LogEntryIter::detect(io::Cursor::new(gzip(&lines)))
+        .unwrap()
+        .lenient();
+    let bodies: Vec<_> = entries.by_ref().map(|entry| entry.body).collect();
+    assert_eq!(vec!["one", "two"], bodies);
+
+    let report = entries.into_report();
+    assert!(!report.is_clean());
+    assert_eq!(2, report.lines.len());
+    assert_eq!(1, report.lines[0].line);
+    assert_eq!(0..11, report.lines[0].bytes);
+    assert!(matches!(report.lines[0].error, ReadError::Json(_)));
+    assert_eq!(4, report.lines[1].line);
+    let end = lines.len() as u64;
+    assert_eq!(end - 14..end, report.lines[1].bytes);
+    assert!(matches!(report.lines[1].error, ReadError::Truncated));
+}
+
+/// Intact files have clean reports.
+#[test]
+fn reports_clean_files() {
+    let mut entries = LogEntryIter::detect(io::Cursor::new(gzip(LINES)))
+        .unwrap()
+        .lenient();
+    assert_eq!(2, entries.by_ref().count());
+    assert!(entries.report().is_clean());
+}
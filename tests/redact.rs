@@ -0,0 +1,77 @@
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{deinit, read, LoggerBuilder};
+use rusty_fork::rusty_fork_test;
+
+/// Replaces every word starting with the prefix.
+fn scrub_words(prefix: &'static str) -> impl Fn(&str) -> String + Send + Sync + 'static {
+    move |text| {
+        text.split(' ')
+            .map(|word| match word.starts_with(prefix) {
+                true => "<redacted>",
+                false => word,
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Rules rewrite bodies in the order they were added.
+#[test]
+fn redacts_bodies() {
+    let path = path("redacts_bodies");
+    let _guard = LoggerBuilder::new()
+        .redact(scrub_words("sk_"))
+        .redact(|text| text.replace("<redacted>", "<key>"))
+        .init(&path)
+        .unwrap();
+    log::info!("charged with sk_live_123 and sk_test_456");
+    log::info!("nothing secret");
+    deinit();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["charged with <key> and <key>", "nothing secret"], bodies);
+}
+
+/// String key-values are redacted too, while keys and other values are not.
+#[cfg(feature = "kv")]
+#[test]
+fn redacts_fields() {
+    let path = path("redacts_fields");
+    let _guard = LoggerBuilder::new()
+        .redact(scrub_words("sk_"))
+        .init(&path)
+        .unwrap();
+    log::info!(sk_key = "sk_live_123", attempt = 2; "login");
+    deinit();
+
+    let entry = read(&path).unwrap().next().unwrap();
+    assert_eq!("<redacted>", entry.fields["sk_key"]);
+    assert_eq!(2, entry.fields["attempt"]);
+}
+
+/// Regex rules replace every match.
+#[cfg(feature = "regex")]
+#[test]
+fn redacts_regex_matches() {
+    let path = path("redacts_regex_matches");
+    let bearer = regex::Regex::new(r"Bearer [A-Za-z0-9._-]+").unwrap();
+    let email = regex::Regex::new(r"[\w.+-]+@([\w-]+\.[\w.]+)").unwrap();
+    let _guard = LoggerBuilder::new()
+        .redact_regex(bearer, "Bearer <token>")
+        .redact_regex(email, "<email at $1>")
+        .init(&path)
+        .unwrap();
+    log::info!("Authorization: Bearer abc.def-1 from ann@example.com");
+    deinit();
+
+    let entry = read(&path).unwrap().next().unwrap();
+    assert_eq!("Authorization: Bearer <token> from <email at example.com>", entry.body);
+}
+
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{deinit, init, read, InitError};
+use log::LevelFilter;
+
+/// Sequential loggers in one process each get their own, finished file, and
+/// failing to install a second one leaves the first one's file intact.
+#[test]
+fn sequential_loggers() {
+    let first = path("reset_first");
+    let _first = init(&first, LevelFilter::Info).unwrap();
+    log::info!(target: "foo", "Before");
+    log::logger().flush();
+    assert!(matches!(
+        init(&first, LevelFilter::Info),
+        Err(InitError::AlreadyInitialized)
+    ));
+    assert!(matches!(
+        jsonl_gzip_logger::non_blocking::init(&first, LevelFilter::Info),
+        Err(InitError::AlreadyInitialized)
+    ));
+    log::info!(target: "foo", "First file");
+    deinit();
+    log::info!(target: "foo", "Discarded");
+
+    let second = path("reset_second");
+    let _second = init(&second, LevelFilter::Info).unwrap();
+    log::info!(target: "foo", "Second file");
+    deinit();
+
+    let entries: Vec<_> = read(&first).unwrap().map(|e| e.body).collect();
+    assert_eq!(vec!["Before", "First file"], entries);
+    let entries: Vec<_> = read(&second).unwrap().map(|e| e.body).collect();
+    assert_eq!(vec!["Second file"], entries);
+}
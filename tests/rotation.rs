@@ -0,0 +1,334 @@
+use jsonl_gzip_logger::{deinit, read, LoggerBuilder};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// Creates an empty directory for a test.
+fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Reads the bodies of one file.
+fn bodies_of(path: &std::path::Path) -> Vec<String> {
+    read(path).unwrap().map(|entry| entry.body).collect()
+}
+
+/// Reads the bodies of every file rotated from `app.jsonl.gz`, oldest first.
+fn bodies(dir: &std::path::Path) -> Vec<String> {
+    let mut rotated = Vec::new();
+    for index in 1.. {
+        let path = dir.join(format!("app.{}.jsonl.gz", index));
+        if !path.exists() {
+            break;
+        }
+        rotated.push(path);
+    }
+    rotated.push(dir.join("app.jsonl.gz"));
+    rotated
+        .into_iter()
+        .flat_map(|path| read(path).unwrap())
+        .map(|entry| entry.body)
+        .collect()
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Entries keep being written to the current file while it cannot be
+/// rotated.
+#[test]
+fn keeps_writing_when_rotation_fails() {
+    let dir = dir("keeps_writing_when_rotation_fails");
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .compression_level(0)
+        .max_file_size(1024)
+        .init(dir.join("app.jsonl.gz"))
+        .unwrap();
+    // the file cannot be renamed over a directory that is not empty
+    fs::create_dir_all(dir.join("app.1.jsonl.gz/blocked")).unwrap();
+    for i in 0..1000 {
+        log::info!("entry {:04} {}", i, "x".repeat(80));
+    }
+    let health = guard.handle().health();
+    drop(guard);
+
+    assert!(health.last_error.is_some());
+    assert_eq!(1000, bodies_of(&dir.join("app.jsonl.gz")).len());
+}
+
+/// Files are rotated once they are large enough, without losing entries.
+#[test]
+fn rotates_by_size() {
+    let dir = dir("rotates_by_size");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .compression_level(0)
+        .max_file_size(16 * 1024)
+        .init(dir.join("app.jsonl.gz"))
+        .unwrap();
+    for i in 0..2000 {
+        log::info!("entry {:04} {}", i, "x".repeat(80));
+    }
+    deinit();
+
+    assert!(dir.join("app.3.jsonl.gz").exists());
+    let expected: Vec<_> = (0..2000)
+        .map(|i| format!("entry {:04} {}", i, "x".repeat(80)))
+        .collect();
+    assert_eq!(expected, bodies(&dir));
+}
+
+/// The oldest files are deleted to keep the total size within the budget.
+#[test]
+fn caps_total_size() {
+    let dir = dir("caps_total_size");
+    let _guard = LoggerBuilder::new()
+        .compression_level(0)
+        .max_file_size(16 * 1024)
+        .max_total_size(64 * 1024)
+        .init(dir.join("app.jsonl.gz"))
+        .unwrap();
+    for i in 0..2000 {
+        log::info!("entry {:04} {}", i, "x".repeat(80));
+    }
+    deinit();
+
+    let total: u64 = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().metadata().unwrap().len())
+        .sum();
+    assert!(total <= 64 * 1024 + 4 * 1024, "{total}");
+    assert!(!dir.join("app.1.jsonl.gz").exists());
+    // the remaining files hold the most recent entries
+    let mut remaining: Vec<_> = (2..100)
+        .map(|index| dir.join(format!("app.{}.jsonl.gz", index)))
+        .filter(|path| path.exists())
+        .collect();
+    remaining.push(dir.join("app.jsonl.gz"));
+    let bodies: Vec<_> = remaining.iter().flat_map(|path| bodies_of(path)).collect();
+    let expected: Vec<_> = (2000 - bodies.len()..2000)
+        .map(|i| format!("entry {:04} {}", i, "x".repeat(80)))
+        .collect();
+    assert!(bodies.len() > 200);
+    assert_eq!(expected, bodies);
+}
+
+/// Numbering continues after files rotated by an earlier run.
+#[test]
+fn continues_numbering() {
+    let dir = dir("continues_numbering");
+    fs::write(dir.join("app.7.jsonl.gz"), "").unwrap();
+    let _guard = LoggerBuilder::new()
+        .compression_level(0)
+        .max_file_size(1024)
+        .init(dir.join("app.jsonl.gz"))
+        .unwrap();
+    for _ in 0..500 {
+        log::info!("{}", "x".repeat(80));
+    }
+    deinit();
+
+    assert!(dir.join("app.8.jsonl.gz").exists());
+}
+
+/// A new file is started once a period has passed.
+#[test]
+fn rotates_by_time() {
+    let dir = dir("rotates_by_time");
+    let _guard = LoggerBuilder::new()
+        .rotate_every(Duration::from_millis(100))
+        .init(dir.join("app.jsonl.gz"))
+        .unwrap();
+    log::info!("first");
+    std::thread::sleep(Duration::from_millis(250));
+    log::info!("second");
+    deinit();
+
+    assert_eq!(vec!["first", "second"], bodies(&dir));
+    assert_eq!(vec!["second"], bodies_of(&dir.join("app.jsonl.gz")));
+}
+
+/// Rotated files older than the maximum age are deleted.
+#[test]
+fn deletes_old_files() {
+    let dir = dir("deletes_old_files");
+    let old = fs::File::create(dir.join("app.1.jsonl.gz")).unwrap();
+    old.set_modified(SystemTime::now() - Duration::from_secs(7200))
+        .unwrap();
+    drop(old);
+    fs::write(dir.join("app.2.jsonl.gz"), "").unwrap();
+    let _guard = LoggerBuilder::new()
+        .max_file_size(1 << 20)
+        .max_age(Duration::from_secs(3600))
+        .init(dir.join("app.jsonl.gz"))
+        .unwrap();
+    deinit();
+
+    assert!(!dir.join("app.1.jsonl.gz").exists());
+    assert!(dir.join("app.2.jsonl.gz").exists());
+    assert!(dir.join("app.jsonl.gz").exists());
+}
+
+/// Files are rotated on demand through the guard's handle.
+#[test]
+fn rotates_on_demand() {
+    let dir = dir("rotates_on_demand");
+    let guard = LoggerBuilder::new().init(dir.join("app.jsonl.gz")).unwrap();
+    let handle = guard.handle();
+    log::info!("first");
+    handle.rotate().unwrap();
+    log::info!("second");
+    drop(guard);
+
+    assert_eq!(vec!["first"], bodies_of(&dir.join("app.1.jsonl.gz")));
+    assert_eq!(vec!["second"], bodies_of(&dir.join("app.jsonl.gz")));
+    assert!(handle.rotate().is_err());
+}
+
+/// Files moved away by another tool are reopened instead of renamed.
+#[test]
+fn reopens_moved_files() {
+    let dir = dir("reopens_moved_files");
+    let guard = LoggerBuilder::new().init(dir.join("app.jsonl.gz")).unwrap();
+    log::info!("first");
+    fs::rename(dir.join("app.jsonl.gz"), dir.join("app.jsonl.gz.1")).unwrap();
+    guard.handle().rotate().unwrap();
+    log::info!("second");
+    drop(guard);
+
+    assert_eq!(vec!["first"], bodies_of(&dir.join("app.jsonl.gz.1")));
+    assert_eq!(vec!["second"], bodies_of(&dir.join("app.jsonl.gz")));
+    assert!(!dir.join("app.1.jsonl.gz").exists());
+}
+
+/// Templates name rotated files, continuing after the highest index of the
+/// names they match.
+#[test]
+fn names_rotated_files() {
+    let dir = dir("names_rotated_files");
+    fs::write(dir.join("app.1999-12-31.4.jsonl.gz"), "").unwrap();
+    let guard = LoggerBuilder::new()
+        .rotated_name("app.{date}.{seq}.jsonl.gz")
+        .init(dir.join("app.jsonl.gz"))
+        .unwrap();
+    log::info!("first");
+    guard.handle().rotate().unwrap();
+    log::info!("second");
+    guard.handle().rotate().unwrap();
+    drop(guard);
+
+    let mut rotated: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name != "app.jsonl.gz" && !name.starts_with("app.1999"))
+        .collect();
+    rotated.sort();
+    assert_eq!(2, rotated.len());
+    for (name, seq, body) in [(&rotated[0], 5, "first"), (&rotated[1], 6, "second")] {
+        let date = name
+            .strip_prefix("app.")
+            .and_then(|rest| rest.strip_suffix(&format!(".{seq}.jsonl.gz")))
+            .unwrap();
+        assert!(date.starts_with("20") && date.len() == 10, "{name}");
+        assert_eq!(vec![body], bodies_of(&dir.join(name)));
+    }
+}
+
+/// Templates without a place for the index cannot be used.
+#[test]
+fn rejects_invalid_templates() {
+    let dir = dir("rejects_invalid_templates");
+    let result = LoggerBuilder::new()
+        .rotated_name("app.{date}.jsonl.gz")
+        .init(dir.join("app.jsonl.gz"));
+    assert!(result.is_err());
+    assert!(!dir.join("app.jsonl.gz").exists());
+}
+
+/// The current file is written uncompressed, and rotated files are
+/// compressed by the time the logger is dropped.
+#[test]
+fn compresses_on_rotation() {
+    let dir = dir("compresses_on_rotation");
+    let guard = LoggerBuilder::new()
+        .compress_on_rotation(true)
+        .rotated_name("app.{seq}.jsonl.gz")
+        .init(dir.join("app.jsonl"))
+        .unwrap();
+    log::info!("first");
+    guard.handle().flush();
+    let plain = fs::read_to_string(dir.join("app.jsonl")).unwrap();
+    assert!(plain.contains("\"body\":\"first\"") && plain.ends_with('\n'));
+    guard.handle().rotate().unwrap();
+    log::info!("second");
+    guard.handle().rotate().unwrap();
+    log::info!("third");
+    drop(guard);
+
+    for (seq, body) in [(1, "first"), (2, "second")] {
+        let rotated = dir.join(format!("app.{seq}.jsonl.gz"));
+        assert!(fs::read(&rotated).unwrap().starts_with(&[0x1f, 0x8b]));
+        assert_eq!(vec![body], bodies_of(&rotated));
+    }
+    assert_eq!(vec!["third"], bodies_of(&dir.join("app.jsonl")));
+    assert_eq!(3, fs::read_dir(&dir).unwrap().count());
+}
+
+/// The latest link points at the most recently rotated file, and is not
+/// mistaken for a rotated file.
+#[cfg(unix)]
+#[test]
+fn links_latest_file() {
+    let dir = dir("links_latest_file");
+    let builder = || {
+        LoggerBuilder::new()
+            .latest_link(true)
+            .rotated_name("app.{date}.{seq}.jsonl.gz")
+    };
+    let guard = builder().init(dir.join("app.jsonl.gz")).unwrap();
+    let link = dir.join("app.latest.jsonl.gz");
+    assert!(!link.exists());
+    log::info!("first");
+    guard.handle().rotate().unwrap();
+    log::info!("second");
+    guard.handle().rotate().unwrap();
+    log::info!("third");
+    drop(guard);
+
+    let target = fs::read_link(&link).unwrap();
+    let name = target.to_str().unwrap();
+    assert!(name.starts_with("app.") && name.ends_with(".2.jsonl.gz"), "{name}");
+    assert_eq!(vec!["second"], bodies_of(&link));
+    // the link is kept when logging starts again
+    let guard = builder().init(dir.join("app.jsonl.gz")).unwrap();
+    drop(guard);
+    assert_eq!(target, fs::read_link(&link).unwrap());
+    assert_eq!(4, fs::read_dir(&dir).unwrap().count());
+}
+
+/// Non-blocking loggers rotate on their writer thread.
+#[test]
+fn rotates_non_blocking_on_demand() {
+    let dir = dir("rotates_non_blocking_on_demand");
+    let guard = LoggerBuilder::new()
+        .init_non_blocking(dir.join("app.jsonl.gz"))
+        .unwrap();
+    log::info!("first");
+    guard.handle().rotate().unwrap();
+    log::info!("second");
+    drop(guard);
+
+    assert_eq!(vec!["first", "second"], bodies(&dir));
+    assert_eq!(vec!["second"], bodies_of(&dir.join("app.jsonl.gz")));
+}
+
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use jsonl_gzip_logger::{assert_logged, assert_not_logged, read, test::TestLogger, LoggerBuilder};
+use log::LevelFilter;
+use std::thread;
+
+/// Concurrent scopes on different threads each capture only their own
+/// entries.
+#[test]
+fn concurrent_scopes_are_isolated() {
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            thread::spawn(move || {
+                let logger = TestLogger::new(LevelFilter::Info);
+                let _guard = logger.set_default().unwrap();
+                for _ in 0..10 {
+                    log::info!(target: "worker", "thread {}", i);
+                }
+                log::debug!(target: "worker", "filtered");
+                logger
+            })
+        })
+        .collect();
+
+    for (i, thread) in threads.into_iter().enumerate() {
+        let entries = thread.join().unwrap().entries();
+        assert_eq!(10, entries.len());
+        let body = format!("thread {}", i);
+        assert!(entries.iter().all(|entry| entry.body == body));
+    }
+}
+
+/// Scopes nest and end when their guard is dropped.
+#[test]
+fn nested_scopes() {
+    let outer = TestLogger::new(LevelFilter::Trace);
+    let inner = TestLogger::new(LevelFilter::Trace);
+    let _outer_guard = outer.set_default().unwrap();
+    {
+        let _inner_guard = inner.set_default().unwrap();
+        log::trace!(target: "nested", "inner");
+    }
+    log::trace!(target: "nested", "outer");
+
+    assert_logged!(inner, body: "inner");
+    assert_not_logged!(inner, body: "outer");
+    assert_logged!(outer, body: "outer");
+    assert_not_logged!(outer, body: "inner");
+}
+
+/// Concurrent scoped file loggers each write only their own thread's entries
+/// to their own file.
+#[test]
+fn concurrent_scoped_files() {
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            thread::spawn(move || {
+                let path = common::path(&format!("scoped_file_{}", i));
+                let guard = LoggerBuilder::new().init_scoped(&path).unwrap();
+                log::info!(target: "worker", "thread {}", i);
+                log::debug!(target: "worker", "filtered");
+                drop(guard);
+                path
+            })
+        })
+        .collect();
+
+    for (i, thread) in threads.into_iter().enumerate() {
+        let bodies: Vec<_> = read(thread.join().unwrap())
+            .unwrap()
+            .map(|entry| entry.body)
+            .collect();
+        assert_eq!(vec![format!("thread {}", i)], bodies);
+    }
+}
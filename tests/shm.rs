@@ -0,0 +1,38 @@
+#![cfg(feature = "shm")]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{read, LoggerBuilder};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// The flusher writes every entry and stops once the writer is done.
+#[test]
+fn flusher_persists_entries() {
+    let ring = path("shm_ring");
+    let path = path("shm");
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .init_shm_ring(&ring, 1 << 20)
+        .unwrap();
+    let flusher = {
+        let (ring, path) = (ring.clone(), path.clone());
+        std::thread::spawn(move || LoggerBuilder::new().flush_shm_ring(ring, path))
+    };
+    for i in 0..100 {
+        log::info!("entry {}", i);
+    }
+    drop(guard);
+    assert_eq!(0, flusher.join().unwrap().unwrap());
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    let expected: Vec<_> = (0..100).map(|i| format!("entry {}", i)).collect();
+    assert_eq!(expected, bodies);
+}
+
+}
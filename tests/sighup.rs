@@ -0,0 +1,56 @@
+#![cfg(all(unix, feature = "sighup"))]
+
+use jsonl_gzip_logger::{read, LoggerBuilder};
+use rusty_fork::rusty_fork_test;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Creates an empty directory for a test.
+fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Reads the bodies of one file.
+fn bodies_of(path: &Path) -> Vec<String> {
+    read(path).unwrap().map(|entry| entry.body).collect()
+}
+
+/// Sends the process `SIGHUP` and waits until the file at `path` has been
+/// rotated to `rotated`, which happens on a background thread.
+fn hang_up(path: &Path, rotated: &Path) {
+    assert_eq!(0, unsafe { libc::raise(libc::SIGHUP) });
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !(rotated.exists() && path.exists()) {
+        assert!(Instant::now() < deadline, "never rotated");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// `SIGHUP` rotates the file while the guard is alive.
+#[test]
+fn rotates_on_sighup() {
+    let dir = dir("rotates_on_sighup");
+    let path = dir.join("app.jsonl.gz");
+    let guard = LoggerBuilder::new().init(&path).unwrap();
+    let sighup = guard.handle().rotate_on_sighup().unwrap();
+    log::info!("first");
+    hang_up(&path, &dir.join("app.1.jsonl.gz"));
+    log::info!("second");
+    drop(sighup);
+    drop(guard);
+
+    assert_eq!(vec!["first"], bodies_of(&dir.join("app.1.jsonl.gz")));
+    assert_eq!(vec!["second"], bodies_of(&path));
+}
+
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "signing")]
+
+mod common;
+
+use common::path;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use jsonl_gzip_logger::{
+    deinit, read,
+    signing::{verify, SigningKey},
+    LoggerBuilder,
+};
+use log::LevelFilter;
+use std::io::{Read, Write};
+
+/// Signed logs verify with the key and are still readable without it.
+#[test]
+fn signed_log_verifies() {
+    let path = path("signed");
+    let key = SigningKey::from_bytes("audit key");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .signing(key.clone())
+        .init(&path)
+        .unwrap();
+    log::info!(target: "audit", "user 7 logged in");
+    log::warn!(target: "audit", "user 7 changed \"role\" to admin");
+    deinit();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(
+        vec!["user 7 logged in", "user 7 changed \"role\" to admin"],
+        bodies
+    );
+
+    let verification = verify(&path, &key).unwrap();
+    assert!(verification.is_valid());
+    assert_eq!(2, verification.valid);
+    let wrong = verify(&path, &SigningKey::from_bytes("other")).unwrap();
+    assert_eq!(vec![1, 2], wrong.invalid);
+
+    // rewrite the file with the second entry modified
+    let mut contents = String::new();
+    GzDecoder::new(std::fs::File::open(&path).unwrap())
+        .read_to_string(&mut contents)
+        .unwrap();
+    let contents = contents.replace("admin", "guest");
+    let mut encoder = GzEncoder::new(std::fs::File::create(&path).unwrap(), Compression::fast());
+    encoder.write_all(contents.as_bytes()).unwrap();
+    encoder.finish().unwrap();
+
+    let verification = verify(&path, &key).unwrap();
+    assert!(!verification.is_valid());
+    assert_eq!(1, verification.valid);
+    assert_eq!(vec![2], verification.invalid);
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "slog")]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{deinit, read, slog::JsonlGzipDrain, LoggerBuilder};
+use log::{Level, LevelFilter};
+use rusty_fork::rusty_fork_test;
+use slog::{o, Drain, Logger};
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Records are written with the key-value pairs of the record and of its
+/// loggers, the record's winning.
+#[test]
+fn writes_records() {
+    let path = path("slog_records");
+    let _guard = LoggerBuilder::new().init(&path).unwrap();
+    let root = Logger::root(JsonlGzipDrain::new().fuse(), o!("service" => "billing", "attempt" => 1));
+    let child = root.new(o!("request" => 7_u64));
+    slog::warn!(child, "charged {} cents", 1250; "attempt" => 2, "ratio" => 0.5, "refund" => false);
+    slog::crit!(root, #"payments", "ledger unavailable"; "error" => %"timed out", "retry" => ?None::<u8>);
+    log::info!("from log");
+    deinit();
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    assert_eq!(3, entries.len());
+    assert_eq!("charged 1250 cents", entries[0].body);
+    assert_eq!(Level::Warn, entries[0].level);
+    assert_eq!("slog", entries[0].target);
+    assert_eq!("billing", entries[0].fields["service"]);
+    assert_eq!(7, entries[0].fields["request"]);
+    assert_eq!(2, entries[0].fields["attempt"]);
+    assert_eq!(0.5, entries[0].fields["ratio"]);
+    assert_eq!(false, entries[0].fields["refund"]);
+    assert_eq!("ledger unavailable", entries[1].body);
+    assert_eq!(Level::Error, entries[1].level);
+    assert_eq!("payments", entries[1].target);
+    assert_eq!("timed out", entries[1].fields["error"]);
+    assert_eq!("None", entries[1].fields["retry"]);
+    assert!(!entries[1].fields.contains_key("request"));
+    assert_eq!("from log", entries[2].body);
+}
+
+/// Records are filtered like entries logged with `log`.
+#[test]
+fn filters_records() {
+    let path = path("slog_filters");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .target_level("noisy", LevelFilter::Error)
+        .init(&path)
+        .unwrap();
+    let root = Logger::root(JsonlGzipDrain::new().fuse(), o!());
+    slog::debug!(root, "too verbose");
+    slog::warn!(root, #"noisy", "filtered by tag");
+    slog::info!(root, "kept");
+    deinit();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["kept"], bodies);
+}
+
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::read;
+use log::{Level, LevelFilter};
+use rusty_fork::rusty_fork_test;
+
+// init() can only be called once per process, so
+// have to run each of these tests in their own process.
+rusty_fork_test! {
+
+#[test]
+fn guard_finishes_file() {
+    let path = path("smol_guard");
+    smol::block_on(async {
+        let _guard = jsonl_gzip_logger::smol::init(&path, LevelFilter::Info).unwrap();
+        log::info!(target: "foo", "This is a log!");
+        log::debug!(target: "foo", "This is filtered!");
+    });
+
+    let mut iter = read(&path).unwrap();
+
+    let entry = iter.next().unwrap();
+    assert_eq!(Level::Info, entry.level);
+    assert_eq!("foo", entry.target);
+    assert_eq!("This is a log!", entry.body);
+
+    assert_eq!(0, iter.count());
+}
+
+#[test]
+fn flush_async_writes_queued_entries() {
+    let path = path("smol_flush_async");
+    smol::block_on(async {
+        let guard = jsonl_gzip_logger::smol::init(&path, LevelFilter::Info).unwrap();
+        log::error!(target: "baz", "Flushed asynchronously!");
+        guard.handle().flush_async().await;
+
+        let entry = read(&path).unwrap().next().unwrap();
+        assert_eq!(Level::Error, entry.level);
+        assert_eq!("Flushed asynchronously!", entry.body);
+    });
+}
+
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{tcp::TcpSink, LogEntry, LoggerBuilder};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+/// Reads `count` entries from the first client of the listener.
+fn receive(listener: &TcpListener, count: usize) -> Vec<String> {
+    let (stream, _) = listener.accept().unwrap();
+    BufReader::new(stream)
+        .lines()
+        .take(count)
+        .map(|line| {
+            serde_json::from_str::<LogEntry>(&line.unwrap())
+                .unwrap()
+                .body
+        })
+        .collect()
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Entries are streamed as JSON lines.
+#[test]
+fn streams_entries() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .init_tcp(TcpSink::new(address))
+        .unwrap();
+    log::info!("first");
+    log::info!("second");
+    log::logger().flush();
+    assert_eq!(vec!["first", "second"], receive(&listener, 2));
+    drop(guard);
+}
+
+/// Dropping the guard of a logger that was already replaced leaves the new
+/// logger installed.
+#[test]
+fn guard_uninstalls_own_logger() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let guard = LoggerBuilder::new().init_tcp(TcpSink::new(address)).unwrap();
+    jsonl_gzip_logger::deinit();
+    let path = path("tcp_replaced");
+    let file_guard = jsonl_gzip_logger::init(&path, LevelFilter::Info).unwrap();
+    drop(guard);
+    log::info!("still logged");
+    drop(file_guard);
+    let entries: Vec<_> = jsonl_gzip_logger::read(&path).unwrap().collect();
+    assert_eq!("still logged", entries[0].body);
+}
+
+/// Entries logged while the collector is down are replayed from the spool.
+#[test]
+fn spools_until_reconnected() {
+    let spool = path("tcp_spool");
+    let _ = std::fs::remove_file(&spool);
+    // reserve a free port, then stop listening on it
+    let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .init_tcp(
+            TcpSink::new(address.to_string())
+                .spool(&spool)
+                .max_backoff(Duration::from_millis(50)),
+        )
+        .unwrap();
+    log::info!("while down");
+    log::logger().flush();
+    assert!(std::fs::metadata(&spool).unwrap().len() > 0);
+
+    let listener = TcpListener::bind(address).unwrap();
+    let received = receive(&listener, 1);
+    assert_eq!(vec!["while down"], received);
+    drop(guard);
+    assert_eq!(0, std::fs::metadata(&spool).unwrap().len());
+}
+
+/// Entries that fail to be written are spooled and sent on the next
+/// connection.
+#[test]
+fn spools_failed_writes() {
+    /// Connection that is broken from the start.
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let spool = path("tcp_spool_failed");
+    let _ = std::fs::remove_file(&spool);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let mut attempts = 0;
+    let sink = TcpSink::with_connector(move || {
+        attempts += 1;
+        if attempts == 1 {
+            Ok(Box::new(Broken) as Box<dyn Write + Send>)
+        } else {
+            Ok(Box::new(TcpStream::connect(address)?) as Box<dyn Write + Send>)
+        }
+    });
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .init_tcp(sink.spool(&spool).max_backoff(Duration::from_millis(50)))
+        .unwrap();
+    log::info!("failed");
+    log::logger().flush();
+    assert!(std::fs::metadata(&spool).unwrap().len() > 0);
+
+    assert_eq!(vec!["failed"], receive(&listener, 1));
+    drop(guard);
+}
+
+/// Entries that do not fit in the spool are dropped.
+#[test]
+fn caps_spool() {
+    let spool = path("tcp_spool_capped");
+    let _ = std::fs::remove_file(&spool);
+    let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .init_tcp(
+            TcpSink::new(address.to_string())
+                .spool(&spool)
+                .max_spool_size(1000)
+                .max_backoff(Duration::from_secs(60)),
+        )
+        .unwrap();
+    for i in 0..100 {
+        log::info!("while down {}", i);
+    }
+    log::logger().flush();
+    let len = std::fs::metadata(&spool).unwrap().len();
+    assert!(len > 0 && len <= 1000, "{len}");
+    drop(guard);
+}
+
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{deinit, read, test::TestLogger, LoggerBuilder};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Both loggers receive the records passing their own levels.
+#[test]
+fn forwards_to_both() {
+    let path = path("tee");
+    let other = TestLogger::new(LevelFilter::Debug);
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .tee(other.clone(), LevelFilter::Debug)
+        .init(&path)
+        .unwrap();
+    log::info!("archived");
+    log::debug!("only printed");
+    log::trace!("dropped");
+    deinit();
+
+    let bodies: Vec<_> = other.entries().into_iter().map(|entry| entry.body).collect();
+    assert_eq!(vec!["archived", "only printed"], bodies);
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["archived"], bodies);
+}
+
+}
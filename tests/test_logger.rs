@@ -0,0 +1,23 @@
+use jsonl_gzip_logger::test::TestLogger;
+use log::{Level, LevelFilter};
+use rusty_fork::rusty_fork_test;
+
+// install() can only be called once per process, so
+// have to run each of these tests in their own process.
+rusty_fork_test! {
+
+#[test]
+fn installed_logger_captures_macros() {
+    let logger = TestLogger::new(LevelFilter::Info);
+    logger.install().unwrap();
+    log::info!(target: "foo", "This is a log!");
+    log::debug!(target: "foo", "This is filtered!");
+
+    let entries = logger.entries();
+    assert_eq!(1, entries.len());
+    assert_eq!(Level::Info, entries[0].level);
+    assert_eq!("foo", entries[0].target);
+    assert_eq!("This is a log!", entries[0].body);
+}
+
+}
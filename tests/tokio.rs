@@ -0,0 +1,138 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{read, tokio::Durability, LoggerBuilder};
+use log::{Level, LevelFilter};
+use rusty_fork::rusty_fork_test;
+
+// init() can only be called once per process, so
+// have to run each of these tests in their own process.
+rusty_fork_test! {
+
+#[test]
+fn guard_finishes_file() {
+    let path = path("tokio_guard");
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let _guard = jsonl_gzip_logger::tokio::init(&path, LevelFilter::Info).unwrap();
+        log::info!(target: "foo", "This is a log!");
+        log::debug!(target: "foo", "This is filtered!");
+    });
+
+    let mut iter = read(&path).unwrap();
+
+    let entry = iter.next().unwrap();
+    assert_eq!(Level::Info, entry.level);
+    assert_eq!("foo", entry.target);
+    assert_eq!("This is a log!", entry.body);
+
+    assert_eq!(0, iter.count());
+}
+
+#[test]
+fn flush_writes_queued_entries() {
+    let path = path("tokio_flush");
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let _guard = jsonl_gzip_logger::tokio::init(&path, LevelFilter::Info).unwrap();
+        log::warn!(target: "bar", "Flushed!");
+        log::logger().flush();
+
+        let entry = read(&path).unwrap().next().unwrap();
+        assert_eq!(Level::Warn, entry.level);
+        assert_eq!("Flushed!", entry.body);
+    });
+}
+
+#[test]
+fn flush_async_writes_queued_entries() {
+    let path = path("tokio_flush_async");
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let guard = jsonl_gzip_logger::tokio::init(&path, LevelFilter::Info).unwrap();
+        log::error!(target: "baz", "Flushed asynchronously!");
+        guard.handle().flush_async().await;
+
+        let entry = read(&path).unwrap().next().unwrap();
+        assert_eq!(Level::Error, entry.level);
+        assert_eq!("Flushed asynchronously!", entry.body);
+    });
+}
+
+#[test]
+fn async_writer_receives_stream() {
+    let path = path("tokio_async_writer");
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let file = tokio::fs::File::create(&path).await.unwrap();
+        let guard = jsonl_gzip_logger::tokio::init_with_writer(file, LevelFilter::Info).unwrap();
+        log::info!(target: "foo", "Written asynchronously!");
+        tokio::task::spawn_blocking(move || drop(guard)).await.unwrap();
+    });
+
+    let mut iter = read(&path).unwrap();
+
+    let entry = iter.next().unwrap();
+    assert_eq!(Level::Info, entry.level);
+    assert_eq!("foo", entry.target);
+    assert_eq!("Written asynchronously!", entry.body);
+
+    assert_eq!(0, iter.count());
+}
+
+#[test]
+fn written_up_to_now_barrier() {
+    let path = path("tokio_written_up_to_now");
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let guard = jsonl_gzip_logger::tokio::init(&path, LevelFilter::Info).unwrap();
+        let handle = guard.handle();
+        log::info!(target: "foo", "Checkpoint 1");
+        handle.written_up_to_now(Durability::Synced).await;
+        assert_eq!(1, read(&path).unwrap().count());
+
+        log::info!(target: "foo", "Checkpoint 2");
+        handle.written_up_to_now(Durability::Written).wait();
+        assert_eq!(2, read(&path).unwrap().count());
+    });
+}
+
+#[test]
+fn finish_on_shutdown_closes_file() {
+    let path = path("tokio_finish_on_shutdown");
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let guard = jsonl_gzip_logger::tokio::init(&path, LevelFilter::Info).unwrap();
+        let (shutdown, signal) = tokio::sync::oneshot::channel::<()>();
+        let finished = tokio::spawn(jsonl_gzip_logger::tokio::finish_on_shutdown(guard, signal));
+        log::info!(target: "foo", "Before shutdown");
+        shutdown.send(()).unwrap();
+        finished.await.unwrap();
+    });
+
+    let mut iter = read(&path).unwrap();
+    assert_eq!("Before shutdown", iter.next().unwrap().body);
+    assert_eq!(0, iter.count());
+}
+
+#[test]
+fn recent_entries_in_memory() {
+    let path = path("tokio_recent");
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let guard = LoggerBuilder::new()
+            .level(LevelFilter::Info)
+            .recent_entries(2)
+            .init_tokio(&path)
+            .unwrap();
+        for i in 0..3 {
+            log::info!(target: "foo", "Entry {}", i);
+        }
+        let recent: Vec<_> = guard.handle().recent().into_iter().map(|e| e.body).collect();
+        assert_eq!(vec!["Entry 1", "Entry 2"], recent);
+    });
+}
+
+}
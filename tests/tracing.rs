@@ -0,0 +1,75 @@
+#![cfg(feature = "tracing")]
+
+mod common;
+
+use common::path;
+use jsonl_gzip_logger::{deinit, read, tracing::JsonlGzipLayer, LoggerBuilder};
+use log::{Level, LevelFilter};
+use rusty_fork::rusty_fork_test;
+use serde_json::json;
+use tracing_subscriber::layer::SubscriberExt;
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Events are written with their message as the body, their fields, and the
+/// spans they happened in, next to entries logged with `log`.
+#[test]
+fn writes_events() {
+    let path = path("tracing_events");
+    let _guard = LoggerBuilder::new().init(&path).unwrap();
+    let subscriber = tracing_subscriber::registry().with(JsonlGzipLayer::new());
+    tracing::subscriber::with_default(subscriber, || {
+        let outer = tracing::info_span!("request", id = 7, path = "/users");
+        let _outer = outer.enter();
+        let inner = tracing::debug_span!("query", rows = tracing::field::Empty);
+        inner.record("rows", 3);
+        inner.in_scope(|| tracing::warn!(elapsed_ms = 12.5, cached = false, "slow query"));
+        tracing::error!(user = ?"ann", "denied");
+    });
+    tracing::info!("no subscriber, so not written");
+    log::info!("from log");
+    deinit();
+
+    let entries: Vec<_> = read(&path).unwrap().collect();
+    assert_eq!(3, entries.len());
+    assert_eq!("slow query", entries[0].body);
+    assert_eq!(Level::Warn, entries[0].level);
+    assert_eq!("tracing", entries[0].target);
+    assert_eq!(12.5, entries[0].fields["elapsed_ms"]);
+    assert_eq!(false, entries[0].fields["cached"]);
+    let spans = json!([
+        {"name": "request", "id": 7, "path": "/users"},
+        {"name": "query", "rows": 3},
+    ]);
+    assert_eq!(spans, entries[0].fields["spans"]);
+    assert_eq!("denied", entries[1].body);
+    assert_eq!("\"ann\"", entries[1].fields["user"]);
+    assert_eq!(json!([{"name": "request", "id": 7, "path": "/users"}]), entries[1].fields["spans"]);
+    assert_eq!("from log", entries[2].body);
+}
+
+/// Events are filtered like entries logged with `log`.
+#[test]
+fn filters_events() {
+    let path = path("tracing_filters");
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .target_level("noisy", LevelFilter::Error)
+        .init(&path)
+        .unwrap();
+    let subscriber = tracing_subscriber::registry().with(JsonlGzipLayer::new());
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug!("too verbose");
+        tracing::warn!(target: "noisy", "filtered by target");
+        tracing::info!("kept");
+    });
+    deinit();
+
+    let bodies: Vec<_> = read(&path).unwrap().map(|entry| entry.body).collect();
+    assert_eq!(vec!["kept"], bodies);
+    assert!(read(&path).unwrap().all(|entry| !entry.fields.contains_key("spans")));
+}
+
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "tui")]
+
+use jsonl_gzip_logger::{tui::LogView, LogEntry};
+use log::{Level, LevelFilter};
+use ratatui::{buffer::Buffer, layout::Rect, widgets::Widget};
+use std::time::Duration;
+
+fn entry(level: Level, target: &str, body: &str) -> LogEntry {
+    LogEntry {
+        offset: Duration::from_millis(1500),
+        level,
+        target: target.to_string(),
+        body: body.to_string(),
+        session: None,
+        pid: None,
+        time: None,
+        module_path: None,
+        file: None,
+        line: None,
+        fields: Default::default(),
+        truncated: false,
+    }
+}
+
+/// Renders the view and returns its rows with trailing spaces removed.
+fn render(view: &LogView, width: u16, height: u16) -> Vec<String> {
+    let area = Rect::new(0, 0, width, height);
+    let mut buf = Buffer::empty(area);
+    view.render(area, &mut buf);
+    (0..height)
+        .map(|y| {
+            let row: String = (0..width).map(|x| buf[(x, y)].symbol()).collect();
+            row.trim_end().to_string()
+        })
+        .collect()
+}
+
+/// The newest entries fill the pane from the bottom.
+#[test]
+fn shows_newest_entries() {
+    let mut view = LogView::new();
+    view.extend((0..5).map(|i| entry(Level::Info, "app", &format!("message {i}"))));
+    assert_eq!(
+        vec![
+            "     1.500 INFO  app: message 3",
+            "     1.500 INFO  app: message 4",
+        ],
+        render(&view, 40, 2)
+    );
+}
+
+/// Level, target and body filters hide non-matching entries.
+#[test]
+fn filters() {
+    let mut view = LogView::new();
+    view.push(entry(Level::Warn, "app::db", "slow query"));
+    view.push(entry(Level::Debug, "app::db", "query"));
+    view.push(entry(Level::Error, "net", "reset"));
+    view.push(entry(Level::Warn, "app::http", "slow request"));
+
+    view.set_level(LevelFilter::Warn);
+    assert_eq!(3, view.visible().count());
+    view.set_target("app");
+    assert_eq!(2, view.visible().count());
+    view.set_search("query");
+    let bodies: Vec<_> = view.visible().map(|entry| entry.body.as_str()).collect();
+    assert_eq!(vec!["slow query"], bodies);
+}
+
+/// A scrolled view keeps showing the same entries as new ones arrive.
+#[test]
+fn scrolling() {
+    let mut view = LogView::new();
+    view.extend((0..5).map(|i| entry(Level::Info, "app", &i.to_string())));
+    view.scroll_up(2);
+    assert!(!view.is_at_bottom());
+    view.push(entry(Level::Info, "app", "5"));
+    assert_eq!(vec!["     1.500 INFO  app: 2"], render(&view, 40, 1));
+
+    view.scroll_up(100);
+    assert_eq!(vec!["     1.500 INFO  app: 0"], render(&view, 40, 1));
+    view.scroll_to_bottom();
+    assert_eq!(vec!["     1.500 INFO  app: 5"], render(&view, 40, 1));
+}
+
+/// Old entries are dropped once the view is full.
+#[test]
+fn capacity() {
+    let mut view = LogView::with_capacity(2);
+    view.extend((0..5).map(|i| entry(Level::Info, "app", &i.to_string())));
+    let bodies: Vec<_> = view.visible().map(|entry| entry.body.as_str()).collect();
+    assert_eq!(vec!["3", "4"], bodies);
+}
@@ -0,0 +1,37 @@
+use jsonl_gzip_logger::{LogEntry, LoggerBuilder};
+use log::{Level, LevelFilter};
+use rusty_fork::rusty_fork_test;
+use std::{net::UdpSocket, time::Duration};
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Each entry arrives as one JSON datagram.
+#[test]
+fn one_datagram_per_entry() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .init_udp(receiver.local_addr().unwrap())
+        .unwrap();
+    log::warn!(target: "net", "first");
+    log::info!(target: "net", "second");
+    drop(guard);
+    log::warn!(target: "net", "not sent");
+
+    let mut buffer = [0; 1024];
+    let len = receiver.recv(&mut buffer).unwrap();
+    let entry: LogEntry = serde_json::from_slice(&buffer[..len]).unwrap();
+    assert_eq!(Level::Warn, entry.level);
+    assert_eq!("net", entry.target);
+    assert_eq!("first", entry.body);
+    let len = receiver.recv(&mut buffer).unwrap();
+    let entry: LogEntry = serde_json::from_slice(&buffer[..len]).unwrap();
+    assert_eq!("second", entry.body);
+    receiver.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+    assert!(receiver.recv(&mut buffer).is_err());
+}
+
+}
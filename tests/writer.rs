@@ -0,0 +1,104 @@
+use jsonl_gzip_logger::{
+    clock::ManualClock, deinit, init_with_writer, LogEntryIter, LoggerBuilder,
+};
+use log::LevelFilter;
+use rusty_fork::rusty_fork_test;
+use std::{
+    io::{self, Cursor, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Writer collecting everything written to it, standing in for a browser
+/// storage buffer.
+#[derive(Clone, Default)]
+struct Sink(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writer failing like a full disk.
+struct Full;
+
+impl Write for Full {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// only one logger can be installed at a time, so each test gets its own
+// process.
+rusty_fork_test! {
+
+/// Entries logged to a writer can be read back from its bytes.
+#[test]
+fn logs_to_writer() {
+    let sink = Sink::default();
+    let clock = ManualClock::new();
+    let _guard = LoggerBuilder::new()
+        .level(LevelFilter::Info)
+        .clock(clock.clone())
+        .init_writer(sink.clone())
+        .unwrap();
+    log::info!(target: "app", "first");
+    clock.set(Duration::from_secs(2));
+    log::debug!(target: "app", "filtered");
+    log::warn!(target: "app", "second");
+    deinit();
+
+    let bytes = sink.0.lock().unwrap().clone();
+    let entries: Vec<_> = LogEntryIter::new(Cursor::new(bytes)).collect();
+    assert_eq!(2, entries.len());
+    assert_eq!("first", entries[0].body);
+    assert_eq!(Duration::from_secs(2), entries[1].offset);
+    assert_eq!("second", entries[1].body);
+}
+
+/// The writer is finished when the guard is dropped.
+#[test]
+fn guard_finishes_writer() {
+    let sink = Sink::default();
+    let guard = init_with_writer(sink.clone(), LevelFilter::Warn).unwrap();
+    log::warn!("kept");
+    log::info!("filtered");
+    drop(guard);
+
+    let bytes = sink.0.lock().unwrap().clone();
+    let bodies: Vec<_> = LogEntryIter::new(Cursor::new(bytes))
+        .map(|entry| entry.body)
+        .collect();
+    assert_eq!(vec!["kept"], bodies);
+}
+
+/// Write errors go to the error hook instead of panicking.
+#[test]
+fn reports_write_errors() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let reported = errors.clone();
+    let _guard = LoggerBuilder::new()
+        .on_error(move |error| reported.lock().unwrap().push(error.kind()))
+        .init_writer(Full)
+        .unwrap();
+    log::info!("lost");
+    log::logger().flush();
+    log::info!("also lost");
+    deinit();
+
+    let errors = errors.lock().unwrap();
+    assert!(!errors.is_empty());
+    assert!(errors.iter().all(|kind| *kind == io::ErrorKind::StorageFull));
+}
+
+}